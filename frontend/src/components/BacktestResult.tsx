@@ -243,6 +243,47 @@ export default function BacktestResult({ statistic, precision }: BacktestResultP
         </div>
       </div>
 
+      {statistic.symbols.length > 1 && (
+        <div className="bg-white rounded-xl border border-gray-200 p-6">
+          <h2 className="text-lg font-medium text-gray-900 mb-4">Symbols</h2>
+
+          <div className="overflow-x-auto">
+            <table className="w-full text-sm">
+              <thead>
+                <tr className="border-b border-gray-200">
+                  <th className="text-left py-3 px-4 font-medium text-gray-700">Symbol</th>
+                  <th className="text-right py-3 px-4 font-medium text-gray-700">Trades</th>
+                  <th className="text-right py-3 px-4 font-medium text-gray-700">Win Rate</th>
+                  <th className="text-right py-3 px-4 font-medium text-gray-700">Gross Profit</th>
+                  <th className="text-right py-3 px-4 font-medium text-gray-700">Gross Loss</th>
+                  <th className="text-right py-3 px-4 font-medium text-gray-700">Net Profit</th>
+                  <th className="text-right py-3 px-4 font-medium text-gray-700">Open Position</th>
+                </tr>
+              </thead>
+              <tbody>
+                {statistic.symbols.map((symbol) => {
+                  const netValue = Number(symbol.net_profit)
+
+                  return (
+                    <tr key={symbol.symbol} className="border-b border-gray-100 hover:bg-gray-50">
+                      <td className="py-3 px-4 font-medium text-gray-900">{symbol.symbol}</td>
+                      <td className="py-3 px-4 text-right text-gray-900">{symbol.total_trades}</td>
+                      <td className="py-3 px-4 text-right text-gray-900">{formatPercent(symbol.win_rate)}</td>
+                      <td className="py-3 px-4 text-right text-green-600 font-mono">{formatNumber(symbol.gross_profit)}</td>
+                      <td className="py-3 px-4 text-right text-red-600 font-mono">{formatNumber(symbol.gross_loss)}</td>
+                      <td className={`py-3 px-4 text-right font-mono font-medium ${netValue > 0 ? 'text-green-600' : netValue < 0 ? 'text-red-600' : 'text-gray-900'}`}>
+                        {formatNumber(symbol.net_profit)}
+                      </td>
+                      <td className="py-3 px-4 text-right text-gray-900 font-mono">{formatAmount(symbol.position)}</td>
+                    </tr>
+                  )
+                })}
+              </tbody>
+            </table>
+          </div>
+        </div>
+      )}
+
       {statistic.trades.length > 0 && (
         <div className="bg-white rounded-xl border border-gray-200 p-6">
           <div className="flex items-center justify-between mb-4">
@@ -257,6 +298,9 @@ export default function BacktestResult({ statistic, precision }: BacktestResultP
               <thead>
                 <tr className="border-b border-gray-200">
                   <th className="text-left py-3 px-4 font-medium text-gray-700">Time</th>
+                  {statistic.symbols.length > 1 && (
+                    <th className="text-left py-3 px-4 font-medium text-gray-700">Symbol</th>
+                  )}
                   <th className="text-left py-3 px-4 font-medium text-gray-700">Type</th>
                   <th className="text-right py-3 px-4 font-medium text-gray-700">Price</th>
                   <th className="text-right py-3 px-4 font-medium text-gray-700">Amount</th>
@@ -268,8 +312,9 @@ export default function BacktestResult({ statistic, precision }: BacktestResultP
                 {statistic.trades
                   .slice((currentPage - 1) * TRADES_PER_PAGE, currentPage * TRADES_PER_PAGE)
                   .map((trade, idx) => {
-                    const isBuy = trade.trade_type === 'market_buy' || trade.trade_type === 'limit_buy'
-                    const isLimit = trade.trade_type === 'limit_buy' || trade.trade_type === 'limit_sell'
+                    const isBuy = trade.trade_type.endsWith('_buy')
+                    const orderKind = trade.trade_type.replace(/_(buy|sell)$/, '')
+                    const isLiquidation = orderKind === 'liquidation'
                     const profitValue = Number(trade.profit)
 
                     return (
@@ -277,14 +322,22 @@ export default function BacktestResult({ statistic, precision }: BacktestResultP
                         <td className="py-3 px-4 text-gray-600">
                           {formatTimestamp(trade.timestamp)}
                         </td>
+                        {statistic.symbols.length > 1 && (
+                          <td className="py-3 px-4 text-gray-900">{trade.symbol}</td>
+                        )}
                         <td className="py-3 px-4">
                           <div className="flex items-center gap-1">
                             <span className={`text-xs font-medium px-2 py-1 rounded ${isBuy ? 'bg-green-100 text-green-700' : 'bg-red-100 text-red-700'}`}>
                               {isBuy ? 'BUY' : 'SELL'}
                             </span>
-                            {isLimit && (
+                            {orderKind !== 'market' && !isLiquidation && (
                               <span className="text-xs text-gray-500 px-2 py-1 bg-gray-100 rounded">
-                                LIMIT
+                                {orderKind.replace(/_/g, ' ').toUpperCase()}
+                              </span>
+                            )}
+                            {isLiquidation && (
+                              <span className="text-xs text-orange-700 px-2 py-1 bg-orange-100 rounded">
+                                LIQUIDATION
                               </span>
                             )}
                           </div>
@@ -1,7 +1,9 @@
-import { useEffect, useState } from 'react'
+import { useCallback, useEffect, useState } from 'react'
 import { api } from '@/services/api'
 import type { BacktestTask } from '@/types'
 
+const HISTORY_SIZE = 50
+
 export function useBacktestStream() {
   const [tasks, setTasks] = useState<Map<string, BacktestTask>>(new Map())
   const [connected, setConnected] = useState(false)
@@ -10,6 +12,22 @@ export function useBacktestStream() {
   useEffect(() => {
     setConnected(true)
 
+    // The stream only carries unfinished tasks, earlier ones come from the task history
+    api.backtest.list({ limit: HISTORY_SIZE })
+      .then((response) => {
+        setTasks((prev) => {
+          const next = new Map<string, BacktestTask>()
+          for (const task of [...response.tasks].reverse()) {
+            next.set(task.id, task)
+          }
+          for (const [id, task] of prev) {
+            next.set(id, task)
+          }
+          return next
+        })
+      })
+      .catch((err) => console.error('Failed to load backtest history:', err))
+
     const cleanup = api.backtest.stream(
       (task: BacktestTask) => {
         setTasks((prev) => {
@@ -30,9 +48,18 @@ export function useBacktestStream() {
     }
   }, [])
 
+  const removeTask = useCallback((id: string) => {
+    setTasks((prev) => {
+      const next = new Map(prev)
+      next.delete(id)
+      return next
+    })
+  }, [])
+
   return {
     tasks: Array.from(tasks.values()),
     connected,
     error,
+    removeTask,
   }
 }
@@ -1,5 +1,5 @@
 import { useEffect, useState, useRef, useCallback } from 'react'
-import { Play, Clock, CheckCircle, XCircle, Loader, TrendingUp, TrendingDown, X } from 'lucide-react'
+import { Play, Clock, CheckCircle, XCircle, Loader, TrendingUp, TrendingDown, X, Ban } from 'lucide-react'
 import { api } from '@/services/api'
 import { useBacktestStream } from '@/hooks/useBacktestStream'
 import ComboBox from '@/components/ComboBox'
@@ -25,8 +25,9 @@ export default function Backtest() {
   const [loadedChartTaskId, setLoadedChartTaskId] = useState<string | null>(null)
   const loadingChartRef = useRef(false)
   const [showResultView, setShowResultView] = useState(false)
+  const [selectedTrades, setSelectedTrades] = useState<Trade[]>([])
 
-  const { tasks, connected } = useBacktestStream()
+  const { tasks, connected, removeTask } = useBacktestStream()
 
   useEffect(() => {
     loadAvailableData()
@@ -80,15 +81,14 @@ export default function Backtest() {
 
   const convertTradesToMarkers = (trades: Trade[]): SeriesMarker<Time>[] => {
     return trades.map(trade => {
-      const isBuy = trade.trade_type === 'market_buy' || trade.trade_type === 'limit_buy'
-      const isLimit = trade.trade_type === 'limit_buy' || trade.trade_type === 'limit_sell'
+      const isBuy = trade.trade_type.endsWith('_buy')
 
       return {
         time: (trade.timestamp / 1000) as Time,
         position: isBuy ? 'belowBar' : 'aboveBar',
         color: isBuy ? '#26a69a' : '#ef5350',
         shape: isBuy ? 'arrowUp' : 'arrowDown',
-        text: `${isLimit ? 'LIMIT' : 'MARKET'} ${isBuy ? 'BUY' : 'SELL'} ${trade.amount} @ ${trade.price}`,
+        text: `${trade.trade_type.replace(/_/g, ' ').toUpperCase()} ${trade.amount} @ ${trade.price}`,
       }
     })
   }
@@ -135,6 +135,8 @@ export default function Backtest() {
         exchange: task.exchange,
         symbol: task.symbol,
         timeframe: task.timeframe,
+        start: task.start,
+        end: task.end,
       })
       const chartData: CandlestickData[] = candles.map((candle: Candle) => ({
         time: (candle.timestamp / 1000) as Time,
@@ -166,8 +168,17 @@ export default function Backtest() {
     if (selectedTaskId && selectedTaskId !== loadedChartTaskId && !loadingChartRef.current) {
       const task = tasks.find(t => t.id === selectedTaskId)
       if (task && task.status === 'completed' && task.statistic) {
-        const markers = convertTradesToMarkers(task.statistic.trades)
-        setTradeMarkers(markers)
+        // Task listings leave out trades, so they are loaded for the selected task only
+        api.backtest.trades(task.id)
+          .then((trades) => {
+            setSelectedTrades(trades)
+            // The chart shows the primary symbol, trades on additional symbols have no candles there
+            const markers = convertTradesToMarkers(
+              trades.filter(trade => !trade.symbol || trade.symbol === task.symbol)
+            )
+            setTradeMarkers(markers)
+          })
+          .catch((error) => console.error('Failed to load trades:', error))
         loadChartForTask(task)
       }
     }
@@ -192,6 +203,31 @@ export default function Backtest() {
         return <CheckCircle className="w-4 h-4 text-green-500" />
       case 'failed':
         return <XCircle className="w-4 h-4 text-red-500" />
+      case 'cancelled':
+        return <Ban className="w-4 h-4 text-gray-400" />
+    }
+  }
+
+  const handleCancelTask = async (e: React.MouseEvent, task: BacktestTask) => {
+    e.stopPropagation()
+    try {
+      await api.backtest.cancel(task.id)
+    } catch (error) {
+      console.error('Failed to cancel task:', error)
+    }
+  }
+
+  const handleDeleteTask = async (e: React.MouseEvent, task: BacktestTask) => {
+    e.stopPropagation()
+    try {
+      await api.backtest.delete(task.id)
+      removeTask(task.id)
+      if (selectedTaskId === task.id) {
+        setSelectedTaskId(null)
+        setShowResultView(false)
+      }
+    } catch (error) {
+      console.error('Failed to delete task:', error)
     }
   }
 
@@ -365,11 +401,41 @@ export default function Backtest() {
                           </div>
                         )}
 
+                        {task.status === 'pending' && task.queue_position && (
+                          <p className="mt-2 text-xs text-gray-500">Queued · #{task.queue_position}</p>
+                        )}
+
+                        {(task.status === 'pending' || task.status === 'compiling' || task.status === 'running') && (
+                          <span
+                            role="button"
+                            onClick={(e) => handleCancelTask(e, task)}
+                            className="inline-block mt-2 text-xs text-gray-500 hover:text-gray-900"
+                          >
+                            Cancel
+                          </span>
+                        )}
+
+                        {(task.status === 'completed' || task.status === 'failed' || task.status === 'cancelled') && (
+                          <span
+                            role="button"
+                            onClick={(e) => handleDeleteTask(e, task)}
+                            className="inline-block mt-2 text-xs text-gray-500 hover:text-red-600"
+                          >
+                            Delete
+                          </span>
+                        )}
+
                         {task.status === 'failed' && task.error_message && (
                           <div className="mt-2">
                             <p className="text-xs text-red-600">{task.error_message}</p>
                           </div>
                         )}
+
+                        {task.status === 'cancelled' && task.error_message && (
+                          <div className="mt-2">
+                            <p className="text-xs text-gray-500">{task.error_message}</p>
+                          </div>
+                        )}
                       </button>
                     ))
                   )}
@@ -414,7 +480,7 @@ export default function Backtest() {
                 const task = tasks.find(t => t.id === selectedTaskId)
                 return task?.statistic && (
                   <BacktestResult
-                    statistic={task.statistic}
+                    statistic={{ ...task.statistic, trades: selectedTrades }}
                     precision={task.precision}
                   />
                 )
@@ -1,5 +1,5 @@
 import { useEffect, useState, useRef } from 'react'
-import { Download, Search, Clock, CheckCircle, XCircle, Loader, BarChart3 } from 'lucide-react'
+import { Download, Search, Clock, CheckCircle, XCircle, Loader, BarChart3, Ban } from 'lucide-react'
 import { api } from '@/services/api'
 import { useFetchCandlesStream } from '@/hooks/useFetchCandlesStream'
 import ComboBox from '@/components/ComboBox'
@@ -161,6 +161,16 @@ export default function MarketData() {
         return <CheckCircle className="w-4 h-4 text-green-500" />
       case 'failed':
         return <XCircle className="w-4 h-4 text-red-500" />
+      case 'cancelled':
+        return <Ban className="w-4 h-4 text-gray-400" />
+    }
+  }
+
+  const handleCancelTask = async (task: FetchCandlesTask) => {
+    try {
+      await api.fetchCandles.cancel(task.id)
+    } catch (error) {
+      console.error('Failed to cancel task:', error)
     }
   }
 
@@ -373,9 +383,26 @@ export default function MarketData() {
                         </div>
                       )}
 
+                      {task.status === 'pending' && task.queue_position && (
+                        <p className="mt-2 text-xs text-gray-500">Queued · #{task.queue_position}</p>
+                      )}
+
+                      {(task.status === 'pending' || task.status === 'running') && (
+                        <button
+                          onClick={() => handleCancelTask(task)}
+                          className="mt-2 text-xs text-gray-500 hover:text-gray-900"
+                        >
+                          Cancel
+                        </button>
+                      )}
+
                       {task.status === 'failed' && task.error_message && (
                         <p className="mt-2 text-xs text-red-600">{task.error_message}</p>
                       )}
+
+                      {task.status === 'cancelled' && task.error_message && (
+                        <p className="mt-2 text-xs text-gray-500">{task.error_message}</p>
+                      )}
                     </div>
                   ))}
                 </div>
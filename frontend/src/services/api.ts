@@ -5,9 +5,18 @@ import type {
   CreateFetchCandlesTaskResponse,
   CreateBacktestTaskRequest,
   CreateBacktestTaskResponse,
+  CreateOptimizationTaskRequest,
+  CreateOptimizationTaskResponse,
   ErrorResponse,
   FetchCandlesTask,
   BacktestTask,
+  ListBacktestTasksQuery,
+  ListBacktestTasksResponse,
+  Trade,
+  OptimizationTask,
+  CreatePaperSessionRequest,
+  CreatePaperSessionResponse,
+  PaperSession,
   Timeframe,
   GetSourceResponse,
   GetSourceQuery,
@@ -16,7 +25,9 @@ import type {
   MoveSourceQuery,
   AddStrategyRequest,
   AvailableCandleInfo,
-  ListStrategiesResponse
+  ListStrategiesResponse,
+  ListParametersQuery,
+  ListParametersResponse
 } from '@/types'
 
 const API_BASE_URL = 'http://localhost:3001'
@@ -94,6 +105,11 @@ export const api = {
 
     getById: (id: string) => fetchAPI<FetchCandlesTask>(`/tasks/fetch/${id}`),
 
+    cancel: (id: string) =>
+      fetchAPI<void>(`/tasks/fetch/${id}/cancel`, {
+        method: 'POST',
+      }),
+
     create: (request: CreateFetchCandlesTaskRequest) =>
       fetchAPI<CreateFetchCandlesTaskResponse>('/tasks/fetch', {
         method: 'POST',
@@ -130,10 +146,29 @@ export const api = {
   },
 
   backtest: {
-    getAll: () => fetchAPI<BacktestTask[]>('/tasks/backtest'),
+    list: (params: ListBacktestTasksQuery = {}) => {
+      const query = new URLSearchParams(
+        Object.entries(params)
+          .filter(([, value]) => value !== undefined)
+          .map(([key, value]) => [key, String(value)])
+      )
+      return fetchAPI<ListBacktestTasksResponse>(`/tasks/backtest?${query}`)
+    },
 
     getById: (id: string) => fetchAPI<BacktestTask>(`/tasks/backtest/${id}`),
 
+    trades: (id: string) => fetchAPI<Trade[]>(`/tasks/backtest/${id}/trades`),
+
+    delete: (id: string) =>
+      fetchAPI<void>(`/tasks/backtest/${id}`, {
+        method: 'DELETE',
+      }),
+
+    cancel: (id: string) =>
+      fetchAPI<void>(`/tasks/backtest/${id}/cancel`, {
+        method: 'POST',
+      }),
+
     create: (request: CreateBacktestTaskRequest) =>
       fetchAPI<CreateBacktestTaskResponse>('/tasks/backtest', {
         method: 'POST',
@@ -169,6 +204,106 @@ export const api = {
     },
   },
 
+  optimization: {
+    getAll: () => fetchAPI<OptimizationTask[]>('/tasks/optimization'),
+
+    getById: (id: string) => fetchAPI<OptimizationTask>(`/tasks/optimization/${id}`),
+
+    cancel: (id: string) =>
+      fetchAPI<void>(`/tasks/optimization/${id}/cancel`, {
+        method: 'POST',
+      }),
+
+    create: (request: CreateOptimizationTaskRequest) =>
+      fetchAPI<CreateOptimizationTaskResponse>('/tasks/optimization', {
+        method: 'POST',
+        body: JSON.stringify(request),
+      }),
+
+    stream: (onEvent: (task: OptimizationTask) => void, onError?: (error: Error) => void) => {
+      const eventSource = new EventSource(`${API_BASE_URL}/tasks/optimization/stream`)
+
+      eventSource.onmessage = (event) => {
+        try {
+          const task: OptimizationTask = JSON.parse(event.data)
+          onEvent(task)
+        } catch (error) {
+          console.error('Failed to parse optimization event:', error)
+          toast.error('Stream Error', {
+            description: 'Failed to parse event data',
+          })
+        }
+      }
+
+      eventSource.onerror = (error) => {
+        console.error('SSE connection error:', error)
+        toast.error('Connection Error', {
+          description: 'Lost connection to optimization stream',
+        })
+        onError?.(new Error('SSE connection failed'))
+      }
+
+      return () => {
+        eventSource.close()
+      }
+    },
+  },
+
+  paper: {
+    getAll: () => fetchAPI<PaperSession[]>('/paper'),
+
+    getById: (id: string) => fetchAPI<PaperSession>(`/paper/${id}`),
+
+    stop: (id: string) =>
+      fetchAPI<void>(`/paper/${id}/stop`, {
+        method: 'POST',
+      }),
+
+    kill: (id: string) =>
+      fetchAPI<void>(`/paper/${id}/kill`, {
+        method: 'POST',
+      }),
+
+    release: (id: string) =>
+      fetchAPI<void>(`/paper/${id}/release`, {
+        method: 'POST',
+      }),
+
+    create: (request: CreatePaperSessionRequest) =>
+      fetchAPI<CreatePaperSessionResponse>('/paper', {
+        method: 'POST',
+        body: JSON.stringify(request),
+      }),
+
+    stream: (onEvent: (session: PaperSession) => void, onError?: (error: Error) => void) => {
+      const eventSource = new EventSource(`${API_BASE_URL}/paper/stream`)
+
+      eventSource.onmessage = (event) => {
+        try {
+          const session: PaperSession = JSON.parse(event.data)
+          onEvent(session)
+        } catch (error) {
+          console.error('Failed to parse paper session event:', error)
+          toast.error('Stream Error', {
+            description: 'Failed to parse event data',
+          })
+        }
+      }
+
+      eventSource.onerror = (error) => {
+        console.error('SSE connection error:', error)
+        toast.error('Connection Error', {
+          description: 'Lost connection to paper session stream',
+        })
+        onError?.(new Error('SSE connection failed'))
+      }
+
+      return () => {
+        eventSource.close()
+      }
+    },
+  },
+
   candles: {
     get: (params: {
       exchange: string
@@ -210,6 +345,9 @@ export const api = {
   strategy: {
     list: () => fetchAPI<ListStrategiesResponse>('/strategy/list'),
 
+    parameters: (query: ListParametersQuery) =>
+      fetchAPI<ListParametersResponse>(`/strategy/parameters?name=${encodeURIComponent(query.name)}`),
+
     add: (request: AddStrategyRequest) =>
       fetchAPI<void>('/strategy/add', {
         method: 'POST',
@@ -0,0 +1,11 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { BacktestEngine } from "./BacktestEngine";
+import type { FillModelConfig } from "./FillModelConfig";
+import type { MarginConfig } from "./MarginConfig";
+import type { MarketPrecision } from "./MarketPrecision";
+import type { RiskLimits } from "./RiskLimits";
+import type { SymbolConfig } from "./SymbolConfig";
+import type { Timeframe } from "./Timeframe";
+import type { TradingFees } from "./TradingFees";
+
+export type BacktestConfig = { exchange: string, symbol: string, timeframe: Timeframe, precision: MarketPrecision, fees: TradingFees, initial_capital: string, quote_currency: string, start?: number, end?: number, equity_points?: number, margin?: MarginConfig, fill_model: FillModelConfig, risk?: RiskLimits, additional_symbols: Array<SymbolConfig>, additional_timeframes: Array<Timeframe>, engine: BacktestEngine, };
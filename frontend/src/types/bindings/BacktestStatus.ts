@@ -1,3 +1,3 @@
 // This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
 
-export type BacktestStatus = "pending" | "compiling" | "running" | "completed" | "failed";
+export type BacktestStatus = "pending" | "compiling" | "running" | "completed" | "failed" | "cancelled";
@@ -1,7 +1,15 @@
 // This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { BacktestEngine } from "./BacktestEngine";
 import type { BacktestStatistic } from "./BacktestStatistic";
 import type { BacktestStatus } from "./BacktestStatus";
+import type { FillModelConfig } from "./FillModelConfig";
+import type { MarginConfig } from "./MarginConfig";
 import type { MarketPrecision } from "./MarketPrecision";
+import type { RiskEvent } from "./RiskEvent";
+import type { RiskLimits } from "./RiskLimits";
+import type { SymbolConfig } from "./SymbolConfig";
 import type { Timeframe } from "./Timeframe";
+import type { TradingFees } from "./TradingFees";
+import type { JsonValue } from "./serde_json/JsonValue";
 
-export type BacktestTask = { id: string, status: BacktestStatus, progress: number, name: string, exchange: string, symbol: string, timeframe: Timeframe, precision: MarketPrecision, statistic?: BacktestStatistic, error_message?: string, created_at: number, started_at?: number, completed_at?: number, updated_at: number, };
+export type BacktestTask = { id: string, status: BacktestStatus, progress: number, name: string, parameters: { [key in string]?: JsonValue }, statistic?: BacktestStatistic, error_message?: string, risk_events: Array<RiskEvent>, timeout_secs?: bigint, queue_position?: number, created_at: number, started_at?: number, completed_at?: number, updated_at: number, exchange: string, symbol: string, timeframe: Timeframe, precision: MarketPrecision, fees: TradingFees, initial_capital: string, quote_currency: string, start?: number, end?: number, equity_points?: number, margin?: MarginConfig, fill_model: FillModelConfig, risk?: RiskLimits, additional_symbols: Array<SymbolConfig>, additional_timeframes: Array<Timeframe>, engine: BacktestEngine, };
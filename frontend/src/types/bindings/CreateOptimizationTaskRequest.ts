@@ -0,0 +1,12 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { BacktestEngine } from "./BacktestEngine";
+import type { FillModelConfig } from "./FillModelConfig";
+import type { MarginConfig } from "./MarginConfig";
+import type { OptimizationMetric } from "./OptimizationMetric";
+import type { ParameterRange } from "./ParameterRange";
+import type { ParameterSearch } from "./ParameterSearch";
+import type { RiskLimits } from "./RiskLimits";
+import type { Timeframe } from "./Timeframe";
+import type { WalkForward } from "./WalkForward";
+
+export type CreateOptimizationTaskRequest = { name: string, walk_forward?: WalkForward, timeout_secs?: bigint, exchange: string, symbol: string, timeframe: Timeframe, start?: number, end?: number, initial_capital?: string, quote_currency?: string, maker_fee?: string, taker_fee?: string, equity_points?: number, margin?: MarginConfig, fill_model?: FillModelConfig, risk?: RiskLimits, additional_symbols?: Array<string>, additional_timeframes?: Array<Timeframe>, engine?: BacktestEngine, ranges: { [key in string]?: ParameterRange }, search: ParameterSearch, metric: OptimizationMetric, };
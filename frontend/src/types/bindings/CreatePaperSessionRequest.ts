@@ -0,0 +1,10 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { BacktestEngine } from "./BacktestEngine";
+import type { BrokerType } from "./BrokerType";
+import type { FillModelConfig } from "./FillModelConfig";
+import type { MarginConfig } from "./MarginConfig";
+import type { RiskLimits } from "./RiskLimits";
+import type { Timeframe } from "./Timeframe";
+import type { JsonValue } from "./serde_json/JsonValue";
+
+export type CreatePaperSessionRequest = { name: string, parameters?: { [key in string]?: JsonValue }, warmup_candles?: number, broker?: BrokerType, exchange: string, symbol: string, timeframe: Timeframe, start?: number, end?: number, initial_capital?: string, quote_currency?: string, maker_fee?: string, taker_fee?: string, equity_points?: number, margin?: MarginConfig, fill_model?: FillModelConfig, risk?: RiskLimits, additional_symbols?: Array<string>, additional_timeframes?: Array<Timeframe>, engine?: BacktestEngine, };
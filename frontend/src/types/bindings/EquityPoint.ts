@@ -0,0 +1,3 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+export type EquityPoint = { timestamp: number, equity: string, cash: string, position_value: string, drawdown: string, drawdown_percent: number, };
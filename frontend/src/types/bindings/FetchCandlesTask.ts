@@ -3,4 +3,4 @@ import type { FetchCandlesResult } from "./FetchCandlesResult";
 import type { FetchCandlesStatus } from "./FetchCandlesStatus";
 import type { Timeframe } from "./Timeframe";
 
-export type FetchCandlesTask = { id: string, status: FetchCandlesStatus, progress: number, symbol: string, exchange: string, timeframe: Timeframe, result?: FetchCandlesResult, error_message?: string, created_at: number, started_at?: number, completed_at?: number, updated_at: number, };
+export type FetchCandlesTask = { id: string, status: FetchCandlesStatus, progress: number, symbol: string, exchange: string, timeframe: Timeframe, result?: FetchCandlesResult, error_message?: string, timeout_secs?: bigint, queue_position?: number, created_at: number, started_at?: number, completed_at?: number, updated_at: number, };
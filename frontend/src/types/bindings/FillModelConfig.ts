@@ -0,0 +1,3 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+export type FillModelConfig = { "type": "ideal" } | { "type": "fixed_slippage", bps: string, } | { "type": "volume_slippage", impact: string, } | { "type": "partial_fill", max_volume_fraction: string, } | { "type": "next_open" };
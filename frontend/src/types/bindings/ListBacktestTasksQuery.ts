@@ -0,0 +1,4 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { BacktestStatus } from "./BacktestStatus";
+
+export type ListBacktestTasksQuery = { status?: BacktestStatus, strategy?: string, exchange?: string, symbol?: string, start?: number, end?: number, limit?: number, offset?: number, };
@@ -0,0 +1,6 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { OptimizationMetric } from "./OptimizationMetric";
+import type { ParameterRange } from "./ParameterRange";
+import type { ParameterSearch } from "./ParameterSearch";
+
+export type Optimization = { ranges: { [key in string]?: ParameterRange }, search: ParameterSearch, metric: OptimizationMetric, };
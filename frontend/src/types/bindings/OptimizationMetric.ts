@@ -0,0 +1,3 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+export type OptimizationMetric = "net_profit" | "return_percent" | "sharpe_ratio" | "profit_factor" | "win_rate" | "max_drawdown";
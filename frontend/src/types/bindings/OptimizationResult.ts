@@ -0,0 +1,5 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { BacktestStatistic } from "./BacktestStatistic";
+import type { JsonValue } from "./serde_json/JsonValue";
+
+export type OptimizationResult = { parameters: { [key in string]?: JsonValue }, score?: number, statistic?: BacktestStatistic, error_message?: string, };
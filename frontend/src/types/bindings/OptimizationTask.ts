@@ -0,0 +1,19 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { BacktestEngine } from "./BacktestEngine";
+import type { BacktestStatistic } from "./BacktestStatistic";
+import type { BacktestStatus } from "./BacktestStatus";
+import type { FillModelConfig } from "./FillModelConfig";
+import type { MarginConfig } from "./MarginConfig";
+import type { MarketPrecision } from "./MarketPrecision";
+import type { OptimizationMetric } from "./OptimizationMetric";
+import type { OptimizationResult } from "./OptimizationResult";
+import type { ParameterRange } from "./ParameterRange";
+import type { ParameterSearch } from "./ParameterSearch";
+import type { RiskLimits } from "./RiskLimits";
+import type { SymbolConfig } from "./SymbolConfig";
+import type { Timeframe } from "./Timeframe";
+import type { TradingFees } from "./TradingFees";
+import type { WalkForward } from "./WalkForward";
+import type { WalkForwardFold } from "./WalkForwardFold";
+
+export type OptimizationTask = { id: string, status: BacktestStatus, progress: number, name: string, total_runs: number, completed_runs: number, results: Array<OptimizationResult>, walk_forward?: WalkForward, folds: Array<WalkForwardFold>, out_of_sample?: BacktestStatistic, error_message?: string, timeout_secs?: bigint, queue_position?: number, created_at: number, started_at?: number, completed_at?: number, updated_at: number, exchange: string, symbol: string, timeframe: Timeframe, precision: MarketPrecision, fees: TradingFees, initial_capital: string, quote_currency: string, start?: number, end?: number, equity_points?: number, margin?: MarginConfig, fill_model: FillModelConfig, risk?: RiskLimits, additional_symbols: Array<SymbolConfig>, additional_timeframes: Array<Timeframe>, engine: BacktestEngine, ranges: { [key in string]?: ParameterRange }, search: ParameterSearch, metric: OptimizationMetric, };
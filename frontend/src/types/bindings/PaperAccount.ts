@@ -0,0 +1,5 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { PaperOrder } from "./PaperOrder";
+import type { Trade } from "./Trade";
+
+export type PaperAccount = { balance: string, position: string, equity: string, net_profit: string, return_percent: number, accrued_interest: string, last_price?: string, last_candle_at?: number, orders: Array<PaperOrder>, trades: Array<Trade>, };
@@ -0,0 +1,4 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { OrderType } from "./OrderType";
+
+export type PaperOrder = { id: string, symbol: string, order_type: OrderType, price: string, trigger_price?: string, trail?: string, amount: string, fee: string, group?: string, parent?: string, };
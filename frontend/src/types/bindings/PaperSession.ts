@@ -0,0 +1,16 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { BacktestEngine } from "./BacktestEngine";
+import type { BrokerType } from "./BrokerType";
+import type { FillModelConfig } from "./FillModelConfig";
+import type { MarginConfig } from "./MarginConfig";
+import type { MarketPrecision } from "./MarketPrecision";
+import type { PaperAccount } from "./PaperAccount";
+import type { PaperStatus } from "./PaperStatus";
+import type { RiskEvent } from "./RiskEvent";
+import type { RiskLimits } from "./RiskLimits";
+import type { SymbolConfig } from "./SymbolConfig";
+import type { Timeframe } from "./Timeframe";
+import type { TradingFees } from "./TradingFees";
+import type { JsonValue } from "./serde_json/JsonValue";
+
+export type PaperSession = { id: string, status: PaperStatus, name: string, parameters: { [key in string]?: JsonValue }, warmup_candles: number, broker: BrokerType, starting_equity?: string, account: PaperAccount, risk_events: Array<RiskEvent>, error_message?: string, created_at: number, started_at?: number, stopped_at?: number, updated_at: number, exchange: string, symbol: string, timeframe: Timeframe, precision: MarketPrecision, fees: TradingFees, initial_capital: string, quote_currency: string, start?: number, end?: number, equity_points?: number, margin?: MarginConfig, fill_model: FillModelConfig, risk?: RiskLimits, additional_symbols: Array<SymbolConfig>, additional_timeframes: Array<Timeframe>, engine: BacktestEngine, };
@@ -0,0 +1,4 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { JsonValue } from "./serde_json/JsonValue";
+
+export type ParameterRange = { "type": "values", values: Array<JsonValue>, } | { "type": "range", min: number, max: number, step: number, };
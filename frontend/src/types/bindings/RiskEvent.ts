@@ -0,0 +1,5 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { OrderType } from "./OrderType";
+import type { RiskRule } from "./RiskRule";
+
+export type RiskEvent = { timestamp: number, rule: RiskRule, symbol: string, order_type: OrderType, price: string, amount: string, message: string, halted: boolean, };
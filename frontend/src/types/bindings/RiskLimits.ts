@@ -0,0 +1,3 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+export type RiskLimits = { max_position?: string, max_order_notional?: string, max_orders_per_minute?: number, max_daily_loss?: string, kill_switch: boolean, halt_on_violation: boolean, };
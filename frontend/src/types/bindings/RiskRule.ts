@@ -0,0 +1,3 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+export type RiskRule = "kill_switch" | "max_orders_per_minute" | "max_order_notional" | "max_position" | "max_daily_loss";
@@ -0,0 +1,5 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { MarketPrecision } from "./MarketPrecision";
+import type { TradingFees } from "./TradingFees";
+
+export type SymbolConfig = { symbol: string, precision: MarketPrecision, fees: TradingFees, };
@@ -0,0 +1,3 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+export type SymbolStatistic = { symbol: string, total_trades: number, buy_trades: number, sell_trades: number, winning_trades: number, losing_trades: number, win_rate: number, gross_profit: string, gross_loss: string, net_profit: string, largest_win: string, largest_loss: string, position: string, };
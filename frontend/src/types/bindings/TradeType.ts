@@ -1,3 +1,3 @@
 // This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
 
-export type TradeType = "market_buy" | "market_sell" | "limit_buy" | "limit_sell";
+export type TradeType = "market_buy" | "market_sell" | "limit_buy" | "limit_sell" | "stop_buy" | "stop_sell" | "take_profit_buy" | "take_profit_sell" | "trailing_stop_buy" | "trailing_stop_sell" | "liquidation_buy" | "liquidation_sell";
@@ -0,0 +1,3 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+export type WalkForward = { in_sample_candles: number, out_of_sample_candles: number, anchored: boolean, };
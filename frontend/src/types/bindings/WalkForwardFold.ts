@@ -0,0 +1,5 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { BacktestStatistic } from "./BacktestStatistic";
+import type { OptimizationResult } from "./OptimizationResult";
+
+export type WalkForwardFold = { index: number, in_sample_start: number, in_sample_end: number, out_of_sample_start: number, out_of_sample_end: number, in_sample?: OptimizationResult, out_of_sample?: BacktestStatistic, error_message?: string, };
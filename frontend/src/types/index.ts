@@ -1,14 +1,23 @@
 export * from './bindings/AddStrategyRequest'
 export * from './bindings/AvailableCandleInfo'
+export * from './bindings/BacktestConfig'
+export * from './bindings/BacktestConfigRequest'
+export * from './bindings/BacktestEngine'
 export * from './bindings/BacktestStatistic'
 export * from './bindings/BacktestStatus'
 export * from './bindings/BacktestTask'
+export * from './bindings/BrokerType'
 export * from './bindings/Candle'
 export * from './bindings/CreateBacktestTaskRequest'
 export * from './bindings/CreateBacktestTaskResponse'
 export * from './bindings/CreateFetchCandlesTaskRequest'
 export * from './bindings/CreateFetchCandlesTaskResponse'
+export * from './bindings/CreateOptimizationTaskRequest'
+export * from './bindings/CreateOptimizationTaskResponse'
+export * from './bindings/CreatePaperSessionRequest'
+export * from './bindings/CreatePaperSessionResponse'
 export * from './bindings/DeleteSourceQuery'
+export * from './bindings/EquityPoint'
 export * from './bindings/ErrorResponse'
 export * from './bindings/ExchangeQuery'
 export * from './bindings/FetchCandlesResult'
@@ -16,14 +25,39 @@ export * from './bindings/FetchCandlesStatus'
 export * from './bindings/FetchCandlesTask'
 export * from './bindings/FileNode'
 export * from './bindings/FileNodeType'
+export * from './bindings/FillModelConfig'
 export * from './bindings/GetCandlesQuery'
 export * from './bindings/GetSourceQuery'
 export * from './bindings/GetSourceResponse'
+export * from './bindings/ListBacktestTasksQuery'
+export * from './bindings/ListBacktestTasksResponse'
+export * from './bindings/ListParametersQuery'
+export * from './bindings/ListParametersResponse'
 export * from './bindings/ListStrategiesResponse'
+export * from './bindings/MarginConfig'
 export * from './bindings/MarketPrecision'
 export * from './bindings/MoveSourceQuery'
+export * from './bindings/Optimization'
+export * from './bindings/OptimizationMetric'
+export * from './bindings/OptimizationResult'
+export * from './bindings/OptimizationTask'
 export * from './bindings/OrderType'
+export * from './bindings/PaperAccount'
+export * from './bindings/PaperOrder'
+export * from './bindings/PaperSession'
+export * from './bindings/PaperStatus'
+export * from './bindings/ParameterRange'
+export * from './bindings/ParameterSearch'
+export * from './bindings/RiskEvent'
+export * from './bindings/RiskLimits'
+export * from './bindings/RiskRule'
 export * from './bindings/SaveSourceQuery'
+export * from './bindings/StrategyParameter'
+export * from './bindings/SymbolConfig'
+export * from './bindings/SymbolStatistic'
 export * from './bindings/Timeframe'
 export * from './bindings/Trade'
 export * from './bindings/TradeType'
+export * from './bindings/TradingFees'
+export * from './bindings/WalkForward'
+export * from './bindings/WalkForwardFold'
@@ -1,22 +1,185 @@
 use proc_macro::TokenStream;
 use quote::quote;
-use syn::{parse_macro_input, DeriveInput};
+use syn::{
+    Data, DeriveInput, Expr, Fields, Ident, MetaNameValue, Token, parse_macro_input,
+    punctuated::Punctuated,
+};
+
+const PARAM_ATTRIBUTE_NAME: &str = "param";
+
+struct Param {
+    ident: Ident,
+    min: Option<Expr>,
+    max: Option<Expr>,
+    step: Option<Expr>,
+    default: Option<Expr>,
+}
+
+fn take_params(input: &mut DeriveInput) -> syn::Result<Vec<Param>> {
+    let Data::Struct(data) = &mut input.data else {
+        return Ok(Vec::new());
+    };
+    let Fields::Named(fields) = &mut data.fields else {
+        return Ok(Vec::new());
+    };
+
+    let mut params = Vec::new();
+    for field in fields.named.iter_mut() {
+        let Some(index) = field
+            .attrs
+            .iter()
+            .position(|attr| attr.path().is_ident(PARAM_ATTRIBUTE_NAME))
+        else {
+            continue;
+        };
+        let attr = field.attrs.remove(index);
+
+        let mut param = Param {
+            ident: field.ident.clone().unwrap(),
+            min: None,
+            max: None,
+            step: None,
+            default: None,
+        };
+
+        if matches!(attr.meta, syn::Meta::List(_)) {
+            let args =
+                attr.parse_args_with(Punctuated::<MetaNameValue, Token![,]>::parse_terminated)?;
+            for arg in args {
+                let slot = if arg.path.is_ident("min") {
+                    &mut param.min
+                } else if arg.path.is_ident("max") {
+                    &mut param.max
+                } else if arg.path.is_ident("step") {
+                    &mut param.step
+                } else if arg.path.is_ident("default") {
+                    &mut param.default
+                } else {
+                    return Err(syn::Error::new_spanned(
+                        arg.path,
+                        "expected one of `min`, `max`, `step`, `default`",
+                    ));
+                };
+                *slot = Some(arg.value);
+            }
+        }
+
+        params.push(param);
+    }
+
+    Ok(params)
+}
+
+fn optional_f64(expr: &Option<Expr>) -> proc_macro2::TokenStream {
+    match expr {
+        Some(expr) => quote! { ::std::option::Option::Some((#expr) as f64) },
+        None => quote! { ::std::option::Option::None },
+    }
+}
 
 #[proc_macro_attribute]
 pub fn strategy(_attr: TokenStream, item: TokenStream) -> TokenStream {
-    let input: DeriveInput = parse_macro_input!(item as DeriveInput);
+    let mut input: DeriveInput = parse_macro_input!(item as DeriveInput);
+    let params = match take_params(&mut input) {
+        Ok(params) => params,
+        Err(err) => return err.to_compile_error().into(),
+    };
     let name = &input.ident;
 
-    const PLUGIN_CREATE_FUNCTION_NAME: &'static str = "_plugin_create";
-    let func_name = syn::Ident::new(PLUGIN_CREATE_FUNCTION_NAME, name.span());
+    const PLUGIN_ABI_VERSION_FUNCTION_NAME: &str = "_thoth_plugin_abi_version";
+    const PLUGIN_BUILD_HASH_FUNCTION_NAME: &str = "_thoth_plugin_build_hash";
+    const PLUGIN_DEPENDENCIES_FUNCTION_NAME: &str = "_thoth_plugin_dependencies";
+    const PLUGIN_VTABLE_FUNCTION_NAME: &str = "_thoth_plugin_vtable";
+    let abi_version_name = Ident::new(PLUGIN_ABI_VERSION_FUNCTION_NAME, name.span());
+    let build_hash_name = Ident::new(PLUGIN_BUILD_HASH_FUNCTION_NAME, name.span());
+    let dependencies_name = Ident::new(PLUGIN_DEPENDENCIES_FUNCTION_NAME, name.span());
+    let vtable_name = Ident::new(PLUGIN_VTABLE_FUNCTION_NAME, name.span());
+
+    let defaults = params.iter().filter_map(|param| {
+        let ident = &param.ident;
+        let key = ident.to_string();
+        param.default.as_ref().map(|default| {
+            quote! {
+                ::thoth::strategy::set_parameter(
+                    &mut strategy.#ident,
+                    #key,
+                    &::thoth::strategy::parameter_value(#default),
+                )?;
+            }
+        })
+    });
+
+    let descriptions = params.iter().map(|param| {
+        let ident = &param.ident;
+        let key = ident.to_string();
+        let min = optional_f64(&param.min);
+        let max = optional_f64(&param.max);
+        let step = optional_f64(&param.step);
+        quote! {
+            ::thoth::strategy::describe_parameter(#key, &strategy.#ident, #min, #max, #step)?
+        }
+    });
+
+    let setters = params.iter().map(|param| {
+        let ident = &param.ident;
+        let key = ident.to_string();
+        quote! {
+            #key => ::thoth::strategy::set_parameter(&mut strategy.#ident, name, value)?,
+        }
+    });
 
     let expanded = quote! {
         #input
 
+        #[doc(hidden)]
+        #[allow(unused_mut)]
+        fn __thoth_default_strategy() -> ::thoth::AppResult<#name> {
+            let mut strategy = <#name as ::std::default::Default>::default();
+            #(#defaults)*
+            Ok(strategy)
+        }
+
+        impl ::thoth::strategy::StrategyPlugin for #name {
+            fn parameters() -> ::thoth::AppResult<::std::vec::Vec<::thoth::StrategyParameter>> {
+                let strategy = __thoth_default_strategy()?;
+                Ok(::std::vec![#(#descriptions),*])
+            }
+
+            #[allow(unused_variables, unused_mut)]
+            fn create(parameters: &::thoth::ParameterValues) -> ::thoth::AppResult<Self> {
+                let mut strategy = __thoth_default_strategy()?;
+                for (name, value) in parameters {
+                    match name.as_str() {
+                        #(#setters)*
+                        _ => {
+                            return Err(::thoth::strategy::unknown_parameter(name));
+                        }
+                    }
+                }
+                Ok(strategy)
+            }
+        }
+
+        #[unsafe(no_mangle)]
+        pub extern "C" fn #abi_version_name() -> u32 {
+            ::thoth::strategy::PLUGIN_ABI_VERSION
+        }
+
+        #[unsafe(no_mangle)]
+        pub extern "C" fn #build_hash_name() -> u64 {
+            ::thoth::strategy::PLUGIN_BUILD_HASH
+        }
+
+        #[unsafe(no_mangle)]
+        pub extern "C" fn #dependencies_name() -> *const ::std::ffi::c_char {
+            ::thoth::strategy::plugin_dependencies()
+        }
+
         #[unsafe(no_mangle)]
-        pub fn #func_name() -> *mut dyn ::thoth::Strategy {
-            let strategy = <#name as ::std::default::Default>::default();
-            Box::into_raw(Box::new(strategy))
+        pub extern "C" fn #vtable_name() -> *const ::thoth::strategy::PluginVTable {
+            static VTABLE: ::thoth::strategy::PluginVTable =
+                ::thoth::strategy::PluginVTable::new::<#name>();
+            &VTABLE
         }
     };
 
@@ -0,0 +1,80 @@
+use std::{
+    collections::hash_map::DefaultHasher,
+    env, fs,
+    hash::{Hash, Hasher},
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+include!("src/strategy/shared_crates.rs");
+
+// Fingerprints the sources and toolchain so strategy plugins built against a different thoth are
+// rejected at load time instead of sharing mismatched types
+fn main() {
+    println!("cargo:rerun-if-changed=src");
+    println!("cargo:rerun-if-changed=Cargo.toml");
+
+    let mut hasher = DefaultHasher::new();
+    env::var("CARGO_PKG_VERSION")
+        .unwrap_or_default()
+        .hash(&mut hasher);
+    env::var("TARGET").unwrap_or_default().hash(&mut hasher);
+
+    let rustc = env::var("RUSTC").unwrap_or_else(|_| "rustc".to_string());
+    if let Ok(output) = Command::new(rustc).arg("-vV").output() {
+        output.stdout.hash(&mut hasher);
+    }
+
+    let manifest_dir = env::var("CARGO_MANIFEST_DIR").unwrap_or_default();
+    let manifest_dir = Path::new(&manifest_dir);
+    if let Ok(manifest) = fs::read(manifest_dir.join("Cargo.toml")) {
+        manifest.hash(&mut hasher);
+    }
+    hash_dir(&manifest_dir.join("src"), &mut hasher);
+
+    // The lock file of the workspace being built, found above the target directory. OUT_DIR
+    // itself is skipped since it holds the copy written below
+    let out_dir = PathBuf::from(env::var("OUT_DIR").unwrap_or_default());
+    let lock_path = out_dir
+        .ancestors()
+        .skip(1)
+        .chain(manifest_dir.ancestors())
+        .map(|dir| dir.join("Cargo.lock"))
+        .find(|path| path.is_file());
+    let lock = match &lock_path {
+        Some(path) => {
+            println!("cargo:rerun-if-changed={}", path.display());
+            fs::read_to_string(path).unwrap_or_default()
+        }
+        None => String::new(),
+    };
+    let dependencies = locked_versions(&lock);
+    dependencies.hash(&mut hasher);
+    let copy = out_dir.join("Cargo.lock");
+    if fs::read_to_string(&copy).ok().as_deref() != Some(lock.as_str()) {
+        let _ = fs::write(copy, &lock);
+    }
+
+    println!("cargo:rustc-env=THOTH_DEPENDENCIES={}", dependencies);
+    println!("cargo:rustc-env=THOTH_BUILD_ID={:016x}", hasher.finish());
+}
+
+fn hash_dir(dir: &Path, hasher: &mut DefaultHasher) {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+    let mut paths: Vec<_> = entries
+        .filter_map(|entry| entry.ok())
+        .map(|e| e.path())
+        .collect();
+    paths.sort();
+
+    for path in paths {
+        if path.is_dir() {
+            hash_dir(&path, hasher);
+        } else if let Ok(contents) = fs::read(&path) {
+            path.file_name().hash(hasher);
+            contents.hash(hasher);
+        }
+    }
+}
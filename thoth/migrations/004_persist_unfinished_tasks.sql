@@ -0,0 +1,3 @@
+ALTER TABLE fetch_candles_tasks ALTER COLUMN completed_at DROP NOT NULL;
+ALTER TABLE backtest_tasks ALTER COLUMN completed_at DROP NOT NULL;
+ALTER TABLE optimization_tasks ALTER COLUMN completed_at DROP NOT NULL;
@@ -0,0 +1,33 @@
+ALTER TABLE backtest_tasks
+    ADD COLUMN status TEXT,
+    ADD COLUMN strategy TEXT,
+    ADD COLUMN exchange TEXT,
+    ADD COLUMN symbol TEXT,
+    ADD COLUMN created_at TIMESTAMPTZ,
+    ADD COLUMN trades JSONB NOT NULL DEFAULT '[]';
+
+-- Trades move to their own column, so listing tasks doesn't read them
+UPDATE backtest_tasks SET
+    status = data->>'status',
+    strategy = data->>'name',
+    exchange = data->>'exchange',
+    symbol = data->>'symbol',
+    created_at = to_timestamp((data->>'created_at')::double precision / 1000),
+    trades = COALESCE(data #> '{statistic,trades}', '[]'),
+    data = CASE
+        WHEN jsonb_typeof(data->'statistic') = 'object'
+        THEN jsonb_set(data, '{statistic,trades}', '[]')
+        ELSE data
+    END;
+
+ALTER TABLE backtest_tasks
+    ALTER COLUMN status SET NOT NULL,
+    ALTER COLUMN strategy SET NOT NULL,
+    ALTER COLUMN exchange SET NOT NULL,
+    ALTER COLUMN symbol SET NOT NULL,
+    ALTER COLUMN created_at SET NOT NULL;
+
+CREATE INDEX backtest_tasks_created_at_idx ON backtest_tasks (created_at DESC);
+CREATE INDEX backtest_tasks_status_idx ON backtest_tasks (status, created_at DESC);
+CREATE INDEX backtest_tasks_strategy_idx ON backtest_tasks (strategy, created_at DESC);
+CREATE INDEX backtest_tasks_symbol_idx ON backtest_tasks (exchange, symbol, created_at DESC);
@@ -0,0 +1,6 @@
+CREATE TABLE paper_sessions (
+    id UUID PRIMARY KEY,
+    data JSONB NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL,
+    stopped_at TIMESTAMPTZ
+);
@@ -0,0 +1,36 @@
+-- Backtests saved before fees and the quote currency were stored ran with the exchange's fees for
+-- the symbol. Those are recovered from the lowest rate the task's own trades paid, since fees round
+-- up: taker for market orders and maker for limit orders, each standing in for the other when the
+-- task made only one kind
+WITH rates AS (
+    SELECT
+        id,
+        (
+            SELECT round(min((t->>'fee')::numeric / ((t->>'price')::numeric * (t->>'amount')::numeric)), 5)
+            FROM jsonb_array_elements(trades) t
+            WHERE t->>'trade_type' IN ('limit_buy', 'limit_sell')
+                AND (t->>'price')::numeric * (t->>'amount')::numeric > 0
+        ) AS maker,
+        (
+            SELECT round(min((t->>'fee')::numeric / ((t->>'price')::numeric * (t->>'amount')::numeric)), 5)
+            FROM jsonb_array_elements(trades) t
+            WHERE t->>'trade_type' IN ('market_buy', 'market_sell')
+                AND (t->>'price')::numeric * (t->>'amount')::numeric > 0
+        ) AS taker
+    FROM backtest_tasks
+    WHERE NOT data ? 'fees'
+)
+UPDATE backtest_tasks SET data = backtest_tasks.data || jsonb_build_object(
+    'fees', jsonb_build_object(
+        'maker', trim_scale(COALESCE(rates.maker, rates.taker, 0))::text,
+        'taker', trim_scale(COALESCE(rates.taker, rates.maker, 0))::text
+    )
+)
+FROM rates
+WHERE backtest_tasks.id = rates.id;
+
+UPDATE backtest_tasks
+SET data = data || jsonb_build_object(
+    'quote_currency', split_part(split_part(symbol, '/', 2), ':', 1)
+)
+WHERE COALESCE(data->>'quote_currency', '') = '';
@@ -1,11 +1,20 @@
 use crate::AppResult;
-use crate::services::tasks::{load_backtest_tasks, load_fetch_candles_tasks};
-use crate::tasks::{BacktestTask, FetchCandlesTask};
-use crate::{handlers, strategy::StrategyManager};
+use crate::exchange::{ExchangeConfig, MarketCache, MarketCacheConfig};
+use crate::handlers;
+use crate::services::tasks::{
+    load_fetch_candles_tasks, load_optimization_tasks, load_paper_sessions,
+    load_unfinished_backtest_tasks, save_backtest_task, save_optimization_task, save_paper_session,
+};
+use crate::strategy::{BrokerType, StrategyManager};
+use crate::tasks::{
+    BacktestTask, FetchCandlesStatus, FetchCandlesTask, OptimizationTask, PaperSession,
+    PaperStatus, StrategyTask, TaskConfig, TaskControl, TaskQueue,
+};
 use axum::{
     Router,
-    routing::{get, post},
+    routing::{delete, get, post},
 };
+use chrono::Utc;
 use sqlx::PgPool;
 use std::collections::HashMap;
 use std::sync::Arc;
@@ -14,48 +23,141 @@ use tokio_util::sync::CancellationToken;
 use tower_http::cors::{Any, CorsLayer};
 use uuid::Uuid;
 
+// Backtests and optimizations cannot pick up where they stopped, so they fail on startup
+const INTERRUPTED_MESSAGE: &str = "Interrupted by a server restart";
+const LIVE_INTERRUPTED_MESSAGE: &str =
+    "Interrupted by a server restart, live sessions are not resumed";
+
 #[derive(Debug, Clone)]
 pub struct AppState {
     pub fetch_candles_event_tx: broadcast::Sender<FetchCandlesTask>,
     pub fetch_candles_tasks: Arc<RwLock<HashMap<Uuid, Arc<RwLock<FetchCandlesTask>>>>>,
     pub backtest_event_tx: broadcast::Sender<BacktestTask>,
     pub backtest_tasks: Arc<RwLock<HashMap<Uuid, Arc<RwLock<BacktestTask>>>>>,
+    pub optimization_event_tx: broadcast::Sender<OptimizationTask>,
+    pub optimization_tasks: Arc<RwLock<HashMap<Uuid, Arc<RwLock<OptimizationTask>>>>>,
+    pub paper_event_tx: broadcast::Sender<PaperSession>,
+    pub paper_sessions: Arc<RwLock<HashMap<Uuid, Arc<RwLock<PaperSession>>>>>,
+    // Sessions that are still trading
+    pub paper_controls: Arc<RwLock<HashMap<Uuid, TaskControl>>>,
+    pub fetch_candles_queue: TaskQueue,
+    // Shared by backtests and optimizations
+    pub backtest_queue: TaskQueue,
     pub strategy_manager: StrategyManager,
+    pub task_config: TaskConfig,
+    // API keys for live trading, by exchange name
+    pub exchanges: Arc<HashMap<String, ExchangeConfig>>,
+    pub markets: Arc<MarketCache>,
     pub db_pool: PgPool,
     pub shutdown_token: CancellationToken,
 }
 
-pub async fn create_app(db_pool: PgPool, shutdown_token: CancellationToken) -> AppResult<Router> {
+pub async fn create_app(
+    db_pool: PgPool,
+    shutdown_token: CancellationToken,
+    task_config: TaskConfig,
+    exchanges: HashMap<String, ExchangeConfig>,
+    market_cache: MarketCacheConfig,
+) -> AppResult<Router> {
     let (fetch_candles_event_tx, _) = broadcast::channel(1000);
     let mut fetch_candles_tasks = HashMap::new();
-    let loaded_fetch_candles_tasks = load_fetch_candles_tasks(&db_pool).await?;
-    for task in loaded_fetch_candles_tasks {
+    let mut resumed_fetch_candles_tasks = Vec::new();
+    let mut loaded_fetch_candles_tasks = load_fetch_candles_tasks(&db_pool).await?;
+    loaded_fetch_candles_tasks.sort_by_key(|task| task.created_at);
+    for mut task in loaded_fetch_candles_tasks {
         let task_id = task.id;
+        let resume = recover_fetch_candles_task(&mut task, &fetch_candles_event_tx);
         let task = Arc::new(RwLock::new(task));
+        if resume {
+            resumed_fetch_candles_tasks.push((task_id, task.clone()));
+        }
         fetch_candles_tasks.insert(task_id, task);
     }
 
     let (backtest_event_tx, _) = broadcast::channel(1000);
-    let mut backtest_tasks = HashMap::new();
-    let loaded_backtest_tasks = load_backtest_tasks(&db_pool).await?;
-    for task in loaded_backtest_tasks {
+    // Finished backtests stay in the database and are listed from there
+    let unfinished_backtest_tasks = load_unfinished_backtest_tasks(&db_pool).await?;
+    for mut task in unfinished_backtest_tasks {
+        interrupt(&mut task);
+        save_backtest_task(&db_pool, &task).await?;
+    }
+
+    let (optimization_event_tx, _) = broadcast::channel(1000);
+    let mut optimization_tasks = HashMap::new();
+    let loaded_optimization_tasks = load_optimization_tasks(&db_pool).await?;
+    for mut task in loaded_optimization_tasks {
         let task_id = task.id;
+        if task.completed_at.is_none() {
+            interrupt(&mut task);
+            save_optimization_task(&db_pool, &task).await?;
+        }
+
         let task = Arc::new(RwLock::new(task));
-        backtest_tasks.insert(task_id, task);
+        optimization_tasks.insert(task_id, task);
+    }
+
+    let (paper_event_tx, _) = broadcast::channel(1000);
+    let mut paper_sessions = HashMap::new();
+    let mut resumed_paper_sessions = Vec::new();
+    for mut session in load_paper_sessions(&db_pool).await? {
+        let session_id = session.id;
+        let trading = session.stopped_at.is_none();
+        let resume = recover_paper_session(&mut session, &paper_event_tx);
+        if trading && !resume {
+            save_paper_session(&db_pool, &session).await?;
+        }
+
+        let session = Arc::new(RwLock::new(session));
+        if resume {
+            resumed_paper_sessions.push((session_id, session.clone()));
+        }
+        paper_sessions.insert(session_id, session);
     }
 
     let strategy_manager = StrategyManager::new().expect("Failed to create StrategyManager");
 
+    let exchanges = Arc::new(exchanges);
     let state = AppState {
         fetch_candles_event_tx,
         fetch_candles_tasks: Arc::new(RwLock::new(fetch_candles_tasks)),
         backtest_event_tx,
-        backtest_tasks: Arc::new(RwLock::new(backtest_tasks)),
+        backtest_tasks: Arc::new(RwLock::new(HashMap::new())),
+        optimization_event_tx,
+        optimization_tasks: Arc::new(RwLock::new(optimization_tasks)),
+        paper_event_tx,
+        paper_sessions: Arc::new(RwLock::new(paper_sessions)),
+        paper_controls: Arc::new(RwLock::new(HashMap::new())),
+        fetch_candles_queue: TaskQueue::new(task_config.max_fetches, db_pool.clone()),
+        backtest_queue: TaskQueue::new(task_config.max_backtests, db_pool.clone()),
         strategy_manager,
+        task_config,
+        markets: Arc::new(MarketCache::new(market_cache, exchanges.clone())),
+        exchanges,
         db_pool,
         shutdown_token,
     };
 
+    for (task_id, task) in resumed_fetch_candles_tasks {
+        tracing::info!("Resuming fetch candles task {}", task_id);
+        let db_pool = state.db_pool.clone();
+        let exchanges = state.exchanges.clone();
+        state
+            .fetch_candles_queue
+            .spawn(
+                task_id,
+                task,
+                async move |task: &mut FetchCandlesTask, control| {
+                    task.execute(db_pool, &exchanges, control).await;
+                },
+            )
+            .await;
+    }
+
+    for (session_id, session) in resumed_paper_sessions {
+        tracing::info!("Resuming paper session {}", session_id);
+        handlers::paper::start_session(&state, session_id, session).await;
+    }
+
     let cors = CorsLayer::new()
         .allow_origin(Any)
         .allow_methods(Any)
@@ -69,17 +171,63 @@ pub async fn create_app(db_pool: PgPool, shutdown_token: CancellationToken) -> A
         .route("/tasks/fetch", get(handlers::fetch_candles::get_all_tasks))
         .route("/tasks/fetch", post(handlers::fetch_candles::create_task))
         .route("/tasks/fetch/{id}", get(handlers::fetch_candles::get_task))
+        .route(
+            "/tasks/fetch/{id}/cancel",
+            post(handlers::fetch_candles::cancel_task),
+        )
         .route(
             "/tasks/fetch/stream",
             get(handlers::fetch_candles::stream_tasks),
         )
-        .route("/tasks/backtest", get(handlers::backtest::get_all_tasks))
+        .route("/tasks/backtest", get(handlers::backtest::list_tasks))
         .route("/tasks/backtest", post(handlers::backtest::create_task))
         .route("/tasks/backtest/{id}", get(handlers::backtest::get_task))
+        .route(
+            "/tasks/backtest/{id}",
+            delete(handlers::backtest::delete_task),
+        )
+        .route(
+            "/tasks/backtest/{id}/trades",
+            get(handlers::backtest::get_trades),
+        )
+        .route(
+            "/tasks/backtest/{id}/cancel",
+            post(handlers::backtest::cancel_task),
+        )
         .route(
             "/tasks/backtest/stream",
             get(handlers::backtest::stream_tasks),
         )
+        .route(
+            "/tasks/optimization",
+            get(handlers::optimization::get_all_tasks),
+        )
+        .route(
+            "/tasks/optimization",
+            post(handlers::optimization::create_task),
+        )
+        .route(
+            "/tasks/optimization/{id}",
+            get(handlers::optimization::get_task),
+        )
+        .route(
+            "/tasks/optimization/{id}/cancel",
+            post(handlers::optimization::cancel_task),
+        )
+        .route(
+            "/tasks/optimization/stream",
+            get(handlers::optimization::stream_tasks),
+        )
+        .route("/paper", get(handlers::paper::get_all_sessions))
+        .route("/paper", post(handlers::paper::create_session))
+        .route("/paper/{id}", get(handlers::paper::get_session))
+        .route("/paper/{id}/stop", post(handlers::paper::stop_session))
+        .route("/paper/{id}/kill", post(handlers::paper::kill_session))
+        .route(
+            "/paper/{id}/release",
+            post(handlers::paper::release_session),
+        )
+        .route("/paper/stream", get(handlers::paper::stream_sessions))
         .route("/candles", get(handlers::candles::get_candles))
         .route(
             "/candles/available",
@@ -87,6 +235,10 @@ pub async fn create_app(db_pool: PgPool, shutdown_token: CancellationToken) -> A
         )
         .route("/strategy/list", get(handlers::strategy::list_strategies))
         .route("/strategy/add", post(handlers::strategy::add_strategy))
+        .route(
+            "/strategy/parameters",
+            get(handlers::strategy::list_parameters),
+        )
         .route("/strategy/source/get", get(handlers::source::get_source))
         .route("/strategy/source/save", post(handlers::source::save_source))
         .route(
@@ -97,3 +249,179 @@ pub async fn create_app(db_pool: PgPool, shutdown_token: CancellationToken) -> A
         .layer(cors)
         .with_state(state))
 }
+
+// Unfinished fetches are queued again and continue from the last stored candle. Returns whether
+// the task is resumed
+fn recover_fetch_candles_task(
+    task: &mut FetchCandlesTask,
+    event_tx: &broadcast::Sender<FetchCandlesTask>,
+) -> bool {
+    if task.completed_at.is_some() {
+        return false;
+    }
+    task.status = FetchCandlesStatus::Pending;
+    task.queue_position = None;
+    task.started_at = None;
+    task.event_tx = Some(event_tx.clone());
+    true
+}
+
+fn interrupt<T: StrategyTask>(task: &mut T) {
+    task.fail(INTERRUPTED_MESSAGE.to_string());
+    task.set_queue_position(None);
+}
+
+// Sessions that were trading when the server stopped pick up where they left off, except live
+// ones: the exchange account moves on while the server is down. Returns whether the session is
+// resumed
+fn recover_paper_session(
+    session: &mut PaperSession,
+    event_tx: &broadcast::Sender<PaperSession>,
+) -> bool {
+    if session.stopped_at.is_some() {
+        return false;
+    }
+    if session.broker == BrokerType::Ccxt {
+        let now = Utc::now();
+        session.status = PaperStatus::Stopped;
+        session.error_message = Some(LIVE_INTERRUPTED_MESSAGE.to_string());
+        session.stopped_at = Some(now);
+        session.updated_at = now;
+        return false;
+    }
+    session.status = PaperStatus::Starting;
+    session.event_tx = Some(event_tx.clone());
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::Timeframe;
+    use crate::tasks::{BacktestConfig, BacktestStatus, PaperAccount};
+    use serde_json::json;
+
+    fn config() -> BacktestConfig {
+        serde_json::from_value(json!({
+            "exchange": "binance",
+            "symbol": "BTC/USDT",
+            "timeframe": "1h",
+            "precision": { "price_precision": "0.01", "amount_precision": "0.001" },
+        }))
+        .unwrap()
+    }
+
+    fn fetch_candles_task(completed: bool) -> FetchCandlesTask {
+        let now = Utc::now();
+        FetchCandlesTask {
+            id: Uuid::new_v4(),
+            status: FetchCandlesStatus::Running,
+            progress: 40.0,
+            symbol: "BTC/USDT".to_string(),
+            exchange: "binance".to_string(),
+            timeframe: Timeframe::H1,
+            result: None,
+            error_message: None,
+            timeout_secs: None,
+            queue_position: Some(2),
+            created_at: now,
+            started_at: Some(now),
+            completed_at: completed.then_some(now),
+            updated_at: now,
+            event_tx: None,
+        }
+    }
+
+    fn paper_session(broker: BrokerType, stopped: bool) -> PaperSession {
+        let now = Utc::now();
+        PaperSession {
+            id: Uuid::new_v4(),
+            status: if stopped {
+                PaperStatus::Stopped
+            } else {
+                PaperStatus::Running
+            },
+            name: "sma".to_string(),
+            config: config(),
+            parameters: Default::default(),
+            warmup_candles: 0,
+            broker,
+            starting_equity: None,
+            account: PaperAccount::default(),
+            risk_events: Vec::new(),
+            error_message: None,
+            created_at: now,
+            started_at: Some(now),
+            stopped_at: stopped.then_some(now),
+            updated_at: now,
+            event_tx: None,
+            kill_switch: Default::default(),
+        }
+    }
+
+    #[test]
+    fn queues_unfinished_fetches_again() {
+        let (event_tx, _) = broadcast::channel(1);
+        let mut task = fetch_candles_task(false);
+        assert!(recover_fetch_candles_task(&mut task, &event_tx));
+        assert_eq!(task.status, FetchCandlesStatus::Pending);
+        assert_eq!(task.queue_position, None);
+        assert_eq!(task.started_at, None);
+        assert!(task.event_tx.is_some());
+
+        let mut finished = fetch_candles_task(true);
+        assert!(!recover_fetch_candles_task(&mut finished, &event_tx));
+        assert_eq!(finished.status, FetchCandlesStatus::Running);
+    }
+
+    #[test]
+    fn fails_interrupted_backtests() {
+        let now = Utc::now();
+        let mut task = BacktestTask {
+            id: Uuid::new_v4(),
+            status: BacktestStatus::Running,
+            progress: 40.0,
+            name: "sma".to_string(),
+            config: config(),
+            parameters: Default::default(),
+            statistic: None,
+            error_message: None,
+            risk_events: Vec::new(),
+            timeout_secs: None,
+            queue_position: Some(1),
+            created_at: now,
+            started_at: Some(now),
+            completed_at: None,
+            updated_at: now,
+            event_tx: None,
+        };
+        interrupt(&mut task);
+        assert_eq!(task.status, BacktestStatus::Failed);
+        assert_eq!(task.error_message.as_deref(), Some(INTERRUPTED_MESSAGE));
+        assert_eq!(task.queue_position, None);
+        assert!(task.completed_at.is_some());
+    }
+
+    #[test]
+    fn resumes_simulated_paper_sessions_but_stops_live_ones() {
+        let (event_tx, _) = broadcast::channel(1);
+        let mut simulated = paper_session(BrokerType::Simulated, false);
+        assert!(recover_paper_session(&mut simulated, &event_tx));
+        assert_eq!(simulated.status, PaperStatus::Starting);
+        assert!(simulated.event_tx.is_some());
+
+        let mut live = paper_session(BrokerType::Ccxt, false);
+        assert!(!recover_paper_session(&mut live, &event_tx));
+        assert_eq!(live.status, PaperStatus::Stopped);
+        assert_eq!(
+            live.error_message.as_deref(),
+            Some(LIVE_INTERRUPTED_MESSAGE)
+        );
+        assert!(live.stopped_at.is_some());
+
+        let mut stopped = paper_session(BrokerType::Simulated, true);
+        assert!(!recover_paper_session(&mut stopped, &event_tx));
+        assert_eq!(stopped.status, PaperStatus::Stopped);
+        assert!(stopped.event_tx.is_none());
+    }
+}
@@ -1,10 +1,19 @@
 use serde::Deserialize;
+use std::collections::HashMap;
+use thoth::exchange::{ExchangeConfig, MarketCacheConfig};
+use thoth::tasks::TaskConfig;
 
 #[derive(Debug, Clone, Deserialize)]
 pub struct Config {
     pub server: ServerConfig,
     pub database: DatabaseConfig,
     pub log_level: String,
+    #[serde(default)]
+    pub tasks: TaskConfig,
+    #[serde(default)]
+    pub exchanges: HashMap<String, ExchangeConfig>,
+    #[serde(default)]
+    pub market_cache: MarketCacheConfig,
 }
 
 #[derive(Debug, Clone, Deserialize)]
@@ -23,6 +32,12 @@ impl Config {
     pub fn load() -> Result<Self, config::ConfigError> {
         let settings = config::Config::builder()
             .add_source(config::File::with_name("config/default"))
+            // Keeps API keys out of the file, e.g. THOTH_EXCHANGES__BINANCE__SECRET
+            .add_source(
+                config::Environment::with_prefix("THOTH")
+                    .prefix_separator("_")
+                    .separator("__"),
+            )
             .build()?;
 
         settings.try_deserialize()
@@ -38,6 +38,9 @@ pub enum AppError {
 
     #[error("Internal Error: {0}")]
     Internal(String),
+
+    #[error("{0}")]
+    Cancelled(String),
 }
 
 impl IntoResponse for AppError {
@@ -110,6 +113,15 @@ impl IntoResponse for AppError {
                 );
                 (StatusCode::INTERNAL_SERVER_ERROR, "Internal", msg.clone())
             }
+            AppError::Cancelled(msg) => {
+                tracing::warn!(
+                    error_type = %"Cancelled",
+                    status_code = %StatusCode::CONFLICT,
+                    message = %msg,
+                    "Task cancelled"
+                );
+                (StatusCode::CONFLICT, "Cancelled", msg.clone())
+            }
         };
 
         let body = Json(ErrorResponse {
@@ -138,6 +150,12 @@ impl From<serde_json::Error> for AppError {
     }
 }
 
+impl From<tokio::task::JoinError> for AppError {
+    fn from(err: tokio::task::JoinError) -> Self {
+        AppError::Internal(err.to_string())
+    }
+}
+
 impl From<libloading::Error> for AppError {
     fn from(err: libloading::Error) -> Self {
         AppError::Strategy(err.to_string())
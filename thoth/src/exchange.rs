@@ -1 +1,246 @@
+mod binance;
+mod cache;
 pub mod ccxt;
+mod config;
+mod okx;
+
+pub use binance::Binance;
+pub use cache::{MarketCache, MarketInfo, MarketMetadata};
+pub use config::{ExchangeConfig, ExchangeConnector, MarketCacheConfig};
+pub use okx::Okx;
+
+use crate::errors::{AppError, AppResult};
+use crate::models::{Candle, MarketPrecision, Timeframe, TradingFees};
+use crate::utils::str_to_bigdecimal;
+use ccxt::CCXT;
+use chrono::{TimeZone, Utc};
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::time::Duration;
+
+// Exchanges with a native connector, usable with `connector = "native"`
+pub const NATIVE_EXCHANGES: [&str; 2] = ["binance", "okx"];
+
+// Market data of an exchange, loaded once when connecting
+pub trait Exchange: Send + Sync {
+    fn symbols(&self) -> AppResult<Vec<String>>;
+
+    fn timeframes(&self) -> AppResult<Vec<Timeframe>>;
+
+    fn fees(&self, symbol: &str) -> AppResult<TradingFees>;
+
+    fn precision(&self, symbol: &str) -> AppResult<MarketPrecision>;
+
+    // Candles opened at or after `since`, oldest first. Exchanges cap how many one call returns
+    fn fetch_candles(
+        &self,
+        symbol: &str,
+        timeframe: Timeframe,
+        since: Option<i64>,
+        limit: Option<i64>,
+    ) -> AppResult<Vec<Candle>>;
+
+    fn first_candle(&self, symbol: &str, timeframe: Timeframe) -> AppResult<Option<Candle>> {
+        let mut left = 0i64;
+        let mut right = Utc::now().timestamp_millis();
+        let mut first_candle: Option<Candle> = None;
+
+        while left <= right {
+            let mid = left + (right - left) / 2;
+            let candles = self.fetch_candles(symbol, timeframe, Some(mid), Some(1))?;
+
+            if let Some(candle) = candles.into_iter().next() {
+                first_candle = Some(candle);
+                right = mid.saturating_sub(1);
+            } else {
+                left = mid.saturating_add(1);
+            }
+        }
+
+        Ok(first_candle)
+    }
+}
+
+// Connects to an exchange through the connector set in its config, CCXT by default
+pub fn connect(
+    exchange: &str,
+    configs: &HashMap<String, ExchangeConfig>,
+) -> AppResult<Box<dyn Exchange>> {
+    let config = configs.get(exchange).cloned().unwrap_or_default();
+    match config.connector {
+        ExchangeConnector::Ccxt => Ok(Box::new(CCXT::with_exchange(exchange)?)),
+        ExchangeConnector::Native => {
+            let base_url = config.base_url.as_deref();
+            match exchange {
+                "binance" => Ok(Box::new(Binance::connect(base_url)?)),
+                "okx" => Ok(Box::new(Okx::connect(base_url)?)),
+                _ => Err(AppError::BadRequest(format!(
+                    "No native connector for exchange: {}",
+                    exchange
+                ))),
+            }
+        }
+    }
+}
+
+// Exchanges CCXT supports along with the ones configured to connect natively
+pub fn available(configs: &HashMap<String, ExchangeConfig>) -> AppResult<Vec<String>> {
+    let native: Vec<String> = NATIVE_EXCHANGES
+        .iter()
+        .filter(|name| {
+            configs
+                .get(**name)
+                .is_some_and(|config| config.connector == ExchangeConnector::Native)
+        })
+        .map(|name| name.to_string())
+        .collect();
+
+    let mut exchanges = match CCXT::exchanges() {
+        Ok(exchanges) => exchanges,
+        // Native connectors work without the Python module
+        Err(e) if !native.is_empty() => {
+            tracing::warn!("Failed to list CCXT exchanges: {}", e);
+            Vec::new()
+        }
+        Err(e) => return Err(e),
+    };
+    for name in native {
+        if !exchanges.contains(&name) {
+            exchanges.push(name);
+        }
+    }
+    exchanges.sort();
+    Ok(exchanges)
+}
+
+// Market data endpoints are public, so native connectors send no API keys
+fn agent() -> ureq::Agent {
+    ureq::Agent::config_builder()
+        .timeout_global(Some(Duration::from_secs(30)))
+        .http_status_as_error(false)
+        .build()
+        .into()
+}
+
+// Failed requests are reported with the exchange's own error body
+fn get_json<T: DeserializeOwned>(
+    agent: &ureq::Agent,
+    exchange: &str,
+    url: &str,
+    query: &[(&str, String)],
+) -> AppResult<T> {
+    let request_error = |e: ureq::Error| format!("{} request failed: {}", exchange, e);
+    let mut response = agent
+        .get(url)
+        .query_pairs(query.iter().map(|(key, value)| (*key, value.as_str())))
+        .call()
+        .map_err(request_error)?;
+
+    let status = response.status();
+    if !status.is_success() {
+        let body = response.body_mut().read_to_string().unwrap_or_default();
+        return Err(format!("{} request failed with {}: {}", exchange, status, body).into());
+    }
+    // Market listings run into several megabytes
+    Ok(response
+        .body_mut()
+        .with_config()
+        .limit(64 * 1024 * 1024)
+        .read_json()
+        .map_err(request_error)?)
+}
+
+// A candle from a row of [open time, open, high, low, close, volume, ...], where exchanges send
+// numbers either as JSON numbers or strings
+fn parse_candle(
+    exchange: &str,
+    symbol: &str,
+    timeframe: Timeframe,
+    row: &[Value],
+) -> AppResult<Candle> {
+    let field = |index: usize| -> AppResult<String> {
+        match row.get(index) {
+            Some(Value::String(value)) => Ok(value.clone()),
+            Some(Value::Number(value)) => Ok(value.to_string()),
+            _ => Err(format!("Invalid candle from {}: {:?}", exchange, row).into()),
+        }
+    };
+
+    let timestamp_ms: i64 = field(0)?
+        .parse()
+        .map_err(|_| format!("Invalid candle from {}: {:?}", exchange, row))?;
+    let Some(timestamp) = Utc.timestamp_millis_opt(timestamp_ms).single() else {
+        return Err(format!("Error while parse timestamp: {}", timestamp_ms).into());
+    };
+
+    Ok(Candle {
+        timestamp,
+        exchange: exchange.to_string(),
+        symbol: symbol.to_string(),
+        timeframe,
+        open: str_to_bigdecimal(&field(1)?, "open price")?,
+        high: str_to_bigdecimal(&field(2)?, "high price")?,
+        low: str_to_bigdecimal(&field(3)?, "low price")?,
+        close: str_to_bigdecimal(&field(4)?, "close price")?,
+        volume: str_to_bigdecimal(&field(5)?, "volume")?,
+    })
+}
+
+// The public APIs have no fee schedule, so native connectors charge every market the exchange's
+// base spot tier
+fn base_spot_fees(maker: &str, taker: &str) -> AppResult<TradingFees> {
+    Ok(TradingFees {
+        maker: str_to_bigdecimal(maker, "maker fee")?,
+        taker: str_to_bigdecimal(taker, "taker fee")?,
+    })
+}
+
+fn unknown_symbol(exchange: &str, symbol: &str) -> AppError {
+    AppError::BadRequest(format!("Unknown symbol on {}: {}", exchange, symbol))
+}
+
+#[cfg(test)]
+pub(super) mod testing {
+    pub(crate) use super::cache::testing::cache;
+    use std::collections::HashMap;
+    use std::io::{BufRead, BufReader, Write};
+    use std::net::TcpListener;
+
+    // Serves each GET with the JSON the handler returns for its path and query, and returns the
+    // server's base URL
+    pub(crate) fn serve(
+        handler: impl Fn(&str, &HashMap<String, String>) -> String + Send + 'static,
+    ) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let base_url = format!("http://{}", listener.local_addr().unwrap());
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                let mut stream = stream.unwrap();
+                let mut reader = BufReader::new(&stream);
+                let mut request_line = String::new();
+                reader.read_line(&mut request_line).unwrap();
+                let mut line = String::new();
+                while reader.read_line(&mut line).unwrap() > 2 {
+                    line.clear();
+                }
+
+                let target = request_line.split(' ').nth(1).unwrap_or_default();
+                let (path, query) = target.split_once('?').unwrap_or((target, ""));
+                let query = query
+                    .split('&')
+                    .filter_map(|pair| pair.split_once('='))
+                    .map(|(key, value)| (key.to_string(), value.to_string()))
+                    .collect();
+                let body = handler(path, &query);
+                let _ = write!(
+                    stream,
+                    "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    body.len(),
+                    body
+                );
+            }
+        });
+        base_url
+    }
+}
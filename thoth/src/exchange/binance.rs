@@ -0,0 +1,257 @@
+use super::{Exchange, agent, base_spot_fees, get_json, parse_candle, unknown_symbol};
+use crate::errors::AppResult;
+use crate::models::{Candle, MarketPrecision, Timeframe, TradingFees};
+use crate::utils::str_to_bigdecimal;
+use bigdecimal::BigDecimal;
+use serde::Deserialize;
+use serde_json::Value;
+use std::collections::HashMap;
+
+const NAME: &str = "binance";
+const BASE_URL: &str = "https://api.binance.com";
+const MAKER_FEE: &str = "0.001";
+const TAKER_FEE: &str = "0.001";
+// Most candles one klines request returns
+const MAX_LIMIT: i64 = 1000;
+// Binance's intervals are named like `Timeframe`'s
+const TIMEFRAMES: [Timeframe; 16] = [
+    Timeframe::S1,
+    Timeframe::M1,
+    Timeframe::M3,
+    Timeframe::M5,
+    Timeframe::M15,
+    Timeframe::M30,
+    Timeframe::H1,
+    Timeframe::H2,
+    Timeframe::H4,
+    Timeframe::H6,
+    Timeframe::H8,
+    Timeframe::H12,
+    Timeframe::D1,
+    Timeframe::D3,
+    Timeframe::W1,
+    Timeframe::MN1,
+];
+
+#[derive(Deserialize)]
+struct ExchangeInfo {
+    symbols: Vec<SymbolInfo>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SymbolInfo {
+    symbol: String,
+    base_asset: String,
+    quote_asset: String,
+    filters: Vec<SymbolFilter>,
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "filterType")]
+enum SymbolFilter {
+    #[serde(rename = "PRICE_FILTER", rename_all = "camelCase")]
+    Price { tick_size: String },
+    #[serde(rename = "LOT_SIZE", rename_all = "camelCase")]
+    LotSize { step_size: String },
+    #[serde(other)]
+    Other,
+}
+
+#[derive(Debug)]
+struct Market {
+    id: String,
+    precision: MarketPrecision,
+}
+
+// Spot market data from Binance's public REST API
+#[derive(Debug)]
+pub struct Binance {
+    agent: ureq::Agent,
+    base_url: String,
+    symbols: Vec<String>,
+    markets: HashMap<String, Market>,
+}
+
+impl Binance {
+    pub fn connect(base_url: Option<&str>) -> AppResult<Self> {
+        let agent = agent();
+        let base_url = base_url
+            .unwrap_or(BASE_URL)
+            .trim_end_matches('/')
+            .to_string();
+        let info: ExchangeInfo = get_json(
+            &agent,
+            NAME,
+            &format!("{}/api/v3/exchangeInfo", base_url),
+            &[],
+        )?;
+
+        let mut symbols = Vec::new();
+        let mut markets = HashMap::new();
+        for info in info.symbols {
+            let mut precision = MarketPrecision {
+                price_precision: BigDecimal::default(),
+                amount_precision: BigDecimal::default(),
+            };
+            for filter in info.filters {
+                match filter {
+                    SymbolFilter::Price { tick_size } => {
+                        precision.price_precision =
+                            str_to_bigdecimal(&tick_size, "price precision")?.normalized();
+                    }
+                    SymbolFilter::LotSize { step_size } => {
+                        precision.amount_precision =
+                            str_to_bigdecimal(&step_size, "amount precision")?.normalized();
+                    }
+                    SymbolFilter::Other => {}
+                }
+            }
+
+            let symbol = format!("{}/{}", info.base_asset, info.quote_asset);
+            symbols.push(symbol.clone());
+            markets.insert(
+                symbol,
+                Market {
+                    id: info.symbol,
+                    precision,
+                },
+            );
+        }
+
+        Ok(Self {
+            agent,
+            base_url,
+            symbols,
+            markets,
+        })
+    }
+
+    fn market(&self, symbol: &str) -> AppResult<&Market> {
+        self.markets
+            .get(symbol)
+            .ok_or_else(|| unknown_symbol(NAME, symbol))
+    }
+}
+
+impl Exchange for Binance {
+    fn symbols(&self) -> AppResult<Vec<String>> {
+        Ok(self.symbols.clone())
+    }
+
+    fn timeframes(&self) -> AppResult<Vec<Timeframe>> {
+        Ok(TIMEFRAMES.to_vec())
+    }
+
+    fn fees(&self, symbol: &str) -> AppResult<TradingFees> {
+        self.market(symbol)?;
+        base_spot_fees(MAKER_FEE, TAKER_FEE)
+    }
+
+    fn precision(&self, symbol: &str) -> AppResult<MarketPrecision> {
+        Ok(self.market(symbol)?.precision.clone())
+    }
+
+    fn fetch_candles(
+        &self,
+        symbol: &str,
+        timeframe: Timeframe,
+        since: Option<i64>,
+        limit: Option<i64>,
+    ) -> AppResult<Vec<Candle>> {
+        let market = self.market(symbol)?;
+        if !TIMEFRAMES.contains(&timeframe) {
+            return Err(format!("Timeframe {} is not supported by {}", timeframe, NAME).into());
+        }
+
+        let mut query = vec![
+            ("symbol", market.id.clone()),
+            ("interval", timeframe.to_string()),
+        ];
+        if let Some(since) = since {
+            query.push(("startTime", since.to_string()));
+        }
+        if let Some(limit) = limit {
+            query.push(("limit", limit.clamp(1, MAX_LIMIT).to_string()));
+        }
+
+        let rows: Vec<Vec<Value>> = get_json(
+            &self.agent,
+            NAME,
+            &format!("{}/api/v3/klines", self.base_url),
+            &query,
+        )?;
+        rows.iter()
+            .map(|row| parse_candle(NAME, symbol, timeframe, row))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::testing::serve;
+    use super::*;
+    use std::str::FromStr;
+
+    const EXCHANGE_INFO: &str = r#"{"symbols": [{
+        "symbol": "BTCUSDT",
+        "baseAsset": "BTC",
+        "quoteAsset": "USDT",
+        "filters": [
+            {"filterType": "PRICE_FILTER", "minPrice": "0.01000000", "tickSize": "0.01000000"},
+            {"filterType": "LOT_SIZE", "minQty": "0.00001000", "stepSize": "0.00001000"},
+            {"filterType": "NOTIONAL", "minNotional": "5.00000000"}
+        ]
+    }]}"#;
+
+    fn decimal(value: &str) -> BigDecimal {
+        BigDecimal::from_str(value).unwrap()
+    }
+
+    fn connect() -> Binance {
+        let base_url = serve(|path, query| match path {
+            "/api/v3/exchangeInfo" => EXCHANGE_INFO.to_string(),
+            "/api/v3/klines" => {
+                assert_eq!(query["symbol"], "BTCUSDT");
+                assert_eq!(query["interval"], "1h");
+                assert_eq!(query["startTime"], "1704067200000");
+                r#"[
+                    [1704067200000, "42283.58", "42554.57", "42261.02", "42475.23", "1271.68108",
+                     1704070799999, "53957248.97", 47134, "682.57581", "28957416.82", "0"],
+                    [1704070800000, "42475.23", "42775.00", "42431.65", "42613.56", "1196.37856",
+                     1704074399999, "50984893.29", 44630, "580.93225", "24755308.25", "0"]
+                ]"#
+                .to_string()
+            }
+            _ => panic!("Unexpected request to {}", path),
+        });
+        Binance::connect(Some(&base_url)).unwrap()
+    }
+
+    #[test]
+    fn reads_market_precision_from_the_symbol_filters() {
+        let binance = connect();
+        assert_eq!(binance.symbols().unwrap(), vec!["BTC/USDT".to_string()]);
+        let precision = binance.precision("BTC/USDT").unwrap();
+        assert_eq!(precision.price_precision, decimal("0.01"));
+        assert_eq!(precision.amount_precision, decimal("0.00001"));
+        assert!(binance.precision("ETH/USDT").is_err());
+    }
+
+    #[test]
+    fn parses_klines_oldest_first() {
+        let binance = connect();
+        let candles = binance
+            .fetch_candles("BTC/USDT", Timeframe::H1, Some(1704067200000), None)
+            .unwrap();
+        assert_eq!(candles.len(), 2);
+        assert_eq!(candles[0].timestamp.timestamp_millis(), 1704067200000);
+        assert_eq!(candles[0].symbol, "BTC/USDT");
+        assert_eq!(candles[0].open, decimal("42283.58"));
+        assert_eq!(candles[0].high, decimal("42554.57"));
+        assert_eq!(candles[0].low, decimal("42261.02"));
+        assert_eq!(candles[0].close, decimal("42475.23"));
+        assert_eq!(candles[0].volume, decimal("1271.68108"));
+        assert_eq!(candles[1].timestamp.timestamp_millis(), 1704070800000);
+    }
+}
@@ -0,0 +1,287 @@
+use super::{ExchangeConfig, MarketCacheConfig, connect};
+use crate::errors::{AppError, AppResult};
+use crate::models::{MarketPrecision, Timeframe, TradingFees};
+use chrono::{DateTime, TimeDelta, Utc, serde::ts_milliseconds};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MarketInfo {
+    pub fees: TradingFees,
+    pub precision: MarketPrecision,
+}
+
+// Everything a backtest needs from an exchange besides candles
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MarketMetadata {
+    pub exchange: String,
+    #[serde(with = "ts_milliseconds")]
+    pub loaded_at: DateTime<Utc>,
+    pub symbols: Vec<String>,
+    pub timeframes: Vec<Timeframe>,
+    pub markets: HashMap<String, MarketInfo>,
+}
+
+impl MarketMetadata {
+    fn load(exchange: &str, configs: &HashMap<String, ExchangeConfig>) -> AppResult<Self> {
+        let connector = connect(exchange, configs)?;
+        let symbols = connector.symbols()?;
+        let timeframes = connector.timeframes()?;
+
+        // Markets with fields the exchange leaves empty are listed, but cannot be backtested
+        let mut markets = HashMap::new();
+        for symbol in &symbols {
+            let market = connector.fees(symbol).and_then(|fees| {
+                Ok(MarketInfo {
+                    fees,
+                    precision: connector.precision(symbol)?,
+                })
+            });
+            match market {
+                Ok(market) => {
+                    markets.insert(symbol.clone(), market);
+                }
+                Err(e) => tracing::debug!("Skipping market {} on {}: {}", symbol, exchange, e),
+            }
+        }
+
+        Ok(Self {
+            exchange: exchange.to_string(),
+            loaded_at: Utc::now(),
+            symbols,
+            timeframes,
+            markets,
+        })
+    }
+
+    fn market(&self, symbol: &str) -> AppResult<&MarketInfo> {
+        self.markets.get(symbol).ok_or_else(|| {
+            AppError::BadRequest(format!(
+                "No fees or precision for {} on {}",
+                symbol, self.exchange
+            ))
+        })
+    }
+
+    pub fn fees(&self, symbol: &str) -> AppResult<TradingFees> {
+        Ok(self.market(symbol)?.fees.clone())
+    }
+
+    pub fn precision(&self, symbol: &str) -> AppResult<MarketPrecision> {
+        Ok(self.market(symbol)?.precision.clone())
+    }
+}
+
+// Market metadata shared by every request, reloaded from the exchange once it is older than the
+// TTL. Each exchange's copy is also saved to disk, so it survives restarts and stands in when the
+// exchange cannot be reached
+#[derive(Debug)]
+pub struct MarketCache {
+    config: MarketCacheConfig,
+    exchanges: Arc<HashMap<String, ExchangeConfig>>,
+    markets: Mutex<HashMap<String, Arc<MarketMetadata>>>,
+}
+
+impl MarketCache {
+    pub fn new(config: MarketCacheConfig, exchanges: Arc<HashMap<String, ExchangeConfig>>) -> Self {
+        Self {
+            config,
+            exchanges,
+            markets: Mutex::new(HashMap::new()),
+        }
+    }
+
+    // Blocks while the exchange is loaded
+    pub fn get(&self, exchange: &str) -> AppResult<Arc<MarketMetadata>> {
+        let cached = self.cached(exchange);
+        let ttl = TimeDelta::seconds(self.config.ttl_secs.try_into().unwrap_or(i64::MAX));
+        if let Some(metadata) = &cached
+            && Utc::now() - metadata.loaded_at < ttl
+        {
+            return Ok(metadata.clone());
+        }
+
+        match MarketMetadata::load(exchange, &self.exchanges) {
+            Ok(metadata) => {
+                let metadata = Arc::new(metadata);
+                if let Err(e) = self.save(&metadata) {
+                    tracing::warn!("Failed to save market data of {}: {}", exchange, e);
+                }
+                self.lock().insert(exchange.to_string(), metadata.clone());
+                Ok(metadata)
+            }
+            Err(e) => match cached {
+                Some(metadata) => {
+                    tracing::warn!(
+                        "Failed to reload market data of {}, using data from {}: {}",
+                        exchange,
+                        metadata.loaded_at,
+                        e
+                    );
+                    Ok(metadata)
+                }
+                None => Err(e),
+            },
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<String, Arc<MarketMetadata>>> {
+        // The map is only ever swapped whole entries, so a panic elsewhere leaves it usable
+        self.markets.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    // From memory, or from disk for the first request after a restart
+    fn cached(&self, exchange: &str) -> Option<Arc<MarketMetadata>> {
+        if let Some(metadata) = self.lock().get(exchange) {
+            return Some(metadata.clone());
+        }
+
+        let path = self.path(exchange)?;
+        let data = fs::read(&path).ok()?;
+        let metadata: MarketMetadata = match serde_json::from_slice(&data) {
+            Ok(metadata) => metadata,
+            Err(e) => {
+                tracing::warn!("Ignoring market data in {}: {}", path.display(), e);
+                return None;
+            }
+        };
+        let mut markets = self.lock();
+        Some(
+            markets
+                .entry(exchange.to_string())
+                .or_insert(Arc::new(metadata))
+                .clone(),
+        )
+    }
+
+    fn save(&self, metadata: &MarketMetadata) -> AppResult<()> {
+        let Some(path) = self.path(&metadata.exchange) else {
+            return Ok(());
+        };
+        fs::create_dir_all(&self.config.dir)?;
+        // Written aside first, so a crash never leaves a half-written file behind
+        let partial = path.with_extension("json.partial");
+        fs::write(&partial, serde_json::to_vec(metadata)?)?;
+        fs::rename(&partial, &path)?;
+        Ok(())
+    }
+
+    // Exchange names come from requests, so only plain ones are turned into file names
+    fn path(&self, exchange: &str) -> Option<PathBuf> {
+        let plain = !exchange.is_empty() && exchange.chars().all(|c| c.is_ascii_alphanumeric());
+        plain.then(|| self.config.dir.join(format!("{}.json", exchange)))
+    }
+}
+
+#[cfg(test)]
+pub(super) mod testing {
+    use super::*;
+
+    // A cache already holding the exchange's markets, so lookups never reach the exchange
+    pub(crate) fn cache(exchange: &str, markets: &[(&str, MarketInfo)]) -> MarketCache {
+        let cache = MarketCache::new(
+            MarketCacheConfig {
+                dir: PathBuf::new(),
+                ..Default::default()
+            },
+            Arc::new(HashMap::new()),
+        );
+        let metadata = MarketMetadata {
+            exchange: exchange.to_string(),
+            loaded_at: Utc::now(),
+            symbols: markets
+                .iter()
+                .map(|(symbol, _)| symbol.to_string())
+                .collect(),
+            timeframes: vec![Timeframe::H1],
+            markets: markets
+                .iter()
+                .map(|(symbol, market)| (symbol.to_string(), market.clone()))
+                .collect(),
+        };
+        cache
+            .lock()
+            .insert(exchange.to_string(), Arc::new(metadata));
+        cache
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::exchange::ExchangeConnector;
+
+    // "kraken" has no native connector, so loading it always fails without reaching the network
+    fn offline_cache(dir: PathBuf, ttl_secs: u64) -> MarketCache {
+        let config = ExchangeConfig {
+            connector: ExchangeConnector::Native,
+            ..Default::default()
+        };
+        MarketCache::new(
+            MarketCacheConfig { ttl_secs, dir },
+            Arc::new(HashMap::from([("kraken".to_string(), config)])),
+        )
+    }
+
+    fn metadata(loaded_at: DateTime<Utc>) -> MarketMetadata {
+        MarketMetadata {
+            exchange: "kraken".to_string(),
+            loaded_at,
+            symbols: vec!["BTC/USD".to_string()],
+            timeframes: vec![Timeframe::H1],
+            markets: HashMap::new(),
+        }
+    }
+
+    fn temp_dir() -> PathBuf {
+        std::env::temp_dir().join(format!("thoth-market-cache-{}", uuid::Uuid::new_v4()))
+    }
+
+    #[test]
+    fn reads_saved_markets_after_a_restart() {
+        let dir = temp_dir();
+        let loaded_at = Utc::now();
+        offline_cache(dir.clone(), 3600)
+            .save(&metadata(loaded_at))
+            .unwrap();
+        assert!(dir.join("kraken.json").exists());
+        assert!(!dir.join("kraken.json.partial").exists());
+
+        let metadata = offline_cache(dir.clone(), 3600).get("kraken").unwrap();
+        assert_eq!(metadata.symbols, ["BTC/USD"]);
+        assert_eq!(
+            metadata.loaded_at.timestamp_millis(),
+            loaded_at.timestamp_millis()
+        );
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn falls_back_to_stale_markets_when_the_exchange_fails() {
+        let dir = temp_dir();
+        let stale = Utc::now() - TimeDelta::hours(2);
+        let cache = offline_cache(dir.clone(), 3600);
+        cache.save(&metadata(stale)).unwrap();
+        let metadata = cache.get("kraken").unwrap();
+        assert_eq!(metadata.loaded_at.timestamp(), stale.timestamp());
+
+        // Without anything cached the error comes through
+        fs::remove_dir_all(&dir).unwrap();
+        assert!(offline_cache(dir, 3600).get("kraken").is_err());
+    }
+
+    #[test]
+    fn only_saves_plainly_named_exchanges() {
+        let cache = offline_cache(PathBuf::from("markets"), 3600);
+        assert_eq!(
+            cache.path("binance"),
+            Some(PathBuf::from("markets/binance.json"))
+        );
+        assert_eq!(cache.path("../binance"), None);
+        assert_eq!(cache.path("bin/ance"), None);
+        assert_eq!(cache.path(""), None);
+    }
+}
@@ -1,9 +1,14 @@
+use super::{Exchange, ExchangeConfig};
 use crate::errors::{AppError, AppResult};
-use crate::models::{Candle, MarketPrecision, Timeframe, TradingFees};
+use crate::models::{
+    Candle, CurrencyBalance, ExchangeOrder, MarketPrecision, Timeframe, TradingFees,
+};
 use crate::utils::str_to_bigdecimal;
+use bigdecimal::{BigDecimal, ToPrimitive, Zero};
 use chrono::{TimeZone, Utc};
 use pyo3::types::PyList;
 use pyo3::{prelude::*, types::PyDict};
+use std::collections::HashMap;
 use std::str::FromStr;
 
 #[derive(Debug)]
@@ -75,6 +80,21 @@ impl CCXT {
     }
 
     pub fn with_exchange(exchange: &str) -> AppResult<Self> {
+        Self::connect(exchange, None)
+    }
+
+    // An instance that can trade with the account's API keys
+    pub fn with_credentials(exchange: &str, config: &ExchangeConfig) -> AppResult<Self> {
+        if !config.has_credentials() {
+            return Err(AppError::BadRequest(format!(
+                "No API keys configured for exchange: {}",
+                exchange
+            )));
+        }
+        Self::connect(exchange, Some(config))
+    }
+
+    fn connect(exchange: &str, config: Option<&ExchangeConfig>) -> AppResult<Self> {
         if !Self::AVAILABLE_EXCHANGES.contains(&exchange) {
             return Err(AppError::BadRequest(format!(
                 "Invalid exchange: {}",
@@ -85,7 +105,24 @@ impl CCXT {
         Python::attach(|py| {
             let ccxt = py.import(Self::MODULE_NAME)?;
             let exchange_class = ccxt.getattr(exchange)?;
-            let exchange_instance = exchange_class.call0()?;
+            let exchange_instance = match config {
+                Some(config) => {
+                    let options = PyDict::new(py);
+                    options.set_item("apiKey", &config.api_key)?;
+                    options.set_item("secret", &config.secret)?;
+                    if let Some(password) = &config.password {
+                        options.set_item("password", password)?;
+                    }
+                    options.set_item("enableRateLimit", true)?;
+                    let instance = exchange_class.call1((options,))?;
+                    // Has to be set before the markets are loaded
+                    if config.sandbox {
+                        instance.call_method1("set_sandbox_mode", (true,))?;
+                    }
+                    instance
+                }
+                None => exchange_class.call0()?,
+            };
             exchange_instance.call_method0("load_markets")?;
 
             Ok(Self {
@@ -95,14 +132,149 @@ impl CCXT {
         })
     }
 
-    pub fn symbols(&self) -> AppResult<Vec<String>> {
+    // Base and quote currency of a market
+    pub fn currencies(&self, symbol: &str) -> AppResult<(String, String)> {
+        Python::attach(|py| {
+            let exchange = self.instance.bind(py);
+            let markets = exchange.getattr("markets")?;
+            let market = markets.get_item(symbol)?;
+
+            let base: String = market.get_item("base")?.extract()?;
+            let quote: String = market.get_item("quote")?.extract()?;
+            Ok((base, quote))
+        })
+    }
+
+    pub fn create_order(
+        &self,
+        symbol: &str,
+        buy: bool,
+        amount: &BigDecimal,
+        price: Option<&BigDecimal>,
+    ) -> AppResult<ExchangeOrder> {
+        let order_type = if price.is_some() { "limit" } else { "market" };
+        let side = if buy { "buy" } else { "sell" };
+        let amount = amount.to_f64().unwrap_or(f64::NAN);
+        let price = price.map(|price| price.to_f64().unwrap_or(f64::NAN));
+
+        Python::attach(|py| {
+            let exchange = self.instance.bind(py);
+            let args = (symbol, order_type, side, amount, price);
+            let order = exchange.call_method1("create_order", args)?;
+            Self::parse_order(&order, symbol)
+        })
+    }
+
+    pub fn cancel_order(&self, id: &str, symbol: &str) -> AppResult<()> {
+        Python::attach(|py| {
+            let exchange = self.instance.bind(py);
+            exchange.call_method1("cancel_order", (id, symbol))?;
+            Ok(())
+        })
+    }
+
+    pub fn fetch_order(&self, id: &str, symbol: &str) -> AppResult<ExchangeOrder> {
+        Python::attach(|py| {
+            let exchange = self.instance.bind(py);
+            let order = exchange.call_method1("fetch_order", (id, symbol))?;
+            Self::parse_order(&order, symbol)
+        })
+    }
+
+    pub fn fetch_open_orders(&self, symbol: &str) -> AppResult<Vec<ExchangeOrder>> {
+        Python::attach(|py| {
+            let exchange = self.instance.bind(py);
+            let orders_any = exchange.call_method1("fetch_open_orders", (symbol,))?;
+            let orders_list = orders_any
+                .cast_into::<PyList>()
+                .map_err(|e| format!("Failed to cast orders to PyList: {}", e))?;
+
+            orders_list
+                .iter()
+                .map(|order| Self::parse_order(&order, symbol))
+                .collect()
+        })
+    }
+
+    pub fn fetch_balance(&self) -> AppResult<HashMap<String, CurrencyBalance>> {
+        Python::attach(|py| {
+            let exchange = self.instance.bind(py);
+            let balance = exchange.call_method0("fetch_balance")?;
+            let free_any = balance.get_item("free")?;
+            let free = free_any.cast::<PyDict>()?;
+            let total_any = balance.get_item("total")?;
+            let total = total_any.cast::<PyDict>()?;
+
+            let mut balances = HashMap::new();
+            for (currency, amount) in total.iter() {
+                let currency: String = currency.extract()?;
+                let total = Self::decimal(&amount, "total balance")?.unwrap_or_default();
+                let free = match free.get_item(&currency)? {
+                    Some(amount) => Self::decimal(&amount, "free balance")?.unwrap_or_default(),
+                    None => BigDecimal::zero(),
+                };
+                balances.insert(currency, CurrencyBalance { free, total });
+            }
+
+            Ok(balances)
+        })
+    }
+
+    fn decimal(value: &Bound<'_, PyAny>, name: &str) -> AppResult<Option<BigDecimal>> {
+        if value.is_none() {
+            return Ok(None);
+        }
+        let value: String = value.str()?.extract()?;
+        Ok(Some(str_to_bigdecimal(&value, name)?))
+    }
+
+    // Exchanges leave out fields they do not report, so only the id and amount are required
+    fn parse_order(order: &Bound<'_, PyAny>, symbol: &str) -> AppResult<ExchangeOrder> {
+        let id: String = order.get_item("id")?.str()?.extract()?;
+        let side: Option<String> = order.get_item("side")?.extract()?;
+        let status: Option<String> = order.get_item("status")?.extract()?;
+        let price = Self::decimal(&order.get_item("price")?, "order price")?;
+        let average = Self::decimal(&order.get_item("average")?, "order average")?;
+        let amount = Self::decimal(&order.get_item("amount")?, "order amount")?
+            .ok_or_else(|| format!("Order {} has no amount", id))?;
+        let filled = Self::decimal(&order.get_item("filled")?, "order filled")?.unwrap_or_default();
+        let cost = match Self::decimal(&order.get_item("cost")?, "order cost")? {
+            Some(cost) => cost,
+            None => &filled * average.or(price.clone()).unwrap_or_default(),
+        };
+
+        let fee_any = order.get_item("fee")?;
+        let fee = if fee_any.is_none() {
+            None
+        } else {
+            let cost = Self::decimal(&fee_any.get_item("cost")?, "order fee")?;
+            let currency: Option<String> = fee_any.get_item("currency")?.extract()?;
+            cost.zip(currency)
+        };
+
+        Ok(ExchangeOrder {
+            id,
+            symbol: symbol.to_string(),
+            buy: side.as_deref() == Some("buy"),
+            status: status.unwrap_or_else(|| "open".to_string()),
+            price,
+            amount,
+            filled,
+            cost,
+            fee,
+        })
+    }
+}
+
+impl Exchange for CCXT {
+    fn symbols(&self) -> AppResult<Vec<String>> {
         Python::attach(|py| {
             let exchange = self.instance.bind(py);
             Ok(exchange.getattr("symbols")?.extract()?)
         })
     }
 
-    pub fn timeframes(&self) -> AppResult<Vec<Timeframe>> {
+    fn timeframes(&self) -> AppResult<Vec<Timeframe>> {
         Python::attach(|py| {
             let exchange = self.instance.bind(py);
             let timeframes_any = exchange.getattr("timeframes")?;
@@ -118,7 +290,7 @@ impl CCXT {
         })
     }
 
-    pub fn fees(&self, symbol: &str) -> AppResult<TradingFees> {
+    fn fees(&self, symbol: &str) -> AppResult<TradingFees> {
         Python::attach(|py| {
             let exchange = self.instance.bind(py);
             let markets = exchange.getattr("markets")?;
@@ -134,7 +306,7 @@ impl CCXT {
         })
     }
 
-    pub fn precision(&self, symbol: &str) -> AppResult<MarketPrecision> {
+    fn precision(&self, symbol: &str) -> AppResult<MarketPrecision> {
         Python::attach(|py| {
             let exchange = self.instance.bind(py);
             let markets = exchange.getattr("markets")?;
@@ -154,7 +326,7 @@ impl CCXT {
         })
     }
 
-    pub fn fetch_candles(
+    fn fetch_candles(
         &self,
         symbol: &str,
         timeframe: Timeframe,
@@ -203,24 +375,4 @@ impl CCXT {
             Ok(candles)
         })
     }
-
-    pub fn first_candle(&self, symbol: &str, timeframe: Timeframe) -> AppResult<Option<Candle>> {
-        let mut left = 0i64;
-        let mut right = Utc::now().timestamp_millis();
-        let mut first_candle: Option<Candle> = None;
-
-        while left <= right {
-            let mid = left + (right - left) / 2;
-            let candles = self.fetch_candles(symbol, timeframe, Some(mid), Some(1))?;
-
-            if let Some(candle) = candles.into_iter().next() {
-                first_candle = Some(candle);
-                right = mid.saturating_sub(1);
-            } else {
-                left = mid.saturating_add(1);
-            }
-        }
-
-        Ok(first_candle)
-    }
 }
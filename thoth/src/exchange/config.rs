@@ -0,0 +1,69 @@
+use serde::Deserialize;
+use std::fmt;
+use std::path::PathBuf;
+
+// How market data is loaded from an exchange
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ExchangeConnector {
+    // Through the embedded Python CCXT module
+    #[default]
+    Ccxt,
+    // Straight from the exchange's REST API, see `exchange::NATIVE_EXCHANGES`
+    Native,
+}
+
+// Settings of an exchange, read from `[exchanges.<name>]` or THOTH_EXCHANGES__<NAME>__*
+#[derive(Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct ExchangeConfig {
+    pub connector: ExchangeConnector,
+    // Replaces the REST endpoint of a native connector, e.g. for regional domains
+    pub base_url: Option<String>,
+    pub api_key: Option<String>,
+    pub secret: Option<String>,
+    // Passphrase some exchanges such as OKX require next to the key
+    pub password: Option<String>,
+    // Trade on the exchange's testnet
+    pub sandbox: bool,
+}
+
+impl ExchangeConfig {
+    pub fn has_credentials(&self) -> bool {
+        self.api_key.is_some() && self.secret.is_some()
+    }
+}
+
+// Keeps keys out of logs
+impl fmt::Debug for ExchangeConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let redact = |value: &Option<String>| value.as_ref().map(|_| "<redacted>");
+        f.debug_struct("ExchangeConfig")
+            .field("connector", &self.connector)
+            .field("base_url", &self.base_url)
+            .field("api_key", &redact(&self.api_key))
+            .field("secret", &redact(&self.secret))
+            .field("password", &redact(&self.password))
+            .field("sandbox", &self.sandbox)
+            .finish()
+    }
+}
+
+// Read from `[market_cache]`
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct MarketCacheConfig {
+    // How long loaded symbols, timeframes, fees and precision are used before they are reloaded
+    pub ttl_secs: u64,
+    // Where each exchange's market data is saved, relative to the working directory
+    pub dir: PathBuf,
+}
+
+impl Default for MarketCacheConfig {
+    fn default() -> Self {
+        Self {
+            ttl_secs: 24 * 60 * 60,
+            dir: PathBuf::from("market_cache"),
+        }
+    }
+}
@@ -0,0 +1,377 @@
+use super::{Exchange, agent, base_spot_fees, get_json, parse_candle, unknown_symbol};
+use crate::errors::AppResult;
+use crate::models::{Candle, MarketPrecision, Timeframe, TradingFees};
+use crate::utils::str_to_bigdecimal;
+use chrono::Utc;
+use serde::Deserialize;
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+use std::collections::HashMap;
+
+const NAME: &str = "okx";
+const BASE_URL: &str = "https://www.okx.com";
+const MAKER_FEE: &str = "0.0008";
+const TAKER_FEE: &str = "0.001";
+// Most candles one history-candles request returns
+const MAX_LIMIT: i64 = 100;
+// Daily and longer bars default to Hong Kong time, the `utc` ones line up with `Timeframe`
+const BARS: [(Timeframe, &str); 13] = [
+    (Timeframe::M1, "1m"),
+    (Timeframe::M3, "3m"),
+    (Timeframe::M5, "5m"),
+    (Timeframe::M15, "15m"),
+    (Timeframe::M30, "30m"),
+    (Timeframe::H1, "1H"),
+    (Timeframe::H2, "2H"),
+    (Timeframe::H4, "4H"),
+    (Timeframe::H6, "6Hutc"),
+    (Timeframe::H12, "12Hutc"),
+    (Timeframe::D1, "1Dutc"),
+    (Timeframe::W1, "1Wutc"),
+    (Timeframe::MN1, "1Mutc"),
+];
+
+// Every v5 response wraps its data, with a non-zero code on failure
+#[derive(Deserialize)]
+struct Response<T> {
+    code: String,
+    msg: String,
+    data: T,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Instrument {
+    inst_id: String,
+    base_ccy: String,
+    quote_ccy: String,
+    tick_sz: String,
+    lot_sz: String,
+}
+
+#[derive(Debug)]
+struct Market {
+    id: String,
+    precision: MarketPrecision,
+}
+
+// Spot market data from OKX's public REST API
+#[derive(Debug)]
+pub struct Okx {
+    agent: ureq::Agent,
+    base_url: String,
+    symbols: Vec<String>,
+    markets: HashMap<String, Market>,
+}
+
+impl Okx {
+    pub fn connect(base_url: Option<&str>) -> AppResult<Self> {
+        let mut okx = Self {
+            agent: agent(),
+            base_url: base_url
+                .unwrap_or(BASE_URL)
+                .trim_end_matches('/')
+                .to_string(),
+            symbols: Vec::new(),
+            markets: HashMap::new(),
+        };
+
+        let instruments: Vec<Instrument> = okx.get(
+            "/api/v5/public/instruments",
+            &[("instType", "SPOT".to_string())],
+        )?;
+        for instrument in instruments {
+            let symbol = format!("{}/{}", instrument.base_ccy, instrument.quote_ccy);
+            let precision = MarketPrecision {
+                price_precision: str_to_bigdecimal(&instrument.tick_sz, "price precision")?
+                    .normalized(),
+                amount_precision: str_to_bigdecimal(&instrument.lot_sz, "amount precision")?
+                    .normalized(),
+            };
+            okx.symbols.push(symbol.clone());
+            okx.markets.insert(
+                symbol,
+                Market {
+                    id: instrument.inst_id,
+                    precision,
+                },
+            );
+        }
+
+        Ok(okx)
+    }
+
+    fn get<T: DeserializeOwned>(&self, path: &str, query: &[(&str, String)]) -> AppResult<T> {
+        let url = format!("{}{}", self.base_url, path);
+        let response: Response<T> = get_json(&self.agent, NAME, &url, query)?;
+        if response.code != "0" {
+            return Err(format!(
+                "{} request failed with code {}: {}",
+                NAME, response.code, response.msg
+            )
+            .into());
+        }
+        Ok(response.data)
+    }
+
+    fn market(&self, symbol: &str) -> AppResult<&Market> {
+        self.markets
+            .get(symbol)
+            .ok_or_else(|| unknown_symbol(NAME, symbol))
+    }
+
+    fn bar(timeframe: Timeframe) -> AppResult<&'static str> {
+        BARS.iter()
+            .find(|(bar_timeframe, _)| *bar_timeframe == timeframe)
+            .map(|(_, bar)| *bar)
+            .ok_or_else(|| format!("Timeframe {} is not supported by {}", timeframe, NAME).into())
+    }
+
+    // Searches for the earliest candle opened at or after `since`. Each request returns the newest
+    // candle up to the middle of the range, which bounds the search from above
+    fn next_candle(
+        &self,
+        symbol: &str,
+        timeframe: Timeframe,
+        since: i64,
+    ) -> AppResult<Option<Candle>> {
+        let mut left = since;
+        let mut right = Utc::now().timestamp_millis();
+        let mut next_candle: Option<Candle> = None;
+
+        while left <= right {
+            let mid = left + (right - left) / 2;
+            let candles = self.history(symbol, timeframe, Some(since - 1), Some(mid + 1), 1)?;
+
+            if let Some(candle) = candles.into_iter().next() {
+                right = candle.timestamp.timestamp_millis().saturating_sub(1);
+                next_candle = Some(candle);
+            } else {
+                left = mid.saturating_add(1);
+            }
+        }
+
+        Ok(next_candle)
+    }
+
+    // Candles opened after `before` and before `after`, both exclusive, oldest first
+    fn history(
+        &self,
+        symbol: &str,
+        timeframe: Timeframe,
+        before: Option<i64>,
+        after: Option<i64>,
+        limit: i64,
+    ) -> AppResult<Vec<Candle>> {
+        let market = self.market(symbol)?;
+        let mut query = vec![
+            ("instId", market.id.clone()),
+            ("bar", Self::bar(timeframe)?.to_string()),
+            ("limit", limit.to_string()),
+        ];
+        if let Some(before) = before {
+            query.push(("before", before.to_string()));
+        }
+        if let Some(after) = after {
+            query.push(("after", after.to_string()));
+        }
+
+        let rows: Vec<Vec<Value>> = self.get("/api/v5/market/history-candles", &query)?;
+        rows.iter()
+            .rev()
+            .map(|row| parse_candle(NAME, symbol, timeframe, row))
+            .collect()
+    }
+}
+
+impl Exchange for Okx {
+    fn symbols(&self) -> AppResult<Vec<String>> {
+        Ok(self.symbols.clone())
+    }
+
+    fn timeframes(&self) -> AppResult<Vec<Timeframe>> {
+        Ok(BARS.iter().map(|(timeframe, _)| *timeframe).collect())
+    }
+
+    fn fees(&self, symbol: &str) -> AppResult<TradingFees> {
+        self.market(symbol)?;
+        base_spot_fees(MAKER_FEE, TAKER_FEE)
+    }
+
+    fn precision(&self, symbol: &str) -> AppResult<MarketPrecision> {
+        Ok(self.market(symbol)?.precision.clone())
+    }
+
+    // OKX pages from the newest candle back, so the oldest ones after `since` are read from a
+    // window just long enough to hold `limit` of them. A window without trades is jumped over to
+    // the next candle after it
+    fn fetch_candles(
+        &self,
+        symbol: &str,
+        timeframe: Timeframe,
+        since: Option<i64>,
+        limit: Option<i64>,
+    ) -> AppResult<Vec<Candle>> {
+        let limit = limit.unwrap_or(MAX_LIMIT).clamp(1, MAX_LIMIT);
+        let Some(mut since) = since else {
+            return self.history(symbol, timeframe, None, None, limit);
+        };
+
+        let window = timeframe.to_ms() as i64 * limit;
+        let candles = self.history(
+            symbol,
+            timeframe,
+            Some(since - 1),
+            Some(since + window),
+            limit,
+        )?;
+        if !candles.is_empty() {
+            return Ok(candles);
+        }
+
+        let Some(next) = self.next_candle(symbol, timeframe, since)? else {
+            return Ok(Vec::new());
+        };
+        since = next.timestamp.timestamp_millis();
+        self.history(
+            symbol,
+            timeframe,
+            Some(since - 1),
+            Some(since + window),
+            limit,
+        )
+    }
+
+    // Searches for the earliest time with a candle at or before it, since a window after a given
+    // time cannot tell whether older candles exist
+    fn first_candle(&self, symbol: &str, timeframe: Timeframe) -> AppResult<Option<Candle>> {
+        let mut left = 0i64;
+        let mut right = Utc::now().timestamp_millis();
+        let mut first_candle: Option<Candle> = None;
+
+        while left <= right {
+            let mid = left + (right - left) / 2;
+            let candles = self.history(symbol, timeframe, None, Some(mid + 1), 1)?;
+
+            if let Some(candle) = candles.into_iter().next() {
+                first_candle = Some(candle);
+                right = mid.saturating_sub(1);
+            } else {
+                left = mid.saturating_add(1);
+            }
+        }
+
+        Ok(first_candle)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::testing::serve;
+    use super::*;
+    use bigdecimal::BigDecimal;
+    use std::str::FromStr;
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    const HOUR: i64 = 60 * 60 * 1000;
+    const START: i64 = 1704067200000;
+
+    fn decimal(value: &str) -> BigDecimal {
+        BigDecimal::from_str(value).unwrap()
+    }
+
+    // An exchange with hourly candles at the given hours after START, answering history-candles
+    // the way OKX does: newest first, between `before` and `after`
+    fn connect(hours: Vec<i64>, requests: Arc<AtomicUsize>) -> Okx {
+        let base_url = serve(move |path, query| match path {
+            "/api/v5/public/instruments" => r#"{"code": "0", "msg": "", "data": [{
+                "instId": "BTC-USDT", "baseCcy": "BTC", "quoteCcy": "USDT",
+                "tickSz": "0.1", "lotSz": "0.00000001", "instType": "SPOT"
+            }]}"#
+                .to_string(),
+            "/api/v5/market/history-candles" => {
+                requests.fetch_add(1, Ordering::SeqCst);
+                assert_eq!(query["instId"], "BTC-USDT");
+                assert_eq!(query["bar"], "1H");
+                let bound = |key: &str, default: i64| {
+                    query
+                        .get(key)
+                        .map_or(default, |value| value.parse().unwrap())
+                };
+                let before = bound("before", i64::MIN);
+                let after = bound("after", i64::MAX);
+                let limit = bound("limit", 100) as usize;
+                let rows: Vec<String> = hours
+                    .iter()
+                    .rev()
+                    .map(|hour| START + hour * HOUR)
+                    .filter(|timestamp| *timestamp > before && *timestamp < after)
+                    .take(limit)
+                    .map(|timestamp| {
+                        format!(
+                            r#"["{}", "42283.5", "42554.6", "42261", "42475.2", "1271.68", "54000000", "54000000", "1"]"#,
+                            timestamp
+                        )
+                    })
+                    .collect();
+                format!(
+                    r#"{{"code": "0", "msg": "", "data": [{}]}}"#,
+                    rows.join(",")
+                )
+            }
+            _ => panic!("Unexpected request to {}", path),
+        });
+        Okx::connect(Some(&base_url)).unwrap()
+    }
+
+    #[test]
+    fn reads_markets_and_candles() {
+        let okx = connect(vec![0, 1, 2], Arc::default());
+        assert_eq!(okx.symbols().unwrap(), vec!["BTC/USDT".to_string()]);
+        let precision = okx.precision("BTC/USDT").unwrap();
+        assert_eq!(precision.price_precision, decimal("0.1"));
+        assert_eq!(precision.amount_precision, decimal("0.00000001"));
+
+        let candles = okx
+            .fetch_candles("BTC/USDT", Timeframe::H1, Some(START + HOUR), None)
+            .unwrap();
+        let timestamps: Vec<i64> = candles
+            .iter()
+            .map(|candle| candle.timestamp.timestamp_millis())
+            .collect();
+        assert_eq!(timestamps, vec![START + HOUR, START + 2 * HOUR]);
+        assert_eq!(candles[0].open, decimal("42283.5"));
+        assert_eq!(candles[0].close, decimal("42475.2"));
+        assert_eq!(candles[0].volume, decimal("1271.68"));
+    }
+
+    #[test]
+    fn jumps_over_windows_without_candles() {
+        let requests = Arc::new(AtomicUsize::new(0));
+        // Trading resumes a thousand windows of ten candles later
+        let okx = connect(vec![0, 10_000, 10_001], requests.clone());
+
+        let candles = okx
+            .fetch_candles("BTC/USDT", Timeframe::H1, Some(START + HOUR), Some(10))
+            .unwrap();
+        let timestamps: Vec<i64> = candles
+            .iter()
+            .map(|candle| candle.timestamp.timestamp_millis())
+            .collect();
+        assert_eq!(
+            timestamps,
+            vec![START + 10_000 * HOUR, START + 10_001 * HOUR]
+        );
+        assert!(requests.load(Ordering::SeqCst) < 64);
+    }
+
+    #[test]
+    fn finds_nothing_after_the_last_candle() {
+        let okx = connect(vec![0, 1], Arc::default());
+        let candles = okx
+            .fetch_candles("BTC/USDT", Timeframe::H1, Some(START + 2 * HOUR), None)
+            .unwrap();
+        assert!(candles.is_empty());
+    }
+}
@@ -1,16 +1,26 @@
 use crate::app::AppState;
-use crate::errors::{ApiResult, AppError};
-use crate::exchange::ccxt::CCXT;
-use crate::models::Timeframe;
-use crate::tasks::{BacktestStatus, BacktestTask};
+use crate::errors::{ApiResult, AppError, AppResult};
+use crate::exchange::MarketCache;
+use crate::models::{MarginConfig, Timeframe, TradingFees};
+use crate::services::tasks::{
+    BacktestTaskFilter, delete_backtest_task, get_backtest_task, get_backtest_trades,
+    list_backtest_tasks, save_backtest_task,
+};
+use crate::strategy::{BacktestEngine, FillModelConfig, ParameterValues, RiskLimits, Trade};
+use crate::tasks::{
+    BacktestConfig, BacktestStatus, BacktestTask, StrategyTask, SymbolConfig,
+    backtest::DEFAULT_INITIAL_CAPITAL,
+};
+use crate::utils::quote_currency;
 use axum::{
-    extract::{Path, State},
+    extract::{Path, Query, State},
     response::{
         Json,
         sse::{Event, KeepAlive, Sse},
     },
 };
-use chrono::Utc;
+use bigdecimal::{BigDecimal, One, Zero};
+use chrono::{DateTime, Utc, serde::ts_milliseconds_option};
 use futures::stream::Stream;
 use serde::{Deserialize, Serialize};
 use std::convert::Infallible;
@@ -19,13 +29,196 @@ use tokio::sync::RwLock;
 use ts_rs::TS;
 use uuid::Uuid;
 
-#[derive(Debug, Deserialize, TS)]
+#[derive(Debug, Clone, Deserialize, TS)]
 #[ts(export)]
-pub struct CreateBacktestTaskRequest {
-    pub name: String,
+pub struct BacktestConfigRequest {
     pub exchange: String,
     pub symbol: String,
     pub timeframe: Timeframe,
+    #[serde(default, with = "ts_milliseconds_option")]
+    #[ts(optional, type = "number")]
+    pub start: Option<DateTime<Utc>>,
+    #[serde(default, with = "ts_milliseconds_option")]
+    #[ts(optional, type = "number")]
+    pub end: Option<DateTime<Utc>>,
+    #[ts(optional, type = "string")]
+    pub initial_capital: Option<BigDecimal>,
+    #[ts(optional)]
+    pub quote_currency: Option<String>,
+    #[ts(optional, type = "string")]
+    pub maker_fee: Option<BigDecimal>,
+    #[ts(optional, type = "string")]
+    pub taker_fee: Option<BigDecimal>,
+    #[ts(optional)]
+    pub equity_points: Option<usize>,
+    #[ts(optional)]
+    pub margin: Option<MarginConfig>,
+    #[ts(optional)]
+    pub fill_model: Option<FillModelConfig>,
+    #[ts(optional)]
+    pub risk: Option<RiskLimits>,
+    #[ts(optional)]
+    pub additional_symbols: Option<Vec<String>>,
+    #[ts(optional)]
+    pub additional_timeframes: Option<Vec<Timeframe>>,
+    #[ts(optional)]
+    pub engine: Option<BacktestEngine>,
+}
+
+impl BacktestConfigRequest {
+    // Fees and precision come from the exchange unless cached, which blocks
+    pub async fn resolve_async(&self, markets: &Arc<MarketCache>) -> AppResult<BacktestConfig> {
+        let request = self.clone();
+        let markets = markets.clone();
+        tokio::task::spawn_blocking(move || request.resolve(&markets)).await?
+    }
+
+    pub fn resolve(&self, markets: &MarketCache) -> AppResult<BacktestConfig> {
+        if let (Some(start), Some(end)) = (self.start, self.end)
+            && start >= end
+        {
+            return Err(AppError::BadRequest(
+                "Backtest start must be before end".to_string(),
+            ));
+        }
+
+        let initial_capital = self
+            .initial_capital
+            .clone()
+            .unwrap_or_else(|| BigDecimal::from(DEFAULT_INITIAL_CAPITAL));
+        if initial_capital <= BigDecimal::zero() {
+            return Err(AppError::BadRequest(
+                "Initial capital must be positive".to_string(),
+            ));
+        }
+
+        let Some(symbol_quote) = quote_currency(&self.symbol) else {
+            return Err(AppError::BadRequest(format!(
+                "Invalid symbol: {}",
+                self.symbol
+            )));
+        };
+        // The portfolio shares one cash balance, so every symbol must be quoted in the same currency
+        for symbol in self.additional_symbols.iter().flatten() {
+            if quote_currency(symbol) != Some(symbol_quote) {
+                return Err(AppError::BadRequest(format!(
+                    "Symbol {} must be quoted in {}",
+                    symbol, symbol_quote
+                )));
+            }
+        }
+
+        let quote_currency = match &self.quote_currency {
+            Some(quote) if quote != symbol_quote => {
+                return Err(AppError::BadRequest(format!(
+                    "Quote currency {} does not match symbol {}",
+                    quote, self.symbol
+                )));
+            }
+            _ => symbol_quote.to_string(),
+        };
+
+        let metadata = markets.get(&self.exchange)?;
+        // Fee overrides apply to every traded symbol
+        let symbol_fees = |symbol: &str| -> AppResult<TradingFees> {
+            let market_fees = metadata.fees(symbol)?;
+            Ok(TradingFees {
+                maker: self.maker_fee.clone().unwrap_or(market_fees.maker),
+                taker: self.taker_fee.clone().unwrap_or(market_fees.taker),
+            })
+        };
+
+        let precision = metadata.precision(&self.symbol)?;
+        let fees = symbol_fees(&self.symbol)?;
+        if fees.maker < BigDecimal::zero() || fees.taker < BigDecimal::zero() {
+            return Err(AppError::BadRequest(
+                "Trading fees cannot be negative".to_string(),
+            ));
+        }
+
+        let mut additional_symbols: Vec<SymbolConfig> = Vec::new();
+        for symbol in self.additional_symbols.iter().flatten() {
+            if symbol == &self.symbol || additional_symbols.iter().any(|s| &s.symbol == symbol) {
+                return Err(AppError::BadRequest(format!(
+                    "Duplicate symbol: {}",
+                    symbol
+                )));
+            }
+            additional_symbols.push(SymbolConfig {
+                symbol: symbol.clone(),
+                precision: metadata.precision(symbol)?,
+                fees: symbol_fees(symbol)?,
+            });
+        }
+
+        if let Some(margin) = &self.margin {
+            if margin.leverage < BigDecimal::one() {
+                return Err(AppError::BadRequest(
+                    "Leverage must be at least 1".to_string(),
+                ));
+            }
+            if margin.maintenance_margin < BigDecimal::zero()
+                || margin.maintenance_margin >= BigDecimal::one()
+            {
+                return Err(AppError::BadRequest(
+                    "Maintenance margin must be between 0 and 1".to_string(),
+                ));
+            }
+            if margin.daily_interest_rate < BigDecimal::zero() {
+                return Err(AppError::BadRequest(
+                    "Interest rate cannot be negative".to_string(),
+                ));
+            }
+        }
+
+        let mut additional_timeframes: Vec<Timeframe> = Vec::new();
+        for timeframe in self.additional_timeframes.iter().flatten() {
+            if *timeframe == self.timeframe || additional_timeframes.contains(timeframe) {
+                return Err(AppError::BadRequest(format!(
+                    "Duplicate timeframe: {}",
+                    timeframe
+                )));
+            }
+            additional_timeframes.push(*timeframe);
+        }
+
+        let fill_model = self.fill_model.clone().unwrap_or_default();
+        fill_model.validate()?;
+        if let Some(risk) = &self.risk {
+            risk.validate()?;
+        }
+
+        Ok(BacktestConfig {
+            exchange: self.exchange.clone(),
+            symbol: self.symbol.clone(),
+            timeframe: self.timeframe,
+            precision,
+            fees,
+            initial_capital,
+            quote_currency,
+            start: self.start,
+            end: self.end,
+            equity_points: self.equity_points,
+            margin: self.margin.clone(),
+            fill_model,
+            risk: self.risk.clone(),
+            additional_symbols,
+            additional_timeframes,
+            engine: self.engine.unwrap_or_default(),
+        })
+    }
+}
+
+#[derive(Debug, Deserialize, TS)]
+#[ts(export)]
+pub struct CreateBacktestTaskRequest {
+    pub name: String,
+    #[serde(flatten)]
+    pub config: BacktestConfigRequest,
+    #[ts(optional)]
+    pub parameters: Option<ParameterValues>,
+    #[ts(optional)]
+    pub timeout_secs: Option<u64>,
 }
 
 #[derive(Debug, Serialize, TS)]
@@ -38,8 +231,7 @@ pub async fn create_task(
     State(state): State<AppState>,
     Json(request): Json<CreateBacktestTaskRequest>,
 ) -> ApiResult<CreateBacktestTaskResponse> {
-    let ccxt = CCXT::with_exchange(&request.exchange)?;
-    let precision = ccxt.precision(&request.symbol)?;
+    let config = request.config.resolve_async(&state.markets).await?;
 
     let now = Utc::now();
     let task = BacktestTask {
@@ -47,18 +239,20 @@ pub async fn create_task(
         status: BacktestStatus::Pending,
         progress: 0.0,
         name: request.name.clone(),
-        exchange: request.exchange.clone(),
-        symbol: request.symbol.clone(),
-        timeframe: request.timeframe,
-        precision,
+        config,
+        parameters: request.parameters.clone().unwrap_or_default(),
         statistic: None,
         error_message: None,
+        risk_events: Vec::new(),
+        timeout_secs: request.timeout_secs.or(state.task_config.timeout_secs),
+        queue_position: None,
         created_at: now,
         started_at: None,
         completed_at: None,
         updated_at: now,
         event_tx: Some(state.backtest_event_tx.clone()),
     };
+    save_backtest_task(&state.db_pool, &task).await?;
     task.broadcast();
 
     let task_id = task.id;
@@ -69,39 +263,185 @@ pub async fn create_task(
         tasks.insert(task_id, task.clone());
     }
 
+    let backtest_tasks = state.backtest_tasks.clone();
+    let finished = state
+        .backtest_queue
+        .spawn(
+            task_id,
+            task,
+            async move |task: &mut BacktestTask, control| {
+                task.execute(
+                    &state.strategy_manager,
+                    &request.name,
+                    state.db_pool,
+                    &state.task_config,
+                    control,
+                )
+                .await;
+            },
+        )
+        .await;
+
+    // Finished tasks are served from the database
     tokio::spawn(async move {
-        let mut task = task.write().await;
-        task.execute(&state.strategy_manager, &request.name, state.db_pool)
-            .await;
+        let _ = finished.await;
+        backtest_tasks.write().await.remove(&task_id);
     });
 
     Ok(Json(CreateBacktestTaskResponse { task_id }))
 }
 
-pub async fn get_all_tasks(State(state): State<AppState>) -> ApiResult<Vec<BacktestTask>> {
+const DEFAULT_PAGE_SIZE: usize = 50;
+const MAX_PAGE_SIZE: usize = 500;
+
+#[derive(Debug, Deserialize, TS)]
+#[ts(export)]
+pub struct ListBacktestTasksQuery {
+    #[ts(optional)]
+    pub status: Option<BacktestStatus>,
+    #[ts(optional)]
+    pub strategy: Option<String>,
+    #[ts(optional)]
+    pub exchange: Option<String>,
+    #[ts(optional)]
+    pub symbol: Option<String>,
+    #[serde(default, with = "ts_milliseconds_option")]
+    #[ts(optional, type = "number")]
+    pub start: Option<DateTime<Utc>>,
+    #[serde(default, with = "ts_milliseconds_option")]
+    #[ts(optional, type = "number")]
+    pub end: Option<DateTime<Utc>>,
+    #[ts(optional)]
+    pub limit: Option<usize>,
+    #[ts(optional)]
+    pub offset: Option<usize>,
+}
+
+#[derive(Debug, Serialize, TS)]
+#[ts(export)]
+pub struct ListBacktestTasksResponse {
+    pub tasks: Vec<BacktestTask>,
+    // Tasks matching the filter across all pages
+    pub total: usize,
+}
+
+pub async fn list_tasks(
+    State(state): State<AppState>,
+    Query(query): Query<ListBacktestTasksQuery>,
+) -> ApiResult<ListBacktestTasksResponse> {
+    let limit = query.limit.unwrap_or(DEFAULT_PAGE_SIZE);
+    if limit > MAX_PAGE_SIZE {
+        return Err(AppError::BadRequest(format!(
+            "limit must be at most {}",
+            MAX_PAGE_SIZE
+        )));
+    }
+
+    let filter = BacktestTaskFilter {
+        status: query.status,
+        strategy: query.strategy,
+        exchange: query.exchange,
+        symbol: query.symbol,
+        start: query.start,
+        end: query.end,
+    };
+    let (stored, total) = list_backtest_tasks(
+        &state.db_pool,
+        &filter,
+        limit as i64,
+        query.offset.unwrap_or(0) as i64,
+    )
+    .await?;
+
+    // Running tasks are only saved when their status changes, so prefer the live state
     let mut tasks = Vec::new();
     let backtest_tasks = state.backtest_tasks.read().await;
-    for task in backtest_tasks.values() {
-        let task = task.read().await;
-        tasks.push(task.clone());
+    for task in stored {
+        match backtest_tasks.get(&task.id) {
+            Some(live) => tasks.push(live.read().await.without_trades()),
+            None => tasks.push(task),
+        }
     }
 
-    Ok(Json(tasks))
+    Ok(Json(ListBacktestTasksResponse {
+        tasks,
+        total: total as usize,
+    }))
 }
 
 pub async fn get_task(
     State(state): State<AppState>,
     Path(task_id): Path<Uuid>,
 ) -> ApiResult<BacktestTask> {
-    let backtest_tasks = state.backtest_tasks.read().await;
-    let task = backtest_tasks.get(&task_id);
+    let live = state.backtest_tasks.read().await.get(&task_id).cloned();
+    if let Some(task) = live {
+        let task = task.read().await;
+        return Ok(Json(task.without_trades()));
+    }
 
-    match task {
-        Some(task) => {
-            let task = task.read().await;
-            Ok(Json(task.clone()))
-        }
-        _ => Err(AppError::NotFound(format!(
+    match get_backtest_task(&state.db_pool, task_id).await? {
+        Some(task) => Ok(Json(task)),
+        None => Err(AppError::NotFound(format!(
+            "Task with id '{}' is not a Backtest task",
+            task_id
+        ))),
+    }
+}
+
+// Trades are left out of task listings since a single backtest can produce thousands
+pub async fn get_trades(
+    State(state): State<AppState>,
+    Path(task_id): Path<Uuid>,
+) -> ApiResult<Vec<Trade>> {
+    match get_backtest_trades(&state.db_pool, task_id).await? {
+        Some(trades) => Ok(Json(trades)),
+        None => Err(AppError::NotFound(format!(
+            "Task with id '{}' is not a Backtest task",
+            task_id
+        ))),
+    }
+}
+
+pub async fn delete_task(
+    State(state): State<AppState>,
+    Path(task_id): Path<Uuid>,
+) -> ApiResult<()> {
+    if state.backtest_tasks.read().await.contains_key(&task_id)
+        && state.backtest_queue.is_active(task_id).await
+    {
+        return Err(AppError::BadRequest(format!(
+            "Task with id '{}' has not finished, cancel it first",
+            task_id
+        )));
+    }
+
+    if !delete_backtest_task(&state.db_pool, task_id).await? {
+        return Err(AppError::NotFound(format!(
+            "Task with id '{}' is not a Backtest task",
+            task_id
+        )));
+    }
+    state.backtest_tasks.write().await.remove(&task_id);
+
+    Ok(Json(()))
+}
+
+pub async fn cancel_task(
+    State(state): State<AppState>,
+    Path(task_id): Path<Uuid>,
+) -> ApiResult<()> {
+    if state.backtest_tasks.read().await.contains_key(&task_id)
+        && state.backtest_queue.cancel(task_id).await
+    {
+        return Ok(Json(()));
+    }
+
+    match get_backtest_task(&state.db_pool, task_id).await? {
+        Some(_) => Err(AppError::BadRequest(format!(
+            "Task with id '{}' has already finished",
+            task_id
+        ))),
+        None => Err(AppError::NotFound(format!(
             "Task with id '{}' is not a Backtest task",
             task_id
         ))),
@@ -117,7 +457,7 @@ pub async fn stream_tasks(
         let backtest_tasks = state.backtest_tasks.read().await;
         for task in backtest_tasks.values() {
             let task = task.read().await;
-            if let Ok(data) = serde_json::to_string(&*task) {
+            if let Ok(data) = serde_json::to_string(&task.without_trades()) {
                 initial_events.push(data);
             }
         }
@@ -138,7 +478,7 @@ pub async fn stream_tasks(
                         break;
                     };
 
-                    let Ok(data) = serde_json::to_string(&task) else {
+                    let Ok(data) = serde_json::to_string(&task.without_trades()) else {
                         continue;
                     };
 
@@ -150,3 +490,152 @@ pub async fn stream_tasks(
 
     Sse::new(stream).keep_alive(KeepAlive::default())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::exchange::{MarketInfo, testing::cache};
+    use crate::models::MarketPrecision;
+    use serde_json::json;
+    use std::str::FromStr;
+
+    fn decimal(value: &str) -> BigDecimal {
+        BigDecimal::from_str(value).unwrap()
+    }
+
+    fn markets() -> MarketCache {
+        let market = MarketInfo {
+            fees: TradingFees {
+                maker: decimal("0.001"),
+                taker: decimal("0.002"),
+            },
+            precision: MarketPrecision {
+                price_precision: decimal("0.01"),
+                amount_precision: decimal("0.0001"),
+            },
+        };
+        cache(
+            "binance",
+            &[("BTC/USDT", market.clone()), ("ETH/USDT", market)],
+        )
+    }
+
+    // A request for BTC/USDT hourly candles with the given fields set
+    fn request(fields: serde_json::Value) -> BacktestConfigRequest {
+        let mut request = json!({
+            "exchange": "binance",
+            "symbol": "BTC/USDT",
+            "timeframe": "1h",
+        });
+        for (key, value) in fields.as_object().unwrap() {
+            request[key] = value.clone();
+        }
+        serde_json::from_value(request).unwrap()
+    }
+
+    fn rejection(fields: serde_json::Value) -> String {
+        match request(fields).resolve(&markets()) {
+            Err(AppError::BadRequest(message)) => message,
+            other => panic!("expected a bad request, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn defaults_to_the_market_fees_and_symbol_quote() {
+        let config = request(json!({})).resolve(&markets()).unwrap();
+        assert_eq!(
+            config.initial_capital,
+            BigDecimal::from(DEFAULT_INITIAL_CAPITAL)
+        );
+        assert_eq!(config.quote_currency, "USDT");
+        assert_eq!(config.fees.maker, decimal("0.001"));
+        assert_eq!(config.fees.taker, decimal("0.002"));
+        assert_eq!(config.start, None);
+        assert_eq!(config.end, None);
+    }
+
+    #[test]
+    fn applies_the_requested_range_capital_and_fees() {
+        let config = request(json!({
+            "start": 1_704_067_200_000i64,
+            "end": 1_706_745_600_000i64,
+            "initial_capital": "2500.5",
+            "quote_currency": "USDT",
+            "taker_fee": "0",
+        }))
+        .resolve(&markets())
+        .unwrap();
+        assert_eq!(config.start.unwrap().timestamp_millis(), 1_704_067_200_000);
+        assert_eq!(config.end.unwrap().timestamp_millis(), 1_706_745_600_000);
+        assert_eq!(config.initial_capital, decimal("2500.5"));
+        assert_eq!(config.fees.maker, decimal("0.001"));
+        assert_eq!(config.fees.taker, decimal("0"));
+    }
+
+    #[test]
+    fn rejects_invalid_settings() {
+        assert_eq!(
+            rejection(json!({ "start": 1_706_745_600_000i64, "end": 1_704_067_200_000i64 })),
+            "Backtest start must be before end"
+        );
+        assert_eq!(
+            rejection(json!({ "initial_capital": "0" })),
+            "Initial capital must be positive"
+        );
+        assert_eq!(
+            rejection(json!({ "quote_currency": "USDC" })),
+            "Quote currency USDC does not match symbol BTC/USDT"
+        );
+        assert_eq!(
+            rejection(json!({ "maker_fee": "-0.001" })),
+            "Trading fees cannot be negative"
+        );
+        assert_eq!(
+            rejection(json!({ "symbol": "BTCUSDT" })),
+            "Invalid symbol: BTCUSDT"
+        );
+        assert_eq!(
+            rejection(json!({ "symbol": "DOGE/USDT" })),
+            "No fees or precision for DOGE/USDT on binance"
+        );
+    }
+
+    #[test]
+    fn additional_symbols_share_the_quote_currency() {
+        let config = request(json!({ "additional_symbols": ["ETH/USDT"] }))
+            .resolve(&markets())
+            .unwrap();
+        let symbols: Vec<String> = config.symbols().into_iter().map(|s| s.symbol).collect();
+        assert_eq!(symbols, vec!["BTC/USDT", "ETH/USDT"]);
+
+        assert_eq!(
+            rejection(json!({ "additional_symbols": ["ETH/BTC"] })),
+            "Symbol ETH/BTC must be quoted in USDT"
+        );
+        assert_eq!(
+            rejection(json!({ "additional_symbols": ["ETH/USDT", "ETH/USDT"] })),
+            "Duplicate symbol: ETH/USDT"
+        );
+        assert_eq!(
+            rejection(json!({ "additional_symbols": ["BTC/USDT"] })),
+            "Duplicate symbol: BTC/USDT"
+        );
+    }
+
+    #[test]
+    fn parses_list_filters_from_the_query_string() {
+        let uri = "/tasks/backtest?status=failed&strategy=sma&start=1704067200000&limit=20"
+            .parse()
+            .unwrap();
+        let Query(query) = Query::<ListBacktestTasksQuery>::try_from_uri(&uri).unwrap();
+        assert_eq!(query.status, Some(BacktestStatus::Failed));
+        assert_eq!(query.strategy.as_deref(), Some("sma"));
+        assert_eq!(
+            query.start.map(|start| start.to_rfc3339()).as_deref(),
+            Some("2024-01-01T00:00:00+00:00")
+        );
+        assert_eq!(query.end, None);
+        assert_eq!(query.limit, Some(20));
+        assert_eq!(query.offset, None);
+    }
+}
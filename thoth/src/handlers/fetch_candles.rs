@@ -1,6 +1,7 @@
 use crate::app::AppState;
 use crate::errors::{ApiResult, AppError};
 use crate::models::Timeframe;
+use crate::services::tasks::save_fetch_candles_task;
 use crate::tasks::{FetchCandlesStatus, FetchCandlesTask};
 use axum::{
     extract::{Path, State},
@@ -24,6 +25,8 @@ pub struct CreateFetchCandlesTaskRequest {
     pub symbol: String,
     pub exchange: String,
     pub timeframe: Timeframe,
+    #[ts(optional)]
+    pub timeout_secs: Option<u64>,
 }
 
 #[derive(Debug, Serialize, TS)]
@@ -46,6 +49,8 @@ pub async fn create_task(
         timeframe: request.timeframe,
         result: None,
         error_message: None,
+        timeout_secs: request.timeout_secs.or(state.task_config.timeout_secs),
+        queue_position: None,
         created_at: now,
         started_at: None,
         completed_at: None,
@@ -53,6 +58,7 @@ pub async fn create_task(
         event_tx: Some(state.fetch_candles_event_tx.clone()),
     };
 
+    save_fetch_candles_task(&state.db_pool, &task).await?;
     let task_id = task.id;
     let task = Arc::new(RwLock::new(task));
 
@@ -61,11 +67,16 @@ pub async fn create_task(
         tasks.insert(task_id, task.clone());
     }
 
-    let db_pool = state.db_pool.clone();
-    tokio::spawn(async move {
-        let mut task = task.write().await;
-        task.execute(db_pool).await;
-    });
+    state
+        .fetch_candles_queue
+        .spawn(
+            task_id,
+            task,
+            async move |task: &mut FetchCandlesTask, control| {
+                task.execute(state.db_pool, &state.exchanges, control).await;
+            },
+        )
+        .await;
 
     Ok(Json(CreateFetchCandlesTaskResponse { task_id }))
 }
@@ -100,6 +111,32 @@ pub async fn get_task(
     }
 }
 
+pub async fn cancel_task(
+    State(state): State<AppState>,
+    Path(task_id): Path<Uuid>,
+) -> ApiResult<()> {
+    if !state
+        .fetch_candles_tasks
+        .read()
+        .await
+        .contains_key(&task_id)
+    {
+        return Err(AppError::NotFound(format!(
+            "Task with id \"{}\" is not a FetchCandles task",
+            task_id
+        )));
+    }
+
+    if !state.fetch_candles_queue.cancel(task_id).await {
+        return Err(AppError::BadRequest(format!(
+            "Task with id \"{}\" has already finished",
+            task_id
+        )));
+    }
+
+    Ok(Json(()))
+}
+
 pub async fn stream_tasks(
     State(state): State<AppState>,
 ) -> Sse<impl Stream<Item = Result<Event, Infallible>>> {
@@ -1,7 +1,11 @@
+use crate::app::AppState;
 use crate::errors::ApiResult;
-use crate::exchange::ccxt::CCXT;
+use crate::exchange;
 use crate::models::Timeframe;
-use axum::{Json, extract::Query};
+use axum::{
+    Json,
+    extract::{Query, State},
+};
 use serde::Deserialize;
 use ts_rs::TS;
 
@@ -15,16 +19,25 @@ pub async fn check() -> ApiResult<&'static str> {
     Ok(Json("OK"))
 }
 
-pub async fn list_exchanges() -> ApiResult<Vec<String>> {
-    Ok(Json(CCXT::exchanges()?))
+pub async fn list_exchanges(State(state): State<AppState>) -> ApiResult<Vec<String>> {
+    Ok(Json(exchange::available(&state.exchanges)?))
 }
 
-pub async fn list_symbols(Query(query): Query<ExchangeQuery>) -> ApiResult<Vec<String>> {
-    let exchange = CCXT::with_exchange(&query.exchange)?;
-    Ok(Json(exchange.symbols()?))
+pub async fn list_symbols(
+    State(state): State<AppState>,
+    Query(query): Query<ExchangeQuery>,
+) -> ApiResult<Vec<String>> {
+    // Loading markets that are not cached yet blocks
+    let metadata =
+        tokio::task::spawn_blocking(move || state.markets.get(&query.exchange)).await??;
+    Ok(Json(metadata.symbols.clone()))
 }
 
-pub async fn list_timeframes(Query(query): Query<ExchangeQuery>) -> ApiResult<Vec<Timeframe>> {
-    let exchange = CCXT::with_exchange(&query.exchange)?;
-    Ok(Json(exchange.timeframes()?))
+pub async fn list_timeframes(
+    State(state): State<AppState>,
+    Query(query): Query<ExchangeQuery>,
+) -> ApiResult<Vec<Timeframe>> {
+    let metadata =
+        tokio::task::spawn_blocking(move || state.markets.get(&query.exchange)).await??;
+    Ok(Json(metadata.timeframes.clone()))
 }
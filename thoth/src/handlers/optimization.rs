@@ -0,0 +1,200 @@
+use crate::app::AppState;
+use crate::errors::{ApiResult, AppError};
+use crate::handlers::backtest::BacktestConfigRequest;
+use crate::services::tasks::save_optimization_task;
+use crate::tasks::optimization::{Optimization, WalkForward};
+use crate::tasks::{BacktestStatus, OptimizationTask, StrategyTask};
+use axum::{
+    extract::{Path, State},
+    response::{
+        Json,
+        sse::{Event, KeepAlive, Sse},
+    },
+};
+use chrono::Utc;
+use futures::stream::Stream;
+use serde::{Deserialize, Serialize};
+use std::convert::Infallible;
+use std::sync::Arc;
+use tokio::sync::RwLock;
+use ts_rs::TS;
+use uuid::Uuid;
+
+#[derive(Debug, Deserialize, TS)]
+#[ts(export)]
+pub struct CreateOptimizationTaskRequest {
+    pub name: String,
+    #[serde(flatten)]
+    pub config: BacktestConfigRequest,
+    #[serde(flatten)]
+    pub optimization: Optimization,
+    #[ts(optional)]
+    pub walk_forward: Option<WalkForward>,
+    #[ts(optional)]
+    pub timeout_secs: Option<u64>,
+}
+
+#[derive(Debug, Serialize, TS)]
+#[ts(export)]
+pub struct CreateOptimizationTaskResponse {
+    pub task_id: Uuid,
+}
+
+pub async fn create_task(
+    State(state): State<AppState>,
+    Json(request): Json<CreateOptimizationTaskRequest>,
+) -> ApiResult<CreateOptimizationTaskResponse> {
+    let total_runs = request.optimization.combinations()?.len();
+    if let Some(walk_forward) = &request.walk_forward {
+        walk_forward.validate()?;
+    }
+    let config = request.config.resolve_async(&state.markets).await?;
+
+    let now = Utc::now();
+    let task = OptimizationTask {
+        id: Uuid::new_v4(),
+        status: BacktestStatus::Pending,
+        progress: 0.0,
+        name: request.name.clone(),
+        config,
+        optimization: request.optimization.clone(),
+        total_runs,
+        completed_runs: 0,
+        results: Vec::new(),
+        walk_forward: request.walk_forward.clone(),
+        folds: Vec::new(),
+        out_of_sample: None,
+        error_message: None,
+        timeout_secs: request.timeout_secs.or(state.task_config.timeout_secs),
+        queue_position: None,
+        created_at: now,
+        started_at: None,
+        completed_at: None,
+        updated_at: now,
+        event_tx: Some(state.optimization_event_tx.clone()),
+    };
+    save_optimization_task(&state.db_pool, &task).await?;
+    task.broadcast();
+
+    let task_id = task.id;
+    let task = Arc::new(RwLock::new(task));
+
+    {
+        let mut tasks = state.optimization_tasks.write().await;
+        tasks.insert(task_id, task.clone());
+    }
+
+    state
+        .backtest_queue
+        .spawn(
+            task_id,
+            task,
+            async move |task: &mut OptimizationTask, control| {
+                task.execute(
+                    &state.strategy_manager,
+                    &request.name,
+                    state.db_pool,
+                    &state.task_config,
+                    control,
+                )
+                .await;
+            },
+        )
+        .await;
+
+    Ok(Json(CreateOptimizationTaskResponse { task_id }))
+}
+
+pub async fn get_all_tasks(State(state): State<AppState>) -> ApiResult<Vec<OptimizationTask>> {
+    let mut tasks = Vec::new();
+    let optimization_tasks = state.optimization_tasks.read().await;
+    for task in optimization_tasks.values() {
+        let task = task.read().await;
+        tasks.push(task.clone());
+    }
+
+    Ok(Json(tasks))
+}
+
+pub async fn get_task(
+    State(state): State<AppState>,
+    Path(task_id): Path<Uuid>,
+) -> ApiResult<OptimizationTask> {
+    let optimization_tasks = state.optimization_tasks.read().await;
+    let task = optimization_tasks.get(&task_id);
+
+    match task {
+        Some(task) => {
+            let task = task.read().await;
+            Ok(Json(task.clone()))
+        }
+        _ => Err(AppError::NotFound(format!(
+            "Task with id '{}' is not an Optimization task",
+            task_id
+        ))),
+    }
+}
+
+pub async fn cancel_task(
+    State(state): State<AppState>,
+    Path(task_id): Path<Uuid>,
+) -> ApiResult<()> {
+    if !state.optimization_tasks.read().await.contains_key(&task_id) {
+        return Err(AppError::NotFound(format!(
+            "Task with id '{}' is not an Optimization task",
+            task_id
+        )));
+    }
+
+    if !state.backtest_queue.cancel(task_id).await {
+        return Err(AppError::BadRequest(format!(
+            "Task with id '{}' has already finished",
+            task_id
+        )));
+    }
+
+    Ok(Json(()))
+}
+
+pub async fn stream_tasks(
+    State(state): State<AppState>,
+) -> Sse<impl Stream<Item = Result<Event, Infallible>>> {
+    let mut rx = state.optimization_event_tx.subscribe();
+    let mut initial_events = Vec::new();
+    {
+        let optimization_tasks = state.optimization_tasks.read().await;
+        for task in optimization_tasks.values() {
+            let task = task.read().await;
+            if let Ok(data) = serde_json::to_string(&*task) {
+                initial_events.push(data);
+            }
+        }
+    }
+
+    let stream = async_stream::stream! {
+        for data in initial_events {
+            yield Ok(Event::default().data(data));
+        }
+
+        loop {
+            tokio::select! {
+                _ = state.shutdown_token.cancelled() => {
+                    break;
+                }
+                result = rx.recv() => {
+                    let Ok(task) = result else {
+                        break;
+                    };
+
+                    let Ok(data) = serde_json::to_string(&task) else {
+                        continue;
+                    };
+
+                    yield Ok(Event::default().data(data));
+                }
+            }
+        }
+    };
+
+    Sse::new(stream).keep_alive(KeepAlive::default())
+}
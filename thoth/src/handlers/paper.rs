@@ -0,0 +1,283 @@
+use crate::app::AppState;
+use crate::errors::{ApiResult, AppError};
+use crate::handlers::backtest::BacktestConfigRequest;
+use crate::strategy::{BrokerType, ParameterValues};
+use crate::tasks::paper::DEFAULT_WARMUP_CANDLES;
+use crate::tasks::{PaperAccount, PaperSession, PaperStatus, TaskControl};
+use axum::{
+    extract::{Path, State},
+    response::{
+        Json,
+        sse::{Event, KeepAlive, Sse},
+    },
+};
+use chrono::Utc;
+use futures::stream::Stream;
+use serde::{Deserialize, Serialize};
+use std::convert::Infallible;
+use std::sync::Arc;
+use tokio::sync::RwLock;
+use ts_rs::TS;
+use uuid::Uuid;
+
+const MAX_WARMUP_CANDLES: usize = 10000;
+
+#[derive(Debug, Deserialize, TS)]
+#[ts(export)]
+pub struct CreatePaperSessionRequest {
+    pub name: String,
+    #[serde(flatten)]
+    pub config: BacktestConfigRequest,
+    #[ts(optional)]
+    pub parameters: Option<ParameterValues>,
+    #[ts(optional)]
+    pub warmup_candles: Option<usize>,
+    #[ts(optional)]
+    pub broker: Option<BrokerType>,
+}
+
+#[derive(Debug, Serialize, TS)]
+#[ts(export)]
+pub struct CreatePaperSessionResponse {
+    pub session_id: Uuid,
+}
+
+pub async fn create_session(
+    State(state): State<AppState>,
+    Json(request): Json<CreatePaperSessionRequest>,
+) -> ApiResult<CreatePaperSessionResponse> {
+    let config_request = &request.config;
+    if config_request.start.is_some() || config_request.end.is_some() {
+        return Err(AppError::BadRequest(
+            "Paper sessions trade from now on and take no start or end".to_string(),
+        ));
+    }
+    if config_request.additional_symbols.is_some() || config_request.additional_timeframes.is_some()
+    {
+        return Err(AppError::BadRequest(
+            "Paper sessions trade a single symbol and timeframe".to_string(),
+        ));
+    }
+
+    let warmup_candles = request.warmup_candles.unwrap_or(DEFAULT_WARMUP_CANDLES);
+    if warmup_candles > MAX_WARMUP_CANDLES {
+        return Err(AppError::BadRequest(format!(
+            "warmup_candles must be at most {}",
+            MAX_WARMUP_CANDLES
+        )));
+    }
+
+    let broker = request.broker.unwrap_or_default();
+    if broker == BrokerType::Ccxt
+        && !state
+            .exchanges
+            .get(&config_request.exchange)
+            .is_some_and(|e| e.has_credentials())
+    {
+        return Err(AppError::BadRequest(format!(
+            "No API keys configured for exchange: {}",
+            config_request.exchange
+        )));
+    }
+
+    let config = request.config.resolve_async(&state.markets).await?;
+
+    let now = Utc::now();
+    let session = PaperSession {
+        id: Uuid::new_v4(),
+        status: PaperStatus::Starting,
+        name: request.name.clone(),
+        account: PaperAccount::new(&config.initial_capital),
+        risk_events: Vec::new(),
+        config,
+        parameters: request.parameters.clone().unwrap_or_default(),
+        warmup_candles,
+        broker,
+        starting_equity: None,
+        error_message: None,
+        created_at: now,
+        started_at: None,
+        stopped_at: None,
+        updated_at: now,
+        event_tx: Some(state.paper_event_tx.clone()),
+        kill_switch: Arc::default(),
+    };
+    session.save(&state.db_pool).await;
+    session.broadcast();
+
+    let session_id = session.id;
+    let session = Arc::new(RwLock::new(session));
+
+    {
+        let mut sessions = state.paper_sessions.write().await;
+        sessions.insert(session_id, session.clone());
+    }
+
+    start_session(&state, session_id, session).await;
+
+    Ok(Json(CreatePaperSessionResponse { session_id }))
+}
+
+// Runs the session until it is stopped, keeping its control around for `stop_session`
+pub(crate) async fn start_session(
+    state: &AppState,
+    session_id: Uuid,
+    session: Arc<RwLock<PaperSession>>,
+) {
+    let control = TaskControl::default();
+    state
+        .paper_controls
+        .write()
+        .await
+        .insert(session_id, control.clone());
+
+    let finished = PaperSession::spawn(
+        session,
+        state.strategy_manager.clone(),
+        state.db_pool.clone(),
+        control,
+        state.exchanges.clone(),
+        state.shutdown_token.clone(),
+    );
+
+    let paper_controls = state.paper_controls.clone();
+    tokio::spawn(async move {
+        if let Err(e) = finished.await {
+            tracing::error!("Paper session {} stopped unexpectedly: {}", session_id, e);
+        }
+        paper_controls.write().await.remove(&session_id);
+    });
+}
+
+pub async fn get_all_sessions(State(state): State<AppState>) -> ApiResult<Vec<PaperSession>> {
+    let mut sessions = Vec::new();
+    let paper_sessions = state.paper_sessions.read().await;
+    for session in paper_sessions.values() {
+        let session = session.read().await;
+        sessions.push(session.clone());
+    }
+    sessions.sort_by_key(|session| std::cmp::Reverse(session.created_at));
+
+    Ok(Json(sessions))
+}
+
+pub async fn get_session(
+    State(state): State<AppState>,
+    Path(session_id): Path<Uuid>,
+) -> ApiResult<PaperSession> {
+    let paper_sessions = state.paper_sessions.read().await;
+    let session = paper_sessions.get(&session_id);
+
+    match session {
+        Some(session) => {
+            let session = session.read().await;
+            Ok(Json(session.clone()))
+        }
+        _ => Err(AppError::NotFound(format!(
+            "Paper session with id '{}' not found",
+            session_id
+        ))),
+    }
+}
+
+pub async fn stop_session(
+    State(state): State<AppState>,
+    Path(session_id): Path<Uuid>,
+) -> ApiResult<()> {
+    if !state.paper_sessions.read().await.contains_key(&session_id) {
+        return Err(AppError::NotFound(format!(
+            "Paper session with id '{}' not found",
+            session_id
+        )));
+    }
+
+    let paper_controls = state.paper_controls.read().await;
+    let Some(control) = paper_controls.get(&session_id) else {
+        return Err(AppError::BadRequest(format!(
+            "Paper session with id '{}' has already stopped",
+            session_id
+        )));
+    };
+    control.cancel();
+
+    Ok(Json(()))
+}
+
+pub async fn kill_session(
+    State(state): State<AppState>,
+    Path(session_id): Path<Uuid>,
+) -> ApiResult<()> {
+    set_kill_switch(&state, session_id, true).await
+}
+
+pub async fn release_session(
+    State(state): State<AppState>,
+    Path(session_id): Path<Uuid>,
+) -> ApiResult<()> {
+    set_kill_switch(&state, session_id, false).await
+}
+
+async fn set_kill_switch(state: &AppState, session_id: Uuid, engaged: bool) -> ApiResult<()> {
+    let Some(session) = state.paper_sessions.read().await.get(&session_id).cloned() else {
+        return Err(AppError::NotFound(format!(
+            "Paper session with id '{}' not found",
+            session_id
+        )));
+    };
+    if !state.paper_controls.read().await.contains_key(&session_id) {
+        return Err(AppError::BadRequest(format!(
+            "Paper session with id '{}' has already stopped",
+            session_id
+        )));
+    }
+
+    let mut session = session.write().await;
+    session.set_kill_switch(engaged);
+    session.broadcast();
+    session.save(&state.db_pool).await;
+
+    Ok(Json(()))
+}
+
+pub async fn stream_sessions(
+    State(state): State<AppState>,
+) -> Sse<impl Stream<Item = Result<Event, Infallible>>> {
+    let mut rx = state.paper_event_tx.subscribe();
+    let mut initial_events = Vec::new();
+    {
+        let paper_sessions = state.paper_sessions.read().await;
+        for session in paper_sessions.values() {
+            let session = session.read().await;
+            if let Ok(data) = serde_json::to_string(&*session) {
+                initial_events.push(data);
+            }
+        }
+    }
+
+    let stream = async_stream::stream! {
+        for data in initial_events {
+            yield Ok(Event::default().data(data));
+        }
+
+        loop {
+            tokio::select! {
+                _ = state.shutdown_token.cancelled() => {
+                    break;
+                }
+                result = rx.recv() => {
+                    let Ok(session) = result else {
+                        break;
+                    };
+
+                    let Ok(data) = serde_json::to_string(&session) else {
+                        continue;
+                    };
+
+                    yield Ok(Event::default().data(data));
+                }
+            }
+        }
+    };
+
+    Sse::new(stream).keep_alive(KeepAlive::default())
+}
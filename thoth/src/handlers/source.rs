@@ -92,10 +92,8 @@ pub async fn get_source(Query(query): Query<GetSourceQuery>) -> ApiResult<GetSou
         }
 
         children.sort_by(|a, b| match (&a.node_type, &b.node_type) {
-            (FileNodeType::Directory { .. }, FileNodeType::File { .. }) => std::cmp::Ordering::Less,
-            (FileNodeType::File { .. }, FileNodeType::Directory { .. }) => {
-                std::cmp::Ordering::Greater
-            }
+            (FileNodeType::Directory, FileNodeType::File) => std::cmp::Ordering::Less,
+            (FileNodeType::File, FileNodeType::Directory) => std::cmp::Ordering::Greater,
             _ => a.name.cmp(&b.name),
         });
 
@@ -1,5 +1,9 @@
+use crate::strategy::StrategyParameter;
 use crate::{app::AppState, errors::ApiResult};
-use axum::{Json, extract::State};
+use axum::{
+    Json,
+    extract::{Query, State},
+};
 use serde::{Deserialize, Serialize};
 use std::fs;
 use toml_edit::DocumentMut;
@@ -60,3 +64,25 @@ pub async fn list_strategies() -> ApiResult<ListStrategiesResponse> {
 
     Ok(Json(ListStrategiesResponse { strategies }))
 }
+
+#[derive(Debug, Clone, Deserialize, TS)]
+#[ts(export)]
+pub struct ListParametersQuery {
+    pub name: String,
+}
+
+#[derive(Debug, Clone, Serialize, TS)]
+#[ts(export)]
+pub struct ListParametersResponse {
+    pub parameters: Vec<StrategyParameter>,
+}
+
+pub async fn list_parameters(
+    State(state): State<AppState>,
+    Query(query): Query<ListParametersQuery>,
+) -> ApiResult<ListParametersResponse> {
+    let strategy_handle = state.strategy_manager.load_strategy(&query.name).await?;
+    let parameters = strategy_handle.parameters().to_vec();
+
+    Ok(Json(ListParametersResponse { parameters }))
+}
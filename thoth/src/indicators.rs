@@ -0,0 +1,51 @@
+mod average;
+mod momentum;
+mod trend;
+mod volatility;
+mod volume;
+
+use crate::strategy::Bar;
+use std::fmt::Debug;
+
+pub use average::{Ema, Sma};
+pub use momentum::{Macd, Rsi, Stochastic};
+pub use trend::Adx;
+pub use volatility::{Atr, Bollinger};
+pub use volume::Vwap;
+
+// Streaming indicators update in constant time per bar and report None until warmed up
+pub trait Indicator: Debug + Send {
+    fn update(&mut self, bar: &Bar);
+
+    fn value(&self) -> Option<f64>;
+
+    // Named outputs of indicators with more than one line, e.g. the MACD signal line
+    fn output(&self, name: &str) -> Option<Option<f64>> {
+        (name == "value").then(|| self.value())
+    }
+}
+
+#[cfg(test)]
+fn bar(high: f64, low: f64, close: f64) -> Bar {
+    use crate::strategy::Number;
+
+    Bar {
+        timestamp: Default::default(),
+        open: Number::Fast(close),
+        high: Number::Fast(high),
+        low: Number::Fast(low),
+        close: Number::Fast(close),
+        volume: Number::Fast(0.0),
+    }
+}
+
+#[cfg(test)]
+fn assert_close(actual: Option<f64>, expected: f64) {
+    let actual = actual.expect("indicator is not warmed up");
+    assert!(
+        (actual - expected).abs() < 1e-9,
+        "expected {}, got {}",
+        expected,
+        actual
+    );
+}
@@ -0,0 +1,132 @@
+use super::Indicator;
+use crate::strategy::Bar;
+use std::collections::VecDeque;
+
+#[derive(Debug, Clone)]
+pub struct Sma {
+    period: usize,
+    window: VecDeque<f64>,
+    sum: f64,
+}
+
+impl Sma {
+    pub fn new(period: usize) -> Self {
+        Self {
+            period: period.max(1),
+            window: VecDeque::with_capacity(period.max(1)),
+            sum: 0.0,
+        }
+    }
+
+    pub fn next(&mut self, value: f64) -> Option<f64> {
+        self.window.push_back(value);
+        self.sum += value;
+        if self.window.len() > self.period
+            && let Some(oldest) = self.window.pop_front()
+        {
+            self.sum -= oldest;
+        }
+        self.current()
+    }
+
+    pub fn current(&self) -> Option<f64> {
+        (self.window.len() == self.period).then(|| self.sum / self.period as f64)
+    }
+}
+
+impl Indicator for Sma {
+    fn update(&mut self, bar: &Bar) {
+        self.next(bar.close.to_f64());
+    }
+
+    fn value(&self) -> Option<f64> {
+        self.current()
+    }
+}
+
+// Seeded with the simple average of the first period values
+#[derive(Debug, Clone)]
+pub struct Ema {
+    period: usize,
+    alpha: f64,
+    count: usize,
+    value: f64,
+}
+
+impl Ema {
+    pub fn new(period: usize) -> Self {
+        let period = period.max(1);
+        Self::with_alpha(period, 2.0 / (period as f64 + 1.0))
+    }
+
+    // Wilder's smoothing, as used by RSI, ATR and ADX
+    pub fn wilder(period: usize) -> Self {
+        let period = period.max(1);
+        Self::with_alpha(period, 1.0 / period as f64)
+    }
+
+    fn with_alpha(period: usize, alpha: f64) -> Self {
+        Self {
+            period,
+            alpha,
+            count: 0,
+            value: 0.0,
+        }
+    }
+
+    pub fn next(&mut self, value: f64) -> Option<f64> {
+        self.count += 1;
+        if self.count <= self.period {
+            self.value += (value - self.value) / self.count as f64;
+        } else {
+            self.value += self.alpha * (value - self.value);
+        }
+        self.current()
+    }
+
+    pub fn current(&self) -> Option<f64> {
+        (self.count >= self.period).then_some(self.value)
+    }
+}
+
+impl Indicator for Ema {
+    fn update(&mut self, bar: &Bar) {
+        self.next(bar.close.to_f64());
+    }
+
+    fn value(&self) -> Option<f64> {
+        self.current()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::indicators::assert_close;
+
+    #[test]
+    fn sma_averages_the_last_period_values() {
+        let mut sma = Sma::new(3);
+        assert_eq!(sma.next(1.0), None);
+        assert_eq!(sma.next(2.0), None);
+        assert_close(sma.next(3.0), 2.0);
+        assert_close(sma.next(4.0), 3.0);
+        assert_close(sma.next(8.0), 5.0);
+    }
+
+    #[test]
+    fn ema_is_seeded_with_the_simple_average() {
+        let mut ema = Ema::new(3);
+        assert_eq!(ema.next(1.0), None);
+        assert_eq!(ema.next(2.0), None);
+        assert_close(ema.next(3.0), 2.0);
+        assert_close(ema.next(4.0), 3.0);
+        assert_close(ema.next(6.0), 4.5);
+
+        let mut wilder = Ema::wilder(3);
+        for value in [1.0, 2.0, 3.0] {
+            wilder.next(value);
+        }
+        assert_close(wilder.next(5.0), 3.0);
+    }
+}
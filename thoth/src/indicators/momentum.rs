@@ -0,0 +1,271 @@
+use super::{Ema, Indicator, Sma};
+use crate::strategy::Bar;
+use std::collections::VecDeque;
+
+#[derive(Debug, Clone)]
+pub struct Rsi {
+    previous: Option<f64>,
+    gain: Ema,
+    loss: Ema,
+}
+
+impl Rsi {
+    pub fn new(period: usize) -> Self {
+        Self {
+            previous: None,
+            gain: Ema::wilder(period),
+            loss: Ema::wilder(period),
+        }
+    }
+
+    pub fn next(&mut self, value: f64) -> Option<f64> {
+        if let Some(previous) = self.previous.replace(value) {
+            let change = value - previous;
+            self.gain.next(change.max(0.0));
+            self.loss.next((-change).max(0.0));
+        }
+        self.current()
+    }
+
+    pub fn current(&self) -> Option<f64> {
+        let gain = self.gain.current()?;
+        let loss = self.loss.current()?;
+        if loss == 0.0 {
+            return Some(if gain == 0.0 { 50.0 } else { 100.0 });
+        }
+        Some(100.0 - 100.0 / (1.0 + gain / loss))
+    }
+}
+
+impl Indicator for Rsi {
+    fn update(&mut self, bar: &Bar) {
+        self.next(bar.close.to_f64());
+    }
+
+    fn value(&self) -> Option<f64> {
+        self.current()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Macd {
+    fast: Ema,
+    slow: Ema,
+    signal: Ema,
+}
+
+impl Macd {
+    pub fn new(fast: usize, slow: usize, signal: usize) -> Self {
+        Self {
+            fast: Ema::new(fast),
+            slow: Ema::new(slow),
+            signal: Ema::new(signal),
+        }
+    }
+
+    pub fn next(&mut self, value: f64) -> Option<f64> {
+        let fast = self.fast.next(value);
+        let slow = self.slow.next(value);
+        if let (Some(fast), Some(slow)) = (fast, slow) {
+            self.signal.next(fast - slow);
+        }
+        self.macd()
+    }
+
+    pub fn macd(&self) -> Option<f64> {
+        Some(self.fast.current()? - self.slow.current()?)
+    }
+
+    pub fn signal(&self) -> Option<f64> {
+        self.signal.current()
+    }
+
+    pub fn histogram(&self) -> Option<f64> {
+        Some(self.macd()? - self.signal()?)
+    }
+}
+
+impl Default for Macd {
+    fn default() -> Self {
+        Self::new(12, 26, 9)
+    }
+}
+
+impl Indicator for Macd {
+    fn update(&mut self, bar: &Bar) {
+        self.next(bar.close.to_f64());
+    }
+
+    fn value(&self) -> Option<f64> {
+        self.macd()
+    }
+
+    fn output(&self, name: &str) -> Option<Option<f64>> {
+        match name {
+            "value" | "macd" => Some(self.macd()),
+            "signal" => Some(self.signal()),
+            "histogram" => Some(self.histogram()),
+            _ => None,
+        }
+    }
+}
+
+// Rolling extreme over a window, kept in a monotonic queue so each update is amortized O(1)
+#[derive(Debug, Clone)]
+pub(super) struct RollingExtreme {
+    period: usize,
+    max: bool,
+    index: usize,
+    queue: VecDeque<(usize, f64)>,
+}
+
+impl RollingExtreme {
+    pub(super) fn new(period: usize, max: bool) -> Self {
+        Self {
+            period: period.max(1),
+            max,
+            index: 0,
+            queue: VecDeque::new(),
+        }
+    }
+
+    pub(super) fn next(&mut self, value: f64) -> Option<f64> {
+        while let Some(&(_, last)) = self.queue.back() {
+            let dominated = if self.max {
+                last <= value
+            } else {
+                last >= value
+            };
+            if !dominated {
+                break;
+            }
+            self.queue.pop_back();
+        }
+        self.queue.push_back((self.index, value));
+        self.index += 1;
+
+        while let Some(&(first, _)) = self.queue.front() {
+            if first + self.period > self.index - 1 {
+                break;
+            }
+            self.queue.pop_front();
+        }
+
+        (self.index >= self.period)
+            .then(|| self.queue.front().map(|(_, value)| *value))
+            .flatten()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Stochastic {
+    highest: RollingExtreme,
+    lowest: RollingExtreme,
+    d: Sma,
+    k: Option<f64>,
+}
+
+impl Stochastic {
+    pub fn new(k_period: usize, d_period: usize) -> Self {
+        Self {
+            highest: RollingExtreme::new(k_period, true),
+            lowest: RollingExtreme::new(k_period, false),
+            d: Sma::new(d_period),
+            k: None,
+        }
+    }
+
+    pub fn k(&self) -> Option<f64> {
+        self.k
+    }
+
+    pub fn d(&self) -> Option<f64> {
+        self.d.current()
+    }
+}
+
+impl Indicator for Stochastic {
+    fn update(&mut self, bar: &Bar) {
+        let high = self.highest.next(bar.high.to_f64());
+        let low = self.lowest.next(bar.low.to_f64());
+        self.k = match (high, low) {
+            (Some(high), Some(low)) if high > low => {
+                Some(100.0 * (bar.close.to_f64() - low) / (high - low))
+            }
+            (Some(_), Some(_)) => Some(50.0),
+            _ => None,
+        };
+        if let Some(k) = self.k {
+            self.d.next(k);
+        }
+    }
+
+    fn value(&self) -> Option<f64> {
+        self.k
+    }
+
+    fn output(&self, name: &str) -> Option<Option<f64>> {
+        match name {
+            "value" | "k" => Some(self.k()),
+            "d" => Some(self.d()),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::indicators::assert_close;
+
+    #[test]
+    fn rsi_matches_wilders_example() {
+        let closes = [
+            44.34, 44.09, 44.15, 43.61, 44.33, 44.83, 45.10, 45.42, 45.84, 46.08, 45.89, 46.03,
+            45.61, 46.28,
+        ];
+        let mut rsi = Rsi::new(14);
+        for close in closes {
+            assert_eq!(rsi.next(close), None);
+        }
+
+        let expected = [
+            (46.28, 70.46413502109705),
+            (46.00, 66.24961855355505),
+            (46.03, 66.48094183471267),
+            (46.41, 69.34685316290866),
+            (46.22, 66.29471265892624),
+            (45.64, 57.91502067008557),
+        ];
+        for (close, value) in expected {
+            assert_close(rsi.next(close), value);
+        }
+    }
+
+    #[test]
+    fn rsi_without_losses_is_100() {
+        let mut rsi = Rsi::new(3);
+        for close in [1.0, 2.0, 3.0] {
+            rsi.next(close);
+        }
+        assert_close(rsi.next(4.0), 100.0);
+    }
+
+    #[test]
+    fn macd_signal_starts_once_the_slow_average_is_ready() {
+        let mut macd = Macd::new(3, 5, 2);
+        for close in [10.0, 11.0, 12.0, 11.0] {
+            assert_eq!(macd.next(close), None);
+        }
+
+        assert_close(macd.next(13.0), 0.6);
+        assert_eq!(macd.signal(), None);
+        assert_close(macd.next(14.0), 0.7333333333333333);
+        assert_close(macd.signal(), 0.6666666666666666);
+        assert_close(macd.next(13.0), 0.4888888888888889);
+        assert_close(macd.signal(), 0.548148148148148);
+        assert_close(macd.next(15.0), 0.6592592592592593);
+        assert_close(macd.signal(), 0.6222222222222222);
+        assert_close(macd.histogram(), 0.6592592592592593 - 0.6222222222222222);
+    }
+}
@@ -0,0 +1,126 @@
+use super::volatility::true_range;
+use super::{Ema, Indicator};
+use crate::strategy::Bar;
+
+#[derive(Debug, Clone)]
+pub struct Adx {
+    previous: Option<(f64, f64, f64)>,
+    range: Ema,
+    plus_movement: Ema,
+    minus_movement: Ema,
+    average: Ema,
+}
+
+impl Adx {
+    pub fn new(period: usize) -> Self {
+        Self {
+            previous: None,
+            range: Ema::wilder(period),
+            plus_movement: Ema::wilder(period),
+            minus_movement: Ema::wilder(period),
+            average: Ema::wilder(period),
+        }
+    }
+
+    pub fn plus_di(&self) -> Option<f64> {
+        let range = self.range.current()?;
+        Some(if range == 0.0 {
+            0.0
+        } else {
+            100.0 * self.plus_movement.current()? / range
+        })
+    }
+
+    pub fn minus_di(&self) -> Option<f64> {
+        let range = self.range.current()?;
+        Some(if range == 0.0 {
+            0.0
+        } else {
+            100.0 * self.minus_movement.current()? / range
+        })
+    }
+
+    pub fn adx(&self) -> Option<f64> {
+        self.average.current()
+    }
+}
+
+impl Indicator for Adx {
+    fn update(&mut self, bar: &Bar) {
+        let high = bar.high.to_f64();
+        let low = bar.low.to_f64();
+        let close = bar.close.to_f64();
+
+        let Some((previous_high, previous_low, previous_close)) =
+            self.previous.replace((high, low, close))
+        else {
+            return;
+        };
+
+        let up = high - previous_high;
+        let down = previous_low - low;
+        let plus = if up > down && up > 0.0 { up } else { 0.0 };
+        let minus = if down > up && down > 0.0 { down } else { 0.0 };
+
+        self.range.next(true_range(bar, Some(previous_close)));
+        self.plus_movement.next(plus);
+        self.minus_movement.next(minus);
+
+        if let (Some(plus_di), Some(minus_di)) = (self.plus_di(), self.minus_di()) {
+            let total = plus_di + minus_di;
+            let dx = if total == 0.0 {
+                0.0
+            } else {
+                100.0 * (plus_di - minus_di).abs() / total
+            };
+            self.average.next(dx);
+        }
+    }
+
+    fn value(&self) -> Option<f64> {
+        self.adx()
+    }
+
+    fn output(&self, name: &str) -> Option<Option<f64>> {
+        match name {
+            "value" | "adx" => Some(self.adx()),
+            "plus_di" => Some(self.plus_di()),
+            "minus_di" => Some(self.minus_di()),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::indicators::{assert_close, bar};
+
+    #[test]
+    fn adx_averages_the_directional_index() {
+        let mut adx = Adx::new(3);
+        let bars = [
+            (10.0, 8.0, 9.0),
+            (11.0, 9.0, 10.0),
+            (12.0, 10.0, 11.5),
+            (11.0, 9.5, 10.0),
+        ];
+        for (high, low, close) in bars {
+            adx.update(&bar(high, low, close));
+        }
+        assert_close(adx.plus_di(), 33.333333333333333);
+        assert_close(adx.minus_di(), 8.333333333333333);
+        assert_eq!(adx.adx(), None);
+
+        adx.update(&bar(13.0, 10.5, 12.5));
+        adx.update(&bar(12.5, 11.0, 11.2));
+        assert_close(adx.plus_di(), 36.03603603603603);
+        assert_close(adx.minus_di(), 3.6036036036036028);
+        assert_close(adx.adx(), 74.54545454545455);
+
+        adx.update(&bar(12.0, 10.0, 10.5));
+        assert_close(adx.plus_di(), 24.242424242424242);
+        assert_close(adx.minus_di(), 18.787878787878785);
+        assert_close(adx.adx(), 53.92232180964576);
+    }
+}
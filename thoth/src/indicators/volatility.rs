@@ -0,0 +1,156 @@
+use super::{Ema, Indicator};
+use crate::strategy::Bar;
+use std::collections::VecDeque;
+
+#[derive(Debug, Clone)]
+pub struct Bollinger {
+    period: usize,
+    multiplier: f64,
+    window: VecDeque<f64>,
+    sum: f64,
+    sum_squares: f64,
+}
+
+impl Bollinger {
+    pub fn new(period: usize, multiplier: f64) -> Self {
+        Self {
+            period: period.max(1),
+            multiplier,
+            window: VecDeque::with_capacity(period.max(1)),
+            sum: 0.0,
+            sum_squares: 0.0,
+        }
+    }
+
+    pub fn next(&mut self, value: f64) -> Option<f64> {
+        self.window.push_back(value);
+        self.sum += value;
+        self.sum_squares += value * value;
+        if self.window.len() > self.period
+            && let Some(oldest) = self.window.pop_front()
+        {
+            self.sum -= oldest;
+            self.sum_squares -= oldest * oldest;
+        }
+        self.middle()
+    }
+
+    pub fn middle(&self) -> Option<f64> {
+        (self.window.len() == self.period).then(|| self.sum / self.period as f64)
+    }
+
+    // Population standard deviation, clamped at zero against rounding in the running sums
+    fn deviation(&self) -> Option<f64> {
+        let mean = self.middle()?;
+        let variance = self.sum_squares / self.period as f64 - mean * mean;
+        Some(variance.max(0.0).sqrt())
+    }
+
+    pub fn upper(&self) -> Option<f64> {
+        Some(self.middle()? + self.multiplier * self.deviation()?)
+    }
+
+    pub fn lower(&self) -> Option<f64> {
+        Some(self.middle()? - self.multiplier * self.deviation()?)
+    }
+}
+
+impl Indicator for Bollinger {
+    fn update(&mut self, bar: &Bar) {
+        self.next(bar.close.to_f64());
+    }
+
+    fn value(&self) -> Option<f64> {
+        self.middle()
+    }
+
+    fn output(&self, name: &str) -> Option<Option<f64>> {
+        match name {
+            "value" | "middle" => Some(self.middle()),
+            "upper" => Some(self.upper()),
+            "lower" => Some(self.lower()),
+            _ => None,
+        }
+    }
+}
+
+pub(super) fn true_range(bar: &Bar, previous_close: Option<f64>) -> f64 {
+    let high = bar.high.to_f64();
+    let low = bar.low.to_f64();
+    match previous_close {
+        Some(close) => (high - low)
+            .max((high - close).abs())
+            .max((low - close).abs()),
+        None => high - low,
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Atr {
+    previous_close: Option<f64>,
+    average: Ema,
+}
+
+impl Atr {
+    pub fn new(period: usize) -> Self {
+        Self {
+            previous_close: None,
+            average: Ema::wilder(period),
+        }
+    }
+}
+
+impl Indicator for Atr {
+    fn update(&mut self, bar: &Bar) {
+        let range = true_range(bar, self.previous_close);
+        self.previous_close = Some(bar.close.to_f64());
+        self.average.next(range);
+    }
+
+    fn value(&self) -> Option<f64> {
+        self.average.current()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::indicators::{assert_close, bar};
+
+    #[test]
+    fn atr_smooths_the_true_range() {
+        let mut atr = Atr::new(3);
+        let bars = [
+            (10.0, 8.0, 9.0),
+            (11.0, 9.0, 10.0),
+            (12.0, 10.0, 11.5),
+            (11.0, 9.5, 10.0),
+            (13.0, 10.5, 12.5),
+            (12.5, 11.0, 11.2),
+            (12.0, 10.0, 10.5),
+        ];
+        let expected = [
+            None,
+            None,
+            Some(2.0),
+            Some(2.0),
+            Some(2.3333333333333335),
+            Some(2.055555555555556),
+            Some(2.037037037037037),
+        ];
+        for ((high, low, close), expected) in bars.into_iter().zip(expected) {
+            atr.update(&bar(high, low, close));
+            match expected {
+                Some(value) => assert_close(atr.value(), value),
+                None => assert_eq!(atr.value(), None),
+            }
+        }
+    }
+
+    #[test]
+    fn true_range_covers_gaps_from_the_previous_close() {
+        assert_eq!(true_range(&bar(12.0, 11.0, 11.5), None), 1.0);
+        assert_eq!(true_range(&bar(12.0, 11.0, 11.5), Some(9.0)), 3.0);
+        assert_eq!(true_range(&bar(12.0, 11.0, 11.5), Some(14.0)), 3.0);
+    }
+}
@@ -0,0 +1,44 @@
+use super::Indicator;
+use crate::strategy::Bar;
+use chrono::NaiveDate;
+
+// Volume weighted typical price, either over the whole run or reset at each UTC day
+#[derive(Debug, Clone, Default)]
+pub struct Vwap {
+    daily: bool,
+    day: Option<NaiveDate>,
+    price_volume: f64,
+    volume: f64,
+}
+
+impl Vwap {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn daily() -> Self {
+        Self {
+            daily: true,
+            ..Self::default()
+        }
+    }
+}
+
+impl Indicator for Vwap {
+    fn update(&mut self, bar: &Bar) {
+        let day = bar.timestamp.date_naive();
+        if self.daily && self.day.replace(day) != Some(day) {
+            self.price_volume = 0.0;
+            self.volume = 0.0;
+        }
+
+        let typical = (bar.high.to_f64() + bar.low.to_f64() + bar.close.to_f64()) / 3.0;
+        let volume = bar.volume.to_f64();
+        self.price_volume += typical * volume;
+        self.volume += volume;
+    }
+
+    fn value(&self) -> Option<f64> {
+        (self.volume > 0.0).then(|| self.price_volume / self.volume)
+    }
+}
@@ -6,6 +6,7 @@ pub mod errors;
 pub mod exchange;
 #[doc(hidden)]
 pub mod handlers;
+pub mod indicators;
 #[doc(hidden)]
 pub mod models;
 #[doc(hidden)]
@@ -18,6 +19,9 @@ pub mod tasks;
 pub mod utils;
 
 pub use crate::errors::AppResult;
-pub use crate::models::{Candle, MarketPrecision, Timeframe, TradingFees};
-pub use crate::strategy::{Order, OrderType, Strategy, StrategyContext, Trade, TradeType};
+pub use crate::models::{Candle, MarginConfig, MarketPrecision, Timeframe, TradingFees};
+pub use crate::strategy::{
+    BracketOrder, Order, OrderType, ParameterValues, Strategy, StrategyContext, StrategyParameter,
+    Trade, TradeType,
+};
 pub use strategy_macro::strategy;
@@ -1,13 +1,15 @@
 mod config;
 
-use thoth::app::create_app;
-use thoth::errors::{AppError, AppResult};
 use sqlx::postgres::PgPoolOptions;
 use std::{
     net::{Ipv4Addr, SocketAddrV4},
     str::FromStr,
 };
+use thoth::app::create_app;
+use thoth::errors::{AppError, AppResult};
+use thoth::tasks::worker::{WORKER_ARG, run_worker};
 use tokio_util::sync::CancellationToken;
+use tracing_subscriber::fmt::writer::BoxMakeWriter;
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
 
 #[tokio::main]
@@ -18,10 +20,24 @@ async fn main() -> AppResult<()> {
         ));
     };
 
+    // Workers report to the server on stdout, so they log to stderr
+    let worker = std::env::args().nth(1).as_deref() == Some(WORKER_ARG);
+    let writer = if worker {
+        BoxMakeWriter::new(std::io::stderr)
+    } else {
+        BoxMakeWriter::new(std::io::stdout)
+    };
     tracing_subscriber::registry()
-        .with(tracing_subscriber::EnvFilter::new(config.log_level))
-        .with(tracing_subscriber::fmt::layer())
+        .with(tracing_subscriber::EnvFilter::new(&config.log_level))
+        .with(tracing_subscriber::fmt::layer().with_writer(writer))
         .init();
+    if worker {
+        let db_pool = PgPoolOptions::new()
+            .max_connections(1)
+            .connect(&config.database.url)
+            .await?;
+        return run_worker(db_pool).await;
+    }
     tracing::info!("Loaded configuration");
 
     tracing::info!("Connecting to database at {}", config.database.url);
@@ -33,9 +49,7 @@ async fn main() -> AppResult<()> {
 
     sqlx::migrate!("./migrations").run(&db_pool).await?;
 
-    let token = CancellationToken::new();
-    let app = create_app(db_pool, token.clone()).await?;
-
+    // Bind first, so a second server fails before it resumes any unfinished tasks
     let Ok(host) = Ipv4Addr::from_str(&config.server.host) else {
         return Err(AppError::Internal(format!(
             "Invalid server host IP: {}",
@@ -44,6 +58,16 @@ async fn main() -> AppResult<()> {
     };
     let addr = SocketAddrV4::new(host, config.server.port);
     let listener = tokio::net::TcpListener::bind(&addr).await?;
+
+    let token = CancellationToken::new();
+    let app = create_app(
+        db_pool,
+        token.clone(),
+        config.tasks,
+        config.exchanges,
+        config.market_cache,
+    )
+    .await?;
     tracing::info!("Server listening on {}", addr);
 
     async fn shutdown_signal(token: CancellationToken) {
@@ -1,5 +1,5 @@
 mod candles;
 mod exchange;
 
-pub use candles::{AvailableCandleInfo, Candle, Timeframe};
-pub use exchange::{MarketPrecision, TradingFees};
+pub use candles::{AvailableCandleInfo, Candle, CandleColumns, Columns, Timeframe};
+pub use exchange::{CurrencyBalance, ExchangeOrder, MarginConfig, MarketPrecision, TradingFees};
@@ -1,10 +1,10 @@
 use crate::errors::AppError;
-use bigdecimal::BigDecimal;
-use chrono::{DateTime, TimeDelta, Utc, serde::ts_milliseconds};
+use bigdecimal::{BigDecimal, ToPrimitive};
+use chrono::{DateTime, Months, TimeDelta, Utc, serde::ts_milliseconds};
 use core::fmt;
 use serde::{Deserialize, Serialize};
 use sqlx::{FromRow, Type};
-use std::{hash::Hash, str::FromStr};
+use std::{hash::Hash, ops::Range, str::FromStr};
 use ts_rs::TS;
 
 #[derive(Debug, Clone, Serialize, FromRow, TS)]
@@ -28,6 +28,128 @@ pub struct Candle {
     pub volume: BigDecimal,
 }
 
+// Candles stored column by column as f64, the layout fast backtests load and simulate on
+#[derive(Debug, Clone, Default)]
+pub struct CandleColumns {
+    pub timestamps: Vec<DateTime<Utc>>,
+    pub open: Vec<f64>,
+    pub high: Vec<f64>,
+    pub low: Vec<f64>,
+    pub close: Vec<f64>,
+    pub volume: Vec<f64>,
+}
+
+impl CandleColumns {
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            timestamps: Vec::with_capacity(capacity),
+            open: Vec::with_capacity(capacity),
+            high: Vec::with_capacity(capacity),
+            low: Vec::with_capacity(capacity),
+            close: Vec::with_capacity(capacity),
+            volume: Vec::with_capacity(capacity),
+        }
+    }
+
+    pub fn push(
+        &mut self,
+        timestamp: DateTime<Utc>,
+        open: f64,
+        high: f64,
+        low: f64,
+        close: f64,
+        volume: f64,
+    ) {
+        self.timestamps.push(timestamp);
+        self.open.push(open);
+        self.high.push(high);
+        self.low.push(low);
+        self.close.push(close);
+        self.volume.push(volume);
+    }
+
+    pub fn len(&self) -> usize {
+        self.timestamps.len()
+    }
+
+    pub fn drop_oldest(&mut self, count: usize) {
+        let count = count.min(self.len());
+        self.timestamps.drain(..count);
+        self.open.drain(..count);
+        self.high.drain(..count);
+        self.low.drain(..count);
+        self.close.drain(..count);
+        self.volume.drain(..count);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.timestamps.is_empty()
+    }
+
+    pub fn view(&self, range: Range<usize>) -> Columns<'_> {
+        Columns {
+            timestamps: &self.timestamps[range.clone()],
+            open: &self.open[range.clone()],
+            high: &self.high[range.clone()],
+            low: &self.low[range.clone()],
+            close: &self.close[range.clone()],
+            volume: &self.volume[range],
+        }
+    }
+}
+
+impl From<&[Candle]> for CandleColumns {
+    fn from(candles: &[Candle]) -> Self {
+        let price = |value: &BigDecimal| value.to_f64().unwrap_or(f64::NAN);
+        let mut columns = Self::with_capacity(candles.len());
+        for candle in candles {
+            columns.push(
+                candle.timestamp,
+                price(&candle.open),
+                price(&candle.high),
+                price(&candle.low),
+                price(&candle.close),
+                price(&candle.volume),
+            );
+        }
+        columns
+    }
+}
+
+impl From<Columns<'_>> for CandleColumns {
+    fn from(columns: Columns<'_>) -> Self {
+        Self {
+            timestamps: columns.timestamps.to_vec(),
+            open: columns.open.to_vec(),
+            high: columns.high.to_vec(),
+            low: columns.low.to_vec(),
+            close: columns.close.to_vec(),
+            volume: columns.volume.to_vec(),
+        }
+    }
+}
+
+// Borrowed columns, each holding the same candles in order
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Columns<'a> {
+    pub timestamps: &'a [DateTime<Utc>],
+    pub open: &'a [f64],
+    pub high: &'a [f64],
+    pub low: &'a [f64],
+    pub close: &'a [f64],
+    pub volume: &'a [f64],
+}
+
+impl Columns<'_> {
+    pub fn len(&self) -> usize {
+        self.timestamps.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.timestamps.is_empty()
+    }
+}
+
 #[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash, Type, TS)]
 #[serde(rename_all = "lowercase")]
 #[sqlx(type_name = "text", rename_all = "lowercase")]
@@ -152,6 +274,19 @@ impl Timeframe {
             Timeframe::Y1 => TimeDelta::days(365),
         }
     }
+
+    // Monthly and yearly candles close on calendar boundaries rather than after a fixed duration
+    pub fn close_time(&self, open: DateTime<Utc>) -> DateTime<Utc> {
+        let months = match self {
+            Timeframe::MN1 => 1,
+            Timeframe::MN3 => 3,
+            Timeframe::MN4 => 4,
+            Timeframe::Y1 => 12,
+            _ => return open + self.to_delta(),
+        };
+        open.checked_add_months(Months::new(months))
+            .unwrap_or_else(|| open + self.to_delta())
+    }
 }
 
 impl fmt::Display for Timeframe {
@@ -2,12 +2,26 @@ use bigdecimal::{BigDecimal, RoundingMode, Zero};
 use serde::{Deserialize, Serialize};
 use ts_rs::TS;
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize, TS)]
+#[ts(export)]
 pub struct TradingFees {
+    #[ts(type = "string")]
     pub maker: BigDecimal,
+    #[ts(type = "string")]
     pub taker: BigDecimal,
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub struct MarginConfig {
+    #[ts(type = "string")]
+    pub leverage: BigDecimal,
+    #[ts(type = "string")]
+    pub maintenance_margin: BigDecimal,
+    #[ts(type = "string")]
+    pub daily_interest_rate: BigDecimal,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, TS)]
 #[ts(export)]
 pub struct MarketPrecision {
@@ -38,3 +52,29 @@ impl MarketPrecision {
         floored * &self.amount_precision
     }
 }
+
+// An order as the exchange reports it. `cost` is the quote amount of the filled part
+#[derive(Debug, Clone)]
+pub struct ExchangeOrder {
+    pub id: String,
+    pub symbol: String,
+    pub buy: bool,
+    pub status: String,
+    pub price: Option<BigDecimal>,
+    pub amount: BigDecimal,
+    pub filled: BigDecimal,
+    pub cost: BigDecimal,
+    pub fee: Option<(BigDecimal, String)>,
+}
+
+impl ExchangeOrder {
+    pub fn is_open(&self) -> bool {
+        self.status == "open"
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct CurrencyBalance {
+    pub free: BigDecimal,
+    pub total: BigDecimal,
+}
@@ -1,5 +1,5 @@
 use crate::errors::AppResult;
-use crate::models::{Candle, Timeframe, AvailableCandleInfo};
+use crate::models::{AvailableCandleInfo, Candle, CandleColumns, Timeframe};
 use chrono::{DateTime, Utc};
 use sqlx::PgPool;
 
@@ -77,6 +77,52 @@ pub async fn get_candles(
     Ok(candles)
 }
 
+// Candles converted to f64 by the database, skipping BigDecimal decoding for fast backtests
+pub async fn get_candle_columns(
+    pool: &PgPool,
+    exchange: &str,
+    symbol: &str,
+    timeframe: Timeframe,
+    start: Option<DateTime<Utc>>,
+    end: Option<DateTime<Utc>>,
+) -> AppResult<CandleColumns> {
+    let mut query_builder = sqlx::QueryBuilder::new(
+        "SELECT timestamp, open::float8, high::float8, low::float8, close::float8, volume::float8
+           FROM candles
+           WHERE exchange = ",
+    );
+
+    query_builder.push_bind(exchange);
+    query_builder.push(" AND symbol = ");
+    query_builder.push_bind(symbol);
+    query_builder.push(" AND timeframe = ");
+    query_builder.push_bind(timeframe);
+
+    if let Some(s) = start {
+        query_builder.push(" AND timestamp >= ");
+        query_builder.push_bind(s);
+    }
+
+    if let Some(e) = end {
+        query_builder.push(" AND timestamp <= ");
+        query_builder.push_bind(e);
+    }
+
+    query_builder.push(" ORDER BY timestamp ASC");
+
+    let rows = query_builder
+        .build_query_as::<(DateTime<Utc>, f64, f64, f64, f64, f64)>()
+        .fetch_all(pool)
+        .await?;
+
+    let mut columns = CandleColumns::with_capacity(rows.len());
+    for (timestamp, open, high, low, close, volume) in rows {
+        columns.push(timestamp, open, high, low, close, volume);
+    }
+
+    Ok(columns)
+}
+
 pub async fn get_latest_candle(
     pool: &PgPool,
     exchange: &str,
@@ -1,10 +1,15 @@
 use crate::errors::AppResult;
-use crate::tasks::{BacktestTask, FetchCandlesTask};
-use sqlx::PgPool;
+use crate::strategy::Trade;
+use crate::tasks::{
+    BacktestStatus, BacktestTask, FetchCandlesTask, OptimizationTask, PaperSession,
+};
+use chrono::{DateTime, Utc};
+use serde_json::Value;
+use sqlx::{PgPool, Postgres, QueryBuilder};
+use uuid::Uuid;
 
 pub async fn save_fetch_candles_task(pool: &PgPool, task: &FetchCandlesTask) -> AppResult<()> {
     let data = serde_json::to_value(task)?;
-    let completed_at = task.completed_at.ok_or("Task not completed yet")?;
 
     sqlx::query!(
         r#"
@@ -16,7 +21,7 @@ pub async fn save_fetch_candles_task(pool: &PgPool, task: &FetchCandlesTask) ->
         "#,
         task.id,
         data,
-        completed_at
+        task.completed_at
     )
     .execute(pool)
     .await?;
@@ -29,7 +34,7 @@ pub async fn load_fetch_candles_tasks(pool: &PgPool) -> AppResult<Vec<FetchCandl
         r#"
         SELECT data
         FROM fetch_candles_tasks
-        ORDER BY completed_at DESC
+        ORDER BY completed_at DESC NULLS FIRST
         "#
     )
     .fetch_all(pool)
@@ -45,12 +50,196 @@ pub async fn load_fetch_candles_tasks(pool: &PgPool) -> AppResult<Vec<FetchCandl
 }
 
 pub async fn save_backtest_task(pool: &PgPool, task: &BacktestTask) -> AppResult<()> {
+    let mut data = serde_json::to_value(task)?;
+    // Trades are kept in their own column, so listing tasks doesn't read them
+    let trades = data
+        .pointer_mut("/statistic/trades")
+        .map(|trades| std::mem::replace(trades, Value::Array(Vec::new())))
+        .unwrap_or_else(|| Value::Array(Vec::new()));
+
+    sqlx::query!(
+        r#"
+        INSERT INTO backtest_tasks (id, data, completed_at, status, strategy, exchange, symbol, created_at, trades)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
+        ON CONFLICT (id) DO UPDATE SET
+            data = EXCLUDED.data,
+            completed_at = EXCLUDED.completed_at,
+            status = EXCLUDED.status,
+            trades = EXCLUDED.trades
+        "#,
+        task.id,
+        data,
+        task.completed_at,
+        &task.status as &BacktestStatus,
+        task.name,
+        task.config.exchange,
+        task.config.symbol,
+        task.created_at,
+        trades
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+// Tasks left unfinished by the last run of the server
+pub async fn load_unfinished_backtest_tasks(pool: &PgPool) -> AppResult<Vec<BacktestTask>> {
+    let rows = sqlx::query!(
+        r#"
+        SELECT data
+        FROM backtest_tasks
+        WHERE completed_at IS NULL
+        "#
+    )
+    .fetch_all(pool)
+    .await?;
+
+    let mut tasks = Vec::new();
+    for row in rows {
+        let task = serde_json::from_value(row.data)?;
+        tasks.push(task);
+    }
+
+    Ok(tasks)
+}
+
+#[derive(Debug, Default)]
+pub struct BacktestTaskFilter {
+    pub status: Option<BacktestStatus>,
+    pub strategy: Option<String>,
+    pub exchange: Option<String>,
+    pub symbol: Option<String>,
+    pub start: Option<DateTime<Utc>>,
+    pub end: Option<DateTime<Utc>>,
+}
+
+fn push_backtest_task_filter(
+    query_builder: &mut QueryBuilder<Postgres>,
+    filter: &BacktestTaskFilter,
+) {
+    if let Some(status) = &filter.status {
+        query_builder.push(" AND status = ");
+        query_builder.push_bind(status.clone());
+    }
+
+    if let Some(strategy) = &filter.strategy {
+        query_builder.push(" AND strategy = ");
+        query_builder.push_bind(strategy.clone());
+    }
+
+    if let Some(exchange) = &filter.exchange {
+        query_builder.push(" AND exchange = ");
+        query_builder.push_bind(exchange.clone());
+    }
+
+    if let Some(symbol) = &filter.symbol {
+        query_builder.push(" AND symbol = ");
+        query_builder.push_bind(symbol.clone());
+    }
+
+    if let Some(s) = filter.start {
+        query_builder.push(" AND created_at >= ");
+        query_builder.push_bind(s);
+    }
+
+    if let Some(e) = filter.end {
+        query_builder.push(" AND created_at <= ");
+        query_builder.push_bind(e);
+    }
+}
+
+// Returns one page of tasks, newest first, along with the number of tasks matching the filter
+pub async fn list_backtest_tasks(
+    pool: &PgPool,
+    filter: &BacktestTaskFilter,
+    limit: i64,
+    offset: i64,
+) -> AppResult<(Vec<BacktestTask>, i64)> {
+    let mut query_builder = QueryBuilder::new("SELECT COUNT(*) FROM backtest_tasks WHERE TRUE");
+    push_backtest_task_filter(&mut query_builder, filter);
+    let total = query_builder
+        .build_query_scalar::<i64>()
+        .fetch_one(pool)
+        .await?;
+
+    let mut query_builder = QueryBuilder::new("SELECT data FROM backtest_tasks WHERE TRUE");
+    push_backtest_task_filter(&mut query_builder, filter);
+    query_builder.push(" ORDER BY created_at DESC LIMIT ");
+    query_builder.push_bind(limit);
+    query_builder.push(" OFFSET ");
+    query_builder.push_bind(offset);
+    let rows = query_builder
+        .build_query_scalar::<Value>()
+        .fetch_all(pool)
+        .await?;
+
+    let mut tasks = Vec::new();
+    for data in rows {
+        let task = serde_json::from_value(data)?;
+        tasks.push(task);
+    }
+
+    Ok((tasks, total))
+}
+
+pub async fn get_backtest_task(pool: &PgPool, task_id: Uuid) -> AppResult<Option<BacktestTask>> {
+    let row = sqlx::query!(
+        r#"
+        SELECT data
+        FROM backtest_tasks
+        WHERE id = $1
+        "#,
+        task_id
+    )
+    .fetch_optional(pool)
+    .await?;
+
+    match row {
+        Some(row) => Ok(Some(serde_json::from_value(row.data)?)),
+        None => Ok(None),
+    }
+}
+
+pub async fn get_backtest_trades(pool: &PgPool, task_id: Uuid) -> AppResult<Option<Vec<Trade>>> {
+    let row = sqlx::query!(
+        r#"
+        SELECT trades
+        FROM backtest_tasks
+        WHERE id = $1
+        "#,
+        task_id
+    )
+    .fetch_optional(pool)
+    .await?;
+
+    match row {
+        Some(row) => Ok(Some(serde_json::from_value(row.trades)?)),
+        None => Ok(None),
+    }
+}
+
+// Returns false when there was no such task
+pub async fn delete_backtest_task(pool: &PgPool, task_id: Uuid) -> AppResult<bool> {
+    let result = sqlx::query!(
+        r#"
+        DELETE FROM backtest_tasks
+        WHERE id = $1
+        "#,
+        task_id
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+pub async fn save_optimization_task(pool: &PgPool, task: &OptimizationTask) -> AppResult<()> {
     let data = serde_json::to_value(task)?;
-    let completed_at = task.completed_at.ok_or("Task not completed yet")?;
 
     sqlx::query!(
         r#"
-        INSERT INTO backtest_tasks (id, data, completed_at)
+        INSERT INTO optimization_tasks (id, data, completed_at)
         VALUES ($1, $2, $3)
         ON CONFLICT (id) DO UPDATE SET
             data = EXCLUDED.data,
@@ -58,7 +247,7 @@ pub async fn save_backtest_task(pool: &PgPool, task: &BacktestTask) -> AppResult
         "#,
         task.id,
         data,
-        completed_at
+        task.completed_at
     )
     .execute(pool)
     .await?;
@@ -66,12 +255,12 @@ pub async fn save_backtest_task(pool: &PgPool, task: &BacktestTask) -> AppResult
     Ok(())
 }
 
-pub async fn load_backtest_tasks(pool: &PgPool) -> AppResult<Vec<BacktestTask>> {
+pub async fn load_optimization_tasks(pool: &PgPool) -> AppResult<Vec<OptimizationTask>> {
     let rows = sqlx::query!(
         r#"
         SELECT data
-        FROM backtest_tasks
-        ORDER BY completed_at DESC
+        FROM optimization_tasks
+        ORDER BY completed_at DESC NULLS FIRST
         "#
     )
     .fetch_all(pool)
@@ -85,3 +274,76 @@ pub async fn load_backtest_tasks(pool: &PgPool) -> AppResult<Vec<BacktestTask>>
 
     Ok(tasks)
 }
+
+pub async fn save_paper_session(pool: &PgPool, session: &PaperSession) -> AppResult<()> {
+    let data = serde_json::to_value(session)?;
+
+    sqlx::query!(
+        r#"
+        INSERT INTO paper_sessions (id, data, created_at, stopped_at)
+        VALUES ($1, $2, $3, $4)
+        ON CONFLICT (id) DO UPDATE SET
+            data = EXCLUDED.data,
+            stopped_at = EXCLUDED.stopped_at
+        "#,
+        session.id,
+        data,
+        session.created_at,
+        session.stopped_at
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+pub async fn load_paper_sessions(pool: &PgPool) -> AppResult<Vec<PaperSession>> {
+    let rows = sqlx::query!(
+        r#"
+        SELECT data
+        FROM paper_sessions
+        ORDER BY created_at DESC
+        "#
+    )
+    .fetch_all(pool)
+    .await?;
+
+    let mut sessions = Vec::new();
+    for row in rows {
+        let session = serde_json::from_value(row.data)?;
+        sessions.push(session);
+    }
+
+    Ok(sessions)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn filtered(filter: &BacktestTaskFilter) -> String {
+        let mut query_builder = QueryBuilder::new("SELECT data FROM backtest_tasks WHERE TRUE");
+        push_backtest_task_filter(&mut query_builder, filter);
+        query_builder.sql().to_string()
+    }
+
+    #[test]
+    fn filters_only_on_the_fields_set() {
+        assert_eq!(
+            filtered(&BacktestTaskFilter::default()),
+            "SELECT data FROM backtest_tasks WHERE TRUE"
+        );
+
+        let filter = BacktestTaskFilter {
+            status: Some(BacktestStatus::Completed),
+            symbol: Some("BTC/USDT".to_string()),
+            end: Some(Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap()),
+            ..Default::default()
+        };
+        assert_eq!(
+            filtered(&filter),
+            "SELECT data FROM backtest_tasks WHERE TRUE AND status = $1 AND symbol = $2 AND created_at <= $3"
+        );
+    }
+}
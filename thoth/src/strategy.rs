@@ -1,12 +1,60 @@
+mod broker;
 mod context;
+mod fill;
 mod handle;
 mod manager;
+mod number;
+mod params;
+mod plugin;
+mod risk;
 
 use crate::errors::AppResult;
-pub use context::{Order, OrderType, StrategyContext, Trade, TradeType};
-pub use handle::StrategyHandle;
+pub use broker::{
+    Broker, BrokerAccount, BrokerType, CcxtBroker, ExitOrders, Market, MockBroker, OrderEvent,
+    OrderRequest, Simulator,
+};
+pub use context::{BracketOrder, Order, OrderType, StrategyContext, Trade, TradeType};
+pub use fill::{
+    FillModel, FillModelConfig, FixedSlippage, IdealFill, NextOpenFill, PartialFill, VolumeSlippage,
+};
+pub use handle::{StrategyHandle, StrategyInstance};
 pub use manager::{STRATEGY_WORKDIR_NAME, StrategyManager};
+pub use number::{BacktestEngine, Bar, Number};
+pub use params::{
+    ParameterValues, StrategyParameter, describe_parameter, parameter_value, resolve_parameters,
+    set_parameter, unknown_parameter,
+};
+pub use plugin::{
+    PLUGIN_ABI_VERSION, PLUGIN_BUILD_HASH, PLUGIN_DEPENDENCIES, PluginVTable, StrategyPlugin,
+    plugin_dependencies,
+};
+pub use risk::{MAX_RISK_EVENTS, RiskEvent, RiskLimits, RiskRule};
 
 pub trait Strategy: Send {
+    fn on_start(&mut self, _context: &mut StrategyContext) -> AppResult<()> {
+        Ok(())
+    }
+
     fn tick(&mut self, context: &mut StrategyContext) -> AppResult<()>;
+
+    fn on_order_filled(
+        &mut self,
+        _context: &mut StrategyContext,
+        _order: &Order,
+        _trade: &Trade,
+    ) -> AppResult<()> {
+        Ok(())
+    }
+
+    fn on_order_cancelled(
+        &mut self,
+        _context: &mut StrategyContext,
+        _order: &Order,
+    ) -> AppResult<()> {
+        Ok(())
+    }
+
+    fn on_finish(&mut self, _context: &mut StrategyContext) -> AppResult<()> {
+        Ok(())
+    }
 }
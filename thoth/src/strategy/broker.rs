@@ -0,0 +1,302 @@
+mod ccxt;
+mod mock;
+mod simulator;
+
+use super::context::SymbolState;
+use super::{Bar, BracketOrder, Number, Order, OrderType, Trade};
+use crate::errors::{AppError, AppResult};
+use crate::models::MarginConfig;
+use bigdecimal::RoundingMode;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::fmt::Debug;
+use ts_rs::TS;
+use uuid::Uuid;
+
+pub use ccxt::CcxtBroker;
+pub use mock::MockBroker;
+pub use simulator::Simulator;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, TS)]
+#[serde(rename_all = "snake_case")]
+#[ts(export)]
+pub enum BrokerType {
+    // Fills simulated against the candles, as in a backtest
+    #[default]
+    Simulated,
+    // Orders sent to the exchange through CCXT with the configured API keys
+    Ccxt,
+    // An in-memory exchange, for trying out the live order flow without keys
+    Mock,
+}
+
+#[derive(Debug, Clone)]
+pub enum OrderEvent {
+    Filled(Order, Trade),
+    Cancelled(Order),
+}
+
+// A resting order, priced and rounded by the context
+#[derive(Debug, Clone)]
+pub struct OrderRequest {
+    pub symbol_index: usize,
+    pub order_type: OrderType,
+    pub price: Number,
+    pub trigger_price: Option<Number>,
+    pub trail: Option<Number>,
+    pub amount: Number,
+}
+
+// A limit target and a stop where filling one cancels the other, waiting on `parent` to fill when
+// it is set
+#[derive(Debug, Clone)]
+pub struct ExitOrders {
+    pub symbol_index: usize,
+    pub buy: bool,
+    pub amount: Number,
+    pub target_price: Number,
+    pub stop_price: Number,
+    pub parent: Option<Uuid>,
+}
+
+// An account a paper session stored, with one entry per symbol in `positions` and
+// `accrued_interest`
+#[derive(Debug, Clone)]
+pub struct BrokerAccount {
+    pub balance: Number,
+    pub positions: Vec<Number>,
+    pub accrued_interest: Vec<Number>,
+    pub orders: Vec<Order<Number>>,
+    pub trades: Vec<Trade>,
+}
+
+// The candles of every symbol as of the current timestamp, which brokers price orders against
+pub struct Market<'m> {
+    symbols: &'m [SymbolState<'m>],
+    timestamp: Option<DateTime<Utc>>,
+}
+
+impl<'m> Market<'m> {
+    pub(crate) fn new(symbols: &'m [SymbolState<'m>], timestamp: Option<DateTime<Utc>>) -> Self {
+        Self { symbols, timestamp }
+    }
+
+    pub fn len(&self) -> usize {
+        self.symbols.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.symbols.is_empty()
+    }
+
+    pub fn timestamp(&self) -> Option<DateTime<Utc>> {
+        self.timestamp
+    }
+
+    pub fn symbol(&self, index: usize) -> &str {
+        &self.symbols[index].symbol
+    }
+
+    // The symbol's candle at the current timestamp, None when it has no candle there
+    pub fn current_bar(&self, index: usize) -> Option<Bar> {
+        let state = &self.symbols[index];
+        if state.columns.timestamps.last() != self.timestamp.as_ref() {
+            return None;
+        }
+        state.last_bar()
+    }
+
+    pub fn last_bar(&self, index: usize) -> Option<Bar> {
+        self.symbols[index].last_bar()
+    }
+
+    pub fn last_close(&self, index: usize) -> Option<Number> {
+        self.symbols[index].last_close()
+    }
+
+    pub fn last_prices(&self) -> Vec<Number> {
+        self.symbols
+            .iter()
+            .map(|s| s.last_close().unwrap_or_default())
+            .collect()
+    }
+
+    pub fn round_price(&self, index: usize, value: &Number, mode: RoundingMode) -> Number {
+        self.symbols[index].round_price(value, mode)
+    }
+
+    pub fn round_amount(&self, index: usize, value: &Number, mode: RoundingMode) -> Number {
+        self.symbols[index].round_amount(value, mode)
+    }
+
+    pub fn fee_rate(&self, index: usize, order_type: &OrderType) -> &Number {
+        let symbol = &self.symbols[index];
+        if order_type.is_maker() {
+            &symbol.maker_fee
+        } else {
+            &symbol.taker_fee
+        }
+    }
+}
+
+// Executes the orders a strategy places and keeps its account. The simulator fills them against
+// the candles, live brokers send them to an exchange
+pub trait Broker: Debug + Send {
+    // Called for every traded symbol in order, so indexes match the market's
+    fn add_symbol(&mut self, symbol: &str) -> AppResult<()>;
+
+    // Loads balances and open orders from the venue when the broker takes over an account
+    fn reconcile(&mut self) -> AppResult<()> {
+        Ok(())
+    }
+
+    // Picks up an account where a paper session left it, after its symbols are added. Balance and
+    // positions are what pending orders don't hold back
+    fn restore(&mut self, _account: BrokerAccount) -> AppResult<()> {
+        Err(AppError::Strategy(
+            "Accounts cannot be restored on this broker".into(),
+        ))
+    }
+
+    fn supports(&self, _order_type: &OrderType) -> bool {
+        true
+    }
+
+    // Quote currency not held back by pending orders
+    fn balance(&self) -> Number;
+
+    // Base amount of the symbol not held back by pending orders
+    fn position(&self, index: usize) -> Number;
+
+    fn equity(&self, market: &Market) -> Number;
+
+    fn orders(&self) -> Vec<Order>;
+
+    fn trades(&self) -> &[Trade];
+
+    fn margin(&self) -> Option<&MarginConfig> {
+        None
+    }
+
+    fn accrued_interest(&self) -> Number {
+        Number::zero(self.balance().engine())
+    }
+
+    // Returns the order id while part of the order is still waiting to fill
+    fn market_order(
+        &mut self,
+        market: &Market,
+        index: usize,
+        buy: bool,
+        amount: Number,
+    ) -> AppResult<Option<Uuid>>;
+
+    fn place_order(&mut self, market: &Market, request: OrderRequest) -> AppResult<Uuid>;
+
+    fn place_exit_orders(
+        &mut self,
+        _market: &Market,
+        _request: ExitOrders,
+    ) -> AppResult<BracketOrder> {
+        Err(AppError::Strategy(
+            "OCO and bracket orders are not supported by this broker".into(),
+        ))
+    }
+
+    fn cancel_order(&mut self, order_id: Uuid);
+
+    // Called before the tick with the candles of a new timestamp. `previous` is the timestamp the
+    // strategy last saw
+    fn update(&mut self, market: &Market, previous: Option<DateTime<Utc>>);
+
+    // Fills and cancellations since the last call, for the strategy's callbacks
+    fn take_events(&mut self) -> Vec<OrderEvent>;
+}
+
+#[cfg(test)]
+pub(super) mod testing {
+    use super::Broker;
+    use crate::errors::AppResult;
+    use crate::models::{CandleColumns, MarketPrecision, Timeframe, TradingFees};
+    use crate::strategy::{
+        BacktestEngine, Order, OrderType, RiskLimits, Strategy, StrategyContext, Trade,
+    };
+    use bigdecimal::BigDecimal;
+    use chrono::{TimeZone, Utc};
+
+    // Records the order callbacks the context dispatches
+    #[derive(Default)]
+    pub(crate) struct Recorder {
+        pub(crate) filled: Vec<OrderType>,
+        pub(crate) cancelled: Vec<OrderType>,
+    }
+
+    impl Strategy for Recorder {
+        fn tick(&mut self, _context: &mut StrategyContext) -> AppResult<()> {
+            Ok(())
+        }
+
+        fn on_order_filled(
+            &mut self,
+            _context: &mut StrategyContext,
+            order: &Order,
+            _trade: &Trade,
+        ) -> AppResult<()> {
+            self.filled.push(order.order_type.clone());
+            Ok(())
+        }
+
+        fn on_order_cancelled(
+            &mut self,
+            _context: &mut StrategyContext,
+            order: &Order,
+        ) -> AppResult<()> {
+            self.cancelled.push(order.order_type.clone());
+            Ok(())
+        }
+    }
+
+    pub(crate) fn decimal(value: &str) -> BigDecimal {
+        value.parse().unwrap()
+    }
+
+    // One-minute candles given as (open, high, low, close)
+    pub(crate) fn candles(prices: &[(f64, f64, f64, f64)]) -> CandleColumns {
+        let mut columns = CandleColumns::default();
+        for (i, (open, high, low, close)) in prices.iter().enumerate() {
+            let timestamp = Utc.timestamp_opt(60 * i as i64, 0).unwrap();
+            columns.push(timestamp, *open, *high, *low, *close, 1000.0);
+        }
+        columns
+    }
+
+    pub(crate) fn context<'a>(broker: Box<dyn Broker>) -> StrategyContext<'a> {
+        let mut context = StrategyContext::new(
+            Timeframe::M1,
+            BacktestEngine::Exact,
+            broker,
+            RiskLimits::default(),
+        )
+        .unwrap();
+        let precision = MarketPrecision {
+            price_precision: decimal("0.01"),
+            amount_precision: decimal("0.001"),
+        };
+        context
+            .add_symbol("BTC/USDT".into(), TradingFees::default(), precision)
+            .unwrap();
+        context
+    }
+
+    // Shows the context the candles up to `index` and runs the order updates of that candle
+    pub(crate) fn advance<'a>(
+        context: &mut StrategyContext<'a>,
+        columns: &'a CandleColumns,
+        index: usize,
+        strategy: &mut dyn Strategy,
+    ) {
+        context.symbols[0].columns = columns.view(0..index + 1);
+        context.before(columns.timestamps[index], strategy).unwrap();
+        context.after(strategy).unwrap();
+    }
+}
@@ -0,0 +1,428 @@
+use super::{Broker, Market, OrderEvent, OrderRequest};
+use crate::errors::{AppError, AppResult};
+use crate::exchange::ExchangeConfig;
+use crate::exchange::ccxt::CCXT;
+use crate::models::ExchangeOrder;
+use crate::strategy::{BacktestEngine, Number, Order, OrderType, Trade};
+use bigdecimal::BigDecimal;
+use chrono::{DateTime, Utc};
+use uuid::Uuid;
+
+#[derive(Debug)]
+struct LiveOrder {
+    order: Order<Number>,
+    exchange_id: String,
+    // Totals the exchange reported so far, so each poll only turns the difference into a fill
+    filled: BigDecimal,
+    cost: BigDecimal,
+    fee: BigDecimal,
+}
+
+// Trades on the exchange through CCXT. Balances and orders are whatever the exchange reports, so
+// orders placed outside the strategy show up after `reconcile`
+#[derive(Debug)]
+pub struct CcxtBroker {
+    exchange: CCXT,
+    exchange_name: String,
+    engine: BacktestEngine,
+    quote: Option<String>,
+    symbols: Vec<String>,
+    bases: Vec<String>,
+    balance: Number,
+    total_balance: Number,
+    positions: Vec<Number>,
+    total_positions: Vec<Number>,
+    orders: Vec<LiveOrder>,
+    trades: Vec<Trade>,
+    events: Vec<OrderEvent>,
+}
+
+impl CcxtBroker {
+    pub fn connect(
+        exchange: &str,
+        config: &ExchangeConfig,
+        engine: BacktestEngine,
+    ) -> AppResult<Self> {
+        Ok(Self {
+            exchange: CCXT::with_credentials(exchange, config)?,
+            exchange_name: exchange.to_string(),
+            engine,
+            quote: None,
+            symbols: Vec::new(),
+            bases: Vec::new(),
+            balance: Number::zero(engine),
+            total_balance: Number::zero(engine),
+            positions: Vec::new(),
+            total_positions: Vec::new(),
+            orders: Vec::new(),
+            trades: Vec::new(),
+            events: Vec::new(),
+        })
+    }
+
+    fn number(&self, value: &BigDecimal) -> Number {
+        Number::new(value, self.engine)
+    }
+
+    fn rejected(&self, error: AppError) -> AppError {
+        AppError::Strategy(format!(
+            "Order rejected by {}: {}",
+            self.exchange_name, error
+        ))
+    }
+
+    fn refresh_balance(&mut self) -> AppResult<()> {
+        let balances = self.exchange.fetch_balance()?;
+        let balance = |currency: &str| balances.get(currency).cloned().unwrap_or_default();
+
+        if let Some(quote) = &self.quote {
+            let quote = balance(quote);
+            self.balance = self.number(&quote.free);
+            self.total_balance = self.number(&quote.total);
+        }
+        for (index, base) in self.bases.iter().enumerate() {
+            let base = balance(base);
+            self.positions[index] = Number::new(&base.free, self.engine);
+            self.total_positions[index] = Number::new(&base.total, self.engine);
+        }
+        Ok(())
+    }
+
+    // Balances are refreshed on the next candle when the exchange does not answer now
+    fn try_refresh_balance(&mut self) {
+        if let Err(e) = self.refresh_balance() {
+            tracing::warn!("Failed to fetch balance from {}: {}", self.exchange_name, e);
+        }
+    }
+
+    fn track(
+        &mut self,
+        index: usize,
+        order_type: OrderType,
+        exchange_order: &ExchangeOrder,
+    ) -> Uuid {
+        let price = exchange_order.price.clone().unwrap_or_default();
+        let order = Order {
+            id: Uuid::new_v4(),
+            symbol: exchange_order.symbol.clone(),
+            order_type,
+            price: self.number(&price),
+            trigger_price: None,
+            trail: None,
+            amount: self.number(&exchange_order.amount),
+            fee: Number::zero(self.engine),
+            group: None,
+            parent: None,
+            reserved: true,
+            symbol_index: index,
+        };
+        let order_id = order.id;
+        self.orders.push(LiveOrder {
+            order,
+            exchange_id: exchange_order.id.clone(),
+            filled: BigDecimal::default(),
+            cost: BigDecimal::default(),
+            fee: BigDecimal::default(),
+        });
+        order_id
+    }
+
+    // Turns what the exchange filled since the last look into a trade, and stops tracking the
+    // order once it is no longer open
+    fn apply(&mut self, order_id: Uuid, exchange_order: &ExchangeOrder) {
+        let Some(pos) = self.orders.iter().position(|o| o.order.id == order_id) else {
+            return;
+        };
+
+        let filled = &exchange_order.filled - &self.orders[pos].filled;
+        if filled > BigDecimal::default() {
+            let cost = &exchange_order.cost - &self.orders[pos].cost;
+            let fee = self.fee(pos, exchange_order);
+            let price = &cost / &filled;
+
+            let live = &mut self.orders[pos];
+            live.filled = exchange_order.filled.clone();
+            live.cost = exchange_order.cost.clone();
+            let mut order = live.order.clone();
+            order.amount = Number::new(&filled, self.engine);
+            live.order.amount -= &order.amount;
+
+            let trade = Trade {
+                timestamp: Utc::now(),
+                symbol: order.symbol.clone(),
+                trade_type: order.order_type.trade_type(),
+                price: price.clone(),
+                amount: filled,
+                fee,
+                profit: None,
+            };
+            self.trades.push(trade.clone());
+            self.events
+                .push(OrderEvent::Filled(order.to_decimal(), trade));
+        }
+
+        if !exchange_order.is_open() {
+            let live = self.orders.remove(pos);
+            if exchange_order.filled < exchange_order.amount {
+                self.events
+                    .push(OrderEvent::Cancelled(live.order.to_decimal()));
+            }
+        }
+    }
+
+    // Fee paid since the last look, in the quote currency. Fees paid in other currencies, such as
+    // an exchange token, are left out
+    fn fee(&mut self, pos: usize, exchange_order: &ExchangeOrder) -> BigDecimal {
+        let Some((fee, currency)) = &exchange_order.fee else {
+            return BigDecimal::default();
+        };
+        let live = &mut self.orders[pos];
+        let paid = fee - &live.fee;
+        live.fee = fee.clone();
+
+        let index = live.order.symbol_index;
+        if Some(currency) == self.quote.as_ref() {
+            paid
+        } else if currency == &self.bases[index] && exchange_order.filled > BigDecimal::default() {
+            paid * &exchange_order.cost / &exchange_order.filled
+        } else {
+            BigDecimal::default()
+        }
+    }
+
+    fn submit(
+        &mut self,
+        index: usize,
+        order_type: OrderType,
+        symbol: &str,
+        amount: &Number,
+        price: Option<&Number>,
+    ) -> AppResult<Uuid> {
+        let price = price.map(Number::to_decimal);
+        let exchange_order = self
+            .exchange
+            .create_order(
+                symbol,
+                order_type.is_buy(),
+                &amount.to_decimal(),
+                price.as_ref(),
+            )
+            .map_err(|e| self.rejected(e))?;
+
+        // Market orders usually fill right away, but not every exchange says so in its response
+        let exchange_order = if exchange_order.is_open() && price.is_none() {
+            self.exchange
+                .fetch_order(&exchange_order.id, symbol)
+                .unwrap_or(exchange_order)
+        } else {
+            exchange_order
+        };
+
+        let order_id = self.track(index, order_type, &exchange_order);
+        self.apply(order_id, &exchange_order);
+        self.try_refresh_balance();
+        Ok(order_id)
+    }
+}
+
+impl Broker for CcxtBroker {
+    fn add_symbol(&mut self, symbol: &str) -> AppResult<()> {
+        let (base, quote) = self.exchange.currencies(symbol)?;
+        if self.quote.as_ref().is_some_and(|q| *q != quote) {
+            return Err(AppError::BadRequest(format!(
+                "All symbols must share the quote currency, {} is quoted in {}",
+                symbol, quote
+            )));
+        }
+
+        self.quote = Some(quote);
+        self.symbols.push(symbol.to_string());
+        self.bases.push(base);
+        self.positions.push(Number::zero(self.engine));
+        self.total_positions.push(Number::zero(self.engine));
+        Ok(())
+    }
+
+    // Picks up the exchange's balances and the open orders on the traded symbols, including
+    // ones left behind by an earlier run
+    fn reconcile(&mut self) -> AppResult<()> {
+        self.refresh_balance()?;
+
+        self.orders.clear();
+        for (index, symbol) in self.symbols.clone().iter().enumerate() {
+            for exchange_order in self.exchange.fetch_open_orders(symbol)? {
+                let order_type = match (exchange_order.buy, exchange_order.price.is_some()) {
+                    (true, true) => OrderType::LimitBuy,
+                    (false, true) => OrderType::LimitSell,
+                    (true, false) => OrderType::MarketBuy,
+                    (false, false) => OrderType::MarketSell,
+                };
+                let order_id = self.track(index, order_type, &exchange_order);
+                // Fills before the restart are already in the balances
+                let live = self
+                    .orders
+                    .iter_mut()
+                    .find(|o| o.order.id == order_id)
+                    .ok_or("Reconciled order went missing")?;
+                live.filled = exchange_order.filled.clone();
+                live.cost = exchange_order.cost.clone();
+                live.order.amount -= Number::new(&exchange_order.filled, self.engine);
+            }
+        }
+        Ok(())
+    }
+
+    fn supports(&self, order_type: &OrderType) -> bool {
+        matches!(
+            order_type,
+            OrderType::MarketBuy
+                | OrderType::MarketSell
+                | OrderType::LimitBuy
+                | OrderType::LimitSell
+        )
+    }
+
+    fn balance(&self) -> Number {
+        self.balance.clone()
+    }
+
+    fn position(&self, index: usize) -> Number {
+        self.positions[index].clone()
+    }
+
+    fn equity(&self, market: &Market) -> Number {
+        let positions: Number = self
+            .total_positions
+            .iter()
+            .zip(market.last_prices())
+            .map(|(position, price)| position * price)
+            .sum();
+        &self.total_balance + positions
+    }
+
+    fn orders(&self) -> Vec<Order> {
+        self.orders.iter().map(|o| o.order.to_decimal()).collect()
+    }
+
+    fn trades(&self) -> &[Trade] {
+        &self.trades
+    }
+
+    fn market_order(
+        &mut self,
+        market: &Market,
+        index: usize,
+        buy: bool,
+        amount: Number,
+    ) -> AppResult<Option<Uuid>> {
+        let order_type = if buy {
+            OrderType::MarketBuy
+        } else {
+            OrderType::MarketSell
+        };
+        let order_id = self.submit(index, order_type, market.symbol(index), &amount, None)?;
+
+        Ok(self
+            .orders
+            .iter()
+            .any(|o| o.order.id == order_id)
+            .then_some(order_id))
+    }
+
+    fn place_order(&mut self, market: &Market, request: OrderRequest) -> AppResult<Uuid> {
+        if !self.supports(&request.order_type) {
+            return Err(AppError::Strategy(format!(
+                "{:?} orders are not supported by this broker",
+                request.order_type
+            )));
+        }
+
+        let index = request.symbol_index;
+        self.submit(
+            index,
+            request.order_type,
+            market.symbol(index),
+            &request.amount,
+            Some(&request.price),
+        )
+    }
+
+    // An order the exchange fails to cancel stays pending, and a fill that raced the cancel shows
+    // up on the next candle
+    fn cancel_order(&mut self, order_id: Uuid) {
+        let Some(live) = self.orders.iter().find(|o| o.order.id == order_id) else {
+            return;
+        };
+        if let Err(e) = self
+            .exchange
+            .cancel_order(&live.exchange_id, &live.order.symbol)
+        {
+            tracing::warn!(
+                "Failed to cancel order {} on {}: {}",
+                live.exchange_id,
+                self.exchange_name,
+                e
+            );
+            return;
+        }
+
+        if let Some(pos) = self.orders.iter().position(|o| o.order.id == order_id) {
+            let live = self.orders.remove(pos);
+            self.events
+                .push(OrderEvent::Cancelled(live.order.to_decimal()));
+        }
+        self.try_refresh_balance();
+    }
+
+    fn update(&mut self, _market: &Market, _previous: Option<DateTime<Utc>>) {
+        let mut symbols: Vec<String> = self.orders.iter().map(|o| o.order.symbol.clone()).collect();
+        symbols.sort();
+        symbols.dedup();
+
+        for symbol in symbols {
+            let open = match self.exchange.fetch_open_orders(&symbol) {
+                Ok(open) => open,
+                Err(e) => {
+                    tracing::warn!(
+                        "Failed to fetch open orders from {}: {}",
+                        self.exchange_name,
+                        e
+                    );
+                    continue;
+                }
+            };
+
+            let tracked: Vec<(Uuid, String)> = self
+                .orders
+                .iter()
+                .filter(|o| o.order.symbol == symbol)
+                .map(|o| (o.order.id, o.exchange_id.clone()))
+                .collect();
+            for (order_id, exchange_id) in tracked {
+                // Orders missing from the open ones were filled or cancelled since the last candle
+                let exchange_order = match open.iter().find(|o| o.id == exchange_id) {
+                    Some(order) => order.clone(),
+                    None => match self.exchange.fetch_order(&exchange_id, &symbol) {
+                        Ok(order) => order,
+                        Err(e) => {
+                            tracing::warn!(
+                                "Failed to fetch order {} from {}: {}",
+                                exchange_id,
+                                self.exchange_name,
+                                e
+                            );
+                            continue;
+                        }
+                    },
+                };
+                self.apply(order_id, &exchange_order);
+            }
+        }
+
+        self.try_refresh_balance();
+    }
+
+    fn take_events(&mut self) -> Vec<OrderEvent> {
+        std::mem::take(&mut self.events)
+    }
+}
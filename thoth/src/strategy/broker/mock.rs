@@ -0,0 +1,438 @@
+use super::{Broker, BrokerAccount, Market, OrderEvent, OrderRequest};
+use crate::errors::{AppError, AppResult};
+use crate::strategy::{BacktestEngine, Number, Order, OrderType, Trade};
+use bigdecimal::BigDecimal;
+use chrono::{DateTime, Utc};
+use uuid::Uuid;
+
+// A local exchange that takes market and limit orders like a live broker does, without fees or
+// slippage. Market orders fill at the last close and limit orders once a candle trades through them
+#[derive(Debug)]
+pub struct MockBroker {
+    engine: BacktestEngine,
+    balance: Number,
+    symbols: usize,
+    positions: Vec<Number>,
+    orders: Vec<Order<Number>>,
+    trades: Vec<Trade>,
+    events: Vec<OrderEvent>,
+}
+
+impl MockBroker {
+    pub fn new(engine: BacktestEngine, balance: &BigDecimal) -> Self {
+        Self {
+            engine,
+            balance: Number::new(balance, engine),
+            symbols: 0,
+            positions: Vec::new(),
+            orders: Vec::new(),
+            trades: Vec::new(),
+            events: Vec::new(),
+        }
+    }
+
+    // Seeds a base currency balance, as if it was already held on the exchange
+    pub fn with_position(mut self, index: usize, amount: &BigDecimal) -> Self {
+        if self.positions.len() <= index {
+            self.positions.resize(index + 1, Number::zero(self.engine));
+        }
+        self.positions[index] = Number::new(amount, self.engine);
+        self
+    }
+
+    fn check_funds(
+        &self,
+        index: usize,
+        buy: bool,
+        price: &Number,
+        amount: &Number,
+    ) -> AppResult<()> {
+        if buy && price * amount > self.balance {
+            return Err(AppError::Strategy("Insufficient funds".into()));
+        }
+        if !buy && amount > &self.positions[index] {
+            return Err(AppError::Strategy(
+                "Insufficient base asset amount to sell".into(),
+            ));
+        }
+        Ok(())
+    }
+
+    fn fill(&mut self, order: Order<Number>, price: Number, timestamp: DateTime<Utc>) {
+        let index = order.symbol_index;
+        let notional = &price * &order.amount;
+        if order.order_type.is_buy() {
+            // Limit buys held back their cost at the limit price, which is also the fill price
+            if !order.reserved {
+                self.balance -= &notional;
+            }
+            self.positions[index] += &order.amount;
+        } else {
+            if !order.reserved {
+                self.positions[index] -= &order.amount;
+            }
+            self.balance += &notional;
+        }
+
+        let trade = Trade {
+            timestamp,
+            symbol: order.symbol.clone(),
+            trade_type: order.order_type.trade_type(),
+            price: price.to_decimal(),
+            amount: order.amount.to_decimal(),
+            fee: BigDecimal::from(0),
+            profit: None,
+        };
+        self.trades.push(trade.clone());
+        self.events
+            .push(OrderEvent::Filled(order.to_decimal(), trade));
+    }
+}
+
+impl Broker for MockBroker {
+    fn add_symbol(&mut self, _symbol: &str) -> AppResult<()> {
+        // Positions seeded up front keep their index
+        self.symbols += 1;
+        if self.positions.len() < self.symbols {
+            self.positions.push(Number::zero(self.engine));
+        }
+        Ok(())
+    }
+
+    // Every pending order held back its funds when it was placed
+    fn restore(&mut self, account: BrokerAccount) -> AppResult<()> {
+        self.balance = account.balance;
+        for (index, position) in account.positions.into_iter().enumerate() {
+            self.positions[index] = position;
+        }
+        self.orders = account
+            .orders
+            .into_iter()
+            .map(|order| Order {
+                reserved: true,
+                ..order
+            })
+            .collect();
+        self.trades = account.trades;
+        Ok(())
+    }
+
+    fn supports(&self, order_type: &OrderType) -> bool {
+        matches!(
+            order_type,
+            OrderType::MarketBuy
+                | OrderType::MarketSell
+                | OrderType::LimitBuy
+                | OrderType::LimitSell
+        )
+    }
+
+    fn balance(&self) -> Number {
+        self.balance.clone()
+    }
+
+    fn position(&self, index: usize) -> Number {
+        self.positions[index].clone()
+    }
+
+    fn equity(&self, market: &Market) -> Number {
+        let prices = market.last_prices();
+        let reserved: Number = self
+            .orders
+            .iter()
+            .map(|o| {
+                if o.order_type.is_buy() {
+                    &o.price * &o.amount
+                } else {
+                    &o.amount * &prices[o.symbol_index]
+                }
+            })
+            .sum();
+        let positions: Number = self
+            .positions
+            .iter()
+            .zip(&prices)
+            .map(|(position, price)| position * price)
+            .sum();
+        &self.balance + reserved + positions
+    }
+
+    fn orders(&self) -> Vec<Order> {
+        self.orders.iter().map(Order::to_decimal).collect()
+    }
+
+    fn trades(&self) -> &[Trade] {
+        &self.trades
+    }
+
+    fn market_order(
+        &mut self,
+        market: &Market,
+        index: usize,
+        buy: bool,
+        amount: Number,
+    ) -> AppResult<Option<Uuid>> {
+        let bar = market
+            .last_bar(index)
+            .ok_or(AppError::Strategy("No candles available".into()))?;
+        self.check_funds(index, buy, &bar.close, &amount)?;
+
+        let order = Order {
+            id: Uuid::new_v4(),
+            symbol: market.symbol(index).to_string(),
+            order_type: if buy {
+                OrderType::MarketBuy
+            } else {
+                OrderType::MarketSell
+            },
+            price: bar.close.clone(),
+            trigger_price: None,
+            trail: None,
+            amount,
+            fee: Number::zero(self.engine),
+            group: None,
+            parent: None,
+            reserved: false,
+            symbol_index: index,
+        };
+        self.fill(order, bar.close, bar.timestamp);
+        Ok(None)
+    }
+
+    fn place_order(&mut self, market: &Market, request: OrderRequest) -> AppResult<Uuid> {
+        if !self.supports(&request.order_type) {
+            return Err(AppError::Strategy(format!(
+                "{:?} orders are not supported by this broker",
+                request.order_type
+            )));
+        }
+
+        let index = request.symbol_index;
+        let buy = request.order_type.is_buy();
+        self.check_funds(index, buy, &request.price, &request.amount)?;
+        if buy {
+            self.balance -= &request.price * &request.amount;
+        } else {
+            self.positions[index] -= &request.amount;
+        }
+
+        let order = Order {
+            id: Uuid::new_v4(),
+            symbol: market.symbol(index).to_string(),
+            order_type: request.order_type,
+            price: request.price,
+            trigger_price: None,
+            trail: None,
+            amount: request.amount,
+            fee: Number::zero(self.engine),
+            group: None,
+            parent: None,
+            reserved: true,
+            symbol_index: index,
+        };
+        let order_id = order.id;
+        self.orders.push(order);
+        Ok(order_id)
+    }
+
+    fn cancel_order(&mut self, order_id: Uuid) {
+        let Some(pos) = self.orders.iter().position(|o| o.id == order_id) else {
+            return;
+        };
+        let order = self.orders.remove(pos);
+        if order.order_type.is_buy() {
+            self.balance += &order.price * &order.amount;
+        } else {
+            self.positions[order.symbol_index] += &order.amount;
+        }
+        self.events.push(OrderEvent::Cancelled(order.to_decimal()));
+    }
+
+    fn update(&mut self, market: &Market, _previous: Option<DateTime<Utc>>) {
+        let mut pending = Vec::new();
+        for order in std::mem::take(&mut self.orders) {
+            let bar = market.current_bar(order.symbol_index);
+            let filled = bar.as_ref().is_some_and(|bar| {
+                if order.order_type.is_buy() {
+                    bar.low <= order.price
+                } else {
+                    bar.high >= order.price
+                }
+            });
+            match bar {
+                Some(bar) if filled => {
+                    let price = order.price.clone();
+                    self.fill(order, price, bar.timestamp);
+                }
+                _ => pending.push(order),
+            }
+        }
+        self.orders = pending;
+    }
+
+    fn take_events(&mut self) -> Vec<OrderEvent> {
+        std::mem::take(&mut self.events)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::testing::{Recorder, advance, candles, context, decimal};
+    use super::*;
+    use crate::strategy::{IdealFill, Simulator};
+    use std::sync::Arc;
+
+    fn mock(balance: &str) -> MockBroker {
+        MockBroker::new(BacktestEngine::Exact, &decimal(balance))
+    }
+
+    #[test]
+    fn fills_market_orders_at_the_close_and_limit_orders_traded_through() {
+        let columns = candles(&[(100.0, 100.0, 100.0, 100.0), (100.0, 101.0, 98.0, 99.0)]);
+        let mut strategy = Recorder::default();
+        let mut context = context(Box::new(mock("1000")));
+        advance(&mut context, &columns, 0, &mut strategy);
+
+        context.market_buy(&decimal("1")).unwrap();
+        context.after(&mut strategy).unwrap();
+        assert_eq!(context.balance(), decimal("900"));
+        assert_eq!(context.position(), decimal("1"));
+
+        context.limit_buy(&decimal("99"), &decimal("1")).unwrap();
+        context
+            .limit_sell(&decimal("105"), &decimal("0.5"))
+            .unwrap();
+        assert_eq!(context.balance(), decimal("801"));
+        assert_eq!(context.position(), decimal("0.5"));
+
+        advance(&mut context, &columns, 1, &mut strategy);
+        assert!(matches!(
+            strategy.filled.as_slice(),
+            [OrderType::MarketBuy, OrderType::LimitBuy]
+        ));
+        assert_eq!(context.balance(), decimal("801"));
+        assert_eq!(context.position(), decimal("1.5"));
+        assert_eq!(context.orders().len(), 1);
+        assert_eq!(context.trades().len(), 2);
+        assert_eq!(context.equity().unwrap(), decimal("999"));
+    }
+
+    #[test]
+    fn cancelling_releases_the_reservation() {
+        let columns = candles(&[(100.0, 100.0, 100.0, 100.0)]);
+        let mut strategy = Recorder::default();
+        let mut context = context(Box::new(mock("1000").with_position(0, &decimal("2"))));
+        advance(&mut context, &columns, 0, &mut strategy);
+
+        let buy = context
+            .limit_buy(&decimal("90"), &decimal("1"))
+            .unwrap()
+            .unwrap();
+        let sell = context
+            .limit_sell(&decimal("110"), &decimal("1"))
+            .unwrap()
+            .unwrap();
+        assert_eq!(context.balance(), decimal("910"));
+        assert_eq!(context.position(), decimal("1"));
+
+        context.cancel_order(buy);
+        context.cancel_order(sell);
+        context.after(&mut strategy).unwrap();
+        assert!(matches!(
+            strategy.cancelled.as_slice(),
+            [OrderType::LimitBuy, OrderType::LimitSell]
+        ));
+        assert_eq!(context.balance(), decimal("1000"));
+        assert_eq!(context.position(), decimal("2"));
+        assert!(context.orders().is_empty());
+    }
+
+    #[test]
+    fn rejects_orders_the_account_cannot_cover() {
+        let columns = candles(&[(100.0, 100.0, 100.0, 100.0)]);
+        let mut strategy = Recorder::default();
+        let mut context = context(Box::new(mock("150").with_position(0, &decimal("1"))));
+        advance(&mut context, &columns, 0, &mut strategy);
+
+        assert!(context.market_buy(&decimal("2")).is_err());
+        assert!(
+            context
+                .limit_sell(&decimal("110"), &decimal("1.5"))
+                .is_err()
+        );
+        assert!(context.limit_buy(&decimal("80"), &decimal("3")).is_err());
+        assert!(context.trades().is_empty());
+        assert!(context.orders().is_empty());
+    }
+
+    #[test]
+    fn takes_over_a_running_account_with_its_own_holdings() {
+        let columns = candles(&[(100.0, 100.0, 100.0, 100.0), (100.0, 112.0, 99.0, 111.0)]);
+        let mut strategy = Recorder::default();
+        let simulator = Simulator::new(
+            BacktestEngine::Exact,
+            &decimal("1000"),
+            None,
+            Arc::new(IdealFill),
+        );
+        let mut context = context(Box::new(simulator));
+        advance(&mut context, &columns, 0, &mut strategy);
+        context.limit_buy(&decimal("95"), &decimal("1")).unwrap();
+
+        // Simulated orders are cancelled and the account becomes the broker's after reconcile
+        let broker = mock("500").with_position(0, &decimal("2"));
+        context.set_broker(Box::new(broker), &mut strategy).unwrap();
+        assert!(matches!(
+            strategy.cancelled.as_slice(),
+            [OrderType::LimitBuy]
+        ));
+        assert_eq!(context.balance(), decimal("500"));
+        assert_eq!(context.position(), decimal("2"));
+        assert!(context.orders().is_empty());
+
+        context.limit_sell(&decimal("110"), &decimal("2")).unwrap();
+        advance(&mut context, &columns, 1, &mut strategy);
+        assert!(matches!(strategy.filled.as_slice(), [OrderType::LimitSell]));
+        assert_eq!(context.balance(), decimal("720"));
+        assert_eq!(context.position(), decimal("0"));
+        assert_eq!(context.trades().len(), 1);
+    }
+
+    #[test]
+    fn restores_a_stored_account_with_its_pending_orders() {
+        let columns = candles(&[(100.0, 100.0, 100.0, 100.0), (100.0, 106.0, 99.0, 105.0)]);
+        let mut strategy = Recorder::default();
+        let mut context = context(Box::new(mock("1000")));
+        let number = |value: &str| Number::new(&decimal(value), BacktestEngine::Exact);
+        let order = Order {
+            id: Uuid::new_v4(),
+            symbol: "BTC/USDT".into(),
+            order_type: OrderType::LimitSell,
+            price: number("105"),
+            trigger_price: None,
+            trail: None,
+            amount: number("1"),
+            fee: number("0"),
+            group: None,
+            parent: None,
+            reserved: false,
+            symbol_index: 0,
+        };
+        context
+            .restore(BrokerAccount {
+                balance: number("400"),
+                positions: vec![number("2")],
+                accrued_interest: vec![number("0")],
+                orders: vec![order],
+                trades: Vec::new(),
+            })
+            .unwrap();
+
+        advance(&mut context, &columns, 0, &mut strategy);
+        assert_eq!(context.equity().unwrap(), decimal("700"));
+        advance(&mut context, &columns, 1, &mut strategy);
+        assert!(matches!(strategy.filled.as_slice(), [OrderType::LimitSell]));
+        assert_eq!(context.balance(), decimal("505"));
+        assert_eq!(context.position(), decimal("2"));
+    }
+}
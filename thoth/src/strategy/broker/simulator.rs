@@ -0,0 +1,1257 @@
+use super::{Broker, BrokerAccount, ExitOrders, Market, OrderEvent, OrderRequest};
+use crate::errors::{AppError, AppResult};
+use crate::models::MarginConfig;
+use crate::strategy::{
+    BacktestEngine, Bar, BracketOrder, FillModel, Number, Order, OrderType, Trade, TradeType,
+};
+use bigdecimal::{BigDecimal, RoundingMode};
+use chrono::{DateTime, Utc};
+use std::collections::HashMap;
+use std::sync::Arc;
+use uuid::Uuid;
+
+const MILLISECONDS_PER_DAY: i64 = 24 * 60 * 60 * 1000;
+
+impl Order<Number> {
+    // Returns the fill price if the order executes within the candle, filling at the open when
+    // the market gapped through the trigger
+    fn trigger(&mut self, bar: &Bar) -> Option<Number> {
+        if self.parent.is_some() {
+            return None;
+        }
+
+        let trigger = self
+            .trigger_price
+            .clone()
+            .unwrap_or_else(|| self.price.clone());
+        let open = bar.open.clone();
+
+        match self.order_type {
+            OrderType::MarketBuy | OrderType::MarketSell => Some(open),
+            OrderType::LimitBuy => (self.price >= bar.low).then(|| self.price.clone()),
+            OrderType::LimitSell => (self.price <= bar.high).then(|| self.price.clone()),
+            OrderType::StopBuy | OrderType::TrailingStopBuy if trigger <= bar.high => {
+                Some(trigger.max(open))
+            }
+            OrderType::StopSell | OrderType::TrailingStopSell if trigger >= bar.low => {
+                Some(trigger.min(open))
+            }
+            OrderType::TakeProfitBuy if trigger >= bar.low => Some(trigger.min(open)),
+            OrderType::TakeProfitSell if trigger <= bar.high => Some(trigger.max(open)),
+            OrderType::StopLimitBuy if trigger <= bar.high => {
+                self.order_type = OrderType::LimitBuy;
+                self.trigger_price = None;
+                (self.price >= bar.low).then(|| self.price.clone().min(trigger.max(open)))
+            }
+            OrderType::StopLimitSell if trigger >= bar.low => {
+                self.order_type = OrderType::LimitSell;
+                self.trigger_price = None;
+                (self.price <= bar.high).then(|| self.price.clone().max(trigger.min(open)))
+            }
+            // Trailing stops only move after the candle closes, the intra-candle path is unknown
+            OrderType::TrailingStopBuy => {
+                if let Some(trail) = &self.trail {
+                    self.trigger_price = Some(trigger.min(&bar.low + trail));
+                }
+                None
+            }
+            OrderType::TrailingStopSell => {
+                if let Some(trail) = &self.trail {
+                    self.trigger_price = Some(trigger.max(&bar.high - trail));
+                }
+                None
+            }
+            _ => None,
+        }
+    }
+
+    // Splits off the filled part of the order, keeping the remainder's share of the reservation
+    fn split(&mut self, amount: &Number) -> Self {
+        let fee = &self.fee * amount / &self.amount;
+        let filled = Order {
+            amount: amount.clone(),
+            fee: fee.clone(),
+            ..self.clone()
+        };
+        self.amount -= amount;
+        self.fee -= fee;
+        filled
+    }
+
+    // Quote currency held back while the order is pending
+    fn reservation(&self) -> Number {
+        if self.order_type.is_buy() {
+            &self.price * &self.amount + &self.fee
+        } else {
+            self.fee.clone()
+        }
+    }
+}
+
+// Fills orders against the candles the way a backtest does, following the fill model
+#[derive(Debug)]
+pub struct Simulator {
+    engine: BacktestEngine,
+    balance: Number,
+    positions: Vec<Number>,
+    volume_left: Vec<Option<Number>>,
+    trades: Vec<Trade>,
+    orders: Vec<Order<Number>>,
+    margin: Option<MarginConfig>,
+    // Interest owed per symbol, on borrowed base for shorts and borrowed quote for longs
+    accrued_interest: Vec<Number>,
+    fill_model: Arc<dyn FillModel>,
+    events: Vec<OrderEvent>,
+}
+
+impl Simulator {
+    pub fn new(
+        engine: BacktestEngine,
+        balance: &BigDecimal,
+        margin: Option<MarginConfig>,
+        fill_model: Arc<dyn FillModel>,
+    ) -> Self {
+        Self {
+            engine,
+            balance: Number::new(balance, engine),
+            positions: Vec::new(),
+            volume_left: Vec::new(),
+            trades: Vec::new(),
+            orders: Vec::new(),
+            margin,
+            accrued_interest: Vec::new(),
+            fill_model,
+            events: Vec::new(),
+        }
+    }
+
+    fn number(&self, value: &BigDecimal) -> Number {
+        Number::new(value, self.engine)
+    }
+
+    fn new_order(&self, market: &Market, request: OrderRequest) -> Order<Number> {
+        let index = request.symbol_index;
+        let notional = &request.price * &request.amount;
+        let fee = market.round_amount(
+            index,
+            &(&notional * market.fee_rate(index, &request.order_type)),
+            RoundingMode::Up,
+        );
+
+        Order {
+            id: Uuid::new_v4(),
+            symbol: market.symbol(index).to_string(),
+            order_type: request.order_type,
+            price: request.price,
+            trigger_price: request.trigger_price,
+            trail: request.trail,
+            amount: request.amount,
+            fee,
+            group: None,
+            parent: None,
+            reserved: false,
+            symbol_index: index,
+        }
+    }
+
+    fn check_reservation(&self, market: &Market, order: &Order<Number>) -> AppResult<()> {
+        let index = order.symbol_index;
+        if self.margin.is_some() {
+            let exposure = if order.order_type.is_buy() {
+                self.exposure(index) + &order.amount
+            } else {
+                self.exposure(index) - &order.amount
+            };
+            return self.check_margin(market, index, &exposure, &order.price, &order.fee);
+        }
+
+        if order.order_type.is_buy() {
+            if order.reservation() > self.balance {
+                return Err(AppError::Strategy("Insufficient funds".into()));
+            }
+        } else if order.amount > self.positions[index] {
+            return Err(AppError::Strategy(
+                "Insufficient base asset amount to sell".into(),
+            ));
+        } else if order.fee > self.balance && !matches!(order.order_type, OrderType::MarketSell) {
+            // Market sells pay their fee out of the proceeds
+            return Err(AppError::Strategy("Insufficient funds to cover fee".into()));
+        }
+
+        Ok(())
+    }
+
+    // Reserves the quote cost of buy orders and the base amount of sell orders until filled or
+    // cancelled
+    fn reserve(&mut self, order: &mut Order<Number>) {
+        self.balance -= order.reservation();
+        if !order.order_type.is_buy() {
+            self.positions[order.symbol_index] -= &order.amount;
+        }
+        order.reserved = true;
+    }
+
+    fn release(&mut self, order: &Order<Number>) {
+        if !order.reserved {
+            return;
+        }
+        self.balance += order.reservation();
+        if !order.order_type.is_buy() {
+            self.positions[order.symbol_index] += &order.amount;
+        }
+    }
+
+    fn activate_children(&mut self, market: &Market, parent: Uuid) {
+        let mut holder: Option<(usize, Number)> = None;
+        for (i, order) in self.orders.iter_mut().enumerate() {
+            if order.parent != Some(parent) {
+                continue;
+            }
+            order.parent = None;
+            let reservation = order.reservation();
+            if holder.as_ref().is_none_or(|(_, max)| &reservation > max) {
+                holder = Some((i, reservation));
+            }
+        }
+
+        let Some((index, _)) = holder else {
+            return;
+        };
+        let mut order = self.orders[index].clone();
+        // Exits the account can no longer cover are dropped rather than failing the backtest
+        if self.check_reservation(market, &order).is_err() {
+            self.cancel_order(order.id);
+            return;
+        }
+        self.reserve(&mut order);
+        self.orders[index] = order;
+    }
+
+    // Fills as much of the order as the candle's remaining volume allows, leaving the rest pending
+    fn fill_order(&mut self, market: &Market, bar: &Bar, order_id: Uuid, price: &Number) {
+        // Skip orders cancelled by a sibling that filled earlier in the candle
+        let Some(pos) = self.orders.iter().position(|o| o.id == order_id) else {
+            return;
+        };
+
+        let index = self.orders[pos].symbol_index;
+        let amount = match &self.volume_left[index] {
+            Some(volume) => market.round_amount(
+                index,
+                &self.orders[pos].amount.clone().min(volume.clone()),
+                RoundingMode::Down,
+            ),
+            None => self.orders[pos].amount.clone(),
+        };
+        if amount <= Number::zero(self.engine) {
+            return;
+        }
+
+        let mut order = self.orders.remove(pos);
+        if let Some(group) = order.group {
+            let siblings: Vec<Uuid> = self
+                .orders
+                .iter()
+                .filter(|o| o.group == Some(group))
+                .map(|o| o.id)
+                .collect();
+            for id in siblings {
+                self.cancel_order(id);
+            }
+        }
+        if !order.reserved {
+            self.reserve(&mut order);
+        }
+
+        let price = if order.order_type.is_maker() {
+            price.clone()
+        } else {
+            self.market_price(
+                market,
+                index,
+                bar,
+                price,
+                &amount,
+                order.order_type.is_buy(),
+            )
+        };
+
+        // A market buy waiting for the next open was reserved at the last close, so a gap up can
+        // cost more than the account holds. Whatever it can't pay for at the fill is cancelled
+        let mut amount = amount;
+        if matches!(order.order_type, OrderType::MarketBuy) && self.margin.is_none() {
+            let affordable = self.affordable(market, &order, &price);
+            if affordable < order.amount {
+                let kept = order.split(&affordable);
+                self.release(&order);
+                self.events.push(OrderEvent::Cancelled(order.to_decimal()));
+                if !affordable.is_positive() {
+                    return;
+                }
+                order = kept;
+                amount = amount.min(affordable);
+            }
+        }
+
+        if let Some(volume) = &mut self.volume_left[index] {
+            *volume -= &amount;
+        }
+
+        if amount < order.amount {
+            let filled = order.split(&amount);
+            let pos = pos.min(self.orders.len());
+            self.orders.insert(pos, order);
+            self.execute_order(market, bar, &filled, &price);
+        } else {
+            self.execute_order(market, bar, &order, &price);
+            self.activate_children(market, order.id);
+        }
+    }
+
+    // Largest amount of a buy the account can pay for at the price, fee included
+    fn affordable(&self, market: &Market, order: &Order<Number>, price: &Number) -> Number {
+        let index = order.symbol_index;
+        let available = &self.balance + order.reservation();
+        let rate = market.fee_rate(index, &order.order_type);
+        let amount = market.round_amount(
+            index,
+            &(&available / (price * (Number::one(self.engine) + rate))),
+            RoundingMode::Down,
+        );
+
+        // The fee rounds up, which can take the total just past what is available
+        let fee = market.round_amount(index, &(price * &amount * rate), RoundingMode::Up);
+        if price * &amount + &fee <= available {
+            return amount;
+        }
+        market.round_amount(index, &((available - fee) / price), RoundingMode::Down)
+    }
+
+    fn market_price(
+        &self,
+        market: &Market,
+        index: usize,
+        bar: &Bar,
+        price: &Number,
+        amount: &Number,
+        buy: bool,
+    ) -> Number {
+        let price = self.fill_model.market_price(bar, price, amount, buy);
+        let mode = if buy {
+            RoundingMode::Up
+        } else {
+            RoundingMode::Down
+        };
+        market.round_price(index, &price, mode)
+    }
+
+    // Settles the reservation against the actual fill, which differs from it when the market
+    // gapped through a stop
+    fn execute_order(&mut self, market: &Market, bar: &Bar, order: &Order<Number>, price: &Number) {
+        let index = order.symbol_index;
+        let notional = price * &order.amount;
+        let fee = market.round_amount(
+            index,
+            &(&notional * market.fee_rate(index, &order.order_type)),
+            RoundingMode::Up,
+        );
+
+        let buy = order.order_type.is_buy();
+        let interest = self.settle_interest(index, buy, &order.amount);
+        if buy {
+            self.balance += order.reservation() - &notional - &fee - &interest;
+            self.positions[index] += &order.amount;
+        } else {
+            self.balance += &notional + &order.fee - &fee - &interest;
+        }
+
+        let trade = Trade {
+            timestamp: bar.timestamp,
+            symbol: order.symbol.clone(),
+            trade_type: order.order_type.trade_type(),
+            price: price.to_decimal(),
+            amount: order.amount.to_decimal(),
+            fee: (fee + interest).into(),
+            profit: None,
+        };
+        self.trades.push(trade.clone());
+        self.events
+            .push(OrderEvent::Filled(order.to_decimal(), trade));
+    }
+
+    // Position including the amount reserved by pending sell orders
+    fn exposure(&self, index: usize) -> Number {
+        let reserved: Number = self
+            .orders
+            .iter()
+            .filter(|o| o.symbol_index == index && o.reserved && !o.order_type.is_buy())
+            .map(|o| &o.amount)
+            .sum();
+        &self.positions[index] + reserved
+    }
+
+    // Equity with each symbol's exposure valued at the given price
+    fn equity_at(&self, prices: &[Number]) -> Number {
+        let reserved: Number = self
+            .orders
+            .iter()
+            .filter(|o| o.reserved)
+            .map(|o| o.reservation())
+            .sum();
+        let exposure_value: Number = prices
+            .iter()
+            .enumerate()
+            .map(|(index, price)| self.exposure(index) * price)
+            .sum();
+        &self.balance + reserved + exposure_value - self.total_interest()
+    }
+
+    fn total_interest(&self) -> Number {
+        self.accrued_interest
+            .iter()
+            .fold(Number::zero(self.engine), |total, interest| {
+                total + interest
+            })
+    }
+
+    // Margin is shared across symbols, so the requirement covers every open exposure
+    fn check_margin(
+        &self,
+        market: &Market,
+        index: usize,
+        exposure: &Number,
+        price: &Number,
+        fee: &Number,
+    ) -> AppResult<()> {
+        let Some(margin) = &self.margin else {
+            return Ok(());
+        };
+
+        let mut prices = market.last_prices();
+        prices[index] = price.clone();
+        let notional: Number = prices
+            .iter()
+            .enumerate()
+            .map(|(i, price)| {
+                if i == index {
+                    exposure.abs() * price
+                } else {
+                    self.exposure(i).abs() * price
+                }
+            })
+            .sum();
+
+        let required = notional / self.number(&margin.leverage);
+        if required > self.equity_at(&prices) - fee {
+            return Err(AppError::Strategy("Insufficient margin".into()));
+        }
+
+        Ok(())
+    }
+
+    // A symbol's interest is paid when its exposure is reduced, as part of the trade's fee. A
+    // partial close pays its share and leaves the rest accruing
+    fn settle_interest(&mut self, index: usize, buy: bool, amount: &Number) -> Number {
+        // A sell's amount has already left the exposure when it was reserved
+        let exposure = if buy {
+            self.exposure(index)
+        } else {
+            self.exposure(index) + amount
+        };
+        let reduces = if buy {
+            exposure.is_negative()
+        } else {
+            exposure.is_positive()
+        };
+        if !reduces || self.accrued_interest[index].is_zero() {
+            return Number::zero(self.engine);
+        }
+
+        let share = (amount / exposure.abs()).min(Number::one(self.engine));
+        let interest = &self.accrued_interest[index] * share;
+        self.accrued_interest[index] -= &interest;
+        interest
+    }
+
+    fn accrue_interest(
+        &mut self,
+        market: &Market,
+        timestamp: DateTime<Utc>,
+        previous: Option<DateTime<Utc>>,
+    ) {
+        let Some(rate) = self
+            .margin
+            .as_ref()
+            .map(|m| self.number(&m.daily_interest_rate))
+        else {
+            return;
+        };
+        let Some(previous) = previous else {
+            return;
+        };
+
+        let elapsed = (timestamp - previous).num_milliseconds();
+        let days = Number::from_i64(elapsed, self.engine)
+            / Number::from_i64(MILLISECONDS_PER_DAY, self.engine);
+        let values: Vec<Option<Number>> = (0..market.len())
+            .map(|index| {
+                let price = match market.current_bar(index) {
+                    Some(bar) => bar.open,
+                    None => market.last_close(index)?,
+                };
+                Some(self.exposure(index) * price)
+            })
+            .collect();
+
+        // Cash borrowed to buy is charged to the long positions in proportion to their value
+        let debt = (-&self.balance).max(Number::zero(self.engine));
+        let long_value: Number = values
+            .iter()
+            .flatten()
+            .filter(|value| value.is_positive())
+            .fold(Number::zero(self.engine), |total, value| total + value);
+
+        for (index, value) in values.iter().enumerate() {
+            let Some(value) = value else {
+                continue;
+            };
+            let borrowed = if value.is_negative() {
+                value.abs()
+            } else if value.is_positive() && debt.is_positive() {
+                &debt * value / &long_value
+            } else {
+                continue;
+            };
+            self.accrued_interest[index] += borrowed * &rate * &days;
+        }
+    }
+
+    fn check_liquidation(&mut self, market: &Market, timestamp: DateTime<Utc>) {
+        let Some(margin) = &self.margin else {
+            return;
+        };
+        let maintenance_margin = self.number(&margin.maintenance_margin);
+
+        let exposures: Vec<Number> = (0..market.len())
+            .map(|index| self.exposure(index))
+            .collect();
+        if exposures.iter().all(|e| e.is_zero()) {
+            return;
+        }
+
+        // Symbols without a candle at this timestamp stay at their last close
+        let closes = market.last_prices();
+        let ranges: Vec<(Number, Number, Number)> = (0..market.len())
+            .map(|index| match market.current_bar(index) {
+                Some(bar) => (bar.open, bar.low, bar.high),
+                None => (
+                    closes[index].clone(),
+                    closes[index].clone(),
+                    closes[index].clone(),
+                ),
+            })
+            .collect();
+        let worst_prices: Vec<Number> = exposures
+            .iter()
+            .zip(&ranges)
+            .map(|(exposure, (_, low, high))| {
+                if exposure.is_positive() {
+                    low.clone()
+                } else {
+                    high.clone()
+                }
+            })
+            .collect();
+        let maintenance: Number = exposures
+            .iter()
+            .zip(&worst_prices)
+            .map(|(exposure, price)| exposure.abs() * price * &maintenance_margin)
+            .sum();
+        if self.equity_at(&worst_prices) >= maintenance {
+            return;
+        }
+
+        let order_ids: Vec<Uuid> = self.orders.iter().map(|o| o.id).collect();
+        for id in order_ids {
+            self.cancel_order(id);
+        }
+
+        // Price at which equity meets the maintenance requirement with the other positions at
+        // their worst, or the open if the market gapped through it
+        let balance = &self.balance - self.total_interest();
+        let weights: Vec<Number> = self
+            .positions
+            .iter()
+            .map(|position| {
+                if position.is_positive() {
+                    Number::one(self.engine) - &maintenance_margin
+                } else {
+                    Number::one(self.engine) + &maintenance_margin
+                }
+            })
+            .collect();
+        let weighted: Vec<Number> = self
+            .positions
+            .iter()
+            .zip(&worst_prices)
+            .zip(&weights)
+            .map(|((position, price), weight)| position * price * weight)
+            .collect();
+        let total: Number = weighted.iter().sum();
+
+        let mut liquidations = Vec::new();
+        for (index, position) in self.positions.iter().enumerate() {
+            if position.is_zero() {
+                continue;
+            }
+
+            let (open, low, high) = &ranges[index];
+            let threshold = -(&balance + &total - &weighted[index]) / (position * &weights[index]);
+            let (price, trade_type) = if position.is_positive() {
+                (
+                    threshold.min(open.clone()).max(low.clone()),
+                    TradeType::LiquidationSell,
+                )
+            } else {
+                (
+                    threshold.max(open.clone()).min(high.clone()),
+                    TradeType::LiquidationBuy,
+                )
+            };
+            liquidations.push((index, price, trade_type));
+        }
+
+        for (index, price, trade_type) in liquidations {
+            let position = self.positions[index].clone();
+            let price = market.round_price(index, &price, RoundingMode::HalfUp);
+
+            let amount = position.abs();
+            let fee = market.round_amount(
+                index,
+                &(&price * &amount * market.fee_rate(index, &OrderType::MarketSell)),
+                RoundingMode::Up,
+            );
+            let interest =
+                std::mem::replace(&mut self.accrued_interest[index], Number::zero(self.engine));
+            let fee = fee + interest;
+
+            self.balance += &position * &price - &fee;
+            self.positions[index] = Number::zero(self.engine);
+
+            // The strategy hears of it as a market order it never placed, the trade type
+            // telling it the position was liquidated
+            let order = Order {
+                id: Uuid::new_v4(),
+                symbol: market.symbol(index).to_string(),
+                order_type: if position.is_positive() {
+                    OrderType::MarketSell
+                } else {
+                    OrderType::MarketBuy
+                },
+                price: price.clone(),
+                trigger_price: None,
+                trail: None,
+                amount: amount.clone(),
+                fee: fee.clone(),
+                group: None,
+                parent: None,
+                reserved: false,
+                symbol_index: index,
+            };
+            let trade = Trade {
+                timestamp,
+                symbol: order.symbol.clone(),
+                trade_type,
+                price: price.into(),
+                amount: amount.into(),
+                fee: fee.into(),
+                profit: None,
+            };
+            self.trades.push(trade.clone());
+            self.events
+                .push(OrderEvent::Filled(order.to_decimal(), trade));
+        }
+    }
+}
+
+impl Broker for Simulator {
+    fn add_symbol(&mut self, _symbol: &str) -> AppResult<()> {
+        self.positions.push(Number::zero(self.engine));
+        self.accrued_interest.push(Number::zero(self.engine));
+        self.volume_left.push(None);
+        Ok(())
+    }
+
+    // Orders hold back funds as they did when placed: exits waiting on their entry hold nothing,
+    // and the legs of an OCO share the reservation of the one that needs more
+    fn restore(&mut self, account: BrokerAccount) -> AppResult<()> {
+        let mut holders: HashMap<Uuid, (Uuid, Number)> = HashMap::new();
+        for order in account.orders.iter().filter(|o| o.parent.is_none()) {
+            let Some(group) = order.group else {
+                continue;
+            };
+            let reservation = order.reservation();
+            if holders
+                .get(&group)
+                .is_none_or(|(_, max)| &reservation > max)
+            {
+                holders.insert(group, (order.id, reservation));
+            }
+        }
+
+        self.balance = account.balance;
+        for (index, position) in account.positions.into_iter().enumerate() {
+            self.positions[index] = position;
+        }
+        for (index, interest) in account.accrued_interest.into_iter().enumerate() {
+            self.accrued_interest[index] = interest;
+        }
+        self.orders = account
+            .orders
+            .into_iter()
+            .map(|mut order| {
+                order.reserved = order.parent.is_none()
+                    && order
+                        .group
+                        .is_none_or(|group| holders[&group].0 == order.id);
+                order
+            })
+            .collect();
+        self.trades = account.trades;
+        Ok(())
+    }
+
+    fn balance(&self) -> Number {
+        self.balance.clone()
+    }
+
+    fn position(&self, index: usize) -> Number {
+        self.positions[index].clone()
+    }
+
+    fn equity(&self, market: &Market) -> Number {
+        self.equity_at(&market.last_prices())
+    }
+
+    fn orders(&self) -> Vec<Order> {
+        self.orders.iter().map(Order::to_decimal).collect()
+    }
+
+    fn trades(&self) -> &[Trade] {
+        &self.trades
+    }
+
+    fn margin(&self) -> Option<&MarginConfig> {
+        self.margin.as_ref()
+    }
+
+    fn accrued_interest(&self) -> Number {
+        self.total_interest()
+    }
+
+    fn market_order(
+        &mut self,
+        market: &Market,
+        index: usize,
+        buy: bool,
+        amount: Number,
+    ) -> AppResult<Option<Uuid>> {
+        let bar = market
+            .last_bar(index)
+            .ok_or(AppError::Strategy("No candles available".into()))?;
+        let price = self.market_price(market, index, &bar, &bar.close, &amount, buy);
+
+        let order_type = if buy {
+            OrderType::MarketBuy
+        } else {
+            let proceeds = &price * &amount;
+            let fee = market.round_amount(
+                index,
+                &(&proceeds * market.fee_rate(index, &OrderType::MarketSell)),
+                RoundingMode::Up,
+            );
+            if proceeds < fee {
+                return Err(AppError::Strategy("Revenue cannot be negative".into()));
+            }
+            OrderType::MarketSell
+        };
+
+        let request = OrderRequest {
+            symbol_index: index,
+            order_type,
+            price,
+            trigger_price: None,
+            trail: None,
+            amount,
+        };
+        let order_id = self.place_order(market, request)?;
+        if !self.fill_model.delays_market_orders() {
+            self.fill_order(market, &bar, order_id, &bar.close);
+        }
+
+        Ok(self
+            .orders
+            .iter()
+            .any(|o| o.id == order_id)
+            .then_some(order_id))
+    }
+
+    fn place_order(&mut self, market: &Market, request: OrderRequest) -> AppResult<Uuid> {
+        let mut order = self.new_order(market, request);
+        self.check_reservation(market, &order)?;
+        self.reserve(&mut order);
+
+        let order_id = order.id;
+        self.orders.push(order);
+
+        Ok(order_id)
+    }
+
+    // The target and stop legs share a single reservation, held by whichever leg needs more
+    fn place_exit_orders(
+        &mut self,
+        market: &Market,
+        request: ExitOrders,
+    ) -> AppResult<BracketOrder> {
+        let (target_type, stop_type) = if request.buy {
+            (OrderType::LimitBuy, OrderType::StopBuy)
+        } else {
+            (OrderType::LimitSell, OrderType::StopSell)
+        };
+
+        let group = Uuid::new_v4();
+        let mut target = self.new_order(
+            market,
+            OrderRequest {
+                symbol_index: request.symbol_index,
+                order_type: target_type,
+                price: request.target_price,
+                trigger_price: None,
+                trail: None,
+                amount: request.amount.clone(),
+            },
+        );
+        let mut stop = self.new_order(
+            market,
+            OrderRequest {
+                symbol_index: request.symbol_index,
+                order_type: stop_type,
+                price: request.stop_price.clone(),
+                trigger_price: Some(request.stop_price),
+                trail: None,
+                amount: request.amount,
+            },
+        );
+        for order in [&mut target, &mut stop] {
+            order.group = Some(group);
+            order.parent = request.parent;
+        }
+
+        if request.parent.is_none() {
+            let holder = if target.reservation() >= stop.reservation() {
+                &mut target
+            } else {
+                &mut stop
+            };
+            self.check_reservation(market, holder)?;
+            self.reserve(holder);
+        }
+
+        let linked = BracketOrder {
+            group,
+            entry: request.parent,
+            target: target.id,
+            stop: stop.id,
+        };
+        self.orders.push(target);
+        self.orders.push(stop);
+
+        Ok(linked)
+    }
+
+    fn cancel_order(&mut self, order_id: Uuid) {
+        let Some(pos) = self.orders.iter().position(|o| o.id == order_id) else {
+            return;
+        };
+        let order = self.orders.remove(pos);
+        self.release(&order);
+        self.events.push(OrderEvent::Cancelled(order.to_decimal()));
+
+        // Cancelling one leg cancels its OCO siblings and any exits waiting on it
+        let linked: Vec<Uuid> = self
+            .orders
+            .iter()
+            .filter(|o| {
+                o.parent == Some(order.id) || (order.group.is_some() && o.group == order.group)
+            })
+            .map(|o| o.id)
+            .collect();
+        for id in linked {
+            self.cancel_order(id);
+        }
+    }
+
+    fn update(&mut self, market: &Market, previous: Option<DateTime<Utc>>) {
+        let Some(timestamp) = market.timestamp() else {
+            return;
+        };
+        self.accrue_interest(market, timestamp, previous);
+
+        // Only symbols with a candle at this timestamp can trigger orders
+        for index in 0..market.len() {
+            let Some(bar) = market.current_bar(index) else {
+                continue;
+            };
+
+            let mut fills = Vec::new();
+            for order in self.orders.iter_mut().filter(|o| o.symbol_index == index) {
+                if let Some(price) = order.trigger(&bar) {
+                    fills.push((order.id, price));
+                }
+            }
+
+            self.volume_left[index] = self.fill_model.fill_limit(&bar);
+            for (order_id, price) in fills {
+                self.fill_order(market, &bar, order_id, &price);
+            }
+        }
+
+        self.check_liquidation(market, timestamp);
+    }
+
+    fn take_events(&mut self) -> Vec<OrderEvent> {
+        std::mem::take(&mut self.events)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::testing::{Recorder, advance, candles, context, decimal};
+    use super::*;
+    use crate::strategy::{IdealFill, StrategyContext};
+    use chrono::TimeZone;
+
+    fn margin_simulator() -> Simulator {
+        interest_simulator(BacktestEngine::Exact, "0")
+    }
+
+    fn interest_simulator(engine: BacktestEngine, daily_interest_rate: &str) -> Simulator {
+        let margin = MarginConfig {
+            leverage: decimal("10"),
+            maintenance_margin: decimal("0.05"),
+            daily_interest_rate: decimal(daily_interest_rate),
+        };
+        Simulator::new(engine, &decimal("1000"), Some(margin), Arc::new(IdealFill))
+    }
+
+    // A long of 50 bought at 100 on 1000 of equity borrows 4000, so it is liquidated where
+    // -4000 + 50 * price = 50 * price * 0.05, at 84.21 after rounding
+    fn liquidate(open: f64, low: f64) -> Trade {
+        let columns = candles(&[(100.0, 100.0, 100.0, 100.0), (open, open, low, low)]);
+        let mut strategy = Recorder::default();
+        let mut context = context(Box::new(margin_simulator()));
+        advance(&mut context, &columns, 0, &mut strategy);
+        context.market_buy(&decimal("50")).unwrap();
+        assert_eq!(context.balance(), decimal("-4000"));
+
+        advance(&mut context, &columns, 1, &mut strategy);
+        assert_eq!(context.position(), decimal("0"));
+        let trade = context.trades().last().unwrap().clone();
+        assert!(matches!(trade.trade_type, TradeType::LiquidationSell));
+        assert!(matches!(
+            strategy.filled.as_slice(),
+            [OrderType::MarketBuy, OrderType::MarketSell]
+        ));
+        assert_eq!(trade.amount, decimal("50"));
+        assert_eq!(
+            context.balance(),
+            decimal("-4000") + &trade.price * decimal("50")
+        );
+        trade
+    }
+
+    #[test]
+    fn liquidates_a_long_at_its_liquidation_price() {
+        assert_eq!(liquidate(95.0, 80.0).price, decimal("84.21"));
+    }
+
+    #[test]
+    fn liquidates_at_the_open_when_the_market_gaps_through() {
+        assert_eq!(liquidate(70.0, 65.0).price, decimal("70"));
+    }
+
+    #[test]
+    fn keeps_positions_above_the_maintenance_margin() {
+        let columns = candles(&[(100.0, 100.0, 100.0, 100.0), (95.0, 95.0, 85.0, 90.0)]);
+        let mut strategy = Recorder::default();
+        let mut context = context(Box::new(margin_simulator()));
+        advance(&mut context, &columns, 0, &mut strategy);
+        context.market_buy(&decimal("50")).unwrap();
+
+        advance(&mut context, &columns, 1, &mut strategy);
+        assert_eq!(context.position(), decimal("50"));
+        assert_eq!(context.trades().len(), 1);
+    }
+
+    // Borrowing 4000 at 144% a day costs 4 a minute
+    #[test]
+    fn accrues_interest_on_borrowed_cash() {
+        for engine in [BacktestEngine::Exact, BacktestEngine::Fast] {
+            let columns = candles(&[(100.0, 100.0, 100.0, 100.0), (100.0, 100.0, 100.0, 100.0)]);
+            let mut strategy = Recorder::default();
+            let mut context = context(Box::new(interest_simulator(engine, "1.44")));
+            advance(&mut context, &columns, 0, &mut strategy);
+            context.market_buy(&decimal("50")).unwrap();
+
+            advance(&mut context, &columns, 1, &mut strategy);
+            let interest = context.broker.accrued_interest().to_f64();
+            assert!((interest - 4.0).abs() < 1e-9);
+            let equity = Number::from(context.equity().unwrap()).to_f64();
+            assert!((equity - 996.0).abs() < 1e-9);
+        }
+    }
+
+    fn simulator() -> Simulator {
+        Simulator::new(
+            BacktestEngine::Exact,
+            &decimal("1000"),
+            None,
+            Arc::new(IdealFill),
+        )
+    }
+
+    // Buys one at the first close of 100, then places the exit and runs the remaining candles
+    fn exit_long(
+        prices: &[(f64, f64, f64, f64)],
+        place: impl FnOnce(&mut StrategyContext) -> AppResult<Option<Uuid>>,
+    ) -> (Recorder, Vec<Trade>, Vec<Order>) {
+        let columns = candles(prices);
+        let mut strategy = Recorder::default();
+        let mut context = context(Box::new(simulator()));
+        advance(&mut context, &columns, 0, &mut strategy);
+        context.market_buy(&decimal("1")).unwrap();
+        place(&mut context).unwrap();
+        context.after(&mut strategy).unwrap();
+        for index in 1..prices.len() {
+            advance(&mut context, &columns, index, &mut strategy);
+        }
+        let trades = context.trades().to_vec();
+        let orders = context.orders();
+        (strategy, trades, orders)
+    }
+
+    #[test]
+    fn stop_fills_at_its_price_or_the_gapped_open() {
+        for ((open, low), price) in [((98.0, 94.0), "95"), ((90.0, 88.0), "90")] {
+            let (strategy, trades, orders) = exit_long(
+                &[(100.0, 100.0, 100.0, 100.0), (open, 99.0, low, 96.0)],
+                |context| context.stop_sell(&decimal("95"), &decimal("1")),
+            );
+            assert!(matches!(
+                strategy.filled.as_slice(),
+                [OrderType::MarketBuy, OrderType::StopSell]
+            ));
+            assert!(matches!(trades[1].trade_type, TradeType::StopSell));
+            assert_eq!(trades[1].price, decimal(price));
+            assert!(orders.is_empty());
+        }
+    }
+
+    #[test]
+    fn stop_already_through_the_close_sells_at_market() {
+        let (strategy, trades, orders) = exit_long(&[(100.0, 100.0, 100.0, 100.0)], |context| {
+            context.stop_sell(&decimal("101"), &decimal("1"))
+        });
+        assert!(matches!(
+            strategy.filled.as_slice(),
+            [OrderType::MarketBuy, OrderType::MarketSell]
+        ));
+        assert_eq!(trades[1].price, decimal("100"));
+        assert!(orders.is_empty());
+    }
+
+    #[test]
+    fn stop_limit_becomes_a_limit_order_once_triggered() {
+        let stop_limit = |context: &mut StrategyContext| {
+            context.stop_limit_sell(&decimal("95"), &decimal("94"), &decimal("1"))
+        };
+
+        let (_, trades, _) = exit_long(
+            &[(100.0, 100.0, 100.0, 100.0), (98.0, 99.0, 93.0, 96.0)],
+            stop_limit,
+        );
+        assert!(matches!(trades[1].trade_type, TradeType::LimitSell));
+        assert_eq!(trades[1].price, decimal("95"));
+
+        // Gapping below the limit leaves it resting until the price comes back
+        let (strategy, trades, orders) = exit_long(
+            &[
+                (100.0, 100.0, 100.0, 100.0),
+                (92.0, 93.0, 90.0, 91.0),
+                (91.0, 96.0, 91.0, 95.0),
+            ],
+            stop_limit,
+        );
+        assert!(matches!(
+            strategy.filled.as_slice(),
+            [OrderType::MarketBuy, OrderType::LimitSell]
+        ));
+        assert_eq!(trades[1].timestamp, Utc.timestamp_opt(120, 0).unwrap());
+        assert_eq!(trades[1].price, decimal("94"));
+        assert!(orders.is_empty());
+    }
+
+    #[test]
+    fn take_profit_fills_at_its_trigger() {
+        let (strategy, trades, _) = exit_long(
+            &[(100.0, 100.0, 100.0, 100.0), (101.0, 106.0, 100.0, 104.0)],
+            |context| context.take_profit_sell(&decimal("105"), &decimal("1")),
+        );
+        assert!(matches!(
+            strategy.filled.as_slice(),
+            [OrderType::MarketBuy, OrderType::TakeProfitSell]
+        ));
+        assert_eq!(trades[1].price, decimal("105"));
+    }
+
+    #[test]
+    fn trailing_stop_follows_the_high_after_each_candle() {
+        let prices = [
+            (100.0, 100.0, 100.0, 100.0),
+            (100.0, 110.0, 99.0, 108.0),
+            (108.0, 109.0, 104.0, 106.0),
+        ];
+        let place = |context: &mut StrategyContext| {
+            context
+                .trailing_stop_sell(&decimal("5"), &decimal("1"))
+                .map(Some)
+        };
+
+        // The first candle reaches 110, so the stop moves from 95 to 105 without filling
+        let (_, trades, orders) = exit_long(&prices[..2], place);
+        assert_eq!(trades.len(), 1);
+        assert_eq!(orders[0].trigger_price, Some(decimal("105")));
+
+        let (strategy, trades, _) = exit_long(&prices, place);
+        assert!(matches!(
+            strategy.filled.as_slice(),
+            [OrderType::MarketBuy, OrderType::TrailingStopSell]
+        ));
+        assert_eq!(trades[1].price, decimal("105"));
+    }
+
+    #[test]
+    fn oco_fill_cancels_the_other_leg() {
+        let (strategy, trades, orders) = exit_long(
+            &[(100.0, 100.0, 100.0, 100.0), (100.0, 111.0, 99.0, 108.0)],
+            |context| {
+                context
+                    .oco_sell(&decimal("1"), &decimal("110"), &decimal("95"))
+                    .map(|oco| Some(oco.target))
+            },
+        );
+        assert!(matches!(
+            strategy.filled.as_slice(),
+            [OrderType::MarketBuy, OrderType::LimitSell]
+        ));
+        assert!(matches!(
+            strategy.cancelled.as_slice(),
+            [OrderType::StopSell]
+        ));
+        assert_eq!(trades[1].price, decimal("110"));
+        assert!(orders.is_empty());
+    }
+
+    #[test]
+    fn oco_legs_share_one_reservation() {
+        let columns = candles(&[(100.0, 100.0, 100.0, 100.0)]);
+        let mut strategy = Recorder::default();
+        let mut context = context(Box::new(simulator()));
+        advance(&mut context, &columns, 0, &mut strategy);
+        context.market_buy(&decimal("1")).unwrap();
+
+        let oco = context
+            .oco_sell(&decimal("1"), &decimal("110"), &decimal("95"))
+            .unwrap();
+        assert_eq!(context.position(), decimal("0"));
+        assert_eq!(context.orders().len(), 2);
+
+        context.cancel_order(oco.stop);
+        context.after(&mut strategy).unwrap();
+        assert!(matches!(
+            strategy.cancelled.as_slice(),
+            [OrderType::StopSell, OrderType::LimitSell]
+        ));
+        assert_eq!(context.position(), decimal("1"));
+        assert!(context.orders().is_empty());
+    }
+
+    #[test]
+    fn bracket_exits_wait_for_the_entry() {
+        let columns = candles(&[
+            (100.0, 100.0, 100.0, 100.0),
+            (100.0, 101.0, 94.0, 96.0),
+            (96.0, 111.0, 96.0, 110.0),
+        ]);
+        let mut strategy = Recorder::default();
+        let mut context = context(Box::new(simulator()));
+        advance(&mut context, &columns, 0, &mut strategy);
+        let bracket = context
+            .bracket_buy(
+                Some(&decimal("98")),
+                &decimal("1"),
+                &decimal("110"),
+                &decimal("95"),
+            )
+            .unwrap();
+        assert!(bracket.entry.is_some());
+        assert_eq!(context.balance(), decimal("902"));
+
+        // The candle that fills the entry also trades through the stop, which is not live yet
+        advance(&mut context, &columns, 1, &mut strategy);
+        assert!(matches!(strategy.filled.as_slice(), [OrderType::LimitBuy]));
+        assert_eq!(context.position(), decimal("0"));
+        assert_eq!(context.orders().len(), 2);
+
+        advance(&mut context, &columns, 2, &mut strategy);
+        assert!(matches!(
+            strategy.filled.as_slice(),
+            [OrderType::LimitBuy, OrderType::LimitSell]
+        ));
+        assert!(matches!(
+            strategy.cancelled.as_slice(),
+            [OrderType::StopSell]
+        ));
+        assert_eq!(context.balance(), decimal("1012"));
+        assert!(context.orders().is_empty());
+    }
+
+    #[test]
+    fn cancelling_a_bracket_entry_cancels_its_exits() {
+        let columns = candles(&[(100.0, 100.0, 100.0, 100.0)]);
+        let mut strategy = Recorder::default();
+        let mut context = context(Box::new(simulator()));
+        advance(&mut context, &columns, 0, &mut strategy);
+        let bracket = context
+            .bracket_buy(
+                Some(&decimal("90")),
+                &decimal("1"),
+                &decimal("110"),
+                &decimal("85"),
+            )
+            .unwrap();
+
+        context.cancel_order(bracket.entry.unwrap());
+        context.after(&mut strategy).unwrap();
+        assert!(matches!(
+            strategy.cancelled.as_slice(),
+            [
+                OrderType::LimitBuy,
+                OrderType::LimitSell,
+                OrderType::StopSell
+            ]
+        ));
+        assert_eq!(context.balance(), decimal("1000"));
+        assert!(context.orders().is_empty());
+    }
+}
@@ -1,8 +1,14 @@
+use super::broker::{Broker, BrokerAccount, ExitOrders, Market, OrderEvent, OrderRequest};
+use super::risk::{RiskEvent, RiskLimits, RiskManager, RiskOrder};
+use super::{BacktestEngine, Bar, Number, Strategy};
 use crate::errors::{AppError, AppResult};
-use crate::models::{Candle, MarketPrecision, TradingFees};
-use bigdecimal::{BigDecimal, RoundingMode, Zero};
+use crate::indicators::Indicator;
+use crate::models::{Candle, Columns, MarginConfig, MarketPrecision, Timeframe, TradingFees};
+use bigdecimal::{BigDecimal, RoundingMode};
 use chrono::{DateTime, Utc, serde::ts_milliseconds};
 use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use std::sync::atomic::AtomicBool;
 use ts_rs::TS;
 use uuid::Uuid;
 
@@ -14,6 +20,28 @@ pub enum TradeType {
     MarketSell,
     LimitBuy,
     LimitSell,
+    StopBuy,
+    StopSell,
+    TakeProfitBuy,
+    TakeProfitSell,
+    TrailingStopBuy,
+    TrailingStopSell,
+    LiquidationBuy,
+    LiquidationSell,
+}
+
+impl TradeType {
+    pub fn is_buy(&self) -> bool {
+        matches!(
+            self,
+            TradeType::MarketBuy
+                | TradeType::LimitBuy
+                | TradeType::StopBuy
+                | TradeType::TakeProfitBuy
+                | TradeType::TrailingStopBuy
+                | TradeType::LiquidationBuy
+        )
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, TS)]
@@ -22,6 +50,8 @@ pub struct Trade {
     #[serde(with = "ts_milliseconds")]
     #[ts(type = "number")]
     pub timestamp: DateTime<Utc>,
+    #[serde(default)]
+    pub symbol: String,
     pub trade_type: TradeType,
     #[ts(type = "string")]
     pub price: BigDecimal,
@@ -37,358 +67,1100 @@ pub struct Trade {
 #[serde(rename_all = "snake_case")]
 #[ts(export)]
 pub enum OrderType {
+    MarketBuy,
+    MarketSell,
     LimitBuy,
     LimitSell,
+    StopBuy,
+    StopSell,
+    StopLimitBuy,
+    StopLimitSell,
+    TakeProfitBuy,
+    TakeProfitSell,
+    TrailingStopBuy,
+    TrailingStopSell,
 }
 
+impl OrderType {
+    pub fn is_buy(&self) -> bool {
+        matches!(
+            self,
+            OrderType::MarketBuy
+                | OrderType::LimitBuy
+                | OrderType::StopBuy
+                | OrderType::StopLimitBuy
+                | OrderType::TakeProfitBuy
+                | OrderType::TrailingStopBuy
+        )
+    }
+
+    pub(crate) fn is_maker(&self) -> bool {
+        matches!(
+            self,
+            OrderType::LimitBuy
+                | OrderType::LimitSell
+                | OrderType::StopLimitBuy
+                | OrderType::StopLimitSell
+        )
+    }
+
+    pub(crate) fn trade_type(&self) -> TradeType {
+        match self {
+            OrderType::MarketBuy => TradeType::MarketBuy,
+            OrderType::MarketSell => TradeType::MarketSell,
+            OrderType::LimitBuy | OrderType::StopLimitBuy => TradeType::LimitBuy,
+            OrderType::LimitSell | OrderType::StopLimitSell => TradeType::LimitSell,
+            OrderType::StopBuy => TradeType::StopBuy,
+            OrderType::StopSell => TradeType::StopSell,
+            OrderType::TakeProfitBuy => TradeType::TakeProfitBuy,
+            OrderType::TakeProfitSell => TradeType::TakeProfitSell,
+            OrderType::TrailingStopBuy => TradeType::TrailingStopBuy,
+            OrderType::TrailingStopSell => TradeType::TrailingStopSell,
+        }
+    }
+}
+
+// Strategies see prices and amounts as BigDecimal, pending orders keep them as the engine's Number
 #[derive(Debug, Clone)]
-pub struct Order {
+pub struct Order<N = BigDecimal> {
     pub id: Uuid,
+    pub symbol: String,
     pub order_type: OrderType,
-    pub price: BigDecimal,
-    pub amount: BigDecimal,
-    pub fee: BigDecimal,
+    pub price: N,
+    pub trigger_price: Option<N>,
+    pub trail: Option<N>,
+    pub amount: N,
+    pub fee: N,
+    pub group: Option<Uuid>,
+    pub parent: Option<Uuid>,
+    pub(crate) reserved: bool,
+    pub(crate) symbol_index: usize,
 }
 
 #[derive(Debug, Clone)]
-pub struct StrategyContext<'a> {
+pub struct BracketOrder {
+    pub group: Uuid,
+    pub entry: Option<Uuid>,
+    pub target: Uuid,
+    pub stop: Uuid,
+}
+
+impl Order<Number> {
+    pub(crate) fn to_decimal(&self) -> Order {
+        Order {
+            id: self.id,
+            symbol: self.symbol.clone(),
+            order_type: self.order_type.clone(),
+            price: self.price.to_decimal(),
+            trigger_price: self.trigger_price.as_ref().map(Number::to_decimal),
+            trail: self.trail.as_ref().map(Number::to_decimal),
+            amount: self.amount.to_decimal(),
+            fee: self.fee.to_decimal(),
+            group: self.group,
+            parent: self.parent,
+            reserved: self.reserved,
+            symbol_index: self.symbol_index,
+        }
+    }
+}
+
+// Exact backtests see the same candles as both Candles and columns, fast ones only as columns
+#[derive(Debug)]
+pub(crate) struct SymbolState<'a> {
+    pub(crate) symbol: String,
     pub(crate) candles: &'a [Candle],
-    pub(crate) balance: BigDecimal,
-    pub(crate) position: BigDecimal,
-    pub(crate) trades: Vec<Trade>,
-    pub(crate) orders: Vec<Order>,
-    pub(crate) fees: TradingFees,
+    pub(crate) columns: Columns<'a>,
+    pub(crate) timeframes: Vec<(Timeframe, &'a [Candle])>,
+    pub(crate) maker_fee: Number,
+    pub(crate) taker_fee: Number,
     pub(crate) precision: MarketPrecision,
+    pub(crate) indicators: Vec<(String, Box<dyn Indicator>)>,
+}
+
+impl SymbolState<'_> {
+    fn bar(&self, index: usize) -> Bar {
+        match self.candles.get(index) {
+            Some(candle) => Bar::from(candle),
+            None => Bar::from_columns(&self.columns, index),
+        }
+    }
+
+    pub(crate) fn last_bar(&self) -> Option<Bar> {
+        self.columns
+            .len()
+            .checked_sub(1)
+            .map(|index| self.bar(index))
+    }
+
+    pub(crate) fn round_price(&self, value: &Number, mode: RoundingMode) -> Number {
+        value.round(&self.precision.price_precision, mode)
+    }
+
+    pub(crate) fn round_amount(&self, value: &Number, mode: RoundingMode) -> Number {
+        value.round(&self.precision.amount_precision, mode)
+    }
+
+    pub(crate) fn last_close(&self) -> Option<Number> {
+        match self.candles.last() {
+            Some(candle) => Some(Number::from(&candle.close)),
+            None => self.columns.close.last().map(|close| Number::Fast(*close)),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct StrategyContext<'a> {
+    pub(crate) symbols: Vec<SymbolState<'a>>,
+    pub(crate) timeframe: Timeframe,
+    pub(crate) engine: BacktestEngine,
+    pub(crate) broker: Box<dyn Broker>,
+    pub(crate) timestamp: Option<DateTime<Utc>>,
+    risk: RiskManager,
+    active: usize,
 }
 
 impl StrategyContext<'_> {
     pub(crate) fn new(
-        balance: BigDecimal,
-        fees: TradingFees,
-        precision: MarketPrecision,
+        timeframe: Timeframe,
+        engine: BacktestEngine,
+        broker: Box<dyn Broker>,
+        risk: RiskLimits,
     ) -> AppResult<Self> {
         Ok(Self {
+            symbols: Vec::new(),
+            timeframe,
+            engine,
+            broker,
+            timestamp: None,
+            risk: RiskManager::new(risk, engine),
+            active: 0,
+        })
+    }
+
+    // The first symbol added is the primary one strategies trade by default
+    pub(crate) fn add_symbol(
+        &mut self,
+        symbol: String,
+        fees: TradingFees,
+        precision: MarketPrecision,
+    ) -> AppResult<()> {
+        if self.symbols.iter().any(|s| s.symbol == symbol) {
+            return Err(AppError::BadRequest(format!(
+                "Duplicate symbol: {}",
+                symbol
+            )));
+        }
+
+        self.broker.add_symbol(&symbol)?;
+        self.symbols.push(SymbolState {
+            symbol,
             candles: &[],
-            balance,
-            position: BigDecimal::zero(),
-            trades: Vec::new(),
-            orders: Vec::new(),
-            fees,
+            columns: Columns::default(),
+            timeframes: Vec::new(),
+            maker_fee: Number::new(&fees.maker, self.engine),
+            taker_fee: Number::new(&fees.taker, self.engine),
             precision,
-        })
+            indicators: Vec::new(),
+        });
+        Ok(())
     }
 
-    pub(crate) fn before(&mut self) -> AppResult<()> {
-        let candle = self.candle()?;
-        let mut orders_to_execute = Vec::new();
-
-        for order in &self.orders {
-            match order.order_type {
-                OrderType::LimitBuy => {
-                    if order.price >= candle.low {
-                        orders_to_execute.push((
-                            order.id,
-                            OrderType::LimitBuy,
-                            order.price.clone(),
-                            order.amount.clone(),
-                            order.fee.clone(),
-                        ));
-                    }
-                }
-                OrderType::LimitSell => {
-                    if order.price <= candle.high {
-                        orders_to_execute.push((
-                            order.id,
-                            OrderType::LimitSell,
-                            order.price.clone(),
-                            order.amount.clone(),
-                            order.fee.clone(),
-                        ));
-                    }
-                }
+    pub(crate) fn before(
+        &mut self,
+        timestamp: DateTime<Utc>,
+        strategy: &mut dyn Strategy,
+    ) -> AppResult<()> {
+        let previous = self.timestamp.replace(timestamp);
+
+        for index in 0..self.symbols.len() {
+            let Some(bar) = Market::new(&self.symbols, self.timestamp).current_bar(index) else {
+                continue;
+            };
+            for (_, indicator) in &mut self.symbols[index].indicators {
+                indicator.update(&bar);
             }
         }
 
-        for (order_id, order_type, price, amount, fee) in orders_to_execute {
-            match order_type {
-                OrderType::LimitBuy => {
-                    self.execute_limit_buy(&candle, &price, &amount, &fee);
-                }
-                OrderType::LimitSell => {
-                    self.execute_limit_sell(&candle, &price, &amount, &fee);
-                }
-            }
-            self.orders.retain(|o| o.id != order_id);
+        let market = Market::new(&self.symbols, self.timestamp);
+        self.broker.update(&market, previous);
+        let broker = &self.broker;
+        self.risk.start_bar(timestamp, || broker.equity(&market));
+
+        self.dispatch_events(strategy)
+    }
+
+    pub(crate) fn after(&mut self, strategy: &mut dyn Strategy) -> AppResult<()> {
+        self.dispatch_events(strategy)?;
+        match self.risk.halted() {
+            Some(reason) => Err(AppError::Strategy(format!(
+                "Run halted by risk limit: {}",
+                reason
+            ))),
+            None => Ok(()),
         }
+    }
 
-        Ok(())
+    pub(crate) fn end(&mut self, strategy: &mut dyn Strategy) -> AppResult<()> {
+        for order in self.broker.orders() {
+            self.broker.cancel_order(order.id);
+        }
+        self.dispatch_events(strategy)
     }
 
-    pub(crate) fn after(&mut self) -> AppResult<()> {
+    // Hands the account over to another broker, as when a paper session goes live after its
+    // warmup. Orders pending with the old broker are cancelled first
+    pub(crate) fn set_broker(
+        &mut self,
+        mut broker: Box<dyn Broker>,
+        strategy: &mut dyn Strategy,
+    ) -> AppResult<()> {
+        self.end(strategy)?;
+        for state in &self.symbols {
+            broker.add_symbol(&state.symbol)?;
+        }
+        broker.reconcile()?;
+        self.broker = broker;
         Ok(())
     }
 
-    pub(crate) fn end(&mut self) -> AppResult<()> {
-        let order_ids: Vec<Uuid> = self.orders.iter().map(|o| o.id).collect();
-        for id in order_ids {
-            self.cancel_order(id);
+    pub(crate) fn restore(&mut self, account: BrokerAccount) -> AppResult<()> {
+        self.broker.restore(account)
+    }
+
+    // Keeps the account, orders and indicators but lets go of the candles, so a paper session can
+    // append candles between ticks. Candles have to be set again before the next tick
+    pub(crate) fn detach(self) -> StrategyContext<'static> {
+        let symbols = self
+            .symbols
+            .into_iter()
+            .map(|state| SymbolState {
+                symbol: state.symbol,
+                candles: &[],
+                columns: Columns::default(),
+                timeframes: Vec::new(),
+                maker_fee: state.maker_fee,
+                taker_fee: state.taker_fee,
+                precision: state.precision,
+                indicators: state.indicators,
+            })
+            .collect();
+
+        StrategyContext {
+            symbols,
+            timeframe: self.timeframe,
+            engine: self.engine,
+            broker: self.broker,
+            timestamp: self.timestamp,
+            risk: self.risk,
+            active: self.active,
         }
-        Ok(())
     }
 
+    pub(crate) fn share_kill_switch(&mut self, kill_switch: Arc<AtomicBool>) {
+        self.risk.share_kill_switch(kill_switch);
+    }
+
+    // Orders the risk limits rejected since the last call
+    pub(crate) fn take_risk_events(&mut self) -> Vec<RiskEvent> {
+        self.risk.take_events()
+    }
+
+    // Callbacks may place or cancel orders themselves, so keep going until no events are left.
+    // Each callback runs with the order's symbol active
+    fn dispatch_events(&mut self, strategy: &mut dyn Strategy) -> AppResult<()> {
+        let active = self.active;
+        loop {
+            let events = self.broker.take_events();
+            if events.is_empty() {
+                return Ok(());
+            }
+            for event in events {
+                let result = match event {
+                    OrderEvent::Filled(order, trade) => {
+                        self.active = order.symbol_index;
+                        strategy.on_order_filled(self, &order, &trade)
+                    }
+                    OrderEvent::Cancelled(order) => {
+                        self.active = order.symbol_index;
+                        strategy.on_order_cancelled(self, &order)
+                    }
+                };
+                self.active = active;
+                result?;
+            }
+        }
+    }
+
+    fn current(&self) -> &SymbolState<'_> {
+        &self.symbols[self.active]
+    }
+
+    // Borrows the broker along with a view of the candles it prices orders against
+    fn broker_and_market(&mut self) -> (&mut dyn Broker, Market<'_>) {
+        (
+            self.broker.as_mut(),
+            Market::new(&self.symbols, self.timestamp),
+        )
+    }
+
+    fn number(&self, value: &BigDecimal) -> Number {
+        Number::new(value, self.engine)
+    }
+
+    // Close of the active symbol's latest candle, which market orders fill at
+    fn close(&self) -> AppResult<Number> {
+        self.current()
+            .last_close()
+            .ok_or(AppError::Strategy("No candles available".into()))
+    }
+
+    pub fn symbol(&self) -> &str {
+        &self.current().symbol
+    }
+
+    pub fn symbols(&self) -> Vec<&str> {
+        self.symbols.iter().map(|s| s.symbol.as_str()).collect()
+    }
+
+    // Runs the closure with another symbol active, so candles, position and orders placed inside
+    // refer to that symbol
+    pub fn with_symbol<T>(
+        &mut self,
+        symbol: &str,
+        f: impl FnOnce(&mut Self) -> AppResult<T>,
+    ) -> AppResult<T> {
+        let index = self
+            .symbols
+            .iter()
+            .position(|s| s.symbol == symbol)
+            .ok_or_else(|| AppError::Strategy(format!("Unknown symbol: {}", symbol)))?;
+
+        let active = std::mem::replace(&mut self.active, index);
+        let result = f(self);
+        self.active = active;
+        result
+    }
+
+    // Empty in fast backtests, which only load columns
     pub fn candles(&self) -> &[Candle] {
-        &self.candles
+        self.current().candles
     }
 
+    // Candles seen so far as f64 columns, available in both engines
+    pub fn columns(&self) -> Columns<'_> {
+        self.current().columns
+    }
+
+    // Fast backtests rebuild the candle from the columns, without the exchange name
     pub fn candle(&self) -> AppResult<Candle> {
-        self.candles
+        let state = self.current();
+        if let Some(candle) = state.candles.last() {
+            return Ok(candle.clone());
+        }
+
+        let bar = state
+            .last_bar()
+            .ok_or(AppError::Strategy("No candles available".into()))?;
+        Ok(Candle {
+            timestamp: bar.timestamp,
+            exchange: String::new(),
+            symbol: state.symbol.clone(),
+            timeframe: self.timeframe,
+            open: bar.open.into(),
+            high: bar.high.into(),
+            low: bar.low.into(),
+            close: bar.close.into(),
+            volume: bar.volume.into(),
+        })
+    }
+
+    pub fn timeframe(&self) -> Timeframe {
+        self.timeframe
+    }
+
+    pub fn timeframes(&self) -> Vec<Timeframe> {
+        std::iter::once(self.timeframe)
+            .chain(
+                self.current()
+                    .timeframes
+                    .iter()
+                    .map(|(timeframe, _)| *timeframe),
+            )
+            .collect()
+    }
+
+    // Candles of another timeframe that closed by the end of the current candle, so a higher
+    // timeframe's candle only shows up once it is complete
+    pub fn candles_for(&self, timeframe: Timeframe) -> AppResult<&[Candle]> {
+        if timeframe == self.timeframe {
+            return Ok(self.candles());
+        }
+
+        self.current()
+            .timeframes
+            .iter()
+            .find(|(t, _)| *t == timeframe)
+            .map(|(_, candles)| *candles)
+            .ok_or_else(|| {
+                AppError::Strategy(format!(
+                    "Timeframe {} is not loaded for this backtest",
+                    timeframe
+                ))
+            })
+    }
+
+    pub fn candle_for(&self, timeframe: Timeframe) -> AppResult<Candle> {
+        self.candles_for(timeframe)?
             .last()
             .cloned()
             .ok_or(AppError::Strategy("No candles available".into()))
     }
 
+    // Registers an indicator on the active symbol. It is fed the candles seen so far right away and
+    // each new candle before the tick
+    pub fn register_indicator(
+        &mut self,
+        name: &str,
+        indicator: impl Indicator + 'static,
+    ) -> AppResult<()> {
+        if name.contains('.') {
+            return Err(AppError::Strategy(format!(
+                "Indicator name cannot contain '.': {}",
+                name
+            )));
+        }
+
+        let state = &mut self.symbols[self.active];
+        if state.indicators.iter().any(|(n, _)| n == name) {
+            return Err(AppError::Strategy(format!(
+                "Indicator {} is already registered",
+                name
+            )));
+        }
+
+        let mut indicator = Box::new(indicator);
+        for index in 0..state.columns.len() {
+            indicator.update(&state.bar(index));
+        }
+        state.indicators.push((name.to_string(), indicator));
+        Ok(())
+    }
+
+    // None while the indicator is warming up. Other outputs are read as "name.output", e.g.
+    // "macd.signal"
+    pub fn indicator(&self, name: &str) -> AppResult<Option<f64>> {
+        let (name, output) = name.split_once('.').unwrap_or((name, "value"));
+        let (_, indicator) = self
+            .current()
+            .indicators
+            .iter()
+            .find(|(n, _)| n == name)
+            .ok_or_else(|| AppError::Strategy(format!("Unknown indicator: {}", name)))?;
+
+        indicator.output(output).ok_or_else(|| {
+            AppError::Strategy(format!("Indicator {} has no output named {}", name, output))
+        })
+    }
+
+    pub fn engine(&self) -> BacktestEngine {
+        self.engine
+    }
+
     pub fn balance(&self) -> BigDecimal {
-        self.balance.clone()
+        self.broker.balance().to_decimal()
     }
 
     pub fn position(&self) -> BigDecimal {
-        self.position.clone()
+        self.broker.position(self.active).to_decimal()
     }
 
     pub fn trades(&self) -> &[Trade] {
-        &self.trades
+        self.broker.trades()
     }
 
-    pub fn orders(&self) -> &[Order] {
-        &self.orders
+    pub fn orders(&self) -> Vec<Order> {
+        self.broker.orders()
     }
 
     pub fn precision(&self) -> &MarketPrecision {
-        &self.precision
+        &self.current().precision
     }
 
+    pub fn margin(&self) -> Option<&MarginConfig> {
+        self.broker.margin()
+    }
+
+    pub fn accrued_interest(&self) -> BigDecimal {
+        self.broker.accrued_interest().to_decimal()
+    }
+
+    pub fn equity(&self) -> AppResult<BigDecimal> {
+        self.close()?;
+        let market = Market::new(&self.symbols, self.timestamp);
+        Ok(self.broker.equity(&market).into())
+    }
+
+    // Cancelling one leg of an OCO or bracket also cancels its siblings and any exits waiting on it
     pub fn cancel_order(&mut self, order_id: Uuid) {
-        if let Some(pos) = self.orders.iter().position(|o| o.id == order_id) {
-            let order = &self.orders[pos];
-            match order.order_type {
-                OrderType::LimitBuy => {
-                    let refund = &order.price * &order.amount + &order.fee;
-                    self.balance += &refund;
-                }
-                OrderType::LimitSell => {
-                    self.position += &order.amount;
-                    self.balance += &order.fee;
-                }
-            }
-            self.orders.remove(pos);
-        }
+        self.broker.cancel_order(order_id);
     }
 
     pub fn market_buy(&mut self, amount: &BigDecimal) -> AppResult<()> {
-        let amount = self.precision.round_amount(amount, RoundingMode::Down);
+        self.check_supported(&[OrderType::MarketBuy])?;
+        let amount = self.order_amount(amount)?;
+        self.submit_market_order(true, amount)?;
+        Ok(())
+    }
 
-        if amount <= BigDecimal::zero() {
-            return Err(AppError::Strategy("Amount must be positive".into()));
-        }
+    pub fn market_sell(&mut self, amount: &BigDecimal) -> AppResult<()> {
+        self.check_supported(&[OrderType::MarketSell])?;
+        let amount = self.order_amount(amount)?;
+        self.submit_market_order(false, amount)?;
+        Ok(())
+    }
 
-        let candle = self.candle()?;
-        let price = candle.close;
+    pub fn limit_buy(
+        &mut self,
+        price: &BigDecimal,
+        amount: &BigDecimal,
+    ) -> AppResult<Option<Uuid>> {
+        self.check_supported(&[OrderType::LimitBuy])?;
+        let price = self
+            .current()
+            .round_price(&self.number(price), RoundingMode::Down);
+        let amount = self.order_amount(amount)?;
+        self.submit_limit_order(true, price, amount)
+    }
 
-        let cost = &price * &amount;
-        let fee = &cost * &self.fees.taker;
-        let fee = self.precision.round_amount(&fee, RoundingMode::Up);
-        let total = &cost + &fee;
+    pub fn limit_sell(
+        &mut self,
+        price: &BigDecimal,
+        amount: &BigDecimal,
+    ) -> AppResult<Option<Uuid>> {
+        self.check_supported(&[OrderType::LimitSell])?;
+        let price = self
+            .current()
+            .round_price(&self.number(price), RoundingMode::Down);
+        let amount = self.order_amount(amount)?;
+        self.submit_limit_order(false, price, amount)
+    }
 
-        if total > self.balance {
-            return Err(AppError::Strategy("Insufficient funds".into()));
-        }
+    pub fn stop_buy(
+        &mut self,
+        stop_price: &BigDecimal,
+        amount: &BigDecimal,
+    ) -> AppResult<Option<Uuid>> {
+        self.check_supported(&[OrderType::StopBuy])?;
+        let stop_price = self
+            .current()
+            .round_price(&self.number(stop_price), RoundingMode::Down);
+        let amount = self.order_amount(amount)?;
 
-        self.balance -= &total;
-        self.position += &amount;
+        let close = self.close()?;
+        if stop_price <= close {
+            return self.submit_market_order(true, amount);
+        };
 
-        self.trades.push(Trade {
-            timestamp: candle.timestamp,
-            trade_type: TradeType::MarketBuy,
-            price,
+        self.place_order(
+            OrderType::StopBuy,
+            stop_price.clone(),
+            Some(stop_price),
+            None,
             amount,
-            fee,
-            profit: None,
-        });
-
-        Ok(())
+        )
+        .map(Some)
     }
 
-    pub fn market_sell(&mut self, amount: &BigDecimal) -> AppResult<()> {
-        let amount = self.precision.round_amount(amount, RoundingMode::Down);
+    pub fn stop_sell(
+        &mut self,
+        stop_price: &BigDecimal,
+        amount: &BigDecimal,
+    ) -> AppResult<Option<Uuid>> {
+        self.check_supported(&[OrderType::StopSell])?;
+        let stop_price = self
+            .current()
+            .round_price(&self.number(stop_price), RoundingMode::Down);
+        let amount = self.order_amount(amount)?;
 
-        if amount <= BigDecimal::zero() {
-            return Err(AppError::Strategy("Amount must be positive".into()));
-        }
+        let close = self.close()?;
+        if stop_price >= close {
+            return self.submit_market_order(false, amount);
+        };
 
-        if amount > self.position {
-            return Err(AppError::Strategy(
-                "Insufficient base asset amount to sell".into(),
-            ));
-        }
+        self.place_order(
+            OrderType::StopSell,
+            stop_price.clone(),
+            Some(stop_price),
+            None,
+            amount,
+        )
+        .map(Some)
+    }
 
-        let candle = self.candle()?;
-        let price = candle.close;
+    pub fn stop_limit_buy(
+        &mut self,
+        stop_price: &BigDecimal,
+        limit_price: &BigDecimal,
+        amount: &BigDecimal,
+    ) -> AppResult<Option<Uuid>> {
+        self.check_supported(&[OrderType::StopLimitBuy])?;
+        let stop_price = self
+            .current()
+            .round_price(&self.number(stop_price), RoundingMode::Down);
+        let limit_price = self
+            .current()
+            .round_price(&self.number(limit_price), RoundingMode::Down);
+        let amount = self.order_amount(amount)?;
 
-        let proceeds = &price * &amount;
-        let fee = self
-            .precision
-            .round_amount(&(&proceeds * &self.fees.taker), RoundingMode::Up);
-        let revenue = &proceeds - &fee;
+        let close = self.close()?;
+        if stop_price <= close {
+            return self.submit_limit_order(true, limit_price, amount);
+        };
 
-        if revenue < BigDecimal::zero() {
-            return Err(AppError::Strategy("Revenue cannot be negative".into()));
-        }
+        self.place_order(
+            OrderType::StopLimitBuy,
+            limit_price,
+            Some(stop_price),
+            None,
+            amount,
+        )
+        .map(Some)
+    }
 
-        self.position -= &amount;
-        self.balance += &revenue;
+    pub fn stop_limit_sell(
+        &mut self,
+        stop_price: &BigDecimal,
+        limit_price: &BigDecimal,
+        amount: &BigDecimal,
+    ) -> AppResult<Option<Uuid>> {
+        self.check_supported(&[OrderType::StopLimitSell])?;
+        let stop_price = self
+            .current()
+            .round_price(&self.number(stop_price), RoundingMode::Down);
+        let limit_price = self
+            .current()
+            .round_price(&self.number(limit_price), RoundingMode::Down);
+        let amount = self.order_amount(amount)?;
 
-        self.trades.push(Trade {
-            timestamp: candle.timestamp,
-            trade_type: TradeType::MarketSell,
-            price,
+        let close = self.close()?;
+        if stop_price >= close {
+            return self.submit_limit_order(false, limit_price, amount);
+        };
+
+        self.place_order(
+            OrderType::StopLimitSell,
+            limit_price,
+            Some(stop_price),
+            None,
             amount,
-            fee,
-            profit: None,
-        });
+        )
+        .map(Some)
+    }
 
-        Ok(())
+    pub fn take_profit_buy(
+        &mut self,
+        trigger_price: &BigDecimal,
+        amount: &BigDecimal,
+    ) -> AppResult<Option<Uuid>> {
+        self.check_supported(&[OrderType::TakeProfitBuy])?;
+        let trigger_price = self
+            .current()
+            .round_price(&self.number(trigger_price), RoundingMode::Down);
+        let amount = self.order_amount(amount)?;
+
+        let close = self.close()?;
+        if trigger_price >= close {
+            return self.submit_market_order(true, amount);
+        };
+
+        self.place_order(
+            OrderType::TakeProfitBuy,
+            trigger_price.clone(),
+            Some(trigger_price),
+            None,
+            amount,
+        )
+        .map(Some)
     }
 
-    pub fn limit_buy(
+    pub fn take_profit_sell(
         &mut self,
-        price: &BigDecimal,
+        trigger_price: &BigDecimal,
         amount: &BigDecimal,
     ) -> AppResult<Option<Uuid>> {
-        let price = self.precision.round_amount(price, RoundingMode::Down);
-        let amount = self.precision.round_amount(amount, RoundingMode::Down);
+        self.check_supported(&[OrderType::TakeProfitSell])?;
+        let trigger_price = self
+            .current()
+            .round_price(&self.number(trigger_price), RoundingMode::Down);
+        let amount = self.order_amount(amount)?;
 
-        if amount <= BigDecimal::zero() {
-            return Err(AppError::Strategy("Amount must be positive".into()));
+        let close = self.close()?;
+        if trigger_price <= close {
+            return self.submit_market_order(false, amount);
+        };
+
+        self.place_order(
+            OrderType::TakeProfitSell,
+            trigger_price.clone(),
+            Some(trigger_price),
+            None,
+            amount,
+        )
+        .map(Some)
+    }
+
+    pub fn trailing_stop_buy(
+        &mut self,
+        trail: &BigDecimal,
+        amount: &BigDecimal,
+    ) -> AppResult<Uuid> {
+        self.check_supported(&[OrderType::TrailingStopBuy])?;
+        let trail = self
+            .current()
+            .round_price(&self.number(trail), RoundingMode::Down);
+        let amount = self.order_amount(amount)?;
+
+        if trail <= Number::zero(self.engine) {
+            return Err(AppError::Strategy("Trail must be positive".into()));
         }
 
-        let candle = self.candle()?;
+        let close = self.close()?;
+        let stop_price = &close + &trail;
 
-        if price >= candle.close {
-            self.market_buy(&amount)?;
-            return Ok(None);
-        };
+        self.place_order(
+            OrderType::TrailingStopBuy,
+            stop_price.clone(),
+            Some(stop_price),
+            Some(trail),
+            amount,
+        )
+    }
 
-        let cost = &amount * &price;
-        let fee = &cost * &self.fees.maker;
-        let fee = self.precision.round_amount(&fee, RoundingMode::Up);
-        let total = &cost + &fee;
+    pub fn trailing_stop_sell(
+        &mut self,
+        trail: &BigDecimal,
+        amount: &BigDecimal,
+    ) -> AppResult<Uuid> {
+        self.check_supported(&[OrderType::TrailingStopSell])?;
+        let trail = self
+            .current()
+            .round_price(&self.number(trail), RoundingMode::Down);
+        let amount = self.order_amount(amount)?;
 
-        if total > self.balance {
-            return Err(AppError::Strategy("Insufficient funds".into()));
+        if trail <= Number::zero(self.engine) {
+            return Err(AppError::Strategy("Trail must be positive".into()));
         }
 
-        self.balance -= &total;
+        let close = self.close()?;
+        let stop_price = &close - &trail;
+        if stop_price <= Number::zero(self.engine) {
+            return Err(AppError::Strategy(
+                "Trail must be below the current price".into(),
+            ));
+        }
 
-        let order_id = Uuid::new_v4();
-        self.orders.push(Order {
-            id: order_id,
-            order_type: OrderType::LimitBuy,
-            price,
+        self.place_order(
+            OrderType::TrailingStopSell,
+            stop_price.clone(),
+            Some(stop_price),
+            Some(trail),
             amount,
-            fee,
-        });
+        )
+    }
+
+    pub fn oco_buy(
+        &mut self,
+        amount: &BigDecimal,
+        target_price: &BigDecimal,
+        stop_price: &BigDecimal,
+    ) -> AppResult<BracketOrder> {
+        self.check_supported(&[OrderType::LimitBuy, OrderType::StopBuy])?;
+        let amount = self.order_amount(amount)?;
+        let target_price = self
+            .current()
+            .round_price(&self.number(target_price), RoundingMode::Down);
+        let stop_price = self
+            .current()
+            .round_price(&self.number(stop_price), RoundingMode::Down);
+
+        let close = self.close()?;
+        if target_price >= close || stop_price <= close {
+            return Err(AppError::Strategy(
+                "Target must be below and stop above the current price".into(),
+            ));
+        }
 
-        Ok(Some(order_id))
+        self.place_exit_orders(true, amount, target_price, stop_price, None)
     }
 
-    pub fn limit_sell(
+    pub fn oco_sell(
         &mut self,
-        price: &BigDecimal,
         amount: &BigDecimal,
-    ) -> AppResult<Option<Uuid>> {
-        let price = self.precision.round_amount(price, RoundingMode::Down);
-        let amount = self.precision.round_amount(amount, RoundingMode::Down);
+        target_price: &BigDecimal,
+        stop_price: &BigDecimal,
+    ) -> AppResult<BracketOrder> {
+        self.check_supported(&[OrderType::LimitSell, OrderType::StopSell])?;
+        let amount = self.order_amount(amount)?;
+        let target_price = self
+            .current()
+            .round_price(&self.number(target_price), RoundingMode::Down);
+        let stop_price = self
+            .current()
+            .round_price(&self.number(stop_price), RoundingMode::Down);
 
-        if amount <= BigDecimal::zero() {
-            return Err(AppError::Strategy("Amount must be positive".into()));
+        let close = self.close()?;
+        if target_price <= close || stop_price >= close {
+            return Err(AppError::Strategy(
+                "Target must be above and stop below the current price".into(),
+            ));
         }
 
-        if amount > self.position {
+        self.place_exit_orders(false, amount, target_price, stop_price, None)
+    }
+
+    // Market entry when entry_price is None, otherwise a limit entry whose exits activate once it
+    // fills
+    pub fn bracket_buy(
+        &mut self,
+        entry_price: Option<&BigDecimal>,
+        amount: &BigDecimal,
+        target_price: &BigDecimal,
+        stop_price: &BigDecimal,
+    ) -> AppResult<BracketOrder> {
+        let entry_type = match entry_price {
+            Some(_) => OrderType::LimitBuy,
+            None => OrderType::MarketBuy,
+        };
+        self.check_supported(&[entry_type, OrderType::LimitSell, OrderType::StopSell])?;
+        let amount = self.order_amount(amount)?;
+        let target_price = self
+            .current()
+            .round_price(&self.number(target_price), RoundingMode::Down);
+        let stop_price = self
+            .current()
+            .round_price(&self.number(stop_price), RoundingMode::Down);
+
+        let reference = match entry_price {
+            Some(price) => self.number(price),
+            None => self.close()?,
+        };
+        if target_price <= reference || stop_price >= reference {
             return Err(AppError::Strategy(
-                "Insufficient base asset amount to sell".into(),
+                "Target must be above and stop below the entry price".into(),
             ));
         }
 
-        let candle = self.candle()?;
-        if price <= candle.close {
-            self.market_sell(&amount)?;
-            return Ok(None);
+        let entry = match entry_price {
+            Some(price) => {
+                let price = self
+                    .current()
+                    .round_price(&self.number(price), RoundingMode::Down);
+                self.submit_limit_order(true, price, amount.clone())?
+            }
+            None => self.submit_market_order(true, amount.clone())?,
         };
 
-        let proceeds = &price * &amount;
-        let fee = self
-            .precision
-            .round_amount(&(&proceeds * &self.fees.maker), RoundingMode::Up);
+        self.place_exit_orders(false, amount, target_price, stop_price, entry)
+    }
 
-        if fee > self.balance {
-            return Err(AppError::Strategy("Insufficient funds to cover fee".into()));
+    pub fn bracket_sell(
+        &mut self,
+        entry_price: Option<&BigDecimal>,
+        amount: &BigDecimal,
+        target_price: &BigDecimal,
+        stop_price: &BigDecimal,
+    ) -> AppResult<BracketOrder> {
+        let entry_type = match entry_price {
+            Some(_) => OrderType::LimitSell,
+            None => OrderType::MarketSell,
+        };
+        self.check_supported(&[entry_type, OrderType::LimitBuy, OrderType::StopBuy])?;
+        let amount = self.order_amount(amount)?;
+        let target_price = self
+            .current()
+            .round_price(&self.number(target_price), RoundingMode::Down);
+        let stop_price = self
+            .current()
+            .round_price(&self.number(stop_price), RoundingMode::Down);
+
+        let reference = match entry_price {
+            Some(price) => self.number(price),
+            None => self.close()?,
+        };
+        if target_price >= reference || stop_price <= reference {
+            return Err(AppError::Strategy(
+                "Target must be below and stop above the entry price".into(),
+            ));
         }
 
-        self.position -= &amount;
-        self.balance -= &fee;
+        let entry = match entry_price {
+            Some(price) => {
+                let price = self
+                    .current()
+                    .round_price(&self.number(price), RoundingMode::Down);
+                self.submit_limit_order(false, price, amount.clone())?
+            }
+            None => self.submit_market_order(false, amount.clone())?,
+        };
 
-        let order_id = Uuid::new_v4();
-        self.orders.push(Order {
-            id: order_id,
-            order_type: OrderType::LimitSell,
-            price,
-            amount,
-            fee,
-        });
+        self.place_exit_orders(true, amount, target_price, stop_price, entry)
+    }
+
+    // Limit orders at or through the close are marketable and fill right away
+    fn submit_limit_order(
+        &mut self,
+        buy: bool,
+        price: Number,
+        amount: Number,
+    ) -> AppResult<Option<Uuid>> {
+        let close = self.close()?;
+        if (buy && price >= close) || (!buy && price <= close) {
+            return self.submit_market_order(buy, amount);
+        }
+
+        let order_type = if buy {
+            OrderType::LimitBuy
+        } else {
+            OrderType::LimitSell
+        };
+        self.place_order(order_type, price, None, None, amount)
+            .map(Some)
+    }
 
-        Ok(Some(order_id))
+    // Returns the order id while part of the order is still waiting to fill
+    fn submit_market_order(&mut self, buy: bool, amount: Number) -> AppResult<Option<Uuid>> {
+        let order_type = if buy {
+            OrderType::MarketBuy
+        } else {
+            OrderType::MarketSell
+        };
+        self.check_risk(order_type, &self.close()?, &amount)?;
+        let index = self.active;
+        let (broker, market) = self.broker_and_market();
+        broker.market_order(&market, index, buy, amount)
+    }
+
+    // Live brokers only take the order types their exchange does
+    fn check_supported(&self, order_types: &[OrderType]) -> AppResult<()> {
+        match order_types.iter().find(|t| !self.broker.supports(t)) {
+            Some(order_type) => Err(AppError::Strategy(format!(
+                "{:?} orders are not supported by this broker",
+                order_type
+            ))),
+            None => Ok(()),
+        }
     }
 
-    fn execute_limit_buy(
+    fn check_risk(
         &mut self,
-        candle: &Candle,
-        price: &BigDecimal,
-        amount: &BigDecimal,
-        fee: &BigDecimal,
-    ) {
-        self.position += amount;
-
-        let trade = Trade {
-            timestamp: candle.timestamp,
-            trade_type: TradeType::LimitBuy,
-            price: price.clone(),
-            amount: amount.clone(),
-            fee: fee.clone(),
-            profit: None,
+        order_type: OrderType,
+        price: &Number,
+        amount: &Number,
+    ) -> AppResult<()> {
+        if !self.risk.enabled() {
+            return Ok(());
+        }
+
+        // Legs of an OCO or bracket only count once, as only one of them can fill
+        let index = self.active;
+        let buy = order_type.is_buy();
+        let mut position = self.broker.position(index);
+        let mut pending = Number::zero(self.engine);
+        let mut groups = Vec::new();
+        for order in self.broker.orders() {
+            if order.symbol_index != index {
+                continue;
+            }
+            let amount = self.number(&order.amount);
+            if order.reserved && !order.order_type.is_buy() {
+                position += &amount;
+            }
+            if order.order_type.is_buy() != buy {
+                continue;
+            }
+            if let Some(group) = order.group {
+                if groups.contains(&group) {
+                    continue;
+                }
+                groups.push(group);
+            }
+            pending += amount;
+        }
+
+        let order = RiskOrder {
+            symbol: &self.symbols[index].symbol,
+            order_type,
+            price,
+            amount,
+            position,
+            pending,
         };
+        let market = Market::new(&self.symbols, self.timestamp);
+        let broker = &self.broker;
+        self.risk
+            .check(self.timestamp.unwrap_or_default(), order, || {
+                broker.equity(&market)
+            })
+    }
+
+    fn order_amount(&self, amount: &BigDecimal) -> AppResult<Number> {
+        let amount = self
+            .current()
+            .round_amount(&self.number(amount), RoundingMode::Down);
+
+        if amount <= Number::zero(self.engine) {
+            return Err(AppError::Strategy("Amount must be positive".into()));
+        }
 
-        self.trades.push(trade);
+        Ok(amount)
     }
 
-    fn execute_limit_sell(
+    fn place_order(
         &mut self,
-        candle: &Candle,
-        price: &BigDecimal,
-        amount: &BigDecimal,
-        fee: &BigDecimal,
-    ) {
-        let proceeds = price * amount;
-        self.balance += &proceeds;
-
-        let trade = Trade {
-            timestamp: candle.timestamp,
-            trade_type: TradeType::LimitSell,
-            price: price.clone(),
-            amount: amount.clone(),
-            fee: fee.clone(),
-            profit: None,
+        order_type: OrderType,
+        price: Number,
+        trigger_price: Option<Number>,
+        trail: Option<Number>,
+        amount: Number,
+    ) -> AppResult<Uuid> {
+        self.check_risk(order_type.clone(), &price, &amount)?;
+        let request = OrderRequest {
+            symbol_index: self.active,
+            order_type,
+            price,
+            trigger_price,
+            trail,
+            amount,
         };
+        let (broker, market) = self.broker_and_market();
+        broker.place_order(&market, request)
+    }
 
-        self.trades.push(trade);
+    fn place_exit_orders(
+        &mut self,
+        buy: bool,
+        amount: Number,
+        target_price: Number,
+        stop_price: Number,
+        parent: Option<Uuid>,
+    ) -> AppResult<BracketOrder> {
+        let (target_type, stop_type) = if buy {
+            (OrderType::LimitBuy, OrderType::StopBuy)
+        } else {
+            (OrderType::LimitSell, OrderType::StopSell)
+        };
+        // Either leg may be the one that fills, so both have to pass at their own price
+        self.check_risk(target_type, &target_price, &amount)?;
+        self.check_risk(stop_type, &stop_price, &amount)?;
+        let request = ExitOrders {
+            symbol_index: self.active,
+            buy,
+            amount,
+            target_price,
+            stop_price,
+            parent,
+        };
+        let (broker, market) = self.broker_and_market();
+        broker.place_exit_orders(&market, request)
     }
 }
@@ -0,0 +1,240 @@
+use super::{Bar, Number};
+use crate::errors::{AppError, AppResult};
+use bigdecimal::{BigDecimal, One, Zero};
+use serde::{Deserialize, Serialize};
+use std::fmt::Debug;
+use std::sync::Arc;
+use ts_rs::TS;
+
+const BASIS_POINTS: i64 = 10000;
+
+pub trait FillModel: Debug + Send + Sync {
+    // Price a market-style order executes at, given the close or open it was triggered at
+    fn market_price(&self, _bar: &Bar, price: &Number, _amount: &Number, _buy: bool) -> Number {
+        price.clone()
+    }
+
+    // Total base amount that can fill within the candle, unlimited when None
+    fn fill_limit(&self, _bar: &Bar) -> Option<Number> {
+        None
+    }
+
+    // Whether market orders wait for the next candle's open instead of filling at the close
+    fn delays_market_orders(&self) -> bool {
+        false
+    }
+}
+
+fn slipped(price: &Number, slippage: &Number, buy: bool) -> Number {
+    let one = Number::one(price.engine());
+    if buy {
+        price * (one + slippage)
+    } else {
+        price * (one - slippage)
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct IdealFill;
+
+impl FillModel for IdealFill {}
+
+#[derive(Debug, Clone)]
+pub struct FixedSlippage {
+    pub bps: BigDecimal,
+}
+
+impl FillModel for FixedSlippage {
+    fn market_price(&self, _bar: &Bar, price: &Number, _amount: &Number, buy: bool) -> Number {
+        slipped(
+            price,
+            &(Number::from(&self.bps) / Number::from(BASIS_POINTS)),
+            buy,
+        )
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct VolumeSlippage {
+    pub impact: BigDecimal,
+}
+
+impl FillModel for VolumeSlippage {
+    fn market_price(&self, bar: &Bar, price: &Number, amount: &Number, buy: bool) -> Number {
+        let one = Number::one(bar.volume.engine());
+        let participation = if bar.volume.is_zero() {
+            one
+        } else {
+            (amount / &bar.volume).min(one)
+        };
+        slipped(price, &(Number::from(&self.impact) * participation), buy)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct PartialFill {
+    pub max_volume_fraction: BigDecimal,
+}
+
+impl FillModel for PartialFill {
+    fn fill_limit(&self, bar: &Bar) -> Option<Number> {
+        Some(&bar.volume * Number::from(&self.max_volume_fraction))
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct NextOpenFill;
+
+impl FillModel for NextOpenFill {
+    fn delays_market_orders(&self) -> bool {
+        true
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, TS)]
+#[serde(tag = "type", rename_all = "snake_case")]
+#[ts(export)]
+pub enum FillModelConfig {
+    #[default]
+    Ideal,
+    FixedSlippage {
+        #[ts(type = "string")]
+        bps: BigDecimal,
+    },
+    VolumeSlippage {
+        #[ts(type = "string")]
+        impact: BigDecimal,
+    },
+    PartialFill {
+        #[ts(type = "string")]
+        max_volume_fraction: BigDecimal,
+    },
+    NextOpen,
+}
+
+impl FillModelConfig {
+    pub fn validate(&self) -> AppResult<()> {
+        match self {
+            FillModelConfig::FixedSlippage { bps } if bps < &BigDecimal::zero() => Err(
+                AppError::BadRequest("Slippage cannot be negative".to_string()),
+            ),
+            FillModelConfig::VolumeSlippage { impact } if impact < &BigDecimal::zero() => Err(
+                AppError::BadRequest("Market impact cannot be negative".to_string()),
+            ),
+            FillModelConfig::PartialFill {
+                max_volume_fraction,
+            } if max_volume_fraction <= &BigDecimal::zero()
+                || max_volume_fraction > &BigDecimal::one() =>
+            {
+                Err(AppError::BadRequest(
+                    "Volume fraction must be greater than 0 and at most 1".to_string(),
+                ))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    pub fn build(&self) -> Arc<dyn FillModel> {
+        match self {
+            FillModelConfig::Ideal => Arc::new(IdealFill),
+            FillModelConfig::FixedSlippage { bps } => Arc::new(FixedSlippage { bps: bps.clone() }),
+            FillModelConfig::VolumeSlippage { impact } => Arc::new(VolumeSlippage {
+                impact: impact.clone(),
+            }),
+            FillModelConfig::PartialFill {
+                max_volume_fraction,
+            } => Arc::new(PartialFill {
+                max_volume_fraction: max_volume_fraction.clone(),
+            }),
+            FillModelConfig::NextOpen => Arc::new(NextOpenFill),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::broker::testing::{Recorder, advance, candles, context, decimal};
+    use super::*;
+    use crate::strategy::{BacktestEngine, OrderType, Simulator};
+
+    fn bar(open: &str, close: &str, volume: &str) -> Bar {
+        Bar {
+            timestamp: Default::default(),
+            open: Number::Exact(decimal(open)),
+            high: Number::Exact(decimal(open).max(decimal(close))),
+            low: Number::Exact(decimal(open).min(decimal(close))),
+            close: Number::Exact(decimal(close)),
+            volume: Number::Exact(decimal(volume)),
+        }
+    }
+
+    fn market_price(model: &dyn FillModel, amount: &str, buy: bool) -> BigDecimal {
+        let bar = bar("100", "100", "1000");
+        let price = Number::Exact(decimal("100"));
+        let amount = Number::Exact(decimal(amount));
+        model.market_price(&bar, &price, &amount, buy).to_decimal()
+    }
+
+    #[test]
+    fn fixed_slippage_moves_the_price_against_the_order() {
+        let model = FixedSlippage { bps: decimal("50") };
+        assert_eq!(market_price(&model, "1", true), decimal("100.5"));
+        assert_eq!(market_price(&model, "1", false), decimal("99.5"));
+    }
+
+    #[test]
+    fn volume_slippage_grows_with_the_share_of_the_candle_volume() {
+        let model = VolumeSlippage {
+            impact: decimal("0.01"),
+        };
+        assert_eq!(market_price(&model, "100", true), decimal("100.1"));
+        assert_eq!(market_price(&model, "500", false), decimal("99.5"));
+        // Orders larger than the candle take the full impact
+        assert_eq!(market_price(&model, "5000", true), decimal("101"));
+    }
+
+    #[test]
+    fn next_open_fills_market_orders_at_the_following_open() {
+        let columns = candles(&[(100.0, 101.0, 99.0, 100.0), (103.0, 105.0, 102.0, 104.0)]);
+        let simulator = Simulator::new(
+            BacktestEngine::Exact,
+            &decimal("1000"),
+            None,
+            Arc::new(NextOpenFill),
+        );
+        let mut strategy = Recorder::default();
+        let mut context = context(Box::new(simulator));
+        advance(&mut context, &columns, 0, &mut strategy);
+
+        context.market_buy(&decimal("2")).unwrap();
+        assert_eq!(context.position(), decimal("0"));
+        assert_eq!(context.orders().len(), 1);
+
+        advance(&mut context, &columns, 1, &mut strategy);
+        assert_eq!(strategy.filled.len(), 1);
+        assert_eq!(context.position(), decimal("2"));
+        assert_eq!(context.trades()[0].price, decimal("103"));
+        assert_eq!(context.balance(), decimal("794"));
+    }
+
+    #[test]
+    fn next_open_cuts_a_buy_the_gap_makes_unaffordable() {
+        let columns = candles(&[(100.0, 101.0, 99.0, 100.0), (125.0, 126.0, 124.0, 125.0)]);
+        let simulator = Simulator::new(
+            BacktestEngine::Exact,
+            &decimal("1000"),
+            None,
+            Arc::new(NextOpenFill),
+        );
+        let mut strategy = Recorder::default();
+        let mut context = context(Box::new(simulator));
+        advance(&mut context, &columns, 0, &mut strategy);
+        context.market_buy(&decimal("10")).unwrap();
+
+        advance(&mut context, &columns, 1, &mut strategy);
+        assert_eq!(context.position(), decimal("8"));
+        assert_eq!(context.balance(), decimal("0"));
+        assert!(context.orders().is_empty());
+        assert!(matches!(strategy.cancelled.as_slice(), [OrderType::MarketBuy]));
+    }
+}
@@ -1,15 +1,24 @@
-use crate::errors::AppResult;
-use crate::strategy::Strategy;
+use crate::errors::{AppError, AppResult};
+use crate::strategy::params::{ParameterValues, StrategyParameter, resolve_parameters};
+use crate::strategy::plugin::{
+    ABI_VERSION_SYMBOL, BUILD_HASH_SYMBOL, DEPENDENCIES_SYMBOL, PLUGIN_ABI_VERSION,
+    PLUGIN_BUILD_HASH, PLUGIN_DEPENDENCIES, PluginVTable, VTABLE_SYMBOL,
+};
+use crate::strategy::{Order, Strategy, StrategyContext, Trade};
 use libloading::{Library, Symbol};
 use std::{
-    ops::{Deref, DerefMut},
+    ffi::{CStr, c_char, c_void},
     path::PathBuf,
 };
 
-const PLUGIN_CREATE_FUNCTION_NAME: &'static str = "_plugin_create";
+type PluginAbiVersionFn = unsafe extern "C" fn() -> u32;
+type PluginBuildHashFn = unsafe extern "C" fn() -> u64;
+type PluginDependenciesFn = unsafe extern "C" fn() -> *const c_char;
+type PluginVTableFn = unsafe extern "C" fn() -> *const PluginVTable;
 
 pub struct StrategyHandle {
-    strategy: Box<dyn Strategy>,
+    vtable: PluginVTable,
+    parameters: Vec<StrategyParameter>,
     _lib: Library, // Keep the library loaded
 }
 
@@ -17,26 +26,119 @@ impl StrategyHandle {
     pub fn try_from_path(path: &PathBuf) -> AppResult<Self> {
         unsafe {
             let lib = Library::new(path)?;
-            let constructor: Symbol<fn() -> *mut dyn Strategy> =
-                lib.get(PLUGIN_CREATE_FUNCTION_NAME.as_bytes())?;
-            let strategy = Box::from_raw(constructor());
+
+            // Only the version function is assumed to exist before checking it
+            let abi_version: Symbol<PluginAbiVersionFn> =
+                lib.get(ABI_VERSION_SYMBOL.as_bytes()).map_err(|_| {
+                    AppError::Strategy(format!(
+                        "{} is not a thoth strategy plugin or was built by an older thoth, rebuild it",
+                        path.display()
+                    ))
+                })?;
+            let abi_version = abi_version();
+            if abi_version != PLUGIN_ABI_VERSION {
+                return Err(AppError::Strategy(format!(
+                    "Strategy plugin ABI version {} does not match thoth's {}, rebuild it",
+                    abi_version, PLUGIN_ABI_VERSION
+                )));
+            }
+
+            let build_hash: Symbol<PluginBuildHashFn> = lib.get(BUILD_HASH_SYMBOL.as_bytes())?;
+            let build_hash = build_hash();
+            if build_hash != PLUGIN_BUILD_HASH {
+                let dependencies: Symbol<PluginDependenciesFn> =
+                    lib.get(DEPENDENCIES_SYMBOL.as_bytes())?;
+                let dependencies = CStr::from_ptr(dependencies()).to_string_lossy();
+                if dependencies != PLUGIN_DEPENDENCIES {
+                    return Err(AppError::Strategy(format!(
+                        "Strategy plugin was built with {} but thoth uses {}, rebuild it",
+                        dependencies, PLUGIN_DEPENDENCIES
+                    )));
+                }
+                return Err(AppError::Strategy(format!(
+                    "Strategy plugin was built against a different thoth build or Rust toolchain \
+                     ({:016x}, expected {:016x}), rebuild it against this thoth",
+                    build_hash, PLUGIN_BUILD_HASH
+                )));
+            }
+
+            let vtable: Symbol<PluginVTableFn> = lib.get(VTABLE_SYMBOL.as_bytes())?;
+            let vtable = *vtable();
+            let parameters = vtable.parameters()?;
             Ok(Self {
-                strategy,
+                vtable,
+                parameters,
                 _lib: lib,
             })
         }
     }
+
+    pub fn parameters(&self) -> &[StrategyParameter] {
+        &self.parameters
+    }
+
+    pub fn resolve_parameters(&self, overrides: &ParameterValues) -> AppResult<ParameterValues> {
+        resolve_parameters(&self.parameters, overrides)
+    }
+
+    pub fn create(&self, parameters: &ParameterValues) -> AppResult<StrategyInstance<'_>> {
+        let parameters = self.resolve_parameters(parameters)?;
+        let strategy = self.vtable.create(&parameters)?;
+        Ok(StrategyInstance {
+            strategy,
+            vtable: &self.vtable, // Must not outlive the library
+        })
+    }
 }
 
-impl Deref for StrategyHandle {
-    type Target = Box<dyn Strategy>;
-    fn deref(&self) -> &Self::Target {
-        &self.strategy
+// A strategy living in a plugin, driven through its function table
+pub struct StrategyInstance<'a> {
+    strategy: *mut c_void,
+    vtable: &'a PluginVTable,
+}
+
+// Plugin strategies implement Strategy, which requires Send
+unsafe impl Send for StrategyInstance<'_> {}
+
+impl Strategy for StrategyInstance<'_> {
+    fn on_start(&mut self, context: &mut StrategyContext) -> AppResult<()> {
+        unsafe { self.vtable.on_start(self.strategy, context) }
+    }
+
+    fn tick(&mut self, context: &mut StrategyContext) -> AppResult<()> {
+        unsafe { self.vtable.tick(self.strategy, context) }
+    }
+
+    fn on_order_filled(
+        &mut self,
+        context: &mut StrategyContext,
+        order: &Order,
+        trade: &Trade,
+    ) -> AppResult<()> {
+        unsafe {
+            self.vtable
+                .on_order_filled(self.strategy, context, order, trade)
+        }
+    }
+
+    fn on_order_cancelled(
+        &mut self,
+        context: &mut StrategyContext,
+        order: &Order,
+    ) -> AppResult<()> {
+        unsafe {
+            self.vtable
+                .on_order_cancelled(self.strategy, context, order)
+        }
+    }
+
+    fn on_finish(&mut self, context: &mut StrategyContext) -> AppResult<()> {
+        unsafe { self.vtable.on_finish(self.strategy, context) }
     }
 }
 
-impl DerefMut for StrategyHandle {
-    fn deref_mut(&mut self) -> &mut Self::Target {
-        &mut self.strategy
+impl Drop for StrategyInstance<'_> {
+    fn drop(&mut self) {
+        unsafe { self.vtable.destroy(self.strategy) }
     }
 }
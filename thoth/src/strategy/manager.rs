@@ -1,5 +1,6 @@
 use crate::errors::AppResult;
 use crate::strategy::handle::StrategyHandle;
+use crate::strategy::plugin::{PLUGIN_DEPENDENCIES, locked_versions};
 use cargo_metadata::MetadataCommand;
 use std::{fs, path::PathBuf, process::Stdio};
 use toml_edit::{DocumentMut, array, table, value};
@@ -16,6 +17,8 @@ const MEMBER_LIB_RS: &str = include_str!(concat!(
     env!("CARGO_MANIFEST_DIR"),
     "/templates/strategy/member/src/lib.rs.template"
 ));
+// Lock file thoth was built with, written by build.rs
+const HOST_CARGO_LOCK: &str = include_str!(concat!(env!("OUT_DIR"), "/Cargo.lock"));
 pub const STRATEGY_WORKDIR_NAME: &str = "strategies";
 
 #[derive(Debug, Clone)]
@@ -99,6 +102,13 @@ impl StrategyManager {
     }
 
     pub async fn load_strategy(&self, strategy_name: &str) -> AppResult<StrategyHandle> {
+        let lib_path = self.build_strategy(strategy_name).await?;
+        StrategyHandle::try_from_path(&lib_path)
+    }
+
+    // Compiles the strategy and returns the path of its plugin library
+    pub async fn build_strategy(&self, strategy_name: &str) -> AppResult<PathBuf> {
+        self.pin_dependencies().await?;
         let metadata = MetadataCommand::new()
             .current_dir(&self.workspace_dir)
             .exec()?;
@@ -139,6 +149,120 @@ impl StrategyManager {
             return Err(format!("Library not found: {:?}", lib_path).into());
         }
 
-        StrategyHandle::try_from_path(&lib_path)
+        Ok(lib_path)
+    }
+
+    // Strategies share types with the server, so the shared crates are moved to the versions the
+    // server was built with. Whatever else the strategies depend on stays as they resolved it
+    async fn pin_dependencies(&self) -> AppResult<()> {
+        if HOST_CARGO_LOCK.is_empty() {
+            return Ok(());
+        }
+
+        let lock_path = self.workspace_dir.join("Cargo.lock");
+        let Ok(lock) = fs::read_to_string(&lock_path) else {
+            fs::write(lock_path, HOST_CARGO_LOCK)?;
+            return Ok(());
+        };
+        let locked = locked_versions(&lock);
+        if locked == PLUGIN_DEPENDENCIES {
+            return Ok(());
+        }
+
+        for (name, version) in crate_versions(PLUGIN_DEPENDENCIES) {
+            for (_, current) in crate_versions(&locked).filter(|(n, v)| *n == name && *v != version)
+            {
+                let output = tokio::process::Command::new("cargo")
+                    .args(["update", "--package"])
+                    .arg(format!("{}@{}", name, current))
+                    .args(["--precise", version])
+                    .current_dir(&self.workspace_dir)
+                    .output()
+                    .await?;
+                if !output.status.success() {
+                    let stderr = String::from_utf8_lossy(&output.stderr);
+                    return Err(format!(
+                        "Strategies must use {} {} as the server does: {}",
+                        name, version, stderr
+                    )
+                    .into());
+                }
+            }
+        }
+
+        // A strategy can still pull in a second version of a shared crate
+        let locked = locked_versions(&fs::read_to_string(&lock_path)?);
+        if locked != PLUGIN_DEPENDENCIES {
+            return Err(format!(
+                "Strategy dependencies {} do not match the server's {}",
+                locked, PLUGIN_DEPENDENCIES
+            )
+            .into());
+        }
+        Ok(())
+    }
+}
+
+// Splits a list in the form of PLUGIN_DEPENDENCIES into crate names and versions
+fn crate_versions(list: &str) -> impl Iterator<Item = (&str, &str)> {
+    list.split(", ").filter_map(|entry| entry.split_once(' '))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LOCK: &str = r#"
+version = 4
+
+[[package]]
+name = "uuid"
+version = "1.18.1"
+source = "registry+https://github.com/rust-lang/crates.io-index"
+
+[[package]]
+name = "bigdecimal"
+version = "0.4.8"
+dependencies = [
+ "num-bigint",
+]
+
+[[package]]
+name = "rand"
+version = "0.8.5"
+
+[[package]]
+name = "chrono"
+version = "0.4.41"
+
+[[package]]
+name = "chrono"
+version = "0.4.38"
+"#;
+
+    #[test]
+    fn lists_only_the_shared_crates_of_a_lock_file() {
+        // A second version of a shared crate is listed too, so it fails the comparison
+        assert_eq!(
+            locked_versions(LOCK),
+            "bigdecimal 0.4.8, chrono 0.4.38, chrono 0.4.41, uuid 1.18.1"
+        );
+        assert_eq!(locked_versions(""), "");
+    }
+
+    #[test]
+    fn splits_a_version_list_into_crates() {
+        let locked = locked_versions(LOCK);
+        let versions: Vec<(&str, &str)> = crate_versions(&locked).collect();
+        assert_eq!(
+            versions,
+            vec![
+                ("bigdecimal", "0.4.8"),
+                ("chrono", "0.4.38"),
+                ("chrono", "0.4.41"),
+                ("uuid", "1.18.1"),
+            ]
+        );
+        assert_eq!(crate_versions("").count(), 0);
     }
 }
@@ -0,0 +1,390 @@
+use crate::models::{Candle, Columns};
+use bigdecimal::{BigDecimal, RoundingMode, Signed, ToPrimitive, Zero};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+use std::iter::Sum;
+use std::ops::{Add, AddAssign, Div, Mul, Neg, Sub, SubAssign};
+use ts_rs::TS;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, TS)]
+#[serde(rename_all = "snake_case")]
+#[ts(export)]
+pub enum BacktestEngine {
+    // BigDecimal candles and accounting, exact to the market's precision
+    #[default]
+    Exact,
+    // Columnar f64 candles and floating point accounting, for long runs and parameter sweeps
+    Fast,
+}
+
+// A price or amount as the engine keeps it. Arithmetic mixing the two falls back to floating point,
+// so exact constants such as fee rates combine with fast values without converting them up front
+#[derive(Debug, Clone)]
+pub enum Number {
+    Exact(BigDecimal),
+    Fast(f64),
+}
+
+impl Number {
+    pub fn new(value: &BigDecimal, engine: BacktestEngine) -> Self {
+        match engine {
+            BacktestEngine::Exact => Number::Exact(value.clone()),
+            BacktestEngine::Fast => Number::Fast(value.to_f64().unwrap_or(f64::NAN)),
+        }
+    }
+
+    pub fn zero(engine: BacktestEngine) -> Self {
+        match engine {
+            BacktestEngine::Exact => Number::Exact(BigDecimal::zero()),
+            BacktestEngine::Fast => Number::Fast(0.0),
+        }
+    }
+
+    pub fn one(engine: BacktestEngine) -> Self {
+        match engine {
+            BacktestEngine::Exact => Number::Exact(BigDecimal::from(1)),
+            BacktestEngine::Fast => Number::Fast(1.0),
+        }
+    }
+
+    pub fn from_i64(value: i64, engine: BacktestEngine) -> Self {
+        match engine {
+            BacktestEngine::Exact => Number::Exact(BigDecimal::from(value)),
+            BacktestEngine::Fast => Number::Fast(value as f64),
+        }
+    }
+
+    pub fn engine(&self) -> BacktestEngine {
+        match self {
+            Number::Exact(_) => BacktestEngine::Exact,
+            Number::Fast(_) => BacktestEngine::Fast,
+        }
+    }
+
+    pub fn to_f64(&self) -> f64 {
+        match self {
+            Number::Exact(value) => value.to_f64().unwrap_or(f64::NAN),
+            Number::Fast(value) => *value,
+        }
+    }
+
+    pub fn to_decimal(&self) -> BigDecimal {
+        match self {
+            Number::Exact(value) => value.clone(),
+            Number::Fast(value) => fast_decimal(*value),
+        }
+    }
+
+    pub fn is_zero(&self) -> bool {
+        match self {
+            Number::Exact(value) => value.is_zero(),
+            Number::Fast(value) => *value == 0.0,
+        }
+    }
+
+    pub fn is_positive(&self) -> bool {
+        match self {
+            Number::Exact(value) => value.is_positive(),
+            Number::Fast(value) => *value > 0.0,
+        }
+    }
+
+    pub fn is_negative(&self) -> bool {
+        match self {
+            Number::Exact(value) => value.is_negative(),
+            Number::Fast(value) => *value < 0.0,
+        }
+    }
+
+    pub fn abs(&self) -> Self {
+        match self {
+            Number::Exact(value) => Number::Exact(value.abs()),
+            Number::Fast(value) => Number::Fast(value.abs()),
+        }
+    }
+
+    pub fn signum(&self) -> Self {
+        let engine = self.engine();
+        if self.is_positive() {
+            Number::one(engine)
+        } else if self.is_negative() {
+            -Number::one(engine)
+        } else {
+            Number::zero(engine)
+        }
+    }
+
+    // Rounds to a multiple of the step, as MarketPrecision does. Floating point values within
+    // rounding noise of a whole step count as on it, so 0.3 / 0.1 does not round down to 0.2
+    pub fn round(&self, step: &BigDecimal, mode: RoundingMode) -> Self {
+        if step.is_zero() {
+            return self.clone();
+        }
+
+        match self {
+            Number::Exact(value) => Number::Exact((value / step).with_scale_round(0, mode) * step),
+            Number::Fast(value) => {
+                let step = step.to_f64().unwrap_or(f64::NAN);
+                let steps = value / step;
+                let nearest = steps.round();
+                let steps = if (steps - nearest).abs() <= 1e-9 * nearest.abs().max(1.0) {
+                    nearest
+                } else {
+                    match mode {
+                        RoundingMode::Down => steps.trunc(),
+                        RoundingMode::Up => steps.abs().ceil().copysign(steps),
+                        RoundingMode::Floor => steps.floor(),
+                        RoundingMode::Ceiling => steps.ceil(),
+                        _ => nearest,
+                    }
+                };
+                // Dividing by the inverse of steps like 0.01 lands on the double nearest the decimal
+                let inverse = 1.0 / step;
+                if inverse >= 1.0 && (inverse - inverse.round()).abs() <= 1e-9 * inverse {
+                    Number::Fast(steps / inverse.round())
+                } else {
+                    Number::Fast(steps * step)
+                }
+            }
+        }
+    }
+}
+
+// Keeps the 15 significant digits an f64 reliably holds, which also drops representation noise
+// such as the trailing 4 of 0.1 + 0.2. The digits are read from the formatted value, since
+// scaling tiny or huge values by a power of ten overflows
+fn fast_decimal(value: f64) -> BigDecimal {
+    if value == 0.0 || !value.is_finite() {
+        return BigDecimal::zero();
+    }
+
+    format!("{:.14e}", value)
+        .parse::<BigDecimal>()
+        .map(|value| value.normalized())
+        .unwrap_or_default()
+}
+
+impl Default for Number {
+    fn default() -> Self {
+        Number::zero(BacktestEngine::default())
+    }
+}
+
+impl From<BigDecimal> for Number {
+    fn from(value: BigDecimal) -> Self {
+        Number::Exact(value)
+    }
+}
+
+impl From<&BigDecimal> for Number {
+    fn from(value: &BigDecimal) -> Self {
+        Number::Exact(value.clone())
+    }
+}
+
+impl From<i64> for Number {
+    fn from(value: i64) -> Self {
+        Number::Exact(BigDecimal::from(value))
+    }
+}
+
+impl From<Number> for BigDecimal {
+    fn from(value: Number) -> Self {
+        match value {
+            Number::Exact(value) => value,
+            Number::Fast(value) => fast_decimal(value),
+        }
+    }
+}
+
+impl std::fmt::Display for Number {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Number::Exact(value) => value.fmt(f),
+            Number::Fast(value) => value.fmt(f),
+        }
+    }
+}
+
+impl Ord for Number {
+    fn cmp(&self, other: &Self) -> Ordering {
+        match (self, other) {
+            (Number::Exact(a), Number::Exact(b)) => a.cmp(b),
+            _ => self.to_f64().total_cmp(&other.to_f64()),
+        }
+    }
+}
+
+impl PartialOrd for Number {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for Number {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Number {}
+
+macro_rules! arithmetic {
+    ($trait:ident, $method:ident, $op:tt) => {
+        impl $trait<&Number> for &Number {
+            type Output = Number;
+
+            fn $method(self, other: &Number) -> Number {
+                match (self, other) {
+                    (Number::Exact(a), Number::Exact(b)) => Number::Exact(a $op b),
+                    _ => Number::Fast(self.to_f64() $op other.to_f64()),
+                }
+            }
+        }
+
+        impl $trait<Number> for &Number {
+            type Output = Number;
+
+            fn $method(self, other: Number) -> Number {
+                match (self, other) {
+                    (Number::Exact(a), Number::Exact(b)) => Number::Exact(a $op b),
+                    (a, b) => Number::Fast(a.to_f64() $op b.to_f64()),
+                }
+            }
+        }
+
+        impl $trait<&Number> for Number {
+            type Output = Number;
+
+            fn $method(self, other: &Number) -> Number {
+                match (self, other) {
+                    (Number::Exact(a), Number::Exact(b)) => Number::Exact(a $op b),
+                    (a, b) => Number::Fast(a.to_f64() $op b.to_f64()),
+                }
+            }
+        }
+
+        impl $trait<Number> for Number {
+            type Output = Number;
+
+            fn $method(self, other: Number) -> Number {
+                match (self, other) {
+                    (Number::Exact(a), Number::Exact(b)) => Number::Exact(a $op b),
+                    (a, b) => Number::Fast(a.to_f64() $op b.to_f64()),
+                }
+            }
+        }
+    };
+}
+
+arithmetic!(Add, add, +);
+arithmetic!(Sub, sub, -);
+arithmetic!(Mul, mul, *);
+arithmetic!(Div, div, /);
+
+impl AddAssign<&Number> for Number {
+    fn add_assign(&mut self, other: &Number) {
+        match (&mut *self, other) {
+            (Number::Exact(a), Number::Exact(b)) => *a += b,
+            _ => *self = Number::Fast(self.to_f64() + other.to_f64()),
+        }
+    }
+}
+
+impl AddAssign<Number> for Number {
+    fn add_assign(&mut self, other: Number) {
+        *self += &other;
+    }
+}
+
+impl SubAssign<&Number> for Number {
+    fn sub_assign(&mut self, other: &Number) {
+        match (&mut *self, other) {
+            (Number::Exact(a), Number::Exact(b)) => *a -= b,
+            _ => *self = Number::Fast(self.to_f64() - other.to_f64()),
+        }
+    }
+}
+
+impl SubAssign<Number> for Number {
+    fn sub_assign(&mut self, other: Number) {
+        *self -= &other;
+    }
+}
+
+impl Neg for Number {
+    type Output = Number;
+
+    fn neg(self) -> Number {
+        match self {
+            Number::Exact(value) => Number::Exact(-value),
+            Number::Fast(value) => Number::Fast(-value),
+        }
+    }
+}
+
+impl Neg for &Number {
+    type Output = Number;
+
+    fn neg(self) -> Number {
+        match self {
+            Number::Exact(value) => Number::Exact(-value),
+            Number::Fast(value) => Number::Fast(-value),
+        }
+    }
+}
+
+// Sums stay in the engine of their values, an empty sum is an exact zero
+impl Sum for Number {
+    fn sum<I: Iterator<Item = Number>>(iter: I) -> Self {
+        iter.reduce(|total, value| total + value)
+            .unwrap_or_default()
+    }
+}
+
+impl<'a> Sum<&'a Number> for Number {
+    fn sum<I: Iterator<Item = &'a Number>>(mut iter: I) -> Self {
+        let Some(first) = iter.next() else {
+            return Number::default();
+        };
+        iter.fold(first.clone(), |total, value| total + value)
+    }
+}
+
+// A candle as the engine sees it, exact when built from a Candle and fast when read from columns
+#[derive(Debug, Clone)]
+pub struct Bar {
+    pub timestamp: DateTime<Utc>,
+    pub open: Number,
+    pub high: Number,
+    pub low: Number,
+    pub close: Number,
+    pub volume: Number,
+}
+
+impl Bar {
+    pub fn from_columns(columns: &Columns, index: usize) -> Self {
+        Self {
+            timestamp: columns.timestamps[index],
+            open: Number::Fast(columns.open[index]),
+            high: Number::Fast(columns.high[index]),
+            low: Number::Fast(columns.low[index]),
+            close: Number::Fast(columns.close[index]),
+            volume: Number::Fast(columns.volume[index]),
+        }
+    }
+}
+
+impl From<&Candle> for Bar {
+    fn from(candle: &Candle) -> Self {
+        Self {
+            timestamp: candle.timestamp,
+            open: Number::from(&candle.open),
+            high: Number::from(&candle.high),
+            low: Number::from(&candle.low),
+            close: Number::from(&candle.close),
+            volume: Number::from(&candle.volume),
+        }
+    }
+}
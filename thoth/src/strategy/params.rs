@@ -0,0 +1,181 @@
+use crate::errors::{AppError, AppResult};
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use ts_rs::TS;
+
+pub type ParameterValues = BTreeMap<String, Value>;
+
+#[derive(Debug, Clone, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub struct StrategyParameter {
+    pub name: String,
+    pub default: Value,
+    #[ts(optional)]
+    pub min: Option<f64>,
+    #[ts(optional)]
+    pub max: Option<f64>,
+    #[ts(optional)]
+    pub step: Option<f64>,
+}
+
+impl StrategyParameter {
+    pub fn validate(&self, value: &Value) -> AppResult<()> {
+        let number = value
+            .as_f64()
+            .or_else(|| value.as_str().and_then(|s| s.parse().ok()));
+        let Some(number) = number else {
+            return Ok(());
+        };
+
+        if let Some(min) = self.min
+            && number < min
+        {
+            return Err(AppError::BadRequest(format!(
+                "Parameter {} must be at least {}",
+                self.name, min
+            )));
+        }
+
+        if let Some(max) = self.max
+            && number > max
+        {
+            return Err(AppError::BadRequest(format!(
+                "Parameter {} must be at most {}",
+                self.name, max
+            )));
+        }
+
+        Ok(())
+    }
+}
+
+pub fn resolve_parameters(
+    schema: &[StrategyParameter],
+    overrides: &ParameterValues,
+) -> AppResult<ParameterValues> {
+    for name in overrides.keys() {
+        if !schema.iter().any(|p| &p.name == name) {
+            return Err(unknown_parameter(name));
+        }
+    }
+
+    let mut values = ParameterValues::new();
+    for parameter in schema {
+        let value = overrides
+            .get(&parameter.name)
+            .cloned()
+            .unwrap_or_else(|| parameter.default.clone());
+        parameter.validate(&value)?;
+        values.insert(parameter.name.clone(), value);
+    }
+
+    Ok(values)
+}
+
+pub fn unknown_parameter(name: &str) -> AppError {
+    AppError::BadRequest(format!("Unknown strategy parameter: {}", name))
+}
+
+#[doc(hidden)]
+pub fn parameter_value(value: impl Into<Value>) -> Value {
+    value.into()
+}
+
+#[doc(hidden)]
+pub fn describe_parameter<T: Serialize>(
+    name: &str,
+    value: &T,
+    min: Option<f64>,
+    max: Option<f64>,
+    step: Option<f64>,
+) -> AppResult<StrategyParameter> {
+    let default = serde_json::to_value(value).map_err(|e| {
+        AppError::Strategy(format!("Failed to serialize parameter {}: {}", name, e))
+    })?;
+
+    Ok(StrategyParameter {
+        name: name.to_string(),
+        default,
+        min,
+        max,
+        step,
+    })
+}
+
+#[doc(hidden)]
+pub fn set_parameter<T: DeserializeOwned>(
+    field: &mut T,
+    name: &str,
+    value: &Value,
+) -> AppResult<()> {
+    *field = serde_json::from_value(value.clone())
+        .map_err(|e| AppError::Strategy(format!("Invalid value for parameter {}: {}", name, e)))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn schema() -> Vec<StrategyParameter> {
+        vec![
+            describe_parameter("period", &14, Some(2.0), Some(50.0), Some(1.0)).unwrap(),
+            describe_parameter("threshold", &"0.5", None, Some(1.0), None).unwrap(),
+            describe_parameter("label", &"fast", None, None, None).unwrap(),
+        ]
+    }
+
+    fn rejection(overrides: ParameterValues) -> String {
+        match resolve_parameters(&schema(), &overrides) {
+            Err(AppError::BadRequest(message)) => message,
+            other => panic!("expected a bad request, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn fills_in_defaults_around_overrides() {
+        let overrides = ParameterValues::from([("period".to_string(), json!(20))]);
+        let values = resolve_parameters(&schema(), &overrides).unwrap();
+        assert_eq!(
+            values,
+            ParameterValues::from([
+                ("label".to_string(), json!("fast")),
+                ("period".to_string(), json!(20)),
+                ("threshold".to_string(), json!("0.5")),
+            ])
+        );
+    }
+
+    #[test]
+    fn rejects_unknown_and_out_of_range_values() {
+        assert_eq!(
+            rejection(ParameterValues::from([("length".to_string(), json!(3))])),
+            "Unknown strategy parameter: length"
+        );
+        assert_eq!(
+            rejection(ParameterValues::from([("period".to_string(), json!(1))])),
+            "Parameter period must be at least 2"
+        );
+        // Decimal parameters are checked through their string form
+        assert_eq!(
+            rejection(ParameterValues::from([(
+                "threshold".to_string(),
+                json!("1.5")
+            )])),
+            "Parameter threshold must be at most 1"
+        );
+    }
+
+    #[test]
+    fn sets_fields_from_values_of_their_type() {
+        let mut period = 14usize;
+        set_parameter(&mut period, "period", &json!(30)).unwrap();
+        assert_eq!(period, 30);
+
+        let error = set_parameter(&mut period, "period", &json!("thirty")).unwrap_err();
+        assert!(matches!(error, AppError::Strategy(_)));
+        assert_eq!(period, 30);
+    }
+}
@@ -0,0 +1,382 @@
+use super::params::{ParameterValues, StrategyParameter};
+use super::{Order, Strategy, StrategyContext, Trade};
+use crate::errors::{AppError, AppResult};
+use crate::utils::catch_panic;
+use bigdecimal::BigDecimal;
+use chrono::{DateTime, Utc};
+use std::ffi::{CStr, c_char, c_void};
+use std::mem::{ManuallyDrop, align_of, size_of};
+use uuid::Uuid;
+
+// Bumped whenever PluginVTable or the values passed through it change shape
+pub const PLUGIN_ABI_VERSION: u32 = 1;
+
+// Strategies still receive the context, orders and trades as Rust types, so the plugin must be
+// compiled from the same thoth sources, toolchain and shared dependency versions as the host. The
+// hash covers all three, plus the layout of the shared types
+pub const PLUGIN_BUILD_HASH: u64 = build_hash();
+
+// Locked versions of the crates whose types are shared with the plugin
+pub const PLUGIN_DEPENDENCIES: &str = env!("THOTH_DEPENDENCIES");
+
+const PLUGIN_DEPENDENCIES_C: &CStr =
+    match CStr::from_bytes_with_nul(concat!(env!("THOTH_DEPENDENCIES"), "\0").as_bytes()) {
+        Ok(dependencies) => dependencies,
+        Err(_) => panic!("THOTH_DEPENDENCIES must not contain a nul byte"),
+    };
+
+include!("shared_crates.rs");
+
+pub(crate) const ABI_VERSION_SYMBOL: &str = "_thoth_plugin_abi_version";
+pub(crate) const BUILD_HASH_SYMBOL: &str = "_thoth_plugin_build_hash";
+pub(crate) const DEPENDENCIES_SYMBOL: &str = "_thoth_plugin_dependencies";
+pub(crate) const VTABLE_SYMBOL: &str = "_thoth_plugin_vtable";
+
+const STATUS_OK: u32 = 0;
+const STATUS_NOT_FOUND: u32 = 1;
+const STATUS_BAD_REQUEST: u32 = 2;
+const STATUS_STRATEGY: u32 = 3;
+const STATUS_INTERNAL: u32 = 4;
+const STATUS_PANIC: u32 = 5;
+
+const fn fnv(mut hash: u64, bytes: &[u8]) -> u64 {
+    let mut i = 0;
+    while i < bytes.len() {
+        hash ^= bytes[i] as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+        i += 1;
+    }
+    hash
+}
+
+const fn build_hash() -> u64 {
+    let layouts = [
+        size_of::<StrategyContext>(),
+        align_of::<StrategyContext>(),
+        size_of::<Order>(),
+        size_of::<Trade>(),
+        size_of::<AppError>(),
+        size_of::<BigDecimal>(),
+        size_of::<DateTime<Utc>>(),
+        size_of::<Uuid>(),
+        size_of::<serde_json::Value>(),
+    ];
+
+    let mut hash = fnv(0xcbf29ce484222325, env!("THOTH_BUILD_ID").as_bytes());
+    let mut i = 0;
+    while i < layouts.len() {
+        hash = fnv(hash, &(layouts[i] as u64).to_le_bytes());
+        i += 1;
+    }
+    hash
+}
+
+pub fn plugin_dependencies() -> *const c_char {
+    PLUGIN_DEPENDENCIES_C.as_ptr()
+}
+
+// Implemented by #[strategy] so the plugin can describe and build its strategy
+pub trait StrategyPlugin: Strategy + Sized {
+    fn parameters() -> AppResult<Vec<StrategyParameter>>;
+
+    fn create(parameters: &ParameterValues) -> AppResult<Self>;
+}
+
+// A string allocated by the plugin, handed back to it to be freed
+#[repr(C)]
+pub struct PluginString {
+    ptr: *mut u8,
+    len: usize,
+    capacity: usize,
+}
+
+impl PluginString {
+    fn new(value: String) -> Self {
+        let mut value = ManuallyDrop::new(value);
+        Self {
+            ptr: value.as_mut_ptr(),
+            len: value.len(),
+            capacity: value.capacity(),
+        }
+    }
+
+    fn empty() -> Self {
+        Self {
+            ptr: std::ptr::null_mut(),
+            len: 0,
+            capacity: 0,
+        }
+    }
+}
+
+#[repr(C)]
+pub struct PluginStatus {
+    code: u32,
+    message: PluginString,
+}
+
+impl PluginStatus {
+    fn ok() -> Self {
+        Self {
+            code: STATUS_OK,
+            message: PluginString::empty(),
+        }
+    }
+
+    fn error(err: AppError) -> Self {
+        let (code, message) = match err {
+            AppError::NotFound(message) => (STATUS_NOT_FOUND, message),
+            AppError::BadRequest(message) => (STATUS_BAD_REQUEST, message),
+            AppError::Strategy(message) => (STATUS_STRATEGY, message),
+            AppError::Internal(message) => (STATUS_INTERNAL, message),
+            err => (STATUS_INTERNAL, err.to_string()),
+        };
+        Self {
+            code,
+            message: PluginString::new(message),
+        }
+    }
+}
+
+// Function table a plugin exports for its strategy type. Its calls only pass C types and opaque
+// pointers, but those point at Rust types, so it is no stable C ABI and PLUGIN_BUILD_HASH has to
+// match. Panics are caught before they cross. Allocations do cross: context methods a
+// strategy calls run in the plugin, so e.g. indicators boxed by `register_indicator` are freed by
+// the host. Neither side sets a global allocator, so both use the system one
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct PluginVTable {
+    parameters: unsafe extern "C" fn(output: *mut PluginString) -> PluginStatus,
+    create: unsafe extern "C" fn(
+        parameters: *const u8,
+        len: usize,
+        output: *mut *mut c_void,
+    ) -> PluginStatus,
+    on_start: unsafe extern "C" fn(strategy: *mut c_void, context: *mut c_void) -> PluginStatus,
+    tick: unsafe extern "C" fn(strategy: *mut c_void, context: *mut c_void) -> PluginStatus,
+    on_order_filled: unsafe extern "C" fn(
+        strategy: *mut c_void,
+        context: *mut c_void,
+        order: *const c_void,
+        trade: *const c_void,
+    ) -> PluginStatus,
+    on_order_cancelled: unsafe extern "C" fn(
+        strategy: *mut c_void,
+        context: *mut c_void,
+        order: *const c_void,
+    ) -> PluginStatus,
+    on_finish: unsafe extern "C" fn(strategy: *mut c_void, context: *mut c_void) -> PluginStatus,
+    destroy: unsafe extern "C" fn(strategy: *mut c_void),
+    free_string: unsafe extern "C" fn(value: PluginString),
+}
+
+impl PluginVTable {
+    pub const fn new<S: StrategyPlugin>() -> Self {
+        Self {
+            parameters: parameters::<S>,
+            create: create::<S>,
+            on_start: on_start::<S>,
+            tick: tick::<S>,
+            on_order_filled: on_order_filled::<S>,
+            on_order_cancelled: on_order_cancelled::<S>,
+            on_finish: on_finish::<S>,
+            destroy: destroy::<S>,
+            free_string,
+        }
+    }
+
+    fn take_string(&self, value: PluginString) -> String {
+        let text = if value.ptr.is_null() {
+            String::new()
+        } else {
+            let bytes = unsafe { std::slice::from_raw_parts(value.ptr, value.len) };
+            String::from_utf8_lossy(bytes).into_owned()
+        };
+        unsafe { (self.free_string)(value) };
+        text
+    }
+
+    fn check(&self, status: PluginStatus) -> AppResult<()> {
+        let code = status.code;
+        let message = self.take_string(status.message);
+        match code {
+            STATUS_OK => Ok(()),
+            STATUS_NOT_FOUND => Err(AppError::NotFound(message)),
+            STATUS_BAD_REQUEST => Err(AppError::BadRequest(message)),
+            STATUS_STRATEGY => Err(AppError::Strategy(message)),
+            STATUS_INTERNAL => Err(AppError::Internal(message)),
+            STATUS_PANIC => Err(AppError::Strategy(format!(
+                "Strategy panicked: {}",
+                message
+            ))),
+            code => Err(AppError::Internal(format!(
+                "Strategy plugin returned unknown status {}: {}",
+                code, message
+            ))),
+        }
+    }
+
+    pub(crate) fn parameters(&self) -> AppResult<Vec<StrategyParameter>> {
+        let mut output = PluginString::empty();
+        let status = unsafe { (self.parameters)(&mut output) };
+        let output = self.take_string(output);
+        self.check(status)?;
+        Ok(serde_json::from_str(&output)?)
+    }
+
+    pub(crate) fn create(&self, parameters: &ParameterValues) -> AppResult<*mut c_void> {
+        let parameters = serde_json::to_vec(parameters)?;
+        let mut strategy = std::ptr::null_mut();
+        let status = unsafe { (self.create)(parameters.as_ptr(), parameters.len(), &mut strategy) };
+        self.check(status)?;
+        Ok(strategy)
+    }
+
+    // Safety for the calls below: `strategy` comes from `create` of this table and is still alive
+    pub(crate) unsafe fn on_start(
+        &self,
+        strategy: *mut c_void,
+        context: &mut StrategyContext,
+    ) -> AppResult<()> {
+        self.check(unsafe { (self.on_start)(strategy, context_ptr(context)) })
+    }
+
+    pub(crate) unsafe fn tick(
+        &self,
+        strategy: *mut c_void,
+        context: &mut StrategyContext,
+    ) -> AppResult<()> {
+        self.check(unsafe { (self.tick)(strategy, context_ptr(context)) })
+    }
+
+    pub(crate) unsafe fn on_order_filled(
+        &self,
+        strategy: *mut c_void,
+        context: &mut StrategyContext,
+        order: &Order,
+        trade: &Trade,
+    ) -> AppResult<()> {
+        let order = (order as *const Order).cast();
+        let trade = (trade as *const Trade).cast();
+        self.check(unsafe { (self.on_order_filled)(strategy, context_ptr(context), order, trade) })
+    }
+
+    pub(crate) unsafe fn on_order_cancelled(
+        &self,
+        strategy: *mut c_void,
+        context: &mut StrategyContext,
+        order: &Order,
+    ) -> AppResult<()> {
+        let order = (order as *const Order).cast();
+        self.check(unsafe { (self.on_order_cancelled)(strategy, context_ptr(context), order) })
+    }
+
+    pub(crate) unsafe fn on_finish(
+        &self,
+        strategy: *mut c_void,
+        context: &mut StrategyContext,
+    ) -> AppResult<()> {
+        self.check(unsafe { (self.on_finish)(strategy, context_ptr(context)) })
+    }
+
+    pub(crate) unsafe fn destroy(&self, strategy: *mut c_void) {
+        unsafe { (self.destroy)(strategy) }
+    }
+}
+
+fn context_ptr(context: &mut StrategyContext) -> *mut c_void {
+    (context as *mut StrategyContext).cast()
+}
+
+// Panics must not unwind into the host, which may be built with a different panic runtime
+fn guard(f: impl FnOnce() -> AppResult<()>) -> PluginStatus {
+    match catch_panic(f) {
+        Ok(Ok(())) => PluginStatus::ok(),
+        Ok(Err(err)) => PluginStatus::error(err),
+        Err(report) => PluginStatus {
+            code: STATUS_PANIC,
+            message: PluginString::new(report),
+        },
+    }
+}
+
+unsafe extern "C" fn parameters<S: StrategyPlugin>(output: *mut PluginString) -> PluginStatus {
+    guard(|| {
+        let parameters = serde_json::to_string(&S::parameters()?)?;
+        unsafe { output.write(PluginString::new(parameters)) };
+        Ok(())
+    })
+}
+
+unsafe extern "C" fn create<S: StrategyPlugin>(
+    parameters: *const u8,
+    len: usize,
+    output: *mut *mut c_void,
+) -> PluginStatus {
+    guard(|| {
+        let parameters = unsafe { std::slice::from_raw_parts(parameters, len) };
+        let parameters: ParameterValues = serde_json::from_slice(parameters)?;
+        let strategy = Box::new(S::create(&parameters)?);
+        unsafe { *output = Box::into_raw(strategy).cast() };
+        Ok(())
+    })
+}
+
+unsafe extern "C" fn on_start<S: StrategyPlugin>(
+    strategy: *mut c_void,
+    context: *mut c_void,
+) -> PluginStatus {
+    guard(|| unsafe { (*strategy.cast::<S>()).on_start(&mut *context.cast::<StrategyContext>()) })
+}
+
+unsafe extern "C" fn tick<S: StrategyPlugin>(
+    strategy: *mut c_void,
+    context: *mut c_void,
+) -> PluginStatus {
+    guard(|| unsafe { (*strategy.cast::<S>()).tick(&mut *context.cast::<StrategyContext>()) })
+}
+
+unsafe extern "C" fn on_order_filled<S: StrategyPlugin>(
+    strategy: *mut c_void,
+    context: *mut c_void,
+    order: *const c_void,
+    trade: *const c_void,
+) -> PluginStatus {
+    guard(|| unsafe {
+        (*strategy.cast::<S>()).on_order_filled(
+            &mut *context.cast::<StrategyContext>(),
+            &*order.cast::<Order>(),
+            &*trade.cast::<Trade>(),
+        )
+    })
+}
+
+unsafe extern "C" fn on_order_cancelled<S: StrategyPlugin>(
+    strategy: *mut c_void,
+    context: *mut c_void,
+    order: *const c_void,
+) -> PluginStatus {
+    guard(|| unsafe {
+        (*strategy.cast::<S>()).on_order_cancelled(
+            &mut *context.cast::<StrategyContext>(),
+            &*order.cast::<Order>(),
+        )
+    })
+}
+
+unsafe extern "C" fn on_finish<S: StrategyPlugin>(
+    strategy: *mut c_void,
+    context: *mut c_void,
+) -> PluginStatus {
+    guard(|| unsafe { (*strategy.cast::<S>()).on_finish(&mut *context.cast::<StrategyContext>()) })
+}
+
+unsafe extern "C" fn destroy<S: StrategyPlugin>(strategy: *mut c_void) {
+    let _ = catch_panic(|| unsafe { drop(Box::from_raw(strategy.cast::<S>())) });
+}
+
+unsafe extern "C" fn free_string(value: PluginString) {
+    if !value.ptr.is_null() {
+        drop(unsafe { String::from_raw_parts(value.ptr, value.len, value.capacity) });
+    }
+}
@@ -0,0 +1,453 @@
+use super::{BacktestEngine, Number, OrderType};
+use crate::errors::{AppError, AppResult};
+use bigdecimal::{BigDecimal, Zero};
+use chrono::{DateTime, NaiveDate, TimeDelta, Utc, serde::ts_milliseconds};
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use ts_rs::TS;
+
+// Violations logged per run, later ones still reject their orders
+pub const MAX_RISK_EVENTS: usize = 1000;
+
+// Checked before every order a strategy places, outside of the strategy's control. Limits left
+// unset are not enforced
+#[derive(Debug, Clone, Default, Serialize, Deserialize, TS)]
+#[serde(default)]
+#[ts(export)]
+pub struct RiskLimits {
+    // Largest base amount a symbol's position may grow to
+    #[ts(optional, type = "string")]
+    pub max_position: Option<BigDecimal>,
+    // Largest quote value of a single order
+    #[ts(optional, type = "string")]
+    pub max_order_notional: Option<BigDecimal>,
+    #[ts(optional)]
+    pub max_orders_per_minute: Option<usize>,
+    // Loss from the equity at the first candle of the UTC day, after which only orders reducing a
+    // position are accepted
+    #[ts(optional, type = "string")]
+    pub max_daily_loss: Option<BigDecimal>,
+    // Rejects every order. Paper sessions can also engage and release it while they run
+    pub kill_switch: bool,
+    // Fails the run at the first violation instead of only rejecting the order
+    pub halt_on_violation: bool,
+}
+
+impl RiskLimits {
+    pub fn validate(&self) -> AppResult<()> {
+        let positive = [
+            ("Max position", &self.max_position),
+            ("Max order notional", &self.max_order_notional),
+            ("Max daily loss", &self.max_daily_loss),
+        ];
+        for (name, limit) in positive {
+            if limit
+                .as_ref()
+                .is_some_and(|limit| limit <= &BigDecimal::zero())
+            {
+                return Err(AppError::BadRequest(format!("{} must be positive", name)));
+            }
+        }
+        if self.max_orders_per_minute == Some(0) {
+            return Err(AppError::BadRequest(
+                "Max orders per minute must be positive".to_string(),
+            ));
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, TS)]
+#[serde(rename_all = "snake_case")]
+#[ts(export)]
+pub enum RiskRule {
+    KillSwitch,
+    MaxOrdersPerMinute,
+    MaxOrderNotional,
+    MaxPosition,
+    MaxDailyLoss,
+}
+
+// An order rejected by the risk limits
+#[derive(Debug, Clone, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub struct RiskEvent {
+    #[serde(with = "ts_milliseconds")]
+    #[ts(type = "number")]
+    pub timestamp: DateTime<Utc>,
+    pub rule: RiskRule,
+    pub symbol: String,
+    pub order_type: OrderType,
+    #[ts(type = "string")]
+    pub price: BigDecimal,
+    #[ts(type = "string")]
+    pub amount: BigDecimal,
+    pub message: String,
+    // Whether the violation stopped the run
+    pub halted: bool,
+}
+
+// An order as the risk limits see it, priced at its limit or the last close. `position` is the
+// base amount held, including what pending sells hold back, and `pending` the amount of pending
+// orders on the same side
+pub(crate) struct RiskOrder<'o> {
+    pub(crate) symbol: &'o str,
+    pub(crate) order_type: OrderType,
+    pub(crate) price: &'o Number,
+    pub(crate) amount: &'o Number,
+    pub(crate) position: Number,
+    pub(crate) pending: Number,
+}
+
+#[derive(Debug, Default)]
+pub(crate) struct RiskManager {
+    enabled: bool,
+    limits: RiskLimits,
+    max_position: Option<Number>,
+    max_order_notional: Option<Number>,
+    max_daily_loss: Option<Number>,
+    // Shared with whoever may engage it while the strategy runs
+    kill_switch: Arc<AtomicBool>,
+    // Times of the orders accepted within the last minute
+    accepted: VecDeque<DateTime<Utc>>,
+    day: Option<NaiveDate>,
+    day_start_equity: Number,
+    events: Vec<RiskEvent>,
+    halted: Option<String>,
+}
+
+impl RiskManager {
+    pub(crate) fn new(limits: RiskLimits, engine: BacktestEngine) -> Self {
+        let number =
+            |limit: &Option<BigDecimal>| limit.as_ref().map(|limit| Number::new(limit, engine));
+        Self {
+            enabled: limits.max_position.is_some()
+                || limits.max_order_notional.is_some()
+                || limits.max_orders_per_minute.is_some()
+                || limits.max_daily_loss.is_some(),
+            max_position: number(&limits.max_position),
+            max_order_notional: number(&limits.max_order_notional),
+            max_daily_loss: number(&limits.max_daily_loss),
+            kill_switch: Arc::new(AtomicBool::new(limits.kill_switch)),
+            limits,
+            ..Self::default()
+        }
+    }
+
+    pub(crate) fn enabled(&self) -> bool {
+        self.enabled || self.kill_switch.load(Ordering::Relaxed)
+    }
+
+    // Replaces the kill switch set by the limits with one engaged from outside the run
+    pub(crate) fn share_kill_switch(&mut self, kill_switch: Arc<AtomicBool>) {
+        self.kill_switch = kill_switch;
+    }
+
+    // Called with every new timestamp, so the daily loss counts from the day's first candle
+    pub(crate) fn start_bar(&mut self, timestamp: DateTime<Utc>, equity: impl FnOnce() -> Number) {
+        if self.max_daily_loss.is_none() || self.day == Some(timestamp.date_naive()) {
+            return;
+        }
+        self.day = Some(timestamp.date_naive());
+        self.day_start_equity = equity();
+    }
+
+    pub(crate) fn check(
+        &mut self,
+        timestamp: DateTime<Utc>,
+        order: RiskOrder,
+        equity: impl FnOnce() -> Number,
+    ) -> AppResult<()> {
+        if let Some(reason) = &self.halted {
+            return Err(AppError::Strategy(format!(
+                "Trading halted by risk limit: {}",
+                reason
+            )));
+        }
+
+        match self.violation(timestamp, &order, equity) {
+            Some((rule, message)) => {
+                let halted = self.limits.halt_on_violation;
+                if halted {
+                    self.halted = Some(message.clone());
+                }
+                if self.events.len() < MAX_RISK_EVENTS {
+                    self.events.push(RiskEvent {
+                        timestamp,
+                        rule,
+                        symbol: order.symbol.to_string(),
+                        order_type: order.order_type,
+                        price: order.price.to_decimal(),
+                        amount: order.amount.to_decimal(),
+                        message: message.clone(),
+                        halted,
+                    });
+                }
+                Err(AppError::Strategy(format!(
+                    "Order rejected by risk limit: {}",
+                    message
+                )))
+            }
+            None => {
+                if self.limits.max_orders_per_minute.is_some() {
+                    self.accepted.push_back(timestamp);
+                }
+                Ok(())
+            }
+        }
+    }
+
+    fn violation(
+        &mut self,
+        timestamp: DateTime<Utc>,
+        order: &RiskOrder,
+        equity: impl FnOnce() -> Number,
+    ) -> Option<(RiskRule, String)> {
+        if self.kill_switch.load(Ordering::Relaxed) {
+            return Some((RiskRule::KillSwitch, "Kill switch is engaged".to_string()));
+        }
+
+        if let Some(max_orders) = self.limits.max_orders_per_minute {
+            let window_start = timestamp - TimeDelta::minutes(1);
+            while self.accepted.front().is_some_and(|t| *t <= window_start) {
+                self.accepted.pop_front();
+            }
+            if self.accepted.len() >= max_orders {
+                return Some((
+                    RiskRule::MaxOrdersPerMinute,
+                    format!("More than {} orders per minute", max_orders),
+                ));
+            }
+        }
+
+        if let Some(max_notional) = &self.max_order_notional {
+            let notional = order.price * order.amount;
+            if &notional > max_notional {
+                return Some((
+                    RiskRule::MaxOrderNotional,
+                    format!(
+                        "Order value {} exceeds the maximum of {}",
+                        notional, max_notional
+                    ),
+                ));
+            }
+        }
+
+        // Position and loss limits never hold back an order that shrinks the position, so a
+        // strategy can always get out
+        let projected = if order.order_type.is_buy() {
+            &order.position + &order.pending + order.amount
+        } else {
+            &order.position - &order.pending - order.amount
+        };
+        let reducing = projected.abs() <= order.position.abs();
+
+        if let Some(max_position) = &self.max_position
+            && !reducing
+            && &projected.abs() > max_position
+        {
+            return Some((
+                RiskRule::MaxPosition,
+                format!(
+                    "Position in {} could reach {}, above the maximum of {}",
+                    order.symbol, projected, max_position
+                ),
+            ));
+        }
+
+        if let Some(max_loss) = &self.max_daily_loss
+            && !reducing
+        {
+            let loss = &self.day_start_equity - equity();
+            if &loss >= max_loss {
+                return Some((
+                    RiskRule::MaxDailyLoss,
+                    format!("Daily loss of {} reached the maximum of {}", loss, max_loss),
+                ));
+            }
+        }
+
+        None
+    }
+
+    pub(crate) fn halted(&self) -> Option<&str> {
+        self.halted.as_deref()
+    }
+
+    pub(crate) fn take_events(&mut self) -> Vec<RiskEvent> {
+        std::mem::take(&mut self.events)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn at(seconds: i64) -> DateTime<Utc> {
+        Utc.timestamp_opt(seconds, 0).unwrap()
+    }
+
+    fn manager(limits: RiskLimits) -> RiskManager {
+        RiskManager::new(limits, BacktestEngine::Fast)
+    }
+
+    // Checks a market order at 100 against the limits, returning the rule that rejected it
+    fn check(
+        risk: &mut RiskManager,
+        seconds: i64,
+        buy: bool,
+        amount: f64,
+        position: f64,
+        equity: f64,
+    ) -> Option<RiskRule> {
+        let price = Number::Fast(100.0);
+        let amount = Number::Fast(amount);
+        let order = RiskOrder {
+            symbol: "BTC/USDT",
+            order_type: if buy {
+                OrderType::MarketBuy
+            } else {
+                OrderType::MarketSell
+            },
+            price: &price,
+            amount: &amount,
+            position: Number::Fast(position),
+            pending: Number::Fast(0.0),
+        };
+        match risk.check(at(seconds), order, || Number::Fast(equity)) {
+            Ok(()) => None,
+            Err(_) => risk.take_events().pop().map(|event| event.rule),
+        }
+    }
+
+    #[test]
+    fn kill_switch_rejects_every_order_until_released() {
+        let mut risk = manager(RiskLimits {
+            kill_switch: true,
+            ..RiskLimits::default()
+        });
+        assert!(risk.enabled());
+        assert_eq!(
+            check(&mut risk, 0, true, 1.0, 0.0, 0.0),
+            Some(RiskRule::KillSwitch)
+        );
+        assert_eq!(
+            check(&mut risk, 0, false, 1.0, 1.0, 0.0),
+            Some(RiskRule::KillSwitch)
+        );
+
+        let kill_switch = Arc::new(AtomicBool::new(false));
+        risk.share_kill_switch(kill_switch.clone());
+        assert!(!risk.enabled());
+        assert_eq!(check(&mut risk, 0, true, 1.0, 0.0, 0.0), None);
+        kill_switch.store(true, Ordering::Relaxed);
+        assert_eq!(
+            check(&mut risk, 0, true, 1.0, 0.0, 0.0),
+            Some(RiskRule::KillSwitch)
+        );
+    }
+
+    #[test]
+    fn max_orders_per_minute_counts_accepted_orders_in_a_sliding_minute() {
+        let mut risk = manager(RiskLimits {
+            max_orders_per_minute: Some(2),
+            ..RiskLimits::default()
+        });
+        assert_eq!(check(&mut risk, 0, true, 1.0, 0.0, 0.0), None);
+        assert_eq!(check(&mut risk, 10, true, 1.0, 0.0, 0.0), None);
+        assert_eq!(
+            check(&mut risk, 20, false, 1.0, 2.0, 0.0),
+            Some(RiskRule::MaxOrdersPerMinute)
+        );
+        assert_eq!(check(&mut risk, 60, true, 1.0, 0.0, 0.0), None);
+    }
+
+    #[test]
+    fn max_order_notional_rejects_larger_orders() {
+        let mut risk = manager(RiskLimits {
+            max_order_notional: Some(BigDecimal::from(1000)),
+            ..RiskLimits::default()
+        });
+        assert_eq!(check(&mut risk, 0, true, 10.0, 0.0, 0.0), None);
+        assert_eq!(
+            check(&mut risk, 0, true, 10.5, 0.0, 0.0),
+            Some(RiskRule::MaxOrderNotional)
+        );
+        assert_eq!(
+            check(&mut risk, 0, false, 10.5, 20.0, 0.0),
+            Some(RiskRule::MaxOrderNotional)
+        );
+    }
+
+    #[test]
+    fn max_position_lets_reducing_orders_through() {
+        let mut risk = manager(RiskLimits {
+            max_position: Some(BigDecimal::from(5)),
+            ..RiskLimits::default()
+        });
+        assert_eq!(check(&mut risk, 0, true, 1.0, 4.0, 0.0), None);
+        assert_eq!(
+            check(&mut risk, 0, true, 2.0, 4.0, 0.0),
+            Some(RiskRule::MaxPosition)
+        );
+        assert_eq!(
+            check(&mut risk, 0, false, 6.0, 0.0, 0.0),
+            Some(RiskRule::MaxPosition)
+        );
+        // Already above the limit, selling part of it is still allowed but flipping short is not
+        assert_eq!(check(&mut risk, 0, false, 2.0, 8.0, 0.0), None);
+        assert_eq!(
+            check(&mut risk, 0, false, 20.0, 8.0, 0.0),
+            Some(RiskRule::MaxPosition)
+        );
+    }
+
+    #[test]
+    fn max_daily_loss_lets_reducing_orders_through_until_the_next_day() {
+        let mut risk = manager(RiskLimits {
+            max_daily_loss: Some(BigDecimal::from(100)),
+            ..RiskLimits::default()
+        });
+        risk.start_bar(at(0), || Number::Fast(1000.0));
+        assert_eq!(check(&mut risk, 60, true, 1.0, 0.0, 950.0), None);
+        assert_eq!(
+            check(&mut risk, 60, true, 1.0, 0.0, 900.0),
+            Some(RiskRule::MaxDailyLoss)
+        );
+        assert_eq!(check(&mut risk, 60, false, 1.0, 2.0, 900.0), None);
+
+        // A new day counts from its first candle's equity
+        risk.start_bar(at(86400), || Number::Fast(900.0));
+        assert_eq!(check(&mut risk, 86400, true, 1.0, 0.0, 900.0), None);
+    }
+
+    #[test]
+    fn halting_rejects_every_later_order() {
+        let mut risk = manager(RiskLimits {
+            max_order_notional: Some(BigDecimal::from(1000)),
+            halt_on_violation: true,
+            ..RiskLimits::default()
+        });
+        assert_eq!(
+            check(&mut risk, 0, true, 20.0, 0.0, 0.0),
+            Some(RiskRule::MaxOrderNotional)
+        );
+        assert!(risk.halted().is_some());
+
+        let price = Number::Fast(100.0);
+        let amount = Number::Fast(1.0);
+        let order = RiskOrder {
+            symbol: "BTC/USDT",
+            order_type: OrderType::MarketBuy,
+            price: &price,
+            amount: &amount,
+            position: Number::Fast(0.0),
+            pending: Number::Fast(0.0),
+        };
+        assert!(risk.check(at(0), order, || Number::Fast(0.0)).is_err());
+        assert!(risk.take_events().is_empty());
+    }
+}
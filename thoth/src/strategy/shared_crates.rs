@@ -0,0 +1,25 @@
+// Included by build.rs and strategy/plugin.rs, so the server and the plugins it loads compare the
+// same list
+
+// Crates whose types strategies share with the server, and so must resolve to the same versions
+const SHARED_CRATES: &[&str] = &["bigdecimal", "num-bigint", "chrono", "uuid", "serde_json"];
+
+// Versions of the shared crates in a Cargo.lock, in the form of PLUGIN_DEPENDENCIES
+pub(crate) fn locked_versions(lock: &str) -> String {
+    let mut versions = Vec::new();
+    for package in lock.split("[[package]]").skip(1) {
+        let field = |key: &str| {
+            package.lines().find_map(|line| {
+                let value = line.strip_prefix(key)?.trim().strip_prefix('=')?;
+                Some(value.trim().trim_matches('"').to_string())
+            })
+        };
+        if let (Some(name), Some(version)) = (field("name"), field("version"))
+            && SHARED_CRATES.contains(&name.as_str())
+        {
+            versions.push(format!("{} {}", name, version));
+        }
+    }
+    versions.sort();
+    versions.join(", ")
+}
@@ -1,5 +1,20 @@
 pub mod backtest;
+mod config;
+mod control;
 pub mod fetch_candles;
+pub mod optimization;
+pub mod paper;
+mod queue;
+pub mod worker;
 
-pub use backtest::{BacktestStatistic, BacktestStatus, BacktestTask};
+pub use backtest::{
+    BacktestConfig, BacktestData, BacktestStatistic, BacktestStatus, BacktestTask, SymbolConfig,
+    SymbolStatistic,
+};
+pub use config::TaskConfig;
+pub use control::TaskControl;
 pub use fetch_candles::{FetchCandlesResult, FetchCandlesStatus, FetchCandlesTask};
+pub use optimization::{OptimizationResult, OptimizationTask};
+pub use paper::{PaperAccount, PaperOrder, PaperSession, PaperStatus};
+pub use queue::{QueuedTask, TaskQueue};
+pub use worker::StrategyTask;
@@ -1,23 +1,53 @@
-use crate::errors::AppResult;
-use crate::exchange::ccxt::CCXT;
-use crate::models::{Candle, MarketPrecision, Timeframe};
-use crate::services::candles::get_candles;
+use crate::errors::{AppError, AppResult};
+use crate::models::{Candle, CandleColumns, MarginConfig, MarketPrecision, Timeframe, TradingFees};
+use crate::services::candles::{get_candle_columns, get_candles};
 use crate::services::tasks::save_backtest_task;
-use crate::strategy::{StrategyContext, StrategyHandle, StrategyManager, Trade, TradeType};
+use crate::strategy::{
+    BacktestEngine, Bar, Broker, FillModelConfig, Number, ParameterValues, RiskEvent, RiskLimits,
+    Simulator, Strategy, StrategyContext, StrategyHandle, Trade,
+};
+use crate::tasks::worker::{StrategyTask, WorkerJob};
+use crate::tasks::{QueuedTask, TaskControl};
+use crate::utils::unbounded_f32;
 use bigdecimal::{BigDecimal, RoundingMode, ToPrimitive, Zero};
 use chrono::{DateTime, Utc, serde::ts_milliseconds, serde::ts_milliseconds_option};
 use serde::{Deserialize, Serialize};
-use sqlx::PgPool;
+use sqlx::{PgPool, Type};
+use std::ops::Range;
 use tokio::sync::broadcast;
 use ts_rs::TS;
 use uuid::Uuid;
 
 const BACKTEST_BROADCAST_INTERVAL: usize = 100;
+pub const DEFAULT_INITIAL_CAPITAL: i64 = 10000;
+
+fn default_initial_capital() -> BigDecimal {
+    BigDecimal::from(DEFAULT_INITIAL_CAPITAL)
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub struct EquityPoint {
+    #[serde(with = "ts_milliseconds")]
+    #[ts(type = "number")]
+    pub timestamp: DateTime<Utc>,
+    #[ts(type = "string")]
+    pub equity: BigDecimal,
+    #[ts(type = "string")]
+    pub cash: BigDecimal,
+    #[ts(type = "string")]
+    pub position_value: BigDecimal,
+    #[ts(type = "string")]
+    pub drawdown: BigDecimal,
+    pub drawdown_percent: f32,
+}
 
 #[derive(Debug, Clone, Serialize, Deserialize, TS)]
 #[ts(export)]
 pub struct BacktestStatistic {
     pub trades: Vec<Trade>,
+    #[serde(default)]
+    pub equity_curve: Vec<EquityPoint>,
     #[ts(type = "string")]
     pub initial_capital: BigDecimal,
     #[ts(type = "string")]
@@ -34,7 +64,11 @@ pub struct BacktestStatistic {
     pub gross_profit: BigDecimal,
     #[ts(type = "string")]
     pub gross_loss: BigDecimal,
+    #[serde(with = "unbounded_f32")]
+    #[ts(type = "number")]
     pub profit_factor: f32,
+    #[serde(with = "unbounded_f32")]
+    #[ts(type = "number")]
     pub sharpe_ratio: f32,
     pub total_trades: usize,
     pub buy_trades: usize,
@@ -50,10 +84,37 @@ pub struct BacktestStatistic {
     pub largest_win: BigDecimal,
     #[ts(type = "string")]
     pub largest_loss: BigDecimal,
+    #[serde(default)]
+    pub symbols: Vec<SymbolStatistic>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, TS)]
+#[derive(Debug, Clone, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub struct SymbolStatistic {
+    pub symbol: String,
+    pub total_trades: usize,
+    pub buy_trades: usize,
+    pub sell_trades: usize,
+    pub winning_trades: usize,
+    pub losing_trades: usize,
+    pub win_rate: f32,
+    #[ts(type = "string")]
+    pub gross_profit: BigDecimal,
+    #[ts(type = "string")]
+    pub gross_loss: BigDecimal,
+    #[ts(type = "string")]
+    pub net_profit: BigDecimal,
+    #[ts(type = "string")]
+    pub largest_win: BigDecimal,
+    #[ts(type = "string")]
+    pub largest_loss: BigDecimal,
+    #[ts(type = "string")]
+    pub position: BigDecimal,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Type, TS)]
 #[serde(rename_all = "snake_case")]
+#[sqlx(type_name = "text", rename_all = "snake_case")]
 #[ts(export)]
 pub enum BacktestStatus {
     Pending,
@@ -61,6 +122,398 @@ pub enum BacktestStatus {
     Running,
     Completed,
     Failed,
+    Cancelled,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub struct SymbolConfig {
+    pub symbol: String,
+    pub precision: MarketPrecision,
+    #[serde(default)]
+    pub fees: TradingFees,
+}
+
+// Candles of every traded symbol aligned on the union of their timestamps, the primary symbol first.
+// Exact data holds both the candles and their columns, fast data only the columns. Additional
+// timeframes hold each symbol's candles per timeframe, in the same symbol order
+#[derive(Debug, Clone)]
+pub struct BacktestData {
+    pub engine: BacktestEngine,
+    pub symbols: Vec<String>,
+    pub timestamps: Vec<DateTime<Utc>>,
+    pub candles: Vec<Vec<Candle>>,
+    pub columns: Vec<CandleColumns>,
+    pub timeframes: Vec<Timeframe>,
+    pub timeframe_candles: Vec<Vec<Vec<Candle>>>,
+}
+
+impl BacktestData {
+    pub fn new(symbols: Vec<String>, candles: Vec<Vec<Candle>>) -> Self {
+        let columns = candles
+            .iter()
+            .map(|candles| CandleColumns::from(candles.as_slice()))
+            .collect();
+        Self::build(BacktestEngine::Exact, symbols, candles, columns)
+    }
+
+    pub fn from_columns(symbols: Vec<String>, columns: Vec<CandleColumns>) -> Self {
+        let candles = vec![Vec::new(); symbols.len()];
+        Self::build(BacktestEngine::Fast, symbols, candles, columns)
+    }
+
+    fn build(
+        engine: BacktestEngine,
+        symbols: Vec<String>,
+        candles: Vec<Vec<Candle>>,
+        columns: Vec<CandleColumns>,
+    ) -> Self {
+        let mut timestamps: Vec<DateTime<Utc>> = columns
+            .iter()
+            .flat_map(|c| c.timestamps.iter().copied())
+            .collect();
+        timestamps.sort_unstable();
+        timestamps.dedup();
+
+        Self {
+            engine,
+            timeframe_candles: vec![Vec::new(); symbols.len()],
+            symbols,
+            timestamps,
+            candles,
+            columns,
+            timeframes: Vec::new(),
+        }
+    }
+
+    // Drops the candles, leaving the columns a fast backtest runs on
+    pub fn into_fast(self) -> Self {
+        Self {
+            engine: BacktestEngine::Fast,
+            candles: vec![Vec::new(); self.symbols.len()],
+            ..self
+        }
+    }
+
+    pub fn with_timeframes(
+        mut self,
+        timeframes: Vec<Timeframe>,
+        timeframe_candles: Vec<Vec<Vec<Candle>>>,
+    ) -> Self {
+        self.timeframes = timeframes;
+        self.timeframe_candles = timeframe_candles;
+        self
+    }
+
+    pub fn len(&self) -> usize {
+        self.timestamps.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.timestamps.is_empty()
+    }
+
+    pub fn slice(&self, range: Range<usize>) -> Self {
+        let timestamps = self.timestamps[range].to_vec();
+        let within = |candles: &Vec<Candle>| match (timestamps.first(), timestamps.last()) {
+            (Some(start), Some(end)) => {
+                let from = candles.partition_point(|c| &c.timestamp < start);
+                let to = candles.partition_point(|c| &c.timestamp <= end);
+                candles[from..to].to_vec()
+            }
+            _ => Vec::new(),
+        };
+        let columns_within = |columns: &CandleColumns| match (timestamps.first(), timestamps.last())
+        {
+            (Some(start), Some(end)) => {
+                let from = columns.timestamps.partition_point(|t| t < start);
+                let to = columns.timestamps.partition_point(|t| t <= end);
+                CandleColumns::from(columns.view(from..to))
+            }
+            _ => CandleColumns::default(),
+        };
+
+        Self {
+            engine: self.engine,
+            symbols: self.symbols.clone(),
+            candles: self.candles.iter().map(within).collect(),
+            columns: self.columns.iter().map(columns_within).collect(),
+            timeframes: self.timeframes.clone(),
+            timeframe_candles: self
+                .timeframe_candles
+                .iter()
+                .map(|candles| candles.iter().map(within).collect())
+                .collect(),
+            timestamps,
+        }
+    }
+
+    // Trades of unknown symbols, such as ones saved before multi-symbol support, go to the primary
+    fn symbol_index(&self, symbol: &str) -> usize {
+        self.symbols.iter().position(|s| s == symbol).unwrap_or(0)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub struct BacktestConfig {
+    pub exchange: String,
+    pub symbol: String,
+    pub timeframe: Timeframe,
+    pub precision: MarketPrecision,
+    #[serde(default)]
+    pub fees: TradingFees,
+    #[serde(default = "default_initial_capital")]
+    #[ts(type = "string")]
+    pub initial_capital: BigDecimal,
+    #[serde(default)]
+    pub quote_currency: String,
+    #[serde(default, with = "ts_milliseconds_option")]
+    #[ts(optional, type = "number")]
+    pub start: Option<DateTime<Utc>>,
+    #[serde(default, with = "ts_milliseconds_option")]
+    #[ts(optional, type = "number")]
+    pub end: Option<DateTime<Utc>>,
+    #[serde(default)]
+    #[ts(optional)]
+    pub equity_points: Option<usize>,
+    #[serde(default)]
+    #[ts(optional)]
+    pub margin: Option<MarginConfig>,
+    #[serde(default)]
+    pub fill_model: FillModelConfig,
+    #[serde(default)]
+    #[ts(optional)]
+    pub risk: Option<RiskLimits>,
+    #[serde(default)]
+    pub additional_symbols: Vec<SymbolConfig>,
+    #[serde(default)]
+    pub additional_timeframes: Vec<Timeframe>,
+    #[serde(default)]
+    pub engine: BacktestEngine,
+}
+
+impl BacktestConfig {
+    pub fn symbols(&self) -> Vec<SymbolConfig> {
+        let primary = SymbolConfig {
+            symbol: self.symbol.clone(),
+            precision: self.precision.clone(),
+            fees: self.fees.clone(),
+        };
+        std::iter::once(primary)
+            .chain(self.additional_symbols.iter().cloned())
+            .collect()
+    }
+
+    pub fn simulator(&self) -> Box<dyn Broker> {
+        Box::new(Simulator::new(
+            self.engine,
+            &self.initial_capital,
+            self.margin.clone(),
+            self.fill_model.build(),
+        ))
+    }
+
+    pub async fn load_data(&self, db_pool: &PgPool) -> AppResult<BacktestData> {
+        let mut symbols = Vec::new();
+        let mut candles = Vec::new();
+        let mut columns = Vec::new();
+        let mut timeframe_candles = Vec::new();
+        for symbol in self.symbols() {
+            let loaded = match self.engine {
+                BacktestEngine::Exact => {
+                    let symbol_candles = get_candles(
+                        db_pool,
+                        &self.exchange,
+                        &symbol.symbol,
+                        self.timeframe,
+                        self.start,
+                        self.end,
+                    )
+                    .await?;
+                    let loaded = symbol_candles.len();
+                    candles.push(symbol_candles);
+                    loaded
+                }
+                BacktestEngine::Fast => {
+                    let symbol_columns = get_candle_columns(
+                        db_pool,
+                        &self.exchange,
+                        &symbol.symbol,
+                        self.timeframe,
+                        self.start,
+                        self.end,
+                    )
+                    .await?;
+                    let loaded = symbol_columns.len();
+                    columns.push(symbol_columns);
+                    loaded
+                }
+            };
+
+            if loaded == 0 {
+                return Err(format!("No candles available for {} backtest", symbol.symbol).into());
+            }
+
+            let mut by_timeframe = Vec::with_capacity(self.additional_timeframes.len());
+            for timeframe in &self.additional_timeframes {
+                by_timeframe.push(
+                    get_candles(
+                        db_pool,
+                        &self.exchange,
+                        &symbol.symbol,
+                        *timeframe,
+                        self.start,
+                        self.end,
+                    )
+                    .await?,
+                );
+            }
+
+            symbols.push(symbol.symbol);
+            timeframe_candles.push(by_timeframe);
+        }
+
+        let data = match self.engine {
+            BacktestEngine::Exact => BacktestData::new(symbols, candles),
+            BacktestEngine::Fast => BacktestData::from_columns(symbols, columns),
+        };
+        Ok(data.with_timeframes(self.additional_timeframes.clone(), timeframe_candles))
+    }
+
+    pub fn context<'a>(&self) -> AppResult<StrategyContext<'a>> {
+        let risk = self.risk.clone().unwrap_or_default();
+        StrategyContext::new(self.timeframe, self.engine, self.simulator(), risk)
+    }
+
+    pub fn run(
+        &self,
+        strategy: &mut dyn Strategy,
+        data: &BacktestData,
+        warmup: usize,
+        on_progress: impl FnMut(usize) -> AppResult<()>,
+    ) -> AppResult<BacktestStatistic> {
+        self.run_with_risk_events(strategy, data, warmup, &mut Vec::new(), on_progress)
+    }
+
+    // Orders rejected by the risk limits are added to risk_events, also when they halted the run
+    pub fn run_with_risk_events(
+        &self,
+        strategy: &mut dyn Strategy,
+        data: &BacktestData,
+        warmup: usize,
+        risk_events: &mut Vec<RiskEvent>,
+        on_progress: impl FnMut(usize) -> AppResult<()>,
+    ) -> AppResult<BacktestStatistic> {
+        if data.engine != self.engine {
+            return Err("Backtest data was loaded for a different engine".into());
+        }
+
+        let mut context = self.context()?;
+        let result = self.simulate(&mut context, strategy, data, warmup, on_progress);
+        risk_events.extend(context.take_risk_events());
+        result
+    }
+
+    fn simulate<'a>(
+        &self,
+        context: &mut StrategyContext<'a>,
+        strategy: &mut dyn Strategy,
+        data: &'a BacktestData,
+        warmup: usize,
+        mut on_progress: impl FnMut(usize) -> AppResult<()>,
+    ) -> AppResult<BacktestStatistic> {
+        for symbol in self.symbols() {
+            context.add_symbol(symbol.symbol, symbol.fees, symbol.precision)?;
+        }
+        if data.columns.len() != context.symbols.len()
+            || data.timeframe_candles.len() != context.symbols.len()
+        {
+            return Err("Backtest data does not match the configured symbols".into());
+        }
+        for (state, candles) in context.symbols.iter_mut().zip(&data.timeframe_candles) {
+            state.timeframes = data
+                .timeframes
+                .iter()
+                .zip(candles)
+                .map(|(timeframe, candles)| (*timeframe, &candles[..0]))
+                .collect();
+        }
+
+        // Only the warmup history is visible before the first tick
+        let warmup = warmup.min(data.len());
+        if let Some(timestamp) = warmup.checked_sub(1).map(|i| data.timestamps[i]) {
+            for (index, state) in context.symbols.iter_mut().enumerate() {
+                let columns = &data.columns[index];
+                let end = columns.timestamps.partition_point(|t| *t <= timestamp);
+                state.columns = columns.view(0..end);
+                state.candles = data.candles[index].get(..end).unwrap_or_default();
+            }
+            self.show_closed_candles(context, data, timestamp);
+            context.timestamp = Some(timestamp);
+        }
+        strategy.on_start(context)?;
+        context.after(strategy)?;
+
+        let mut ends: Vec<usize> = context.symbols.iter().map(|s| s.columns.len()).collect();
+        let mut interest = Vec::new();
+        for i in warmup..data.len() {
+            let timestamp = data.timestamps[i];
+            for (index, (state, end)) in context.symbols.iter_mut().zip(&mut ends).enumerate() {
+                let columns = &data.columns[index];
+                if columns.timestamps.get(*end) == Some(&timestamp) {
+                    *end += 1;
+                    state.columns = columns.view(0..*end);
+                    state.candles = data.candles[index].get(..*end).unwrap_or_default();
+                }
+            }
+            self.show_closed_candles(context, data, timestamp);
+
+            context.before(timestamp, strategy)?;
+            strategy.tick(context)?;
+            context.after(strategy)?;
+            if self.margin.is_some() {
+                interest.push(context.broker.accrued_interest());
+            }
+
+            on_progress(i)?;
+        }
+
+        context.end(strategy)?;
+        strategy.on_finish(context)?;
+
+        let mut statistic = BacktestStatistic::calculate(
+            self.initial_capital.clone(),
+            data,
+            warmup,
+            context.trades(),
+            &interest,
+        );
+        if let Some(max_points) = self.equity_points {
+            statistic.downsample_equity_curve(max_points);
+        }
+
+        Ok(statistic)
+    }
+
+    // Additional timeframes only expose candles that closed by the close of the current candle
+    fn show_closed_candles<'a>(
+        &self,
+        context: &mut StrategyContext<'a>,
+        data: &'a BacktestData,
+        timestamp: DateTime<Utc>,
+    ) {
+        let now = self.timeframe.close_time(timestamp);
+        for (state, candles) in context.symbols.iter_mut().zip(&data.timeframe_candles) {
+            for ((timeframe, visible), candles) in state.timeframes.iter_mut().zip(candles) {
+                let closed = candles[visible.len()..]
+                    .iter()
+                    .take_while(|c| timeframe.close_time(c.timestamp) <= now)
+                    .count();
+                *visible = &candles[..visible.len() + closed];
+            }
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, TS)]
@@ -70,14 +523,22 @@ pub struct BacktestTask {
     pub status: BacktestStatus,
     pub progress: f32,
     pub name: String,
-    pub exchange: String,
-    pub symbol: String,
-    pub timeframe: Timeframe,
-    pub precision: MarketPrecision,
+    #[serde(flatten)]
+    pub config: BacktestConfig,
+    #[serde(default)]
+    pub parameters: ParameterValues,
     #[ts(optional)]
     pub statistic: Option<BacktestStatistic>,
     #[ts(optional)]
     pub error_message: Option<String>,
+    #[serde(default)]
+    pub risk_events: Vec<RiskEvent>,
+    #[serde(default)]
+    #[ts(optional)]
+    pub timeout_secs: Option<u64>,
+    #[serde(default)]
+    #[ts(optional)]
+    pub queue_position: Option<usize>,
     #[serde(with = "ts_milliseconds")]
     #[ts(type = "number")]
     pub created_at: DateTime<Utc>,
@@ -96,44 +557,105 @@ pub struct BacktestTask {
 }
 
 impl BacktestTask {
-    pub fn broadcast(&self) {
-        if let Some(tx) = &self.event_tx {
-            let _ = tx.send(self.clone());
+    // Trades are served on their own by `/tasks/backtest/{id}/trades`
+    pub fn without_trades(&self) -> BacktestTask {
+        let mut task = self.clone();
+        if let Some(statistic) = &mut task.statistic {
+            statistic.trades = Vec::new();
         }
+        task
     }
 
-    pub async fn execute(
+    pub(crate) async fn execute_backtest(
         &mut self,
-        strategy_manager: &StrategyManager,
-        strategy_name: &str,
-        db_pool: PgPool,
-    ) {
-        let now = Utc::now();
-        self.status = BacktestStatus::Compiling;
-        self.started_at = Some(now);
-        self.updated_at = now;
-        self.broadcast();
+        db_pool: &PgPool,
+        strategy_handle: &StrategyHandle,
+        control: &TaskControl,
+    ) -> AppResult<BacktestStatistic> {
+        control.check()?;
+        let config = self.config.clone();
 
-        let mut strategy_handle = match strategy_manager.load_strategy(strategy_name).await {
-            Ok(handle) => handle,
-            Err(e) => {
-                let now = Utc::now();
-                self.status = BacktestStatus::Failed;
-                self.error_message = Some(format!("Failed to load strategy: {}", e));
-                self.completed_at = Some(now);
-                self.updated_at = now;
+        tracing::info!(
+            "Running backtest on {}/{} with timeframe {}",
+            config.exchange,
+            config.symbol,
+            config.timeframe
+        );
+
+        let data = config.load_data(db_pool).await?;
+        let total_candles = data.len();
+        control.check()?;
+
+        self.parameters = strategy_handle.resolve_parameters(&self.parameters)?;
+        let mut strategy = strategy_handle.create(&self.parameters)?;
+
+        let mut risk_events = Vec::new();
+        let result = config.run_with_risk_events(&mut strategy, &data, 0, &mut risk_events, |i| {
+            if i % BACKTEST_BROADCAST_INTERVAL == 0 {
+                let progress = 100.0 * ((i + 1) as f32) / (total_candles as f32);
+                self.progress = progress;
+                self.updated_at = Utc::now();
                 self.broadcast();
-                return;
             }
-        };
+            control.check()
+        });
+        self.risk_events = risk_events;
+        let backtest_stat = result?;
+
+        self.progress = 100.0;
+        self.updated_at = Utc::now();
+        self.broadcast();
+
+        Ok(backtest_stat)
+    }
+}
+
+impl StrategyTask for BacktestTask {
+    type Output = BacktestStatistic;
+
+    const KIND: &'static str = "Backtest";
+
+    fn into_job(self) -> WorkerJob {
+        WorkerJob::Backtest(Box::new(self))
+    }
+
+    fn from_job(job: WorkerJob) -> Option<Self> {
+        match job {
+            WorkerJob::Backtest(task) => Some(*task),
+            _ => None,
+        }
+    }
+
+    fn event_tx(&mut self) -> &mut Option<broadcast::Sender<Self>> {
+        &mut self.event_tx
+    }
+
+    fn timeout_secs(&self) -> Option<u64> {
+        self.timeout_secs
+    }
+
+    fn broadcast(&self) {
+        if let Some(tx) = &self.event_tx {
+            let _ = tx.send(self.clone());
+        }
+    }
 
+    fn start(&mut self, status: BacktestStatus) {
         let now = Utc::now();
-        self.status = BacktestStatus::Running;
+        self.status = status;
         self.started_at = Some(now);
         self.updated_at = now;
-        self.broadcast();
+    }
 
-        let result = self.execute_backtest(&db_pool, &mut strategy_handle).await;
+    fn fail(&mut self, message: String) {
+        let now = Utc::now();
+        self.status = BacktestStatus::Failed;
+        self.error_message = Some(message);
+        self.completed_at = Some(now);
+        self.updated_at = now;
+    }
+
+    fn complete(&mut self, result: AppResult<BacktestStatistic>) {
         let now = Utc::now();
         match result {
             Ok(statistic) => {
@@ -143,240 +665,339 @@ impl BacktestTask {
                 self.completed_at = Some(now);
                 self.updated_at = now;
             }
-            Err(e) => {
-                self.status = BacktestStatus::Failed;
-                self.error_message = Some(e.to_string());
+            Err(AppError::Cancelled(message)) => {
+                self.status = BacktestStatus::Cancelled;
+                self.error_message = Some(message);
                 self.completed_at = Some(now);
                 self.updated_at = now;
             }
+            Err(e) => self.fail(e.to_string()),
         };
-
-        self.broadcast();
-
-        save_backtest_task(&db_pool, self)
-            .await
-            .expect("Failed to save backtest task");
     }
 
-    async fn execute_backtest(
+    async fn run(
         &mut self,
         db_pool: &PgPool,
-        strategy_handle: &mut StrategyHandle,
+        strategy_handle: &StrategyHandle,
+        control: &TaskControl,
     ) -> AppResult<BacktestStatistic> {
-        let exchange = self.exchange.clone();
-        let symbol = self.symbol.clone();
-        let timeframe = self.timeframe;
+        self.execute_backtest(db_pool, strategy_handle, control)
+            .await
+    }
+}
 
-        tracing::info!(
-            "Running backtest on {}/{} with timeframe {}",
-            exchange,
-            symbol,
-            timeframe
-        );
+impl QueuedTask for BacktestTask {
+    fn id(&self) -> Uuid {
+        self.id
+    }
 
-        let all_candles = get_candles(db_pool, &exchange, &symbol, timeframe, None, None).await?;
-        let total_candles = all_candles.len();
-        if total_candles == 0 {
-            return Err("No candles available for backtest".into());
+    fn subscribe(&self) -> Option<broadcast::Receiver<Self>> {
+        self.event_tx.as_ref().map(broadcast::Sender::subscribe)
+    }
+
+    fn set_queue_position(&mut self, position: Option<usize>) {
+        if self.queue_position != position {
+            self.queue_position = position;
+            self.broadcast();
         }
+    }
 
-        let initial_capital = BigDecimal::from(10000);
-        let ccxt = CCXT::with_exchange(&exchange)?;
-        let fees = ccxt.fees(&symbol)?;
-        let precision = ccxt.precision(&symbol)?;
-        let mut context = StrategyContext::new(initial_capital.clone(), fees, precision)?;
+    fn cancel(&mut self, error: AppError) {
+        self.complete(Err(error));
+        self.broadcast();
+    }
 
-        for i in 0..all_candles.len() {
-            context.candles = &all_candles[0..=i];
+    async fn save(&self, db_pool: &PgPool) {
+        if let Err(e) = save_backtest_task(db_pool, self).await {
+            tracing::error!("Failed to save backtest task {}: {}", self.id, e);
+        }
+    }
+}
 
-            context.before()?;
-            strategy_handle.tick(&mut context)?;
-            context.after()?;
+// Running per-symbol accounting, kept in the engine's number type
+#[derive(Default)]
+struct Ledger {
+    engine: BacktestEngine,
+    balance: Number,
+    position: Number,
+    entry_value: Number,
+    entry_fees: Number,
+    buy_trades: usize,
+    sell_trades: usize,
+    closing_trades: usize,
+    winning_trades: usize,
+    losing_trades: usize,
+    gross_profit: Number,
+    gross_loss: Number,
+    largest_win: Number,
+    largest_loss: Number,
+}
 
-            if i % BACKTEST_BROADCAST_INTERVAL == 0 {
-                let progress = 100.0 * ((i + 1) as f32) / (total_candles as f32);
-                self.progress = progress;
-                self.updated_at = Utc::now();
-                self.broadcast();
+impl Ledger {
+    fn new(engine: BacktestEngine) -> Self {
+        let zero = Number::zero(engine);
+        Self {
+            engine,
+            balance: zero.clone(),
+            position: zero.clone(),
+            entry_value: zero.clone(),
+            entry_fees: zero.clone(),
+            gross_profit: zero.clone(),
+            gross_loss: zero.clone(),
+            largest_win: zero.clone(),
+            largest_loss: zero,
+            ..Self::default()
+        }
+    }
+
+    fn apply(&mut self, trade: &Trade) -> Trade {
+        let is_buy = trade.trade_type.is_buy();
+        let price = Number::new(&trade.price, self.engine);
+        let amount = Number::new(&trade.amount, self.engine);
+        let fee = Number::new(&trade.fee, self.engine);
+        let notional = &price * &amount;
+
+        if is_buy {
+            self.buy_trades += 1;
+            self.balance -= &notional + &fee;
+        } else {
+            self.sell_trades += 1;
+            self.balance += &notional - &fee;
+        }
+
+        let opening = self.position.is_zero() || self.position.is_positive() == is_buy;
+        if opening {
+            self.entry_value += &notional;
+            self.entry_fees += &fee;
+            if is_buy {
+                self.position += &amount;
+            } else {
+                self.position -= &amount;
             }
+            return trade.clone();
         }
 
-        context.end()?;
-        self.progress = 100.0;
-        self.updated_at = Utc::now();
-        self.broadcast();
+        // Reduce the open position, flipping to the opposite side with any remainder
+        let open_amount = self.position.abs();
+        let closed = amount.clone().min(open_amount.clone());
+        let closed_fee = &fee * &closed / &amount;
+        let entry_value = &self.entry_value * &closed / &open_amount;
+        let entry_fees = &self.entry_fees * &closed / &open_amount;
+        let gross = if self.position.is_positive() {
+            &closed * &price - &entry_value
+        } else {
+            &entry_value - &closed * &price
+        };
+        let profit = gross - entry_fees.clone() - &closed_fee;
 
-        let backtest_stat = Self::calculate_backtest_statistic(
-            initial_capital,
-            context.candles(),
-            context.trades(),
-        );
+        self.entry_value -= entry_value;
+        self.entry_fees -= entry_fees;
+        if is_buy {
+            self.position += &closed;
+        } else {
+            self.position -= &closed;
+        }
 
-        Ok(backtest_stat)
+        let remainder = &amount - &closed;
+        if remainder.is_positive() {
+            self.entry_value = &remainder * &price;
+            self.entry_fees = &fee - &closed_fee;
+            self.position = if is_buy { remainder } else { -remainder };
+        } else if self.position.is_zero() {
+            self.entry_value = Number::zero(self.engine);
+            self.entry_fees = Number::zero(self.engine);
+        }
+
+        self.closing_trades += 1;
+        if profit > Number::zero(self.engine) {
+            self.winning_trades += 1;
+            self.gross_profit += &profit;
+            if profit > self.largest_win {
+                self.largest_win = profit.clone();
+            }
+        } else if profit < Number::zero(self.engine) {
+            self.losing_trades += 1;
+            self.gross_loss += &profit;
+            if profit < self.largest_loss {
+                self.largest_loss = profit.clone();
+            }
+        }
+
+        Trade {
+            profit: Some(profit.into()),
+            ..trade.clone()
+        }
     }
 
-    fn calculate_backtest_statistic(
+    fn into_statistic(self, symbol: &str) -> SymbolStatistic {
+        SymbolStatistic {
+            symbol: symbol.to_string(),
+            total_trades: self.buy_trades + self.sell_trades,
+            buy_trades: self.buy_trades,
+            sell_trades: self.sell_trades,
+            winning_trades: self.winning_trades,
+            losing_trades: self.losing_trades,
+            win_rate: win_rate(self.winning_trades, self.closing_trades),
+            net_profit: BigDecimal::from(&self.gross_profit + &self.gross_loss)
+                .with_scale_round(2, RoundingMode::HalfUp),
+            gross_profit: self.gross_profit.into(),
+            gross_loss: self.gross_loss.into(),
+            largest_win: self.largest_win.into(),
+            largest_loss: self.largest_loss.into(),
+            position: self.position.into(),
+        }
+    }
+}
+
+fn win_rate(winning_trades: usize, closing_trades: usize) -> f32 {
+    if closing_trades > 0 {
+        (winning_trades as f32 / closing_trades as f32) * 100.0
+    } else {
+        0.0
+    }
+}
+
+impl BacktestStatistic {
+    // Statistics over the timestamps from start on, with one ledger per symbol sharing the cash.
+    // Interest holds what is owed but not yet paid at each of those timestamps, if anything
+    pub fn calculate(
         initial_capital: BigDecimal,
-        candles: &[Candle],
+        data: &BacktestData,
+        start: usize,
         trades: &[Trade],
+        interest: &[Number],
     ) -> BacktestStatistic {
-        let mut balance = initial_capital.clone();
-        let mut position = BigDecimal::zero();
-        let mut total_cost = BigDecimal::zero();
-        let mut max_equity = initial_capital.clone();
-        let mut max_drawdown = BigDecimal::zero();
-        let mut max_drawdown_percent = 0.0f32;
+        let engine = data.engine;
+        let timestamps = &data.timestamps[start.min(data.len())..];
+        let mut ledgers: Vec<Ledger> = data.symbols.iter().map(|_| Ledger::new(engine)).collect();
+        let mut cursors: Vec<usize> = data
+            .columns
+            .iter()
+            .map(|columns| match timestamps.first() {
+                Some(first) => columns.timestamps.partition_point(|t| t < first),
+                None => columns.len(),
+            })
+            .collect();
+        let mut last_bars: Vec<Option<Bar>> = vec![None; data.columns.len()];
 
-        let mut buy_trades = 0usize;
-        let mut sell_trades = 0usize;
-        let mut winning_trades = 0usize;
-        let mut losing_trades = 0usize;
-        let mut gross_profit = BigDecimal::zero();
-        let mut gross_loss = BigDecimal::zero();
-        let mut largest_win = BigDecimal::zero();
-        let mut largest_loss = BigDecimal::zero();
+        let initial = Number::new(&initial_capital, engine);
+        let mut max_equity = initial.clone();
+        let mut max_drawdown = Number::zero(engine);
+        let mut max_drawdown_percent = 0.0f32;
 
         let mut trades_iter = trades.iter().peekable();
         let mut trades_with_profit = Vec::with_capacity(trades.len());
+        let mut equity_curve = Vec::with_capacity(timestamps.len());
 
-        for candle in candles.iter() {
-            while let Some(trade) = trades_iter.peek() {
-                if trade.timestamp > candle.timestamp {
-                    break;
+        for (i, timestamp) in timestamps.iter().enumerate() {
+            for (index, (cursor, last)) in cursors.iter_mut().zip(&mut last_bars).enumerate() {
+                let columns = &data.columns[index];
+                if columns.timestamps.get(*cursor) == Some(timestamp) {
+                    *last = Some(match data.candles[index].get(*cursor) {
+                        Some(candle) => Bar::from(candle),
+                        None => Bar::from_columns(&columns.view(0..columns.len()), *cursor),
+                    });
+                    *cursor += 1;
                 }
+            }
 
-                let trade = trades_iter.next().unwrap();
-                let is_buy = matches!(trade.trade_type, TradeType::MarketBuy | TradeType::LimitBuy);
+            while let Some(trade) = trades_iter.next_if(|t| &t.timestamp <= timestamp) {
+                let index = data.symbol_index(&trade.symbol);
+                trades_with_profit.push(ledgers[index].apply(trade));
+            }
 
-                if is_buy {
-                    buy_trades += 1;
-                    let cost = &trade.price * &trade.amount + &trade.fee;
-                    total_cost += &cost;
-                    balance -= &cost;
-                    position += &trade.amount;
-                    trades_with_profit.push(trade.clone());
+            let mut cash = &initial + ledgers.iter().map(|l| &l.balance).sum::<Number>();
+            if let Some(interest) = interest.get(i) {
+                cash -= interest;
+            }
+            let mut best_value = cash.clone();
+            let mut worst_value = cash.clone();
+            let mut position_value = Number::zero(engine);
+            for (ledger, last) in ledgers.iter().zip(&last_bars) {
+                let Some(bar) = last else {
+                    continue;
+                };
+                // Symbols without a candle at this timestamp are valued at their last close
+                let (high, low) = if &bar.timestamp == timestamp {
+                    (&bar.high, &bar.low)
                 } else {
-                    sell_trades += 1;
-                    let proceeds = &trade.price * &trade.amount;
-                    let revenue = &proceeds - &trade.fee;
-                    let average_cost = if position.is_zero() {
-                        BigDecimal::zero()
-                    } else {
-                        &total_cost / &position
-                    };
-                    let profit = &revenue - (&average_cost * &trade.amount);
-
-                    position -= &trade.amount;
-                    balance += &revenue;
-
-                    if position.is_zero() {
-                        total_cost = BigDecimal::zero();
-                    } else {
-                        total_cost -= &average_cost * &trade.amount;
-                    }
-
-                    if profit > BigDecimal::zero() {
-                        winning_trades += 1;
-                        gross_profit += &profit;
-                        if profit > largest_win {
-                            largest_win = profit.clone();
-                        }
-                    } else if profit < BigDecimal::zero() {
-                        losing_trades += 1;
-                        gross_loss += &profit;
-                        if profit < largest_loss {
-                            largest_loss = profit.clone();
-                        }
-                    }
-
-                    trades_with_profit.push(Trade {
-                        timestamp: trade.timestamp,
-                        trade_type: trade.trade_type.clone(),
-                        price: trade.price.clone(),
-                        amount: trade.amount.clone(),
-                        fee: trade.fee.clone(),
-                        profit: Some(profit.clone()),
-                    });
+                    (&bar.close, &bar.close)
+                };
+                let high_value = &ledger.position * high;
+                let low_value = &ledger.position * low;
+                if ledger.position.is_negative() {
+                    best_value += low_value;
+                    worst_value += high_value;
+                } else {
+                    best_value += high_value;
+                    worst_value += low_value;
                 }
+                position_value += &ledger.position * &bar.close;
             }
-
-            let high_value = &position * &candle.high + &balance;
-            if high_value > max_equity {
-                max_equity = high_value;
+            if best_value > max_equity {
+                max_equity = best_value;
             }
 
-            let low_value = &position * &candle.low + &balance;
-            let drawdown = &max_equity - &low_value;
+            let drawdown = &max_equity - &worst_value;
+            let drawdown_percent = if max_equity.is_zero() {
+                0.0
+            } else {
+                (&drawdown / &max_equity).to_f64() as f32 * 100.0
+            };
             if drawdown > max_drawdown {
                 max_drawdown = drawdown.clone();
-                if !max_equity.is_zero() {
-                    max_drawdown_percent =
-                        (&drawdown / &max_equity).to_f32().unwrap_or(0.0) * 100.0;
-                }
+                max_drawdown_percent = drawdown_percent;
             }
-        }
 
-        while let Some(trade) = trades_iter.next() {
-            let is_buy = matches!(trade.trade_type, TradeType::MarketBuy | TradeType::LimitBuy);
-
-            if is_buy {
-                buy_trades += 1;
-                let cost = &trade.price * &trade.amount + &trade.fee;
-                total_cost += &cost;
-                balance -= &cost;
-                position += &trade.amount;
-                trades_with_profit.push(trade.clone());
-            } else {
-                sell_trades += 1;
-                let proceeds = &trade.price * &trade.amount;
-                let revenue = &proceeds - &trade.fee;
-                let average_cost = if position.is_zero() {
-                    BigDecimal::zero()
-                } else {
-                    &total_cost / &position
-                };
-                let profit = &revenue - (&average_cost * &trade.amount);
-
-                position -= &trade.amount;
-                balance += &revenue;
-
-                if position.is_zero() {
-                    total_cost = BigDecimal::zero();
-                } else {
-                    total_cost -= &average_cost * &trade.amount;
-                }
+            equity_curve.push(EquityPoint {
+                timestamp: *timestamp,
+                equity: (&position_value + &cash).into(),
+                cash: cash.into(),
+                position_value: position_value.into(),
+                drawdown: drawdown.into(),
+                drawdown_percent,
+            });
+        }
 
-                if profit > BigDecimal::zero() {
-                    winning_trades += 1;
-                    gross_profit += &profit;
-                    if profit > largest_win {
-                        largest_win = profit.clone();
-                    }
-                } else if profit < BigDecimal::zero() {
-                    losing_trades += 1;
-                    gross_loss += &profit;
-                    if profit < largest_loss {
-                        largest_loss = profit.clone();
-                    }
-                }
+        for trade in trades_iter {
+            let index = data.symbol_index(&trade.symbol);
+            trades_with_profit.push(ledgers[index].apply(trade));
+        }
 
-                trades_with_profit.push(Trade {
-                    timestamp: trade.timestamp,
-                    trade_type: trade.trade_type.clone(),
-                    price: trade.price.clone(),
-                    amount: trade.amount.clone(),
-                    fee: trade.fee.clone(),
-                    profit: Some(profit.clone()),
-                });
+        let mut total_cost = Number::zero(engine);
+        let mut buy_trades = 0;
+        let mut sell_trades = 0;
+        let mut closing_trades = 0;
+        let mut winning_trades = 0;
+        let mut losing_trades = 0;
+        let mut gross_profit = Number::zero(engine);
+        let mut gross_loss = Number::zero(engine);
+        let mut largest_win = Number::zero(engine);
+        let mut largest_loss = Number::zero(engine);
+        let mut symbols = Vec::with_capacity(ledgers.len());
+        for (symbol, ledger) in data.symbols.iter().zip(ledgers) {
+            total_cost += &ledger.entry_value + &ledger.entry_fees;
+            buy_trades += ledger.buy_trades;
+            sell_trades += ledger.sell_trades;
+            closing_trades += ledger.closing_trades;
+            winning_trades += ledger.winning_trades;
+            losing_trades += ledger.losing_trades;
+            gross_profit += &ledger.gross_profit;
+            gross_loss += &ledger.gross_loss;
+            if ledger.largest_win > largest_win {
+                largest_win = ledger.largest_win.clone();
+            }
+            if ledger.largest_loss < largest_loss {
+                largest_loss = ledger.largest_loss.clone();
             }
+            symbols.push(ledger.into_statistic(symbol));
         }
+        let total_cost = BigDecimal::from(total_cost);
+        let gross_profit = BigDecimal::from(gross_profit);
+        let gross_loss = BigDecimal::from(gross_loss);
 
         let total_trades = buy_trades + sell_trades;
-        let win_rate = if sell_trades > 0 {
-            (winning_trades as f32 / sell_trades as f32) * 100.0
-        } else {
-            0.0
-        };
+        let win_rate = win_rate(winning_trades, closing_trades);
 
         let avg_win = if winning_trades > 0 {
             (&gross_profit / BigDecimal::from(winning_trades as i64))
@@ -414,12 +1035,13 @@ impl BacktestTask {
 
         BacktestStatistic {
             trades: trades_with_profit,
+            equity_curve,
             initial_capital,
             total_cost,
             net_profit,
             return_percent,
-            max_equity,
-            max_drawdown,
+            max_equity: max_equity.into(),
+            max_drawdown: max_drawdown.into(),
             max_drawdown_percent,
             gross_profit,
             gross_loss,
@@ -433,11 +1055,33 @@ impl BacktestTask {
             win_rate,
             avg_win,
             avg_loss,
-            largest_win,
-            largest_loss,
+            largest_win: largest_win.into(),
+            largest_loss: largest_loss.into(),
+            symbols,
         }
     }
 
+    pub fn downsample_equity_curve(&mut self, max_points: usize) {
+        if max_points == 0 || self.equity_curve.len() <= max_points {
+            return;
+        }
+
+        // Keep the last point of each bucket along with the bucket's deepest drawdown
+        let bucket_size = self.equity_curve.len().div_ceil(max_points);
+        self.equity_curve = self
+            .equity_curve
+            .chunks(bucket_size)
+            .map(|bucket| {
+                let mut point = bucket[bucket.len() - 1].clone();
+                if let Some(deepest) = bucket.iter().max_by(|a, b| a.drawdown.cmp(&b.drawdown)) {
+                    point.drawdown = deepest.drawdown.clone();
+                    point.drawdown_percent = deepest.drawdown_percent;
+                }
+                point
+            })
+            .collect();
+    }
+
     fn calculate_sharpe_ratio(trades: &[Trade], initial_capital: &BigDecimal) -> f32 {
         if trades.is_empty() {
             return 0.0;
@@ -490,3 +1134,451 @@ impl BacktestTask {
         (mean_return / std_dev) as f32
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::strategy::{Order, TradeType};
+    use chrono::TimeZone;
+    use std::str::FromStr;
+
+    const HOUR_MS: i64 = 60 * 60 * 1000;
+
+    fn decimal(value: &str) -> BigDecimal {
+        BigDecimal::from_str(value).unwrap()
+    }
+
+    fn assert_near(left: &BigDecimal, right: &BigDecimal) {
+        assert!(
+            (left - right).abs() < decimal("0.000001"),
+            "{} is not {}",
+            left,
+            right
+        );
+    }
+
+    // Hourly candles from 2024 opening at the previous close, with prices to the cent
+    fn candles(symbol: &str, closes: &[f64]) -> Vec<Candle> {
+        let start = Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap();
+        let price = |value: f64| decimal(&format!("{:.2}", value));
+        closes
+            .iter()
+            .enumerate()
+            .map(|(i, close)| {
+                let open = if i == 0 { *close } else { closes[i - 1] };
+                Candle {
+                    timestamp: start + chrono::TimeDelta::milliseconds(i as i64 * HOUR_MS),
+                    exchange: "binance".to_string(),
+                    symbol: symbol.to_string(),
+                    timeframe: Timeframe::H1,
+                    open: price(open),
+                    high: price(open.max(*close) + 0.5),
+                    low: price(open.min(*close) - 0.5),
+                    close: price(*close),
+                    volume: decimal("10"),
+                }
+            })
+            .collect()
+    }
+
+    // A price that swings through several trends
+    fn walk(len: usize) -> Vec<f64> {
+        (0..len)
+            .map(|i| {
+                let i = i as f64;
+                100.0 + 10.0 * (i / 7.0).sin() + 3.0 * (i / 2.3).sin()
+            })
+            .collect()
+    }
+
+    fn data(engine: BacktestEngine, symbols: &[(&str, Vec<Candle>)]) -> BacktestData {
+        let names = symbols.iter().map(|(name, _)| name.to_string()).collect();
+        let candles: Vec<Vec<Candle>> = symbols.iter().map(|(_, c)| c.clone()).collect();
+        match engine {
+            BacktestEngine::Exact => BacktestData::new(names, candles),
+            BacktestEngine::Fast => BacktestData::from_columns(
+                names,
+                candles
+                    .iter()
+                    .map(|c| CandleColumns::from(c.as_slice()))
+                    .collect(),
+            ),
+        }
+    }
+
+    fn config(engine: BacktestEngine) -> BacktestConfig {
+        BacktestConfig {
+            exchange: "binance".to_string(),
+            symbol: "BTC/USDT".to_string(),
+            timeframe: Timeframe::H1,
+            precision: MarketPrecision {
+                price_precision: decimal("0.01"),
+                amount_precision: decimal("0.001"),
+            },
+            fees: TradingFees {
+                maker: decimal("0.001"),
+                taker: decimal("0.001"),
+            },
+            initial_capital: decimal("1000"),
+            quote_currency: "USDT".to_string(),
+            start: None,
+            end: None,
+            equity_points: None,
+            margin: None,
+            fill_model: FillModelConfig::default(),
+            risk: None,
+            additional_symbols: Vec::new(),
+            additional_timeframes: Vec::new(),
+            engine,
+        }
+    }
+
+    // Enters on a rising close with a bracket 2% either side, so runs exercise market, limit and
+    // stop fills
+    struct Breakout;
+
+    impl Strategy for Breakout {
+        fn tick(&mut self, context: &mut StrategyContext) -> AppResult<()> {
+            let closes = context.columns().close;
+            let n = closes.len();
+            if n < 2 || closes[n - 1] <= closes[n - 2] || !context.orders().is_empty() {
+                return Ok(());
+            }
+            if context.position() > BigDecimal::zero() {
+                return Ok(());
+            }
+
+            let close = context.candle()?.close;
+            context.bracket_buy(
+                None,
+                &decimal("2.5"),
+                &(&close * decimal("1.02")),
+                &(&close * decimal("0.98")),
+            )?;
+            Ok(())
+        }
+    }
+
+    fn run(config: &BacktestConfig, data: &BacktestData) -> BacktestStatistic {
+        config.run(&mut Breakout, data, 0, |_| Ok(())).unwrap()
+    }
+
+    #[test]
+    fn fast_engine_matches_the_exact_engine() {
+        let candles = candles("BTC/USDT", &walk(500));
+        let exact = run(
+            &config(BacktestEngine::Exact),
+            &data(BacktestEngine::Exact, &[("BTC/USDT", candles.clone())]),
+        );
+        let fast = run(
+            &config(BacktestEngine::Fast),
+            &data(BacktestEngine::Fast, &[("BTC/USDT", candles)]),
+        );
+
+        assert!(exact.total_trades > 20);
+        assert_eq!(exact.trades.len(), fast.trades.len());
+        for (exact, fast) in exact.trades.iter().zip(&fast.trades) {
+            assert_eq!(exact.timestamp, fast.timestamp);
+            assert_eq!(
+                format!("{:?}", exact.trade_type),
+                format!("{:?}", fast.trade_type)
+            );
+            assert_near(&exact.price, &fast.price);
+            assert_near(&exact.amount, &fast.amount);
+            assert_near(&exact.fee, &fast.fee);
+        }
+
+        assert_eq!(exact.winning_trades, fast.winning_trades);
+        assert_eq!(exact.losing_trades, fast.losing_trades);
+        assert_near(&exact.net_profit, &fast.net_profit);
+        assert_near(&exact.max_drawdown, &fast.max_drawdown);
+        assert_eq!(exact.equity_curve.len(), fast.equity_curve.len());
+        for (exact, fast) in exact.equity_curve.iter().zip(&fast.equity_curve) {
+            assert_near(&exact.equity, &fast.equity);
+            assert_near(&exact.cash, &fast.cash);
+        }
+    }
+
+    fn trade(candle: &Candle, trade_type: TradeType, amount: &str) -> Trade {
+        Trade {
+            timestamp: candle.timestamp,
+            symbol: candle.symbol.clone(),
+            trade_type,
+            price: candle.close.clone(),
+            amount: decimal(amount),
+            fee: BigDecimal::zero(),
+            profit: None,
+        }
+    }
+
+    #[test]
+    fn equity_curve_marks_positions_to_each_close() {
+        let candles = candles("BTC/USDT", &[100.0, 110.0, 90.0, 120.0]);
+        let data = data(BacktestEngine::Exact, &[("BTC/USDT", candles.clone())]);
+        let trades = [
+            trade(&candles[0], TradeType::MarketBuy, "1"),
+            trade(&candles[3], TradeType::MarketSell, "1"),
+        ];
+        let statistic = BacktestStatistic::calculate(decimal("1000"), &data, 0, &trades, &[]);
+
+        let series = |field: fn(&EquityPoint) -> &BigDecimal| -> Vec<BigDecimal> {
+            statistic.equity_curve.iter().map(field).cloned().collect()
+        };
+        let decimals = |values: &[&str]| -> Vec<BigDecimal> {
+            values.iter().map(|value| decimal(value)).collect()
+        };
+        assert_eq!(
+            series(|p| &p.equity),
+            decimals(&["1000", "1010", "990", "1020"])
+        );
+        assert_eq!(
+            series(|p| &p.cash),
+            decimals(&["900", "900", "900", "1020"])
+        );
+        assert_eq!(
+            series(|p| &p.position_value),
+            decimals(&["100", "110", "90", "0"])
+        );
+        // Drawdowns run from the best intrabar equity to the worst, so they include the wicks
+        assert_eq!(series(|p| &p.drawdown), decimals(&["1", "11", "21", "0"]));
+        assert_eq!(statistic.max_drawdown, decimal("21"));
+        assert!((statistic.max_drawdown_percent - 2100.0 / 1010.5).abs() < 1e-4);
+        assert_eq!(statistic.trades[1].profit, Some(decimal("20")));
+    }
+
+    #[test]
+    fn equity_curve_pays_interest_out_of_cash() {
+        let candles = candles("BTC/USDT", &[100.0, 100.0, 100.0]);
+        let data = data(BacktestEngine::Exact, &[("BTC/USDT", candles)]);
+        let interest: Vec<Number> = [0, 1, 3].into_iter().map(Number::from).collect();
+        let statistic = BacktestStatistic::calculate(decimal("1000"), &data, 0, &[], &interest);
+
+        let cash: Vec<BigDecimal> = statistic
+            .equity_curve
+            .iter()
+            .map(|p| p.cash.clone())
+            .collect();
+        assert_eq!(cash, vec![decimal("1000"), decimal("999"), decimal("997")]);
+    }
+
+    #[test]
+    fn downsampling_keeps_each_buckets_deepest_drawdown() {
+        let candles = candles("BTC/USDT", &[100.0; 10]);
+        let data = data(BacktestEngine::Exact, &[("BTC/USDT", candles.clone())]);
+        let mut statistic = BacktestStatistic::calculate(decimal("1000"), &data, 0, &[], &[]);
+        for (i, point) in statistic.equity_curve.iter_mut().enumerate() {
+            point.drawdown = BigDecimal::from([0, 5, 2, 1, 0, 0, 7, 0, 3, 0][i]);
+        }
+
+        statistic.downsample_equity_curve(3);
+        let points: Vec<(DateTime<Utc>, BigDecimal)> = statistic
+            .equity_curve
+            .iter()
+            .map(|p| (p.timestamp, p.drawdown.clone()))
+            .collect();
+        assert_eq!(
+            points,
+            vec![
+                (candles[3].timestamp, BigDecimal::from(5)),
+                (candles[7].timestamp, BigDecimal::from(7)),
+                (candles[9].timestamp, BigDecimal::from(3)),
+            ]
+        );
+    }
+
+    // Buys on a dip and places its exit from the fill callback
+    #[derive(Default)]
+    struct Lifecycle {
+        events: Vec<String>,
+    }
+
+    impl Strategy for Lifecycle {
+        fn on_start(&mut self, _context: &mut StrategyContext) -> AppResult<()> {
+            self.events.push("start".to_string());
+            Ok(())
+        }
+
+        fn tick(&mut self, context: &mut StrategyContext) -> AppResult<()> {
+            self.events.push("tick".to_string());
+            if context.trades().is_empty() && context.orders().is_empty() {
+                context.limit_buy(&decimal("99"), &decimal("1"))?;
+            }
+            Ok(())
+        }
+
+        fn on_order_filled(
+            &mut self,
+            context: &mut StrategyContext,
+            order: &Order,
+            trade: &Trade,
+        ) -> AppResult<()> {
+            self.events
+                .push(format!("filled {:?} at {}", order.order_type, trade.price));
+            context.limit_sell(&decimal("200"), &trade.amount)?;
+            Ok(())
+        }
+
+        fn on_order_cancelled(
+            &mut self,
+            _context: &mut StrategyContext,
+            order: &Order,
+        ) -> AppResult<()> {
+            self.events
+                .push(format!("cancelled {:?}", order.order_type));
+            Ok(())
+        }
+
+        fn on_finish(&mut self, context: &mut StrategyContext) -> AppResult<()> {
+            self.events.push("finish".to_string());
+            assert!(context.orders().is_empty());
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn calls_back_on_each_step_of_an_orders_life() {
+        let candles = candles("BTC/USDT", &[100.0, 98.0, 98.0]);
+        let data = data(BacktestEngine::Exact, &[("BTC/USDT", candles)]);
+        let mut strategy = Lifecycle::default();
+        let statistic = config(BacktestEngine::Exact)
+            .run(&mut strategy, &data, 0, |_| Ok(()))
+            .unwrap();
+
+        assert_eq!(
+            strategy.events,
+            [
+                "start",
+                "tick",
+                "filled LimitBuy at 99.00",
+                "tick",
+                "tick",
+                "cancelled LimitSell",
+                "finish",
+            ]
+        );
+        assert_eq!(statistic.trades.len(), 1);
+    }
+
+    // Buys both symbols from one cash balance and records the ether price it sees each tick
+    #[derive(Default)]
+    struct Portfolio {
+        ether_closes: Vec<BigDecimal>,
+    }
+
+    impl Strategy for Portfolio {
+        fn tick(&mut self, context: &mut StrategyContext) -> AppResult<()> {
+            if context.trades().is_empty() {
+                context.market_buy(&decimal("1"))?;
+                context.with_symbol("ETH/USDT", |context| context.market_buy(&decimal("2")))?;
+            }
+            let close = context.with_symbol("ETH/USDT", |context| Ok(context.candle()?.close))?;
+            self.ether_closes.push(close);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn portfolio_shares_cash_across_symbols() {
+        let bitcoin = candles("BTC/USDT", &[100.0, 110.0, 120.0]);
+        // Ether has no candle in the second hour
+        let mut ether = candles("ETH/USDT", &[50.0, 55.0, 60.0]);
+        ether.remove(1);
+
+        let fees = TradingFees {
+            maker: BigDecimal::zero(),
+            taker: BigDecimal::zero(),
+        };
+        let mut config = config(BacktestEngine::Exact);
+        config.fees = fees.clone();
+        config.additional_symbols = vec![SymbolConfig {
+            symbol: "ETH/USDT".to_string(),
+            precision: config.precision.clone(),
+            fees,
+        }];
+        let data = data(
+            BacktestEngine::Exact,
+            &[("BTC/USDT", bitcoin), ("ETH/USDT", ether)],
+        );
+        let mut strategy = Portfolio::default();
+        let statistic = config.run(&mut strategy, &data, 0, |_| Ok(())).unwrap();
+
+        assert_eq!(
+            strategy.ether_closes,
+            vec![decimal("50"), decimal("50"), decimal("60")]
+        );
+        let equity: Vec<BigDecimal> = statistic
+            .equity_curve
+            .iter()
+            .map(|p| p.equity.clone())
+            .collect();
+        assert_eq!(
+            equity,
+            vec![decimal("1000"), decimal("1010"), decimal("1040")]
+        );
+        assert_eq!(statistic.equity_curve[2].cash, decimal("800"));
+
+        let positions: Vec<(&str, BigDecimal, usize)> = statistic
+            .symbols
+            .iter()
+            .map(|s| (s.symbol.as_str(), s.position.clone(), s.buy_trades))
+            .collect();
+        assert_eq!(
+            positions,
+            vec![("BTC/USDT", decimal("1"), 1), ("ETH/USDT", decimal("2"), 1)]
+        );
+    }
+
+    // Records how many four-hour candles each hourly tick can see
+    #[derive(Default)]
+    struct HigherTimeframe {
+        visible: Vec<usize>,
+    }
+
+    impl Strategy for HigherTimeframe {
+        fn tick(&mut self, context: &mut StrategyContext) -> AppResult<()> {
+            self.visible.push(context.candles_for(Timeframe::H4)?.len());
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn higher_timeframe_candles_appear_once_closed() {
+        let hourly = candles("BTC/USDT", &walk(9));
+        let four_hourly: Vec<Candle> = candles("BTC/USDT", &walk(2))
+            .into_iter()
+            .enumerate()
+            .map(|(i, candle)| Candle {
+                timestamp: hourly[4 * i].timestamp,
+                timeframe: Timeframe::H4,
+                ..candle
+            })
+            .collect();
+
+        let mut config = config(BacktestEngine::Exact);
+        config.additional_timeframes = vec![Timeframe::H4];
+        let data = data(BacktestEngine::Exact, &[("BTC/USDT", hourly)])
+            .with_timeframes(vec![Timeframe::H4], vec![vec![four_hourly]]);
+        let mut strategy = HigherTimeframe::default();
+        config.run(&mut strategy, &data, 0, |_| Ok(())).unwrap();
+
+        // The first four-hour candle closes with the fourth hourly one
+        assert_eq!(strategy.visible, vec![0, 0, 0, 1, 1, 1, 1, 2, 2]);
+    }
+
+    #[test]
+    fn timeframes_not_loaded_are_an_error() {
+        struct Daily;
+        impl Strategy for Daily {
+            fn tick(&mut self, context: &mut StrategyContext) -> AppResult<()> {
+                context.candles_for(Timeframe::D1).map(|_| ())
+            }
+        }
+
+        let data = data(
+            BacktestEngine::Exact,
+            &[("BTC/USDT", candles("BTC/USDT", &walk(2)))],
+        );
+        let result = config(BacktestEngine::Exact).run(&mut Daily, &data, 0, |_| Ok(()));
+        assert!(matches!(result, Err(AppError::Strategy(_))));
+    }
+}
@@ -0,0 +1,25 @@
+use serde::Deserialize;
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct TaskConfig {
+    // Run strategies in a child process, so a crash in strategy code cannot take down the server
+    pub worker_process: bool,
+    // Wall-clock limit for a task once it starts running, unless the request sets its own
+    pub timeout_secs: Option<u64>,
+    // Backtests and optimizations running at once, the rest wait in a queue
+    pub max_backtests: usize,
+    // Candle fetches running at once
+    pub max_fetches: usize,
+}
+
+impl Default for TaskConfig {
+    fn default() -> Self {
+        Self {
+            worker_process: false,
+            timeout_secs: None,
+            max_backtests: 2,
+            max_fetches: 2,
+        }
+    }
+}
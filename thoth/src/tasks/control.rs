@@ -0,0 +1,127 @@
+use crate::errors::{AppError, AppResult};
+use std::sync::{Arc, OnceLock};
+use std::time::{Duration, Instant};
+use tokio_util::sync::CancellationToken;
+
+// Stops a task on request or once its time limit runs out. Tasks check it between candles
+#[derive(Debug, Clone, Default)]
+pub struct TaskControl {
+    token: CancellationToken,
+    timeout: Arc<OnceLock<(Instant, Duration)>>,
+}
+
+impl TaskControl {
+    pub fn cancel(&self) {
+        self.token.cancel();
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.token.is_cancelled()
+    }
+
+    pub async fn cancelled(&self) {
+        self.token.cancelled().await
+    }
+
+    // Cancels the task after `timeout_secs` of wall-clock time
+    pub fn start_timer(&self, timeout_secs: Option<u64>) {
+        let Some(timeout) = timeout_secs.map(Duration::from_secs) else {
+            return;
+        };
+        let deadline = Instant::now() + timeout;
+        if self.timeout.set((deadline, timeout)).is_err() {
+            return;
+        }
+
+        // Backtests block their thread between checks, so check() also compares the deadline
+        let token = self.token.clone();
+        tokio::spawn(async move {
+            tokio::select! {
+                _ = token.cancelled() => {}
+                _ = tokio::time::sleep_until(deadline.into()) => token.cancel(),
+            }
+        });
+    }
+
+    pub fn check(&self) -> AppResult<()> {
+        if self.timed_out() {
+            self.token.cancel();
+        }
+
+        if self.token.is_cancelled() {
+            Err(self.error())
+        } else {
+            Ok(())
+        }
+    }
+
+    // Runs `future` unless the task is cancelled first
+    pub async fn until_cancelled<F: Future>(&self, future: F) -> AppResult<F::Output> {
+        tokio::select! {
+            output = future => Ok(output),
+            _ = self.token.cancelled() => Err(self.error()),
+        }
+    }
+
+    fn timed_out(&self) -> bool {
+        self.timeout
+            .get()
+            .is_some_and(|(deadline, _)| Instant::now() >= *deadline)
+    }
+
+    fn error(&self) -> AppError {
+        match self.timeout.get() {
+            Some((_, timeout)) if self.timed_out() => AppError::Cancelled(format!(
+                "Task timed out after {} seconds",
+                timeout.as_secs()
+            )),
+            _ => AppError::Cancelled("Task was cancelled".to_string()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn message(result: AppResult<()>) -> String {
+        match result {
+            Err(AppError::Cancelled(message)) => message,
+            other => panic!("expected a cancellation, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn cancelling_any_copy_stops_the_task() {
+        let control = TaskControl::default();
+        let running = control.clone();
+        assert!(running.check().is_ok());
+
+        control.cancel();
+        assert!(running.is_cancelled());
+        assert_eq!(message(running.check()), "Task was cancelled");
+    }
+
+    #[tokio::test]
+    async fn times_out_once_the_deadline_passes() {
+        let control = TaskControl::default();
+        control.start_timer(None);
+        assert!(control.check().is_ok());
+
+        control.start_timer(Some(0));
+        // Only the first timer counts
+        control.start_timer(Some(60));
+        assert_eq!(message(control.check()), "Task timed out after 0 seconds");
+    }
+
+    #[tokio::test]
+    async fn until_cancelled_stops_waiting_on_cancellation() {
+        let control = TaskControl::default();
+        assert_eq!(control.until_cancelled(async { 1 }).await.unwrap(), 1);
+
+        let timer = control.clone();
+        timer.start_timer(Some(0));
+        let result = control.until_cancelled(std::future::pending::<()>()).await;
+        assert_eq!(message(result), "Task timed out after 0 seconds");
+    }
+}
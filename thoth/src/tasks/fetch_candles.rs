@@ -1,11 +1,16 @@
-use crate::exchange::ccxt::CCXT;
+use crate::exchange::{self, ExchangeConfig};
 use crate::models::Timeframe;
 use crate::services::candles;
-use crate::{errors::AppResult, services::tasks::save_fetch_candles_task};
+use crate::tasks::{QueuedTask, TaskControl};
+use crate::{
+    errors::{AppError, AppResult},
+    services::tasks::save_fetch_candles_task,
+};
 use bigdecimal::ToPrimitive;
 use chrono::{DateTime, Utc, serde::ts_milliseconds, serde::ts_milliseconds_option};
 use serde::{Deserialize, Serialize};
 use sqlx::PgPool;
+use std::collections::HashMap;
 use tokio::sync::broadcast;
 use ts_rs::TS;
 use uuid::Uuid;
@@ -27,6 +32,7 @@ pub enum FetchCandlesStatus {
     Running,
     Completed,
     Failed,
+    Cancelled,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, TS)]
@@ -42,6 +48,12 @@ pub struct FetchCandlesTask {
     pub result: Option<FetchCandlesResult>,
     #[ts(optional)]
     pub error_message: Option<String>,
+    #[serde(default)]
+    #[ts(optional)]
+    pub timeout_secs: Option<u64>,
+    #[serde(default)]
+    #[ts(optional)]
+    pub queue_position: Option<usize>,
     #[serde(with = "ts_milliseconds")]
     #[ts(type = "number")]
     pub created_at: DateTime<Utc>,
@@ -66,14 +78,29 @@ impl FetchCandlesTask {
         }
     }
 
-    pub async fn execute(&mut self, db_pool: PgPool) {
+    pub async fn execute(
+        &mut self,
+        db_pool: PgPool,
+        exchanges: &HashMap<String, ExchangeConfig>,
+        control: &TaskControl,
+    ) {
+        control.start_timer(self.timeout_secs);
+
         let now = Utc::now();
         self.status = FetchCandlesStatus::Running;
         self.started_at = Some(now);
         self.updated_at = now;
         self.broadcast();
+        self.save(&db_pool).await;
+
+        let result = self.execute_fetch(&db_pool, exchanges, control).await;
+        self.complete(result);
+        self.broadcast();
+
+        self.save(&db_pool).await;
+    }
 
-        let result = self.execute_fetch(&db_pool).await;
+    fn complete(&mut self, result: AppResult<FetchCandlesResult>) {
         let now = Utc::now();
         match result {
             Ok(fetch_result) => {
@@ -83,6 +110,12 @@ impl FetchCandlesTask {
                 self.completed_at = Some(now);
                 self.updated_at = now;
             }
+            Err(AppError::Cancelled(message)) => {
+                self.status = FetchCandlesStatus::Cancelled;
+                self.error_message = Some(message);
+                self.completed_at = Some(now);
+                self.updated_at = now;
+            }
             Err(e) => {
                 self.status = FetchCandlesStatus::Failed;
                 self.error_message = Some(e.to_string());
@@ -90,14 +123,15 @@ impl FetchCandlesTask {
                 self.updated_at = now;
             }
         }
-        self.broadcast();
-
-        save_fetch_candles_task(&db_pool, self)
-            .await
-            .expect("Failed to save fetch candles task");
     }
 
-    async fn execute_fetch(&mut self, db_pool: &PgPool) -> AppResult<FetchCandlesResult> {
+    async fn execute_fetch(
+        &mut self,
+        db_pool: &PgPool,
+        exchanges: &HashMap<String, ExchangeConfig>,
+        control: &TaskControl,
+    ) -> AppResult<FetchCandlesResult> {
+        control.check()?;
         let exchange = self.exchange.clone();
         let symbol = self.symbol.clone();
         let timeframe = self.timeframe;
@@ -109,7 +143,7 @@ impl FetchCandlesTask {
             timeframe
         );
 
-        let ccxt = CCXT::with_exchange(&exchange)?;
+        let connector = exchange::connect(&exchange, exchanges)?;
 
         let timeframe_ms = timeframe.to_ms();
         let timeframe_delta = timeframe.to_delta();
@@ -117,7 +151,7 @@ impl FetchCandlesTask {
             match candles::get_latest_candle(db_pool, &exchange, &symbol, timeframe).await? {
                 Some(latest_candle) => latest_candle.timestamp + timeframe_delta,
                 None => {
-                    let first_candle = ccxt.first_candle(&symbol, timeframe)?;
+                    let first_candle = connector.first_candle(&symbol, timeframe)?;
                     let Some(first_candle) = first_candle else {
                         return Err(format!(
                             "No candles data available for {} on {}",
@@ -142,7 +176,7 @@ impl FetchCandlesTask {
         };
 
         let mut count: u64 = 0;
-        let total = (time_diff_ms + timeframe_ms - 1) / timeframe_ms;
+        let total = time_diff_ms.div_ceil(timeframe_ms);
         let mut progress = 0.0;
 
         self.progress = progress;
@@ -150,8 +184,9 @@ impl FetchCandlesTask {
         self.broadcast();
 
         loop {
+            control.check()?;
             let next_since_ms = next_since.timestamp_millis();
-            let epoch = ccxt.fetch_candles(&symbol, timeframe, Some(next_since_ms), None)?;
+            let epoch = connector.fetch_candles(&symbol, timeframe, Some(next_since_ms), None)?;
             let Some(latest) = epoch.last() else {
                 break;
             };
@@ -175,3 +210,31 @@ impl FetchCandlesTask {
         })
     }
 }
+
+impl QueuedTask for FetchCandlesTask {
+    fn id(&self) -> Uuid {
+        self.id
+    }
+
+    fn subscribe(&self) -> Option<broadcast::Receiver<Self>> {
+        self.event_tx.as_ref().map(broadcast::Sender::subscribe)
+    }
+
+    fn set_queue_position(&mut self, position: Option<usize>) {
+        if self.queue_position != position {
+            self.queue_position = position;
+            self.broadcast();
+        }
+    }
+
+    fn cancel(&mut self, error: AppError) {
+        self.complete(Err(error));
+        self.broadcast();
+    }
+
+    async fn save(&self, db_pool: &PgPool) {
+        if let Err(e) = save_fetch_candles_task(db_pool, self).await {
+            tracing::error!("Failed to save fetch candles task {}: {}", self.id, e);
+        }
+    }
+}
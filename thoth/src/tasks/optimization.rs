@@ -0,0 +1,1044 @@
+use crate::errors::{AppError, AppResult};
+use crate::services::tasks::save_optimization_task;
+use crate::strategy::{Bar, ParameterValues, StrategyHandle, Trade, TradeType};
+use crate::tasks::worker::{StrategyTask, WorkerJob};
+use crate::tasks::{
+    BacktestConfig, BacktestData, BacktestStatistic, BacktestStatus, QueuedTask, TaskControl,
+};
+use bigdecimal::{BigDecimal, RoundingMode, Signed, ToPrimitive, Zero};
+use chrono::{DateTime, Utc, serde::ts_milliseconds, serde::ts_milliseconds_option};
+use rand::{SeedableRng, rngs::StdRng, seq::index};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sqlx::PgPool;
+use std::collections::BTreeMap;
+use tokio::sync::broadcast;
+use ts_rs::TS;
+use uuid::Uuid;
+
+pub const MAX_OPTIMIZATION_RUNS: usize = 10000;
+
+#[derive(Debug, Clone, Serialize, Deserialize, TS)]
+#[serde(tag = "type", rename_all = "snake_case")]
+#[ts(export, tag = "type")]
+pub enum ParameterRange {
+    Values { values: Vec<Value> },
+    Range { min: f64, max: f64, step: f64 },
+}
+
+impl ParameterRange {
+    pub fn values(&self, name: &str) -> AppResult<Vec<Value>> {
+        match self {
+            ParameterRange::Values { values } => {
+                if values.is_empty() {
+                    return Err(AppError::BadRequest(format!(
+                        "Parameter {} has no values",
+                        name
+                    )));
+                }
+                Ok(values.clone())
+            }
+            ParameterRange::Range { min, max, step } => {
+                if step.is_nan() || *step <= 0.0 || min > max {
+                    return Err(AppError::BadRequest(format!(
+                        "Invalid range for parameter {}",
+                        name
+                    )));
+                }
+
+                let count = ((max - min) / step + 1e-9).floor() as usize + 1;
+                if count > MAX_OPTIMIZATION_RUNS {
+                    return Err(AppError::BadRequest(format!(
+                        "Range for parameter {} has too many values",
+                        name
+                    )));
+                }
+
+                let integral = min.fract() == 0.0 && step.fract() == 0.0;
+                Ok((0..count)
+                    .map(|i| {
+                        let value = min + step * i as f64;
+                        if integral {
+                            Value::from(value as i64)
+                        } else {
+                            Value::from(value)
+                        }
+                    })
+                    .collect())
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, TS)]
+#[serde(tag = "type", rename_all = "snake_case")]
+#[ts(export, tag = "type")]
+pub enum ParameterSearch {
+    Grid,
+    Random {
+        samples: usize,
+        #[ts(optional, type = "number")]
+        seed: Option<u64>,
+    },
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, TS)]
+#[serde(rename_all = "snake_case")]
+#[ts(export)]
+pub enum OptimizationMetric {
+    NetProfit,
+    ReturnPercent,
+    SharpeRatio,
+    ProfitFactor,
+    WinRate,
+    MaxDrawdown,
+}
+
+impl OptimizationMetric {
+    pub fn score(&self, statistic: &BacktestStatistic) -> f64 {
+        match self {
+            OptimizationMetric::NetProfit => statistic.net_profit.to_f64().unwrap_or(0.0),
+            OptimizationMetric::ReturnPercent => statistic.return_percent as f64,
+            OptimizationMetric::SharpeRatio => statistic.sharpe_ratio as f64,
+            OptimizationMetric::ProfitFactor => statistic.profit_factor as f64,
+            OptimizationMetric::WinRate => statistic.win_rate as f64,
+            // Lower drawdown is better, so rank by its negation
+            OptimizationMetric::MaxDrawdown => -(statistic.max_drawdown_percent as f64),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub struct OptimizationResult {
+    pub parameters: ParameterValues,
+    #[ts(optional)]
+    pub score: Option<f64>,
+    #[ts(optional)]
+    pub statistic: Option<BacktestStatistic>,
+    #[ts(optional)]
+    pub error_message: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub struct Optimization {
+    pub ranges: BTreeMap<String, ParameterRange>,
+    pub search: ParameterSearch,
+    pub metric: OptimizationMetric,
+}
+
+impl Optimization {
+    pub fn combinations(&self) -> AppResult<Vec<ParameterValues>> {
+        let mut names = Vec::new();
+        let mut axes = Vec::new();
+        let mut total = 1usize;
+        for (name, range) in &self.ranges {
+            let values = range.values(name)?;
+            total = total.saturating_mul(values.len());
+            names.push(name.clone());
+            axes.push(values);
+        }
+
+        let indices: Vec<usize> = match &self.search {
+            ParameterSearch::Grid => {
+                if total > MAX_OPTIMIZATION_RUNS {
+                    return Err(AppError::BadRequest(format!(
+                        "Parameter grid has {} combinations, at most {} are allowed",
+                        total, MAX_OPTIMIZATION_RUNS
+                    )));
+                }
+                (0..total).collect()
+            }
+            ParameterSearch::Random { samples, seed } => {
+                if *samples == 0 || *samples > MAX_OPTIMIZATION_RUNS {
+                    return Err(AppError::BadRequest(format!(
+                        "Random search samples must be between 1 and {}",
+                        MAX_OPTIMIZATION_RUNS
+                    )));
+                }
+                let mut rng = match seed {
+                    Some(seed) => StdRng::seed_from_u64(*seed),
+                    None => StdRng::from_entropy(),
+                };
+                let mut indices = index::sample(&mut rng, total, (*samples).min(total)).into_vec();
+                indices.sort_unstable();
+                indices
+            }
+        };
+
+        Ok(indices
+            .into_iter()
+            .map(|mut index| {
+                let mut parameters = ParameterValues::new();
+                for (name, values) in names.iter().zip(&axes).rev() {
+                    parameters.insert(name.clone(), values[index % values.len()].clone());
+                    index /= values.len();
+                }
+                parameters
+            })
+            .collect())
+    }
+
+    pub fn run(
+        &self,
+        config: &BacktestConfig,
+        strategy_handle: &StrategyHandle,
+        data: &BacktestData,
+        combinations: &[ParameterValues],
+        control: &TaskControl,
+        mut on_result: impl FnMut(&OptimizationResult),
+    ) -> AppResult<Vec<OptimizationResult>> {
+        let mut results = Vec::with_capacity(combinations.len());
+        for parameters in combinations {
+            let outcome = strategy_handle
+                .create(parameters)
+                .and_then(|mut strategy| config.run(&mut strategy, data, 0, |_| control.check()));
+            // A cancelled run is not a result of its parameters
+            control.check()?;
+
+            let result = match outcome {
+                Ok(mut statistic) => {
+                    statistic.trades.clear();
+                    statistic.equity_curve.clear();
+                    OptimizationResult {
+                        parameters: parameters.clone(),
+                        score: Some(self.metric.score(&statistic)),
+                        statistic: Some(statistic),
+                        error_message: None,
+                    }
+                }
+                Err(e) => OptimizationResult {
+                    parameters: parameters.clone(),
+                    score: None,
+                    statistic: None,
+                    error_message: Some(e.to_string()),
+                },
+            };
+
+            on_result(&result);
+            results.push(result);
+        }
+
+        Self::rank(&mut results);
+        Ok(results)
+    }
+
+    pub fn rank(results: &mut [OptimizationResult]) {
+        results.sort_by(|a, b| match (a.score, b.score) {
+            (Some(a), Some(b)) => b.total_cmp(&a),
+            (Some(_), None) => std::cmp::Ordering::Less,
+            (None, Some(_)) => std::cmp::Ordering::Greater,
+            (None, None) => std::cmp::Ordering::Equal,
+        });
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub struct WalkForward {
+    pub in_sample_candles: usize,
+    pub out_of_sample_candles: usize,
+    #[serde(default)]
+    pub anchored: bool,
+}
+
+impl WalkForward {
+    pub fn validate(&self) -> AppResult<()> {
+        if self.in_sample_candles == 0 || self.out_of_sample_candles == 0 {
+            return Err(AppError::BadRequest(
+                "Walk-forward windows must contain at least one candle".to_string(),
+            ));
+        }
+        Ok(())
+    }
+
+    pub fn windows(&self, total_candles: usize) -> AppResult<Vec<WalkForwardWindow>> {
+        self.validate()?;
+
+        let mut windows = Vec::new();
+        let mut out_of_sample_start = self.in_sample_candles;
+        while out_of_sample_start < total_candles {
+            let in_sample_start = if self.anchored {
+                0
+            } else {
+                out_of_sample_start - self.in_sample_candles
+            };
+            let out_of_sample_end =
+                (out_of_sample_start + self.out_of_sample_candles).min(total_candles);
+            windows.push(WalkForwardWindow {
+                in_sample_start,
+                out_of_sample_start,
+                out_of_sample_end,
+            });
+            out_of_sample_start = out_of_sample_end;
+        }
+
+        if windows.is_empty() {
+            return Err(format!(
+                "Not enough candles for walk-forward analysis: {} available, more than {} required",
+                total_candles, self.in_sample_candles
+            )
+            .into());
+        }
+
+        Ok(windows)
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct WalkForwardWindow {
+    pub in_sample_start: usize,
+    pub out_of_sample_start: usize,
+    pub out_of_sample_end: usize,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub struct WalkForwardFold {
+    pub index: usize,
+    #[serde(with = "ts_milliseconds")]
+    #[ts(type = "number")]
+    pub in_sample_start: DateTime<Utc>,
+    #[serde(with = "ts_milliseconds")]
+    #[ts(type = "number")]
+    pub in_sample_end: DateTime<Utc>,
+    #[serde(with = "ts_milliseconds")]
+    #[ts(type = "number")]
+    pub out_of_sample_start: DateTime<Utc>,
+    #[serde(with = "ts_milliseconds")]
+    #[ts(type = "number")]
+    pub out_of_sample_end: DateTime<Utc>,
+    #[ts(optional)]
+    pub in_sample: Option<OptimizationResult>,
+    #[ts(optional)]
+    pub out_of_sample: Option<BacktestStatistic>,
+    #[ts(optional)]
+    pub error_message: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub struct OptimizationTask {
+    pub id: Uuid,
+    pub status: BacktestStatus,
+    pub progress: f32,
+    pub name: String,
+    #[serde(flatten)]
+    pub config: BacktestConfig,
+    #[serde(flatten)]
+    pub optimization: Optimization,
+    pub total_runs: usize,
+    pub completed_runs: usize,
+    pub results: Vec<OptimizationResult>,
+    #[serde(default)]
+    #[ts(optional)]
+    pub walk_forward: Option<WalkForward>,
+    #[serde(default)]
+    pub folds: Vec<WalkForwardFold>,
+    #[serde(default)]
+    #[ts(optional)]
+    pub out_of_sample: Option<BacktestStatistic>,
+    #[ts(optional)]
+    pub error_message: Option<String>,
+    #[serde(default)]
+    #[ts(optional)]
+    pub timeout_secs: Option<u64>,
+    #[serde(default)]
+    #[ts(optional)]
+    pub queue_position: Option<usize>,
+    #[serde(with = "ts_milliseconds")]
+    #[ts(type = "number")]
+    pub created_at: DateTime<Utc>,
+    #[serde(with = "ts_milliseconds_option")]
+    #[ts(optional, type = "number")]
+    pub started_at: Option<DateTime<Utc>>,
+    #[serde(with = "ts_milliseconds_option")]
+    #[ts(optional, type = "number")]
+    pub completed_at: Option<DateTime<Utc>>,
+    #[serde(with = "ts_milliseconds")]
+    #[ts(type = "number")]
+    pub updated_at: DateTime<Utc>,
+    #[serde(skip)]
+    #[ts(skip)]
+    pub event_tx: Option<broadcast::Sender<OptimizationTask>>,
+}
+
+impl OptimizationTask {
+    pub(crate) async fn execute_optimization(
+        &mut self,
+        db_pool: &PgPool,
+        strategy_handle: &StrategyHandle,
+        control: &TaskControl,
+    ) -> AppResult<()> {
+        control.check()?;
+        let config = self.config.clone();
+        let optimization = self.optimization.clone();
+
+        tracing::info!(
+            "Running optimization on {}/{} with timeframe {}",
+            config.exchange,
+            config.symbol,
+            config.timeframe
+        );
+
+        let combinations = optimization
+            .combinations()?
+            .iter()
+            .map(|parameters| strategy_handle.resolve_parameters(parameters))
+            .collect::<AppResult<Vec<_>>>()?;
+        let data = config.load_data(db_pool).await?;
+        control.check()?;
+
+        if let Some(walk_forward) = self.walk_forward.clone() {
+            return self.execute_walk_forward(
+                &walk_forward,
+                strategy_handle,
+                &data,
+                &combinations,
+                control,
+            );
+        }
+
+        self.total_runs = combinations.len();
+        self.completed_runs = 0;
+        self.updated_at = Utc::now();
+        self.broadcast();
+
+        self.results = optimization.run(
+            &config,
+            strategy_handle,
+            &data,
+            &combinations,
+            control,
+            |_| self.complete_run(),
+        )?;
+
+        Ok(())
+    }
+
+    fn execute_walk_forward(
+        &mut self,
+        walk_forward: &WalkForward,
+        strategy_handle: &StrategyHandle,
+        data: &BacktestData,
+        combinations: &[ParameterValues],
+        control: &TaskControl,
+    ) -> AppResult<()> {
+        let config = self.config.clone();
+        let optimization = self.optimization.clone();
+        let windows = walk_forward.windows(data.len())?;
+
+        self.total_runs = windows.len() * (combinations.len() + 1);
+        self.completed_runs = 0;
+        self.folds.clear();
+        self.updated_at = Utc::now();
+        self.broadcast();
+
+        // Each fold trades the equity the previous one ended with, so the stitched ledger compounds
+        let mut capital = config.initial_capital.clone();
+        let mut out_of_sample_trades = Vec::new();
+        for (index, window) in windows.iter().enumerate() {
+            let in_sample = data.slice(window.in_sample_start..window.out_of_sample_start);
+            let mut fold = WalkForwardFold {
+                index,
+                in_sample_start: data.timestamps[window.in_sample_start],
+                in_sample_end: data.timestamps[window.out_of_sample_start - 1],
+                out_of_sample_start: data.timestamps[window.out_of_sample_start],
+                out_of_sample_end: data.timestamps[window.out_of_sample_end - 1],
+                in_sample: None,
+                out_of_sample: None,
+                error_message: None,
+            };
+
+            let results = optimization.run(
+                &config,
+                strategy_handle,
+                &in_sample,
+                combinations,
+                control,
+                |_| self.complete_run(),
+            )?;
+            let best = results.into_iter().find(|result| result.score.is_some());
+
+            // The in-sample window is kept as history so indicators are warmed up
+            let out_of_sample = data.slice(window.in_sample_start..window.out_of_sample_end);
+            let warmup = window.out_of_sample_start - window.in_sample_start;
+            match &best {
+                Some(best) => {
+                    let fold_config = BacktestConfig {
+                        initial_capital: capital.clone(),
+                        ..config.clone()
+                    };
+                    let outcome =
+                        strategy_handle
+                            .create(&best.parameters)
+                            .and_then(|mut strategy| {
+                                fold_config
+                                    .run(&mut strategy, &out_of_sample, warmup, |_| control.check())
+                            });
+                    control.check()?;
+                    match outcome {
+                        Ok(statistic) => {
+                            let mut trades = statistic.trades;
+                            trades.extend(closing_trades(
+                                &config,
+                                data,
+                                window.out_of_sample_end,
+                                &trades,
+                            ));
+                            let mut statistic = BacktestStatistic::calculate(
+                                capital.clone(),
+                                &out_of_sample,
+                                warmup,
+                                &trades,
+                                &[],
+                            );
+                            if let Some(last) = statistic.equity_curve.last() {
+                                capital = last.equity.clone();
+                            }
+                            if let Some(max_points) = config.equity_points {
+                                statistic.downsample_equity_curve(max_points);
+                            }
+                            out_of_sample_trades.extend(trades);
+                            fold.out_of_sample = Some(statistic);
+                        }
+                        Err(e) => fold.error_message = Some(e.to_string()),
+                    }
+                }
+                None => {
+                    fold.error_message =
+                        Some("No parameter combination completed in-sample".to_string());
+                }
+            }
+            fold.in_sample = best;
+
+            self.folds.push(fold);
+            self.complete_run();
+        }
+
+        let first = windows[0].out_of_sample_start;
+        let mut out_of_sample = BacktestStatistic::calculate(
+            config.initial_capital.clone(),
+            data,
+            first,
+            &out_of_sample_trades,
+            &[],
+        );
+        if let Some(max_points) = config.equity_points {
+            out_of_sample.downsample_equity_curve(max_points);
+        }
+        self.out_of_sample = Some(out_of_sample);
+
+        Ok(())
+    }
+
+    fn complete_run(&mut self) {
+        self.completed_runs += 1;
+        self.progress = 100.0 * (self.completed_runs as f32) / (self.total_runs as f32);
+        self.updated_at = Utc::now();
+        self.broadcast_progress();
+    }
+
+    // Broadcasts the progress without the results so far. Copying them after every run would
+    // make a task quadratic in its run count, so they are only sent once the task ends
+    fn broadcast_progress(&mut self) {
+        let results = std::mem::take(&mut self.results);
+        let folds = std::mem::take(&mut self.folds);
+        let out_of_sample = self.out_of_sample.take();
+        self.broadcast();
+        self.results = results;
+        self.folds = folds;
+        self.out_of_sample = out_of_sample;
+    }
+}
+
+// Trades closing what a fold left open at the last close before `end`, so the next fold starts
+// from cash in the stitched out-of-sample ledger just as its own run does. They pay the taker fee
+fn closing_trades(
+    config: &BacktestConfig,
+    data: &BacktestData,
+    end: usize,
+    trades: &[Trade],
+) -> Vec<Trade> {
+    let Some(last) = end.checked_sub(1).map(|i| data.timestamps[i]) else {
+        return Vec::new();
+    };
+
+    let mut closing = Vec::new();
+    for (symbol_index, symbol) in config.symbols().into_iter().enumerate() {
+        let position: BigDecimal = trades
+            .iter()
+            .filter(|trade| trade.symbol == symbol.symbol)
+            .map(|trade| {
+                if trade.trade_type.is_buy() {
+                    trade.amount.clone()
+                } else {
+                    -trade.amount.clone()
+                }
+            })
+            .sum();
+        if position.is_zero() {
+            continue;
+        }
+
+        let columns = &data.columns[symbol_index];
+        let Some(candle) = columns
+            .timestamps
+            .partition_point(|timestamp| *timestamp <= last)
+            .checked_sub(1)
+        else {
+            continue;
+        };
+        let price = match data.candles[symbol_index].get(candle) {
+            Some(candle) => candle.close.clone(),
+            None => Bar::from_columns(&columns.view(0..columns.len()), candle)
+                .close
+                .to_decimal(),
+        };
+
+        let amount = position.abs();
+        let fee = symbol
+            .precision
+            .round_amount(&(&price * &amount * &symbol.fees.taker), RoundingMode::Up);
+        closing.push(Trade {
+            timestamp: columns.timestamps[candle],
+            symbol: symbol.symbol,
+            trade_type: if position.is_positive() {
+                TradeType::MarketSell
+            } else {
+                TradeType::MarketBuy
+            },
+            price,
+            amount,
+            fee,
+            profit: None,
+        });
+    }
+    closing
+}
+
+impl StrategyTask for OptimizationTask {
+    type Output = ();
+
+    const KIND: &'static str = "Optimization";
+
+    fn into_job(self) -> WorkerJob {
+        WorkerJob::Optimization(Box::new(self))
+    }
+
+    fn from_job(job: WorkerJob) -> Option<Self> {
+        match job {
+            WorkerJob::Optimization(task) => Some(*task),
+            _ => None,
+        }
+    }
+
+    fn event_tx(&mut self) -> &mut Option<broadcast::Sender<Self>> {
+        &mut self.event_tx
+    }
+
+    fn timeout_secs(&self) -> Option<u64> {
+        self.timeout_secs
+    }
+
+    fn broadcast(&self) {
+        if let Some(tx) = &self.event_tx {
+            let _ = tx.send(self.clone());
+        }
+    }
+
+    fn start(&mut self, status: BacktestStatus) {
+        let now = Utc::now();
+        self.status = status;
+        self.started_at = Some(now);
+        self.updated_at = now;
+    }
+
+    fn fail(&mut self, message: String) {
+        let now = Utc::now();
+        self.status = BacktestStatus::Failed;
+        self.error_message = Some(message);
+        self.completed_at = Some(now);
+        self.updated_at = now;
+    }
+
+    fn complete(&mut self, result: AppResult<()>) {
+        let now = Utc::now();
+        match result {
+            Ok(()) => {
+                self.status = BacktestStatus::Completed;
+                self.progress = 100.0;
+                self.completed_at = Some(now);
+                self.updated_at = now;
+            }
+            Err(AppError::Cancelled(message)) => {
+                self.status = BacktestStatus::Cancelled;
+                self.error_message = Some(message);
+                self.completed_at = Some(now);
+                self.updated_at = now;
+            }
+            Err(e) => self.fail(e.to_string()),
+        };
+    }
+
+    async fn run(
+        &mut self,
+        db_pool: &PgPool,
+        strategy_handle: &StrategyHandle,
+        control: &TaskControl,
+    ) -> AppResult<()> {
+        self.execute_optimization(db_pool, strategy_handle, control)
+            .await
+    }
+}
+
+impl QueuedTask for OptimizationTask {
+    fn id(&self) -> Uuid {
+        self.id
+    }
+
+    fn subscribe(&self) -> Option<broadcast::Receiver<Self>> {
+        self.event_tx.as_ref().map(broadcast::Sender::subscribe)
+    }
+
+    fn set_queue_position(&mut self, position: Option<usize>) {
+        if self.queue_position != position {
+            self.queue_position = position;
+            self.broadcast();
+        }
+    }
+
+    fn cancel(&mut self, error: AppError) {
+        self.complete(Err(error));
+        self.broadcast();
+    }
+
+    async fn save(&self, db_pool: &PgPool) {
+        if let Err(e) = save_optimization_task(db_pool, self).await {
+            tracing::error!("Failed to save optimization task {}: {}", self.id, e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{Candle, CandleColumns, Timeframe};
+    use serde_json::json;
+    use std::str::FromStr;
+
+    fn optimization(search: ParameterSearch) -> Optimization {
+        Optimization {
+            ranges: BTreeMap::from([
+                (
+                    "fast".to_string(),
+                    ParameterRange::Range {
+                        min: 2.0,
+                        max: 6.0,
+                        step: 2.0,
+                    },
+                ),
+                (
+                    "mode".to_string(),
+                    ParameterRange::Values {
+                        values: vec![json!("long"), json!("short")],
+                    },
+                ),
+            ]),
+            search,
+            metric: OptimizationMetric::NetProfit,
+        }
+    }
+
+    fn result(score: Option<f64>) -> OptimizationResult {
+        OptimizationResult {
+            parameters: ParameterValues::new(),
+            score,
+            statistic: None,
+            error_message: None,
+        }
+    }
+
+    #[test]
+    fn expands_ranges_into_values() {
+        let integral = ParameterRange::Range {
+            min: 5.0,
+            max: 20.0,
+            step: 5.0,
+        };
+        assert_eq!(
+            integral.values("period").unwrap(),
+            vec![json!(5), json!(10), json!(15), json!(20)]
+        );
+
+        // The end is kept despite 0.1 steps not adding up exactly
+        let fractional = ParameterRange::Range {
+            min: 0.1,
+            max: 0.3,
+            step: 0.1,
+        };
+        assert_eq!(fractional.values("threshold").unwrap().len(), 3);
+
+        for invalid in [
+            ParameterRange::Range {
+                min: 1.0,
+                max: 0.0,
+                step: 1.0,
+            },
+            ParameterRange::Range {
+                min: 0.0,
+                max: 1.0,
+                step: 0.0,
+            },
+            ParameterRange::Values { values: Vec::new() },
+        ] {
+            assert!(invalid.values("period").is_err());
+        }
+    }
+
+    #[test]
+    fn grid_covers_every_combination_in_order() {
+        let combinations = optimization(ParameterSearch::Grid).combinations().unwrap();
+        let pairs: Vec<(Value, Value)> = combinations
+            .iter()
+            .map(|c| (c["fast"].clone(), c["mode"].clone()))
+            .collect();
+        assert_eq!(
+            pairs,
+            vec![
+                (json!(2), json!("long")),
+                (json!(2), json!("short")),
+                (json!(4), json!("long")),
+                (json!(4), json!("short")),
+                (json!(6), json!("long")),
+                (json!(6), json!("short")),
+            ]
+        );
+    }
+
+    #[test]
+    fn grid_is_capped() {
+        let mut optimization = optimization(ParameterSearch::Grid);
+        optimization.ranges.insert(
+            "slow".to_string(),
+            ParameterRange::Range {
+                min: 1.0,
+                max: 2000.0,
+                step: 1.0,
+            },
+        );
+        assert!(optimization.combinations().is_err());
+    }
+
+    #[test]
+    fn random_search_samples_the_grid_repeatably() {
+        let search = ParameterSearch::Random {
+            samples: 4,
+            seed: Some(7),
+        };
+        let sampled = optimization(search.clone()).combinations().unwrap();
+        assert_eq!(sampled.len(), 4);
+        assert_eq!(sampled, optimization(search).combinations().unwrap());
+
+        let grid = optimization(ParameterSearch::Grid).combinations().unwrap();
+        assert!(sampled.iter().all(|c| grid.contains(c)));
+
+        // Asking for more samples than the grid holds runs the whole grid
+        let all = optimization(ParameterSearch::Random {
+            samples: 100,
+            seed: Some(7),
+        })
+        .combinations()
+        .unwrap();
+        assert_eq!(all, grid);
+    }
+
+    #[test]
+    fn ranks_best_scores_first_and_failures_last() {
+        let mut results = vec![result(None), result(Some(1.0)), result(Some(3.0))];
+        Optimization::rank(&mut results);
+        let scores: Vec<Option<f64>> = results.iter().map(|r| r.score).collect();
+        assert_eq!(scores, vec![Some(3.0), Some(1.0), None]);
+    }
+
+    #[test]
+    fn progress_is_broadcast_without_results() {
+        let config: BacktestConfig = serde_json::from_value(json!({
+            "exchange": "binance",
+            "symbol": "BTC/USDT",
+            "timeframe": "1h",
+            "precision": { "price_precision": "0.01", "amount_precision": "0.001" },
+        }))
+        .unwrap();
+        let (tx, mut rx) = broadcast::channel(4);
+        let now = Utc::now();
+        let mut task = OptimizationTask {
+            id: Uuid::new_v4(),
+            status: BacktestStatus::Running,
+            progress: 0.0,
+            name: "sma".to_string(),
+            config,
+            optimization: optimization(ParameterSearch::Grid),
+            total_runs: 2,
+            completed_runs: 0,
+            results: Vec::new(),
+            walk_forward: None,
+            folds: Vec::new(),
+            out_of_sample: None,
+            error_message: None,
+            timeout_secs: None,
+            queue_position: None,
+            created_at: now,
+            started_at: Some(now),
+            completed_at: None,
+            updated_at: now,
+            event_tx: Some(tx),
+        };
+
+        task.results.push(result(Some(1.0)));
+        task.complete_run();
+
+        let progress = rx.try_recv().unwrap();
+        assert_eq!(progress.completed_runs, 1);
+        assert_eq!(progress.progress, 50.0);
+        assert!(progress.results.is_empty());
+        assert_eq!(task.results.len(), 1);
+    }
+
+    fn windows(walk_forward: &WalkForward, total_candles: usize) -> Vec<(usize, usize, usize)> {
+        walk_forward
+            .windows(total_candles)
+            .unwrap()
+            .iter()
+            .map(|w| {
+                (
+                    w.in_sample_start,
+                    w.out_of_sample_start,
+                    w.out_of_sample_end,
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn rolling_windows_slide_over_the_candles() {
+        let walk_forward = WalkForward {
+            in_sample_candles: 100,
+            out_of_sample_candles: 50,
+            anchored: false,
+        };
+        // The last fold is cut short by the end of the data
+        assert_eq!(
+            windows(&walk_forward, 320),
+            vec![
+                (0, 100, 150),
+                (50, 150, 200),
+                (100, 200, 250),
+                (150, 250, 300),
+                (200, 300, 320)
+            ]
+        );
+    }
+
+    #[test]
+    fn anchored_windows_grow_from_the_first_candle() {
+        let walk_forward = WalkForward {
+            in_sample_candles: 100,
+            out_of_sample_candles: 50,
+            anchored: true,
+        };
+        assert_eq!(
+            windows(&walk_forward, 250),
+            vec![(0, 100, 150), (0, 150, 200), (0, 200, 250)]
+        );
+    }
+
+    #[test]
+    fn walk_forward_needs_candles_after_the_first_in_sample_window() {
+        let walk_forward = WalkForward {
+            in_sample_candles: 100,
+            out_of_sample_candles: 50,
+            anchored: false,
+        };
+        assert!(walk_forward.windows(100).is_err());
+        assert_eq!(windows(&walk_forward, 101), vec![(0, 100, 101)]);
+
+        let empty = WalkForward {
+            in_sample_candles: 100,
+            out_of_sample_candles: 0,
+            anchored: false,
+        };
+        assert!(empty.validate().is_err());
+    }
+
+    fn candle(hour: i64, close: &str) -> Candle {
+        let price = BigDecimal::from_str(close).unwrap();
+        Candle {
+            timestamp: DateTime::from_timestamp_millis(hour * 60 * 60 * 1000).unwrap(),
+            exchange: "binance".to_string(),
+            symbol: "BTC/USDT".to_string(),
+            timeframe: Timeframe::H1,
+            open: price.clone(),
+            high: price.clone(),
+            low: price.clone(),
+            close: price,
+            volume: BigDecimal::from(1),
+        }
+    }
+
+    fn trade(hour: i64, trade_type: TradeType, amount: &str) -> Trade {
+        Trade {
+            timestamp: DateTime::from_timestamp_millis(hour * 60 * 60 * 1000).unwrap(),
+            symbol: "BTC/USDT".to_string(),
+            trade_type,
+            price: BigDecimal::from(100),
+            amount: BigDecimal::from_str(amount).unwrap(),
+            fee: BigDecimal::zero(),
+            profit: None,
+        }
+    }
+
+    #[test]
+    fn closes_what_a_fold_leaves_open_at_its_last_close() {
+        let config: BacktestConfig = serde_json::from_value(json!({
+            "exchange": "binance",
+            "symbol": "BTC/USDT",
+            "timeframe": "1h",
+            "precision": { "price_precision": "0.01", "amount_precision": "0.001" },
+            "fees": { "maker": "0.001", "taker": "0.002" },
+        }))
+        .unwrap();
+        let candles: Vec<Candle> = ["100", "110", "120", "130"]
+            .iter()
+            .enumerate()
+            .map(|(hour, close)| candle(hour as i64, close))
+            .collect();
+        let trades = vec![
+            trade(0, TradeType::MarketBuy, "2"),
+            trade(1, TradeType::LimitSell, "0.5"),
+        ];
+
+        let symbols = vec!["BTC/USDT".to_string()];
+        for data in [
+            BacktestData::new(symbols.clone(), vec![candles.clone()]),
+            BacktestData::from_columns(
+                symbols.clone(),
+                vec![CandleColumns::from(candles.as_slice())],
+            ),
+        ] {
+            let closing = closing_trades(&config, &data, 3, &trades);
+            assert_eq!(closing.len(), 1);
+            let close = &closing[0];
+            assert_eq!(close.timestamp, candles[2].timestamp);
+            assert!(matches!(close.trade_type, TradeType::MarketSell));
+            assert_eq!(close.price, BigDecimal::from(120));
+            assert_eq!(close.amount, BigDecimal::from_str("1.5").unwrap());
+            assert_eq!(close.fee, BigDecimal::from_str("0.36").unwrap());
+
+            let mut flat = trades.clone();
+            flat.push(trade(2, TradeType::MarketSell, "1.5"));
+            assert!(closing_trades(&config, &data, 3, &flat).is_empty());
+        }
+    }
+}
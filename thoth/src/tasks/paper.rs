@@ -0,0 +1,804 @@
+use crate::errors::{AppError, AppResult};
+use crate::exchange::{self, Exchange, ExchangeConfig};
+use crate::models::{Candle, CandleColumns};
+use crate::services::tasks::save_paper_session;
+use crate::strategy::{
+    BacktestEngine, Broker, BrokerAccount, BrokerType, CcxtBroker, MAX_RISK_EVENTS, MockBroker,
+    Number, Order, OrderType, ParameterValues, RiskEvent, Strategy, StrategyContext,
+    StrategyHandle, StrategyManager, Trade,
+};
+use crate::tasks::{BacktestConfig, TaskControl};
+use crate::utils::catch_panic_async;
+use bigdecimal::{BigDecimal, ToPrimitive, Zero};
+use chrono::{DateTime, Utc, serde::ts_milliseconds, serde::ts_milliseconds_option};
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+use tokio::runtime::Handle;
+use tokio::sync::{RwLock, broadcast};
+use tokio::task::JoinHandle;
+use tokio_util::sync::CancellationToken;
+use ts_rs::TS;
+use uuid::Uuid;
+
+pub const DEFAULT_WARMUP_CANDLES: usize = 100;
+// Exchanges can take a moment to publish a candle after it closes
+const CANDLE_DELAY: Duration = Duration::from_secs(2);
+// Wait before polling again when the candle is not out yet or the exchange fails
+const RETRY_DELAY: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, TS)]
+#[serde(rename_all = "snake_case")]
+#[ts(export)]
+pub enum PaperStatus {
+    Starting,
+    Running,
+    Stopped,
+    Failed,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub struct PaperOrder {
+    pub id: Uuid,
+    pub symbol: String,
+    pub order_type: OrderType,
+    #[ts(type = "string")]
+    pub price: BigDecimal,
+    #[ts(optional, type = "string")]
+    pub trigger_price: Option<BigDecimal>,
+    #[ts(optional, type = "string")]
+    pub trail: Option<BigDecimal>,
+    #[ts(type = "string")]
+    pub amount: BigDecimal,
+    // Held back along with the order's cost
+    #[serde(default)]
+    #[ts(type = "string")]
+    pub fee: BigDecimal,
+    // Shared by the legs of an OCO or bracket
+    #[ts(optional)]
+    pub group: Option<Uuid>,
+    // The entry an exit order waits on
+    #[ts(optional)]
+    pub parent: Option<Uuid>,
+}
+
+impl From<Order> for PaperOrder {
+    fn from(order: Order) -> Self {
+        Self {
+            id: order.id,
+            symbol: order.symbol,
+            order_type: order.order_type,
+            price: order.price,
+            trigger_price: order.trigger_price,
+            trail: order.trail,
+            amount: order.amount,
+            fee: order.fee,
+            group: order.group,
+            parent: order.parent,
+        }
+    }
+}
+
+// The simulated account as of the last candle the session traded
+#[derive(Debug, Clone, Default, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub struct PaperAccount {
+    #[ts(type = "string")]
+    pub balance: BigDecimal,
+    #[ts(type = "string")]
+    pub position: BigDecimal,
+    #[ts(type = "string")]
+    pub equity: BigDecimal,
+    #[ts(type = "string")]
+    pub net_profit: BigDecimal,
+    pub return_percent: f32,
+    // Margin interest owed, paid when the position is reduced
+    #[serde(default)]
+    #[ts(type = "string")]
+    pub accrued_interest: BigDecimal,
+    #[ts(optional, type = "string")]
+    pub last_price: Option<BigDecimal>,
+    #[serde(default, with = "ts_milliseconds_option")]
+    #[ts(optional, type = "number")]
+    pub last_candle_at: Option<DateTime<Utc>>,
+    pub orders: Vec<PaperOrder>,
+    pub trades: Vec<Trade>,
+}
+
+impl PaperAccount {
+    pub fn new(initial_capital: &BigDecimal) -> Self {
+        Self {
+            balance: initial_capital.clone(),
+            equity: initial_capital.clone(),
+            ..Self::default()
+        }
+    }
+
+    fn from_context(context: &StrategyContext, starting_equity: &BigDecimal) -> AppResult<Self> {
+        let candle = context.candle()?;
+        let equity = context.equity()?;
+        let net_profit = &equity - starting_equity;
+        let return_percent = if !starting_equity.is_zero() {
+            (&net_profit / starting_equity).to_f32().unwrap_or(0.0) * 100.0
+        } else {
+            0.0
+        };
+
+        Ok(Self {
+            balance: context.balance(),
+            position: context.position(),
+            equity,
+            net_profit,
+            return_percent,
+            accrued_interest: context.accrued_interest(),
+            last_price: Some(candle.close),
+            last_candle_at: Some(candle.timestamp),
+            orders: context.orders().into_iter().map(PaperOrder::from).collect(),
+            trades: context.trades().to_vec(),
+        })
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub struct PaperSession {
+    pub id: Uuid,
+    pub status: PaperStatus,
+    pub name: String,
+    #[serde(flatten)]
+    pub config: BacktestConfig,
+    #[serde(default)]
+    pub parameters: ParameterValues,
+    pub warmup_candles: usize,
+    #[serde(default)]
+    pub broker: BrokerType,
+    // Equity when a live broker first took over the account, which profit is measured from
+    #[ts(optional, type = "string")]
+    pub starting_equity: Option<BigDecimal>,
+    pub account: PaperAccount,
+    // Orders the risk limits rejected on live candles, the latest last
+    #[serde(default)]
+    pub risk_events: Vec<RiskEvent>,
+    #[ts(optional)]
+    pub error_message: Option<String>,
+    // Candles that closed before this are warmup history, later ones are traded
+    #[serde(with = "ts_milliseconds")]
+    #[ts(type = "number")]
+    pub created_at: DateTime<Utc>,
+    #[serde(with = "ts_milliseconds_option")]
+    #[ts(optional, type = "number")]
+    pub started_at: Option<DateTime<Utc>>,
+    #[serde(with = "ts_milliseconds_option")]
+    #[ts(optional, type = "number")]
+    pub stopped_at: Option<DateTime<Utc>>,
+    #[serde(with = "ts_milliseconds")]
+    #[ts(type = "number")]
+    pub updated_at: DateTime<Utc>,
+    #[serde(skip)]
+    #[ts(skip)]
+    pub event_tx: Option<broadcast::Sender<PaperSession>>,
+    // Read by the running strategy's risk limits, `config.risk` keeps it across restarts
+    #[serde(skip)]
+    #[ts(skip)]
+    pub kill_switch: Arc<AtomicBool>,
+}
+
+impl PaperSession {
+    pub fn broadcast(&self) {
+        if let Some(tx) = &self.event_tx {
+            let _ = tx.send(self.clone());
+        }
+    }
+
+    pub async fn save(&self, db_pool: &PgPool) {
+        if let Err(e) = save_paper_session(db_pool, self).await {
+            tracing::error!("Failed to save paper session {}: {}", self.id, e);
+        }
+    }
+
+    // Rejects every order the strategy places from its next one on, until released
+    pub fn set_kill_switch(&mut self, engaged: bool) {
+        self.config.risk.get_or_insert_default().kill_switch = engaged;
+        self.kill_switch.store(engaged, Ordering::Relaxed);
+        self.updated_at = Utc::now();
+    }
+
+    // Trades on the blocking pool until the session is stopped or fails. A server shutdown leaves
+    // the session running, so it is resumed on the next start
+    pub fn spawn(
+        session: Arc<RwLock<PaperSession>>,
+        strategy_manager: StrategyManager,
+        db_pool: PgPool,
+        control: TaskControl,
+        exchanges: Arc<HashMap<String, ExchangeConfig>>,
+        shutdown_token: CancellationToken,
+    ) -> JoinHandle<()> {
+        let runtime = Handle::current();
+        tokio::task::spawn_blocking(move || {
+            runtime.block_on(async {
+                tokio::select! {
+                    _ = shutdown_token.cancelled() => {}
+                    _ = Self::run(&session, &strategy_manager, &db_pool, &control, &exchanges) => {}
+                }
+            });
+        })
+    }
+
+    async fn run(
+        session: &RwLock<PaperSession>,
+        strategy_manager: &StrategyManager,
+        db_pool: &PgPool,
+        control: &TaskControl,
+        exchanges: &HashMap<String, ExchangeConfig>,
+    ) {
+        let name = {
+            let mut session = session.write().await;
+            session.status = PaperStatus::Starting;
+            session.updated_at = Utc::now();
+            session.broadcast();
+            session.name.clone()
+        };
+
+        let loaded = control
+            .until_cancelled(strategy_manager.load_strategy(&name))
+            .await;
+        let result = match loaded {
+            Ok(Ok(strategy_handle)) => {
+                let trade = Self::trade(session, &strategy_handle, db_pool, control, exchanges);
+                catch_panic_async(trade).await.unwrap_or_else(|report| {
+                    Err(AppError::Internal(format!(
+                        "Paper session panicked: {}",
+                        report
+                    )))
+                })
+            }
+            Ok(Err(e)) => Err(format!("Failed to load strategy: {}", e).into()),
+            Err(e) => Err(e),
+        };
+
+        let mut session = session.write().await;
+        session.complete(result);
+        session.broadcast();
+        session.save(db_pool).await;
+    }
+
+    fn log_risk_events(&mut self, risk_events: Vec<RiskEvent>) {
+        for event in &risk_events {
+            tracing::warn!(
+                "Paper session {} rejected a {:?} order: {}",
+                self.id,
+                event.order_type,
+                event.message
+            );
+        }
+        self.risk_events.extend(risk_events);
+        let excess = self.risk_events.len().saturating_sub(MAX_RISK_EVENTS);
+        self.risk_events.drain(..excess);
+    }
+
+    fn complete(&mut self, result: AppResult<()>) {
+        let now = Utc::now();
+        match result {
+            Ok(()) | Err(AppError::Cancelled(_)) => {
+                self.status = PaperStatus::Stopped;
+            }
+            Err(e) => {
+                self.status = PaperStatus::Failed;
+                self.error_message = Some(e.to_string());
+            }
+        }
+        self.stopped_at = Some(now);
+        self.updated_at = now;
+    }
+
+    async fn trade(
+        session: &RwLock<PaperSession>,
+        strategy_handle: &StrategyHandle,
+        db_pool: &PgPool,
+        control: &TaskControl,
+        exchanges: &HashMap<String, ExchangeConfig>,
+    ) -> AppResult<()> {
+        let (config, parameters, warmup_candles, broker, starting_equity, account, created_at) = {
+            let session = session.read().await;
+            let engaged = session
+                .config
+                .risk
+                .as_ref()
+                .is_some_and(|risk| risk.kill_switch);
+            session.kill_switch.store(engaged, Ordering::Relaxed);
+            (
+                session.config.clone(),
+                session.parameters.clone(),
+                session.warmup_candles,
+                session.broker,
+                session.starting_equity.clone(),
+                session.account.clone(),
+                session.created_at,
+            )
+        };
+        let kill_switch = session.read().await.kill_switch.clone();
+
+        let parameters = strategy_handle.resolve_parameters(&parameters)?;
+        let mut strategy = strategy_handle.create(&parameters)?;
+        let market_data = exchange::connect(&config.exchange, exchanges)?;
+
+        // A session that traded before picks up the account it stored and trades on from its last
+        // candle. Strategy state cannot be stored, so the strategy starts over on the warmup
+        // candles up to that one
+        let resumed = account.last_candle_at;
+        let timeframe = config.timeframe;
+        let next_open = resumed.map_or(created_at, |last| timeframe.close_time(last));
+        let mut feed = PaperFeed::new(config, next_open, warmup_candles, kill_switch)?;
+
+        let since =
+            resumed.unwrap_or(created_at) - timeframe.to_delta() * (warmup_candles as i32 + 1);
+        let mut history = feed.fetch(market_data.as_ref(), since)?;
+        let end = history.partition_point(|c| match resumed {
+            Some(last) => c.timestamp <= last,
+            None => timeframe.close_time(c.timestamp) <= created_at,
+        });
+        history.truncate(end);
+
+        let broker = Self::connect(broker, &feed.config, exchanges)?;
+        let starting_equity = if resumed.is_some() {
+            if let Some(broker) = broker {
+                feed.connect(&mut strategy, broker)?;
+            }
+            feed.restore(&account)?;
+            feed.start(&mut strategy, history, warmup_candles)?;
+            starting_equity
+        } else {
+            feed.start(&mut strategy, history, warmup_candles)?;
+            // Live brokers take over after the warmup, which is always simulated
+            match broker {
+                Some(broker) => {
+                    feed.connect(&mut strategy, broker)?;
+                    match starting_equity {
+                        Some(equity) => Some(equity),
+                        None => feed.equity()?,
+                    }
+                }
+                None => None,
+            }
+        };
+        if let Some(equity) = &starting_equity {
+            feed.starting_equity = equity.clone();
+        }
+
+        {
+            let account = feed.account()?;
+            let mut session = session.write().await;
+            let now = Utc::now();
+            session.status = PaperStatus::Running;
+            session.parameters = parameters.clone();
+            session.starting_equity = starting_equity;
+            session.account = account;
+            session.started_at = Some(now);
+            session.updated_at = now;
+            session.broadcast();
+            session.save(db_pool).await;
+        }
+
+        let result = Self::follow(
+            session,
+            db_pool,
+            control,
+            market_data.as_ref(),
+            &mut feed,
+            &mut strategy,
+        )
+        .await;
+        if let Err(AppError::Cancelled(_)) = result {
+            feed.finish(&mut strategy)?;
+            let account = feed.account()?;
+            session.write().await.account = account;
+        }
+        result
+    }
+
+    fn connect(
+        broker: BrokerType,
+        config: &BacktestConfig,
+        exchanges: &HashMap<String, ExchangeConfig>,
+    ) -> AppResult<Option<Box<dyn Broker>>> {
+        Ok(match broker {
+            BrokerType::Simulated => None,
+            BrokerType::Mock => Some(Box::new(MockBroker::new(
+                config.engine,
+                &config.initial_capital,
+            ))),
+            BrokerType::Ccxt => {
+                let exchange_config = exchanges.get(&config.exchange).cloned().unwrap_or_default();
+                Some(Box::new(CcxtBroker::connect(
+                    &config.exchange,
+                    &exchange_config,
+                    config.engine,
+                )?))
+            }
+        })
+    }
+
+    // Trades each candle once it closes, until the session is stopped or fails
+    async fn follow(
+        session: &RwLock<PaperSession>,
+        db_pool: &PgPool,
+        control: &TaskControl,
+        market_data: &dyn Exchange,
+        feed: &mut PaperFeed,
+        strategy: &mut dyn Strategy,
+    ) -> AppResult<()> {
+        let timeframe = feed.config.timeframe;
+        loop {
+            let since = feed.next_open;
+            let close = timeframe.close_time(since) - Utc::now();
+            if let Ok(wait) = close.to_std() {
+                control
+                    .until_cancelled(tokio::time::sleep(wait + CANDLE_DELAY))
+                    .await?;
+            }
+            control.check()?;
+
+            let candles = match feed.fetch(market_data, since) {
+                Ok(candles) => candles,
+                Err(e) => {
+                    let session_id = session.read().await.id;
+                    tracing::warn!(
+                        "Paper session {} failed to fetch candles: {}",
+                        session_id,
+                        e
+                    );
+                    Vec::new()
+                }
+            };
+            if candles.is_empty() {
+                control
+                    .until_cancelled(tokio::time::sleep(RETRY_DELAY))
+                    .await?;
+                continue;
+            }
+
+            let result = candles
+                .into_iter()
+                .try_for_each(|candle| feed.tick(strategy, candle));
+            let risk_events = feed.take_risk_events();
+            if !risk_events.is_empty() {
+                session.write().await.log_risk_events(risk_events);
+            }
+            result?;
+
+            let account = feed.account()?;
+            let mut session = session.write().await;
+            session.account = account;
+            session.updated_at = Utc::now();
+            session.broadcast();
+            session.save(db_pool).await;
+        }
+    }
+}
+
+// The latest candles a session has seen, as many as its warmup. The context only borrows them
+// while a candle is traded and is detached in between, so new candles can be appended
+struct PaperFeed {
+    config: BacktestConfig,
+    next_open: DateTime<Utc>,
+    max_candles: usize,
+    candles: Vec<Candle>,
+    columns: CandleColumns,
+    context: Option<StrategyContext<'static>>,
+    starting_equity: BigDecimal,
+}
+
+impl PaperFeed {
+    fn new(
+        config: BacktestConfig,
+        next_open: DateTime<Utc>,
+        warmup: usize,
+        kill_switch: Arc<AtomicBool>,
+    ) -> AppResult<Self> {
+        let mut context = config.context()?;
+        context.share_kill_switch(kill_switch);
+        context.add_symbol(
+            config.symbol.clone(),
+            config.fees.clone(),
+            config.precision.clone(),
+        )?;
+
+        Ok(Self {
+            starting_equity: config.initial_capital.clone(),
+            config,
+            next_open,
+            max_candles: warmup.max(1),
+            candles: Vec::new(),
+            columns: CandleColumns::default(),
+            context: Some(context),
+        })
+    }
+
+    // Closed candles opened at or after `since`. The exchange's latest candle is usually still
+    // open, so it is left for the next poll
+    fn fetch(&self, market_data: &dyn Exchange, since: DateTime<Utc>) -> AppResult<Vec<Candle>> {
+        let timeframe = self.config.timeframe;
+        let now = Utc::now();
+        let mut candles: Vec<Candle> = Vec::new();
+        let mut next = since;
+        loop {
+            let page = market_data.fetch_candles(
+                &self.config.symbol,
+                timeframe,
+                Some(next.timestamp_millis()),
+                None,
+            )?;
+            let closed: Vec<Candle> = page
+                .into_iter()
+                .filter(|c| c.timestamp >= next && timeframe.close_time(c.timestamp) <= now)
+                .collect();
+            let Some(last) = closed.last() else {
+                break;
+            };
+            next = timeframe.close_time(last.timestamp);
+            candles.extend(closed);
+        }
+        Ok(candles)
+    }
+
+    fn push(&mut self, candle: Candle) {
+        let price = |value: &BigDecimal| value.to_f64().unwrap_or(f64::NAN);
+        self.columns.push(
+            candle.timestamp,
+            price(&candle.open),
+            price(&candle.high),
+            price(&candle.low),
+            price(&candle.close),
+            price(&candle.volume),
+        );
+        self.next_open = self.config.timeframe.close_time(candle.timestamp);
+        if self.config.engine == BacktestEngine::Exact {
+            self.candles.push(candle);
+        }
+
+        // Indicators are fed candle by candle, so older candles are only dropped now and then
+        let excess = self.columns.len().saturating_sub(self.max_candles);
+        if excess >= self.max_candles {
+            self.columns.drop_oldest(excess);
+            self.candles.drain(..excess.min(self.candles.len()));
+        }
+    }
+
+    // Shows the strategy the latest candles for the duration of `f`
+    fn with_context<T>(
+        &mut self,
+        f: impl FnOnce(&mut StrategyContext<'_>) -> AppResult<T>,
+    ) -> AppResult<T> {
+        let mut context: StrategyContext<'_> = self
+            .context
+            .take()
+            .ok_or("Paper session lost its strategy context")?;
+        let len = self.columns.len();
+        let state = &mut context.symbols[0];
+        state.columns = self.columns.view(len.saturating_sub(self.max_candles)..len);
+        state.candles = &self.candles[self.candles.len().saturating_sub(self.max_candles)..];
+
+        let result = f(&mut context);
+        self.context = Some(context.detach());
+        result
+    }
+
+    // Only the last `warmup` candles of the history are shown, the rest just move the feed on
+    fn start(
+        &mut self,
+        strategy: &mut dyn Strategy,
+        mut history: Vec<Candle>,
+        warmup: usize,
+    ) -> AppResult<()> {
+        if let Some(last) = history.last() {
+            self.next_open = self.config.timeframe.close_time(last.timestamp);
+        }
+        let timestamp = history.last().map(|c| c.timestamp);
+        for candle in history.split_off(history.len().saturating_sub(warmup)) {
+            self.push(candle);
+        }
+
+        self.with_context(|context| {
+            context.timestamp = timestamp;
+            strategy.on_start(context)?;
+            context.after(strategy)
+        })
+    }
+
+    fn tick(&mut self, strategy: &mut dyn Strategy, candle: Candle) -> AppResult<()> {
+        // Pages can overlap, only candles after the last traded one count
+        if candle.timestamp < self.next_open {
+            return Ok(());
+        }
+
+        let timestamp = candle.timestamp;
+        self.push(candle);
+        self.with_context(|context| {
+            context.before(timestamp, strategy)?;
+            strategy.tick(context)?;
+            context.after(strategy)
+        })
+    }
+
+    fn take_risk_events(&mut self) -> Vec<RiskEvent> {
+        self.context
+            .as_mut()
+            .map(StrategyContext::take_risk_events)
+            .unwrap_or_default()
+    }
+
+    fn connect(&mut self, strategy: &mut dyn Strategy, broker: Box<dyn Broker>) -> AppResult<()> {
+        self.with_context(|context| context.set_broker(broker, strategy))
+    }
+
+    fn restore(&mut self, account: &PaperAccount) -> AppResult<()> {
+        let engine = self.config.engine;
+        let number = |value: &BigDecimal| Number::new(value, engine);
+        let orders = account
+            .orders
+            .iter()
+            .map(|order| Order {
+                id: order.id,
+                symbol: order.symbol.clone(),
+                order_type: order.order_type.clone(),
+                price: number(&order.price),
+                trigger_price: order.trigger_price.as_ref().map(number),
+                trail: order.trail.as_ref().map(number),
+                amount: number(&order.amount),
+                fee: number(&order.fee),
+                group: order.group,
+                parent: order.parent,
+                reserved: false,
+                symbol_index: 0,
+            })
+            .collect();
+        let account = BrokerAccount {
+            balance: number(&account.balance),
+            positions: vec![number(&account.position)],
+            accrued_interest: vec![number(&account.accrued_interest)],
+            orders,
+            trades: account.trades.clone(),
+        };
+        self.with_context(|context| context.restore(account))
+    }
+
+    // None before the first candle, which the account is valued at
+    fn equity(&mut self) -> AppResult<Option<BigDecimal>> {
+        if self.columns.is_empty() {
+            return Ok(None);
+        }
+        self.with_context(|context| context.equity().map(Some))
+    }
+
+    fn finish(&mut self, strategy: &mut dyn Strategy) -> AppResult<()> {
+        self.with_context(|context| {
+            context.end(strategy)?;
+            strategy.on_finish(context)
+        })
+    }
+
+    fn account(&mut self) -> AppResult<PaperAccount> {
+        let starting_equity = self.starting_equity.clone();
+        if self.columns.is_empty() {
+            return Ok(PaperAccount::new(&starting_equity));
+        }
+        self.with_context(|context| PaperAccount::from_context(context, &starting_equity))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{MarketPrecision, Timeframe, TradingFees};
+    use crate::strategy::FillModelConfig;
+    use chrono::TimeZone;
+
+    fn decimal(value: &str) -> BigDecimal {
+        value.parse().unwrap()
+    }
+
+    fn config() -> BacktestConfig {
+        BacktestConfig {
+            exchange: "binance".to_string(),
+            symbol: "BTC/USDT".to_string(),
+            timeframe: Timeframe::H1,
+            precision: MarketPrecision {
+                price_precision: decimal("0.01"),
+                amount_precision: decimal("0.001"),
+            },
+            fees: TradingFees::default(),
+            initial_capital: decimal("1000"),
+            quote_currency: "USDT".to_string(),
+            start: None,
+            end: None,
+            equity_points: None,
+            margin: None,
+            fill_model: FillModelConfig::default(),
+            risk: None,
+            additional_symbols: Vec::new(),
+            additional_timeframes: Vec::new(),
+            engine: BacktestEngine::Exact,
+        }
+    }
+
+    // The hourly candle `index` hours after 2024-01-01, opening at the previous close
+    fn candle(index: i64, open: &str, close: &str) -> Candle {
+        let (open, close) = (decimal(open), decimal(close));
+        Candle {
+            timestamp: Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap()
+                + chrono::TimeDelta::hours(index),
+            exchange: "binance".to_string(),
+            symbol: "BTC/USDT".to_string(),
+            timeframe: Timeframe::H1,
+            high: open.clone().max(close.clone()) + decimal("0.5"),
+            low: open.clone().min(close.clone()) - decimal("0.5"),
+            open,
+            close,
+            volume: decimal("10"),
+        }
+    }
+
+    // Buys once and leaves a take-profit above the market
+    struct Buyer;
+
+    impl Strategy for Buyer {
+        fn tick(&mut self, context: &mut StrategyContext) -> AppResult<()> {
+            if context.trades().is_empty() && context.orders().is_empty() {
+                context.market_buy(&decimal("1"))?;
+                context.limit_sell(&decimal("120"), &decimal("1"))?;
+            }
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn resumes_trading_from_the_saved_account() {
+        let history = vec![candle(0, "100", "100")];
+        let mut feed = PaperFeed::new(config(), history[0].timestamp, 2, Arc::default()).unwrap();
+        feed.start(&mut Buyer, history.clone(), 2).unwrap();
+        feed.tick(&mut Buyer, candle(1, "100", "101")).unwrap();
+
+        let account = feed.account().unwrap();
+        // The take-profit holds the bought amount back from the free position
+        assert_eq!(account.position, decimal("0"));
+        assert_eq!(account.equity, decimal("1000"));
+        assert_eq!(account.balance, decimal("899"));
+        assert_eq!(account.trades.len(), 1);
+        assert!(matches!(
+            account.orders.as_slice(),
+            [PaperOrder {
+                order_type: OrderType::LimitSell,
+                ..
+            }]
+        ));
+        let last = account.last_candle_at.unwrap();
+        assert_eq!(last, candle(1, "100", "101").timestamp);
+
+        // After a restart the strategy starts over on the history, but on the stored account
+        let history = vec![candle(0, "100", "100"), candle(1, "100", "101")];
+        let next_open = Timeframe::H1.close_time(last);
+        let mut restored = PaperFeed::new(config(), next_open, 2, Arc::default()).unwrap();
+        restored.restore(&account).unwrap();
+        restored.start(&mut Buyer, history, 2).unwrap();
+        let resumed = restored.account().unwrap();
+        assert_eq!(resumed.position, account.position);
+        assert_eq!(resumed.balance, account.balance);
+        assert_eq!(resumed.equity, account.equity);
+        assert_eq!(resumed.orders[0].id, account.orders[0].id);
+
+        // Candles the session already traded are not traded again
+        restored.tick(&mut Buyer, candle(1, "100", "101")).unwrap();
+        assert_eq!(restored.account().unwrap().trades.len(), 1);
+
+        restored.tick(&mut Buyer, candle(2, "101", "121")).unwrap();
+        let account = restored.account().unwrap();
+        assert_eq!(account.position, decimal("0"));
+        assert_eq!(account.balance, decimal("1019"));
+        assert_eq!(account.trades.len(), 2);
+        assert!(account.orders.is_empty());
+    }
+}
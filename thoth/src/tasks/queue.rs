@@ -0,0 +1,325 @@
+use crate::errors::{AppError, AppResult};
+use crate::tasks::TaskControl;
+use sqlx::PgPool;
+use std::collections::HashMap;
+use std::sync::Arc;
+use tokio::runtime::Handle;
+use tokio::sync::{OwnedSemaphorePermit, RwLock, Semaphore, broadcast, watch};
+use tokio::task::JoinHandle;
+use uuid::Uuid;
+
+// A task that waits in a TaskQueue before it runs
+pub trait QueuedTask: Clone {
+    fn id(&self) -> Uuid;
+
+    // The task's broadcasts, which keep its shared state current while it runs
+    fn subscribe(&self) -> Option<broadcast::Receiver<Self>>;
+
+    // Records the task's place in the queue, None once it leaves the queue
+    fn set_queue_position(&mut self, position: Option<usize>);
+
+    // Ends a task that was cancelled before it started
+    fn cancel(&mut self, error: AppError);
+
+    // Stores the task's current state, logging rather than returning failures
+    fn save(&self, db_pool: &PgPool) -> impl Future<Output = ()> + Send;
+}
+
+// Runs at most `limit` tasks at a time in the order they were queued
+#[derive(Debug, Clone)]
+pub struct TaskQueue {
+    slots: Arc<Semaphore>,
+    waiting: Arc<watch::Sender<Vec<Uuid>>>,
+    controls: Arc<RwLock<HashMap<Uuid, TaskControl>>>,
+    db_pool: PgPool,
+}
+
+impl TaskQueue {
+    pub fn new(limit: usize, db_pool: PgPool) -> Self {
+        Self {
+            slots: Arc::new(Semaphore::new(limit.max(1))),
+            waiting: Arc::new(watch::Sender::new(Vec::new())),
+            controls: Arc::new(RwLock::new(HashMap::new())),
+            db_pool,
+        }
+    }
+
+    // Queues the task and runs `execute` once a slot frees up. Strategy ticks and CCXT calls
+    // block, so tasks run on the blocking pool. The handle finishes once the task has ended
+    pub async fn spawn<T, F>(
+        &self,
+        task_id: Uuid,
+        task: Arc<RwLock<T>>,
+        execute: F,
+    ) -> JoinHandle<()>
+    where
+        T: QueuedTask + Send + Sync + 'static,
+        F: AsyncFnOnce(&mut T, &TaskControl) + Send + 'static,
+    {
+        let control = TaskControl::default();
+        {
+            let mut controls = self.controls.write().await;
+            controls.insert(task_id, control.clone());
+        }
+
+        let queue = self.clone();
+        tokio::spawn(async move {
+            match queue.wait(task_id, &task, &control).await {
+                Ok(permit) => {
+                    let runtime = Handle::current();
+                    let control = control.clone();
+                    let result = tokio::task::spawn_blocking(move || {
+                        let _permit = permit;
+                        runtime.block_on(async {
+                            // The task runs on its own copy, so readers never wait on it
+                            let mut running = task.read().await.clone();
+                            let mirror = running
+                                .subscribe()
+                                .map(|updates| tokio::spawn(mirror(task.clone(), updates)));
+                            execute(&mut running, &control).await;
+                            if let Some(mirror) = mirror {
+                                mirror.abort();
+                                let _ = mirror.await;
+                            }
+                            *task.write().await = running;
+                        });
+                    })
+                    .await;
+                    if let Err(e) = result {
+                        tracing::error!("Task {} stopped unexpectedly: {}", task_id, e);
+                    }
+                }
+                Err(e) => {
+                    let mut task = task.write().await;
+                    task.cancel(e);
+                    task.save(&queue.db_pool).await;
+                }
+            }
+
+            queue.controls.write().await.remove(&task_id);
+        })
+    }
+
+    // Returns false when the task is neither queued nor running
+    pub async fn cancel(&self, task_id: Uuid) -> bool {
+        let controls = self.controls.read().await;
+        let Some(control) = controls.get(&task_id) else {
+            return false;
+        };
+        control.cancel();
+        true
+    }
+
+    // True while the task is queued or running
+    pub async fn is_active(&self, task_id: Uuid) -> bool {
+        self.controls.read().await.contains_key(&task_id)
+    }
+
+    async fn wait<T: QueuedTask>(
+        &self,
+        task_id: Uuid,
+        task: &RwLock<T>,
+        control: &TaskControl,
+    ) -> AppResult<OwnedSemaphorePermit> {
+        // Permits go to waiters first, so a free slot means nothing is queued ahead
+        if let Ok(permit) = self.slots.clone().try_acquire_owned() {
+            return Ok(permit);
+        }
+
+        self.waiting.send_modify(|waiting| waiting.push(task_id));
+        let mut changes = self.waiting.subscribe();
+        let acquire = self.slots.clone().acquire_owned();
+        tokio::pin!(acquire);
+
+        let permit = control
+            .until_cancelled(async {
+                loop {
+                    let position = changes
+                        .borrow_and_update()
+                        .iter()
+                        .position(|id| *id == task_id)
+                        .map(|index| index + 1);
+                    task.write().await.set_queue_position(position);
+
+                    tokio::select! {
+                        permit = &mut acquire => break permit,
+                        _ = changes.changed() => {}
+                    }
+                }
+            })
+            .await;
+
+        self.waiting
+            .send_modify(|waiting| waiting.retain(|id| *id != task_id));
+        task.write().await.set_queue_position(None);
+
+        permit?.map_err(|e| AppError::Internal(e.to_string()))
+    }
+}
+
+// Copies each broadcast of the task into its shared state
+async fn mirror<T: QueuedTask>(task: Arc<RwLock<T>>, mut updates: broadcast::Receiver<T>) {
+    let task_id = task.read().await.id();
+    loop {
+        match updates.recv().await {
+            Ok(update) if update.id() == task_id => *task.write().await = update,
+            Ok(_) | Err(broadcast::error::RecvError::Lagged(_)) => continue,
+            Err(broadcast::error::RecvError::Closed) => break,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+    use std::time::Duration;
+    use tokio::sync::Notify;
+
+    #[derive(Debug, Clone)]
+    struct Job {
+        id: Uuid,
+        progress: usize,
+        positions: Vec<Option<usize>>,
+        error: Option<String>,
+        event_tx: Option<broadcast::Sender<Job>>,
+    }
+
+    impl Job {
+        fn new() -> Arc<RwLock<Job>> {
+            Arc::new(RwLock::new(Job {
+                id: Uuid::new_v4(),
+                progress: 0,
+                positions: Vec::new(),
+                error: None,
+                event_tx: Some(broadcast::channel(16).0),
+            }))
+        }
+    }
+
+    impl QueuedTask for Job {
+        fn id(&self) -> Uuid {
+            self.id
+        }
+
+        fn subscribe(&self) -> Option<broadcast::Receiver<Self>> {
+            self.event_tx.as_ref().map(broadcast::Sender::subscribe)
+        }
+
+        // Records changes only, as the tasks broadcast them
+        fn set_queue_position(&mut self, position: Option<usize>) {
+            if self.positions.last().copied().flatten() != position {
+                self.positions.push(position);
+            }
+        }
+
+        fn cancel(&mut self, error: AppError) {
+            self.error = Some(error.to_string());
+        }
+
+        async fn save(&self, _db_pool: &PgPool) {}
+    }
+
+    fn queue(limit: usize) -> TaskQueue {
+        let db_pool = PgPool::connect_lazy("postgres://localhost/thoth").unwrap();
+        TaskQueue::new(limit, db_pool)
+    }
+
+    // Queues the job, recording when it starts and holding it until `release` is notified
+    async fn spawn(
+        queue: &TaskQueue,
+        job: &Arc<RwLock<Job>>,
+        started: &Arc<Mutex<Vec<Uuid>>>,
+        release: &Arc<Notify>,
+    ) -> JoinHandle<()> {
+        let id = job.read().await.id;
+        let started = started.clone();
+        let release = release.clone();
+        queue
+            .spawn(id, job.clone(), async move |job: &mut Job, _control| {
+                started.lock().unwrap().push(job.id);
+                job.progress = 1;
+                if let Some(tx) = &job.event_tx {
+                    let _ = tx.send(job.clone());
+                }
+                release.notified().await;
+                job.progress = 2;
+            })
+            .await
+    }
+
+    async fn until(condition: impl AsyncFn() -> bool) {
+        while !condition().await {
+            tokio::time::sleep(Duration::from_millis(1)).await;
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn runs_tasks_in_queue_order() {
+        let queue = queue(1);
+        let started = Arc::new(Mutex::new(Vec::new()));
+        let release = Arc::new(Notify::new());
+        let jobs = [Job::new(), Job::new(), Job::new()];
+
+        let first = spawn(&queue, &jobs[0], &started, &release).await;
+        until(async || started.lock().unwrap().len() == 1).await;
+        let second = spawn(&queue, &jobs[1], &started, &release).await;
+        until(async || jobs[1].read().await.positions == [Some(1)]).await;
+        let third = spawn(&queue, &jobs[2], &started, &release).await;
+        until(async || jobs[2].read().await.positions == [Some(2)]).await;
+
+        // Progress broadcast by the running task shows while it runs
+        assert_eq!(jobs[0].read().await.progress, 1);
+
+        release.notify_one();
+        first.await.unwrap();
+        until(async || started.lock().unwrap().len() == 2).await;
+        release.notify_one();
+        second.await.unwrap();
+        until(async || started.lock().unwrap().len() == 3).await;
+        release.notify_one();
+        third.await.unwrap();
+
+        let mut ids = Vec::new();
+        for job in &jobs {
+            let job = job.read().await;
+            assert_eq!(job.progress, 2);
+            ids.push(job.id);
+        }
+        assert_eq!(*started.lock().unwrap(), ids);
+        assert!(jobs[0].read().await.positions.is_empty());
+        assert_eq!(jobs[1].read().await.positions, [Some(1), None]);
+        assert_eq!(jobs[2].read().await.positions, [Some(2), Some(1), None]);
+        assert!(!queue.is_active(ids[2]).await);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn cancelling_a_queued_task_ends_it_without_running() {
+        let queue = queue(1);
+        let started = Arc::new(Mutex::new(Vec::new()));
+        let release = Arc::new(Notify::new());
+        let running = Job::new();
+        let queued = Job::new();
+
+        let first = spawn(&queue, &running, &started, &release).await;
+        until(async || started.lock().unwrap().len() == 1).await;
+        let second = spawn(&queue, &queued, &started, &release).await;
+        until(async || queued.read().await.positions == [Some(1)]).await;
+
+        let id = queued.read().await.id;
+        assert!(queue.is_active(id).await);
+        assert!(queue.cancel(id).await);
+        second.await.unwrap();
+        assert!(!queue.is_active(id).await);
+        assert!(!queue.cancel(id).await);
+
+        let queued = queued.read().await;
+        assert_eq!(queued.error.as_deref(), Some("Task was cancelled"));
+        assert_eq!(queued.positions, [Some(1), None]);
+        assert_eq!(queued.progress, 0);
+
+        release.notify_one();
+        first.await.unwrap();
+        assert_eq!(started.lock().unwrap().len(), 1);
+    }
+}
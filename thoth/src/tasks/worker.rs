@@ -0,0 +1,359 @@
+use crate::errors::{AppError, AppResult};
+use crate::strategy::{StrategyHandle, StrategyManager};
+use crate::tasks::{
+    BacktestStatus, BacktestTask, OptimizationTask, QueuedTask, TaskConfig, TaskControl,
+};
+use crate::utils::catch_panic_async;
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+#[cfg(unix)]
+use std::os::fd::AsFd;
+use std::{
+    fs::File,
+    io::{Read, Write},
+    path::{Path, PathBuf},
+    process::Stdio,
+    sync::{Mutex, OnceLock},
+    thread,
+};
+use tokio::{
+    io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
+    process::Command,
+    sync::broadcast,
+};
+
+// Argument that starts the server binary as a strategy worker
+pub const WORKER_ARG: &str = "worker";
+
+// Where a task runs its strategy: loaded into the server, or in a worker process
+pub enum StrategyRuntime {
+    InProcess(StrategyHandle),
+    Worker(PathBuf),
+}
+
+impl StrategyRuntime {
+    pub async fn load(
+        strategy_manager: &StrategyManager,
+        strategy_name: &str,
+        task_config: &TaskConfig,
+    ) -> AppResult<Self> {
+        if task_config.worker_process {
+            if !cfg!(unix) {
+                return Err(AppError::Internal(
+                    "worker_process is only supported on unix".to_string(),
+                ));
+            }
+            Ok(StrategyRuntime::Worker(
+                strategy_manager.build_strategy(strategy_name).await?,
+            ))
+        } else {
+            Ok(StrategyRuntime::InProcess(
+                strategy_manager.load_strategy(strategy_name).await?,
+            ))
+        }
+    }
+}
+
+// A task that runs a strategy, loaded into the server or in a worker process
+pub trait StrategyTask: QueuedTask + Clone + Send + 'static {
+    type Output;
+
+    // Names the task in panic messages
+    const KIND: &'static str;
+
+    fn into_job(self) -> WorkerJob;
+
+    fn from_job(job: WorkerJob) -> Option<Self>;
+
+    fn event_tx(&mut self) -> &mut Option<broadcast::Sender<Self>>;
+
+    fn timeout_secs(&self) -> Option<u64>;
+
+    fn broadcast(&self);
+
+    // Moves the task to Compiling or Running
+    fn start(&mut self, status: BacktestStatus);
+
+    fn fail(&mut self, message: String);
+
+    fn complete(&mut self, result: AppResult<Self::Output>);
+
+    // Runs the strategy on the current thread
+    fn run(
+        &mut self,
+        db_pool: &PgPool,
+        strategy_handle: &StrategyHandle,
+        control: &TaskControl,
+    ) -> impl Future<Output = AppResult<Self::Output>> + Send;
+
+    fn execute(
+        &mut self,
+        strategy_manager: &StrategyManager,
+        strategy_name: &str,
+        db_pool: PgPool,
+        task_config: &TaskConfig,
+        control: &TaskControl,
+    ) -> impl Future<Output = ()> + Send {
+        async move {
+            self.start(BacktestStatus::Compiling);
+            self.broadcast();
+
+            // A cancelled build keeps running in the background and is reused by the next task
+            let loaded = control
+                .until_cancelled(StrategyRuntime::load(
+                    strategy_manager,
+                    strategy_name,
+                    task_config,
+                ))
+                .await;
+            let runtime = match loaded {
+                Ok(Ok(runtime)) => runtime,
+                Err(e) => {
+                    self.complete(Err(e));
+                    self.broadcast();
+                    self.save(&db_pool).await;
+                    return;
+                }
+                Ok(Err(e)) => {
+                    self.fail(format!("Failed to load strategy: {}", e));
+                    self.broadcast();
+                    self.save(&db_pool).await;
+                    return;
+                }
+            };
+
+            self.start(BacktestStatus::Running);
+            self.broadcast();
+            self.save(&db_pool).await;
+            control.start_timer(self.timeout_secs());
+
+            match runtime {
+                StrategyRuntime::InProcess(strategy_handle) => {
+                    let result = run_caught(self, &db_pool, &strategy_handle, control).await;
+                    self.complete(result);
+                }
+                StrategyRuntime::Worker(library) => {
+                    let job = self.clone().into_job();
+                    let result = run_in_worker(&library, job, control, |job| {
+                        if let Some(task) = Self::from_job(job) {
+                            self.replace(task);
+                            self.broadcast();
+                        }
+                    })
+                    .await;
+                    match result.map(Self::from_job) {
+                        Ok(Some(task)) => self.replace(task),
+                        Ok(None) => {
+                            self.complete(Err("Strategy worker returned another task".into()))
+                        }
+                        Err(e) => self.complete(Err(e)),
+                    }
+                }
+            }
+
+            self.broadcast();
+
+            self.save(&db_pool).await;
+        }
+    }
+
+    // Takes over the state reported by a worker, keeping this task's event channel
+    fn replace(&mut self, task: Self) {
+        let event_tx = self.event_tx().take();
+        *self = task;
+        *self.event_tx() = event_tx;
+    }
+}
+
+// Runs the task's strategy, reporting a panic as the task's error
+async fn run_caught<T: StrategyTask>(
+    task: &mut T,
+    db_pool: &PgPool,
+    strategy_handle: &StrategyHandle,
+    control: &TaskControl,
+) -> AppResult<T::Output> {
+    catch_panic_async(task.run(db_pool, strategy_handle, control))
+        .await
+        .unwrap_or_else(|report| {
+            Err(AppError::Internal(format!(
+                "{} panicked: {}",
+                T::KIND,
+                report
+            )))
+        })
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "type", content = "task", rename_all = "snake_case")]
+pub enum WorkerJob {
+    Backtest(Box<BacktestTask>),
+    Optimization(Box<OptimizationTask>),
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct WorkerRequest {
+    library: PathBuf,
+    job: WorkerJob,
+}
+
+// Written to stdout one JSON object per line
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "type", content = "job", rename_all = "snake_case")]
+enum WorkerMessage {
+    Progress(WorkerJob),
+    Finished(WorkerJob),
+}
+
+// The worker's original stdout, which only messages are written to
+static PROTOCOL: OnceLock<Mutex<File>> = OnceLock::new();
+
+// Strategies may print to stdout themselves, so messages move to a copy of it and fd 1 is
+// pointed at stderr before any strategy code is loaded
+#[cfg(unix)]
+fn take_stdout() -> std::io::Result<()> {
+    let protocol = std::io::stdout().as_fd().try_clone_to_owned()?;
+    // SAFETY: dup2 only swaps what fd 1 refers to; both descriptors stay open
+    if unsafe { libc::dup2(libc::STDERR_FILENO, libc::STDOUT_FILENO) } < 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    let _ = PROTOCOL.set(Mutex::new(File::from(protocol)));
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn take_stdout() -> std::io::Result<()> {
+    Err(std::io::Error::new(
+        std::io::ErrorKind::Unsupported,
+        "Worker processes are only supported on unix",
+    ))
+}
+
+fn write_message(message: &WorkerMessage) {
+    let Some(protocol) = PROTOCOL.get() else {
+        return;
+    };
+    if let Ok(line) = serde_json::to_string(message) {
+        let mut protocol = protocol.lock().unwrap_or_else(|e| e.into_inner());
+        let _ = writeln!(protocol, "{}", line);
+        let _ = protocol.flush();
+    }
+}
+
+// Streams the task's broadcasts to stdout until the sender is dropped
+fn forward_progress<T: Clone + Send + 'static>(
+    wrap: fn(T) -> WorkerJob,
+) -> (broadcast::Sender<T>, thread::JoinHandle<()>) {
+    let (tx, mut rx) = broadcast::channel(16);
+    let forwarder = thread::spawn(move || {
+        loop {
+            match rx.blocking_recv() {
+                Ok(task) => write_message(&WorkerMessage::Progress(wrap(task))),
+                Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                Err(broadcast::error::RecvError::Closed) => break,
+            }
+        }
+    });
+    (tx, forwarder)
+}
+
+// Entry point of the worker process: runs one job read from stdin and reports it on stdout.
+// Cancellation is handled by the server killing the process
+pub async fn run_worker(db_pool: PgPool) -> AppResult<()> {
+    let mut input = String::new();
+    std::io::stdin().read_to_string(&mut input)?;
+    let request: WorkerRequest = serde_json::from_str(&input)?;
+    take_stdout()?;
+    let handle = StrategyHandle::try_from_path(&request.library);
+    let control = TaskControl::default();
+
+    let job = match request.job {
+        WorkerJob::Backtest(task) => run_job(*task, &handle, &db_pool, &control).await,
+        WorkerJob::Optimization(task) => run_job(*task, &handle, &db_pool, &control).await,
+    };
+
+    write_message(&WorkerMessage::Finished(job));
+    Ok(())
+}
+
+// Runs the task in the worker, streaming its progress to stdout
+async fn run_job<T: StrategyTask>(
+    mut task: T,
+    handle: &AppResult<StrategyHandle>,
+    db_pool: &PgPool,
+    control: &TaskControl,
+) -> WorkerJob {
+    let (tx, forwarder) = forward_progress(T::into_job);
+    *task.event_tx() = Some(tx);
+    let result = match handle {
+        Ok(handle) => run_caught(&mut task, db_pool, handle, control).await,
+        Err(e) => Err(AppError::Strategy(format!(
+            "Failed to load strategy: {}",
+            e
+        ))),
+    };
+    *task.event_tx() = None;
+    let _ = forwarder.join();
+    task.complete(result);
+    task.into_job()
+}
+
+// Runs a job in a child process, passing each progress snapshot to `on_progress`. Cancelling
+// the task kills the process, which also stops strategies stuck inside a tick
+pub async fn run_in_worker(
+    library: &Path,
+    job: WorkerJob,
+    control: &TaskControl,
+    mut on_progress: impl FnMut(WorkerJob),
+) -> AppResult<WorkerJob> {
+    control.check()?;
+    let request = serde_json::to_vec(&WorkerRequest {
+        library: library.to_path_buf(),
+        job,
+    })?;
+
+    let mut child = Command::new(std::env::current_exe()?)
+        .arg(WORKER_ARG)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        // Also carries whatever the strategy prints
+        .stderr(Stdio::inherit())
+        .kill_on_drop(true)
+        .spawn()?;
+
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin.write_all(&request).await?;
+    }
+
+    let mut finished = None;
+    if let Some(stdout) = child.stdout.take() {
+        let mut lines = BufReader::new(stdout).lines();
+        loop {
+            let line = tokio::select! {
+                line = lines.next_line() => line?,
+                _ = control.cancelled() => break,
+            };
+            let Some(line) = line else {
+                break;
+            };
+            match serde_json::from_str::<WorkerMessage>(&line) {
+                Ok(WorkerMessage::Progress(job)) if finished.is_none() => on_progress(job),
+                Ok(WorkerMessage::Progress(_)) => {}
+                Ok(WorkerMessage::Finished(job)) => finished = Some(job),
+                Err(e) => tracing::warn!("Invalid message from strategy worker: {}", e),
+            }
+        }
+    }
+
+    if finished.is_none() && control.is_cancelled() {
+        let _ = child.kill().await;
+        control.check()?;
+    }
+
+    let status = child.wait().await?;
+    finished.ok_or_else(|| {
+        AppError::Strategy(format!(
+            "Strategy worker exited without a result ({})",
+            status
+        ))
+    })
+}
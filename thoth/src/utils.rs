@@ -1,8 +1,14 @@
 use crate::errors::{AppError, AppResult};
 use bigdecimal::BigDecimal;
+use futures::FutureExt;
 use std::{
+    any::Any,
+    backtrace::Backtrace,
+    cell::RefCell,
+    panic::{AssertUnwindSafe, catch_unwind},
     path::{Path, PathBuf},
     str::FromStr,
+    sync::Once,
 };
 
 pub fn safe_join(base_dir: &Path, path: &str) -> AppResult<PathBuf> {
@@ -43,3 +49,98 @@ pub fn safe_join(base_dir: &Path, path: &str) -> AppResult<PathBuf> {
 pub fn str_to_bigdecimal(value: &str, field_name: &str) -> AppResult<BigDecimal> {
     BigDecimal::from_str(value).map_err(|_| format!("Invalid {}: {}", field_name, value).into())
 }
+
+pub fn quote_currency(symbol: &str) -> Option<&str> {
+    let (_, quote) = symbol.split_once('/')?;
+    let quote = quote.split(':').next().unwrap_or(quote);
+    if quote.is_empty() { None } else { Some(quote) }
+}
+
+// Infinite ratios serialize to null in JSON, so null is read back as infinity
+pub mod unbounded_f32 {
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    pub fn serialize<S: Serializer>(value: &f32, serializer: S) -> Result<S::Ok, S::Error> {
+        value.serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<f32, D::Error> {
+        Ok(Option::<f32>::deserialize(deserializer)?.unwrap_or(f32::INFINITY))
+    }
+}
+
+thread_local! {
+    static PANIC_BACKTRACE: RefCell<Option<Backtrace>> = const { RefCell::new(None) };
+}
+
+static PANIC_HOOK: Once = Once::new();
+
+// Keeps the default panic output and records where the panic happened for catch_panic
+fn install_panic_hook() {
+    PANIC_HOOK.call_once(|| {
+        let default_hook = std::panic::take_hook();
+        std::panic::set_hook(Box::new(move |info| {
+            PANIC_BACKTRACE.with(|backtrace| {
+                *backtrace.borrow_mut() = Some(Backtrace::force_capture());
+            });
+            default_hook(info);
+        }));
+    });
+}
+
+fn panic_report(panic: Box<dyn Any + Send>) -> String {
+    let message = if let Some(message) = panic.downcast_ref::<&str>() {
+        message.to_string()
+    } else if let Some(message) = panic.downcast_ref::<String>() {
+        message.clone()
+    } else {
+        "unknown panic".to_string()
+    };
+
+    match PANIC_BACKTRACE.with(|backtrace| backtrace.borrow_mut().take()) {
+        Some(backtrace) => format!("{}\n\nBacktrace:\n{}", message, backtrace),
+        None => message,
+    }
+}
+
+// Runs `f`, turning a panic into its message and backtrace
+pub fn catch_panic<T>(f: impl FnOnce() -> T) -> Result<T, String> {
+    install_panic_hook();
+    catch_unwind(AssertUnwindSafe(f)).map_err(panic_report)
+}
+
+pub async fn catch_panic_async<F: Future>(future: F) -> Result<F::Output, String> {
+    install_panic_hook();
+    AssertUnwindSafe(future)
+        .catch_unwind()
+        .await
+        .map_err(panic_report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn catch_panic_reports_the_message_and_where_it_happened() {
+        assert_eq!(catch_panic(|| 42), Ok(42));
+
+        let index = 3;
+        let report = catch_panic(|| panic!("index {} out of range", index)).unwrap_err();
+        assert!(report.starts_with("index 3 out of range\n\nBacktrace:\n"));
+        assert!(report.contains("catch_panic_reports_the_message"));
+    }
+
+    #[tokio::test]
+    async fn catch_panic_async_reports_panics_across_awaits() {
+        assert_eq!(catch_panic_async(async { 42 }).await, Ok(42));
+
+        let report = catch_panic_async(async {
+            tokio::task::yield_now().await;
+            panic!("strategy failed");
+        })
+        .await
+        .unwrap_err();
+        assert!(report.starts_with("strategy failed\n\nBacktrace:\n"));
+    }
+}